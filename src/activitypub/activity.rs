@@ -0,0 +1,158 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use super::ACTIVITYSTREAMS_CONTEXT;
+
+/// An activity as delivered to an inbox. The `object` is kept as raw JSON
+/// because it may be either a link (a bare id) or an embedded object.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Activity {
+    #[serde(rename = "@context", default, skip_serializing_if = "Value::is_null")]
+    pub context: Value,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(deserialize_with = "id_of")]
+    pub actor: String,
+    #[serde(default)]
+    pub object: Value,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub target: Value,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub to: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub cc: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published: Option<String>,
+}
+
+impl Activity {
+    pub fn new(kind: &str, id: String, actor: String, object: Value) -> Self {
+        Self {
+            context: Value::from(ACTIVITYSTREAMS_CONTEXT),
+            id,
+            kind: kind.to_string(),
+            actor,
+            object,
+            target: Value::Null,
+            to: vec![],
+            cc: vec![],
+            published: None,
+        }
+    }
+
+    /// The id of the object, whether it is embedded or only linked.
+    pub fn object_id(&self) -> Option<&str> {
+        match &self.object {
+            Value::String(id) => Some(id),
+            obj => obj["id"].as_str(),
+        }
+    }
+
+    /// The id of the target, whether it is embedded or only linked.
+    pub fn target_id(&self) -> Option<&str> {
+        match &self.target {
+            Value::String(id) => Some(id),
+            target => target["id"].as_str(),
+        }
+    }
+
+    /// The type of an embedded object. Linked objects have no known type.
+    pub fn object_type(&self) -> Option<&str> {
+        self.object["type"].as_str()
+    }
+}
+
+/// Deserialize a reference that may be a bare id or an object with an `id`.
+pub fn id_of<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(id) => Ok(id),
+        obj => obj["id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| serde::de::Error::custom("expected an id or an object with an id")),
+    }
+}
+
+/// Deserialize a property that may hold a single id or an array of them.
+pub fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let ids = match Value::deserialize(deserializer)? {
+        Value::Null => vec![],
+        Value::Array(values) => values
+            .iter()
+            .filter_map(|v| match v {
+                Value::String(id) => Some(id.clone()),
+                obj => obj["id"].as_str().map(str::to_string),
+            })
+            .collect(),
+        Value::String(id) => vec![id],
+        obj => obj["id"].as_str().map(str::to_string).into_iter().collect(),
+    };
+
+    Ok(ids)
+}
+
+/// Deserialize a property that holds a single object or an array of them.
+pub fn objects<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Value>, D::Error> {
+    let objects = match Value::deserialize(deserializer)? {
+        Value::Null => vec![],
+        Value::Array(values) => values,
+        value => vec![value],
+    };
+
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Activity;
+
+    #[test]
+    fn linked_and_embedded_objects_are_understood() {
+        let linked: Activity = serde_json::from_value(serde_json::json!({
+            "id": "https://example.com/follows/1",
+            "type": "Follow",
+            "actor": "https://example.com/users/alice",
+            "object": "https://rhodos.example/users/bob",
+            "to": "https://rhodos.example/users/bob",
+        }))
+        .unwrap();
+        assert_eq!(linked.object_id(), Some("https://rhodos.example/users/bob"));
+        assert_eq!(linked.object_type(), None);
+        assert_eq!(linked.to, vec!["https://rhodos.example/users/bob"]);
+
+        let embedded: Activity = serde_json::from_value(serde_json::json!({
+            "id": "https://example.com/undo/1",
+            "type": "Undo",
+            "actor": { "id": "https://example.com/users/alice", "type": "Person" },
+            "object": {
+                "id": "https://example.com/follows/1",
+                "type": "Follow",
+            },
+        }))
+        .unwrap();
+        assert_eq!(embedded.actor, "https://example.com/users/alice");
+        assert_eq!(embedded.object_id(), Some("https://example.com/follows/1"));
+        assert_eq!(embedded.object_type(), Some("Follow"));
+        assert!(embedded.to.is_empty());
+    }
+
+    #[test]
+    fn activity_without_actor_is_rejected() {
+        let res = serde_json::from_value::<Activity>(serde_json::json!({
+            "id": "https://example.com/follows/1",
+            "type": "Follow",
+            "object": "https://rhodos.example/users/bob",
+        }));
+        assert!(res.is_err());
+    }
+}
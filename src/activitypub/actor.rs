@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    activity::{objects, one_or_many},
+    actor_url, followers_url, following_url, inbox_url, instance_actor_url, outbox_url,
+    shared_inbox_url, ACTIVITYSTREAMS_CONTEXT, SECURITY_CONTEXT,
+};
+use crate::{
+    entities::{account, account_key, instance_key, user},
+    routes::escape_html,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Person {
+    #[serde(rename = "@context", default)]
+    pub context: serde_json::Value,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub preferred_username: Option<String>,
+    pub name: Option<String>,
+    pub summary: Option<String>,
+    pub url: Option<String>,
+    pub inbox: String,
+    pub outbox: Option<String>,
+    pub followers: Option<String>,
+    pub following: Option<String>,
+    #[serde(default)]
+    pub manually_approves_followers: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoints: Option<Endpoints>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<PublicKey>,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub also_known_as: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moved_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<serde_json::Value>,
+    /// Extra profile fields, as `PropertyValue`s
+    #[serde(
+        default,
+        deserialize_with = "objects",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub attachment: Vec<serde_json::Value>,
+}
+
+/// An extra name/value field on the profile of a local account. Both are
+/// plain text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileField {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Endpoints {
+    pub shared_inbox: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKey {
+    pub id: String,
+    pub owner: String,
+    pub public_key_pem: String,
+}
+
+impl Person {
+    /// Build the actor document of a local account. `base_url` is the
+    /// externally visible URL of the tenant the account belongs to.
+    pub fn from_account(base_url: &str, account: &account::Model, user: &user::Model) -> Self {
+        let handle = account.username.clone().unwrap_or_default();
+        let id = actor_url(base_url, &handle);
+        let fields: Vec<ProfileField> =
+            serde_json::from_value(account.fields.clone()).unwrap_or_default();
+
+        Self {
+            context: serde_json::json!([ACTIVITYSTREAMS_CONTEXT, SECURITY_CONTEXT]),
+            id: id.clone(),
+            kind: "Person".to_string(),
+            preferred_username: Some(handle.clone()),
+            name: Some(user.name.clone()),
+            summary: account.summary.clone(),
+            url: Some(id),
+            inbox: inbox_url(base_url, &handle),
+            outbox: Some(outbox_url(base_url, &handle)),
+            followers: Some(followers_url(base_url, &handle)),
+            following: Some(following_url(base_url, &handle)),
+            manually_approves_followers: account.locked,
+            endpoints: Some(Endpoints {
+                shared_inbox: Some(shared_inbox_url(base_url)),
+            }),
+            public_key: None,
+            also_known_as: serde_json::from_value(account.also_known_as.clone())
+                .unwrap_or_default(),
+            moved_to: account.moved_to.clone(),
+            icon: account.avatar_url.as_ref().map(|url| {
+                serde_json::json!({
+                    "type": "Image",
+                    "url": url,
+                })
+            }),
+            attachment: fields
+                .iter()
+                .map(|f| {
+                    serde_json::json!({
+                        "type": "PropertyValue",
+                        "name": f.name,
+                        "value": escape_html(&f.value),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Build the instance actor of the tenant at `base_url`, whose host is
+    /// `domain`. It only exists to own the key `key`.
+    pub fn instance_actor(base_url: &str, domain: &str, key: &instance_key::Model) -> Self {
+        let id = instance_actor_url(base_url);
+
+        Self {
+            context: serde_json::json!([ACTIVITYSTREAMS_CONTEXT, SECURITY_CONTEXT]),
+            id: id.clone(),
+            kind: "Application".to_string(),
+            preferred_username: Some(domain.to_string()),
+            name: None,
+            summary: None,
+            url: Some(base_url.to_string()),
+            inbox: shared_inbox_url(base_url),
+            outbox: None,
+            followers: None,
+            following: None,
+            manually_approves_followers: true,
+            endpoints: Some(Endpoints {
+                shared_inbox: Some(shared_inbox_url(base_url)),
+            }),
+            public_key: None,
+            also_known_as: vec![],
+            moved_to: None,
+            icon: None,
+            attachment: vec![],
+        }
+        .with_key(&key.key_id, &key.public_key_pem)
+    }
+
+    /// Publish `key` as the actor's public key.
+    pub fn with_public_key(self, key: &account_key::Model) -> Self {
+        self.with_key(&key.key_id, &key.public_key_pem)
+    }
+
+    fn with_key(mut self, key_id: &str, public_key_pem: &str) -> Self {
+        self.public_key = Some(PublicKey {
+            id: format!("{}#{}", self.id, key_id),
+            owner: self.id.clone(),
+            public_key_pem: public_key_pem.to_string(),
+        });
+
+        self
+    }
+}
@@ -0,0 +1,72 @@
+use serde::Serialize;
+
+use super::ACTIVITYSTREAMS_CONTEXT;
+
+/// The entry point of a paged collection. Items are only served by its pages.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderedCollection {
+    #[serde(rename = "@context")]
+    pub context: &'static str,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub total_items: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last: Option<String>,
+}
+
+impl OrderedCollection {
+    pub fn new(id: String, total_items: u64, first: String, last: Option<String>) -> Self {
+        Self {
+            first: Some(first),
+            last,
+            ..Self::count_only(id, total_items)
+        }
+    }
+
+    /// A collection that only tells how many items it has, without pages to
+    /// read them from.
+    pub fn count_only(id: String, total_items: u64) -> Self {
+        Self {
+            context: ACTIVITYSTREAMS_CONTEXT,
+            id,
+            kind: "OrderedCollection",
+            total_items,
+            first: None,
+            last: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderedCollectionPage<T: Serialize> {
+    #[serde(rename = "@context")]
+    pub context: &'static str,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub part_of: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev: Option<String>,
+    pub ordered_items: Vec<T>,
+}
+
+impl<T: Serialize> OrderedCollectionPage<T> {
+    pub fn new(id: String, part_of: String, ordered_items: Vec<T>) -> Self {
+        Self {
+            context: ACTIVITYSTREAMS_CONTEXT,
+            id,
+            kind: "OrderedCollectionPage",
+            part_of,
+            next: None,
+            prev: None,
+            ordered_items,
+        }
+    }
+}
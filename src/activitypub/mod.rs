@@ -0,0 +1,228 @@
+//! Shared vocabulary for talking to the fediverse: media types, JSON-LD
+//! contexts, object types and the URL scheme under which local ActivityPub
+//! objects are published.
+use axum::http::{header, HeaderMap};
+
+pub mod activity;
+pub mod actor;
+pub mod collection;
+pub mod note;
+
+pub const ACTIVITY_JSON: &str = "application/activity+json";
+pub const LD_JSON: &str = "application/ld+json";
+pub const JRD_JSON: &str = "application/jrd+json";
+
+pub const ACTIVITYSTREAMS_CONTEXT: &str = "https://www.w3.org/ns/activitystreams";
+pub const SECURITY_CONTEXT: &str = "https://w3id.org/security/v1";
+
+/// The special collection that addresses an object to everyone.
+pub const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+pub const PROFILE_PAGE_REL: &str = "http://webfinger.net/rel/profile-page";
+
+/// The actor that represents the tenant itself rather than one of its
+/// accounts.
+pub fn instance_actor_url(base_url: &str) -> String {
+    format!("{}/actor", base_url)
+}
+
+pub fn actor_url(base_url: &str, handle: &str) -> String {
+    format!("{}/users/{}", base_url, handle)
+}
+
+pub fn inbox_url(base_url: &str, handle: &str) -> String {
+    format!("{}/inbox", actor_url(base_url, handle))
+}
+
+pub fn outbox_url(base_url: &str, handle: &str) -> String {
+    format!("{}/outbox", actor_url(base_url, handle))
+}
+
+pub fn followers_url(base_url: &str, handle: &str) -> String {
+    format!("{}/followers", actor_url(base_url, handle))
+}
+
+pub fn following_url(base_url: &str, handle: &str) -> String {
+    format!("{}/following", actor_url(base_url, handle))
+}
+
+pub fn shared_inbox_url(base_url: &str) -> String {
+    format!("{}/inbox", base_url)
+}
+
+/// The ActivityPub id of a local post.
+pub fn status_url(base_url: &str, handle: &str, id: i64) -> String {
+    format!("{}/statuses/{}", actor_url(base_url, handle), id)
+}
+
+/// The human readable page of a local post.
+pub fn status_page_url(base_url: &str, handle: &str, id: i64) -> String {
+    format!("{}/@{}/{}", base_url, handle, id)
+}
+
+/// The listing of the posts tagged with the case folded tag `name`. Tags
+/// outside ASCII are percent-encoded.
+pub fn tag_url(base_url: &str, name: &str) -> String {
+    let name: String = url::form_urlencoded::byte_serialize(name.as_bytes()).collect();
+    format!("{}/tags/{}", base_url, name)
+}
+
+/// Format a timestamp the way ActivityPub `published` and `updated`
+/// properties expect it.
+pub fn format_timestamp(t: chrono::NaiveDateTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(t, chrono::Utc)
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// The handle of the local actor identified by `url`, if `url` is an actor
+/// URL of the tenant at `base_url`.
+pub fn handle_from_actor_url<'a>(base_url: &str, url: &'a str) -> Option<&'a str> {
+    url.strip_prefix(&actor_url(base_url, ""))
+        .filter(|h| !h.is_empty() && !h.contains(['/', '#', '?']))
+}
+
+/// The handle of the author and the id of the local post identified by `url`,
+/// if `url` is a status URL of the tenant at `base_url`.
+pub fn status_from_url<'a>(base_url: &str, url: &'a str) -> Option<(&'a str, i64)> {
+    let (handle, id) = url
+        .strip_prefix(&actor_url(base_url, ""))?
+        .split_once("/statuses/")?;
+    if handle.is_empty() || handle.contains(['/', '#', '?']) {
+        return None;
+    }
+
+    Some((handle, id.parse().ok()?))
+}
+
+/// Returns true if `url` is an absolute http(s) URL, the only kind that is
+/// safe to link to from our pages.
+pub fn is_http_url(url: &str) -> bool {
+    matches!(url::Url::parse(url), Ok(parsed) if matches!(parsed.scheme(), "http" | "https"))
+}
+
+/// Returns true if the `Accept` header asks for an ActivityPub representation
+/// rather than HTML.
+pub fn wants_activity_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.split(';').next().unwrap_or_default().trim())
+        .any(|v| v.eq_ignore_ascii_case(ACTIVITY_JSON) || v.eq_ignore_ascii_case(LD_JSON))
+}
+
+/// Returns true if the `Accept` header explicitly asks for HTML, the way
+/// browsers do. A missing header or a bare `*/*` does not count.
+pub fn wants_html(headers: &HeaderMap) -> bool {
+    !wants_activity_json(headers)
+        && headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(|v| v.split(';').next().unwrap_or_default().trim())
+            .any(|v| {
+                v.eq_ignore_ascii_case("text/html")
+                    || v.eq_ignore_ascii_case("application/xhtml+xml")
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, HeaderMap, HeaderValue};
+
+    use super::{
+        handle_from_actor_url, is_http_url, status_from_url, wants_activity_json, wants_html,
+    };
+
+    #[test]
+    fn activity_json_accept_headers_are_detected() {
+        let cases = [
+            "application/activity+json",
+            r#"application/ld+json; profile="https://www.w3.org/ns/activitystreams""#,
+            "text/html;q=0.5, application/activity+json",
+        ];
+        for case in cases {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_static(case));
+            assert!(wants_activity_json(&headers), "{} wants ActivityPub", case);
+        }
+    }
+
+    #[test]
+    fn browser_accept_headers_are_not_activity_json() {
+        let mut headers = HeaderMap::new();
+        assert!(!wants_activity_json(&headers), "no Accept header is HTML");
+
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("text/html,application/xhtml+xml,*/*;q=0.8"),
+        );
+        assert!(!wants_activity_json(&headers), "browsers get HTML");
+    }
+
+    #[test]
+    fn only_explicit_html_accept_headers_want_html() {
+        let mut headers = HeaderMap::new();
+        assert!(!wants_html(&headers), "no Accept header is not a browser");
+
+        headers.insert(header::ACCEPT, HeaderValue::from_static("*/*"));
+        assert!(!wants_html(&headers), "*/* is not a browser");
+
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("text/html,application/xhtml+xml,*/*;q=0.8"),
+        );
+        assert!(wants_html(&headers), "browsers ask for HTML");
+
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("text/html;q=0.5, application/activity+json"),
+        );
+        assert!(!wants_html(&headers), "ActivityPub wins");
+    }
+
+    #[test]
+    fn only_http_urls_are_linkable() {
+        assert!(is_http_url("https://example.com/@alice/1"));
+        assert!(is_http_url("http://example.com/@alice/1"));
+        for url in ["javascript:alert(1)", "data:text/html,hi", "/@alice/1", ""] {
+            assert!(!is_http_url(url), "{}", url);
+        }
+    }
+
+    #[test]
+    fn local_actor_urls_are_recognised() {
+        let base = "https://example.com";
+        assert_eq!(
+            handle_from_actor_url(base, "https://example.com/users/alice"),
+            Some("alice")
+        );
+        for url in [
+            "https://example.com/users/alice/inbox",
+            "https://example.com/users/alice#main-key",
+            "https://example.com/users/",
+            "https://other.example/users/alice",
+        ] {
+            assert_eq!(handle_from_actor_url(base, url), None, "{}", url);
+        }
+    }
+
+    #[test]
+    fn local_status_urls_are_recognised() {
+        let base = "https://example.com";
+        assert_eq!(
+            status_from_url(base, "https://example.com/users/alice/statuses/42"),
+            Some(("alice", 42))
+        );
+        for url in [
+            "https://example.com/users/alice/statuses/42#votes",
+            "https://example.com/users/alice/statuses/",
+            "https://example.com/users//statuses/42",
+            "https://other.example/users/alice/statuses/42",
+        ] {
+            assert_eq!(status_from_url(base, url), None, "{}", url);
+        }
+    }
+}
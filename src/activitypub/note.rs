@@ -0,0 +1,694 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    activity::{id_of, objects, one_or_many, Activity},
+    actor_url, followers_url, format_timestamp, is_http_url, status_page_url, status_url, tag_url,
+    PUBLIC,
+};
+use crate::{
+    domain::{ParsedHashtag, ParsedMention, Visibility},
+    entities::{content, mention},
+    polls::{Poll, RemotePoll},
+    routes::escape_html,
+};
+
+/// A short post. This is the object type both rhodos and the rest of the
+/// fediverse use for microblog content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Note {
+    #[serde(rename = "@context", default, skip_serializing_if = "Value::is_null")]
+    pub context: Value,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(deserialize_with = "id_of")]
+    pub attributed_to: String,
+    pub content: Option<String>,
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub sensitive: bool,
+    pub published: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<Value>,
+    pub in_reply_to: Option<String>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub to: Vec<String>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub cc: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tag: Vec<Value>,
+    #[serde(
+        default,
+        deserialize_with = "objects",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub attachment: Vec<Value>,
+    /// The options of a `Question` voters pick one of
+    #[serde(
+        default,
+        deserialize_with = "objects",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub one_of: Vec<Value>,
+    /// The options of a `Question` voters pick any of
+    #[serde(
+        default,
+        deserialize_with = "objects",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub any_of: Vec<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<String>,
+    /// When a `Question` closed. Some servers only say whether it did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closed: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voters_count: Option<i32>,
+    /// The option picked, if the note is a vote in a `Question`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Note {
+    /// Build the Note of a local post by the account with `handle`, which
+    /// mentions `mentions`. Hashtags are taken from the text of the post.
+    pub fn from_content(
+        base_url: &str,
+        handle: &str,
+        post: &content::Model,
+        mentions: &[mention::Model],
+    ) -> Self {
+        let visibility = Visibility::try_from(post.visibility.as_str()).unwrap_or_default();
+        let (mut to, mut cc) = addressing(visibility, &followers_url(base_url, handle));
+        // Direct posts are addressed to the mentioned accounts only
+        let recipients = match visibility {
+            Visibility::Direct => &mut to,
+            _ => &mut cc,
+        };
+        for mention in mentions {
+            if !recipients.contains(&mention.actor_id) {
+                recipients.push(mention.actor_id.clone());
+            }
+        }
+        let body = post.body.as_deref().unwrap_or_default();
+        let mut tag: Vec<Value> = mentions
+            .iter()
+            .map(|m| {
+                serde_json::json!({
+                    "type": "Mention",
+                    "href": m.actor_id,
+                    "name": format!("@{}", acct(m)),
+                })
+            })
+            .collect();
+        for name in ParsedHashtag::parse_all(body)
+            .iter()
+            .map(ParsedHashtag::folded)
+        {
+            let hashtag = serde_json::json!({
+                "type": "Hashtag",
+                "href": tag_url(base_url, &name),
+                "name": format!("#{}", name),
+            });
+            if !tag.contains(&hashtag) {
+                tag.push(hashtag);
+            }
+        }
+        let summary = post.cw.clone().filter(|cw| !cw.trim().is_empty());
+
+        Self {
+            context: Value::Null,
+            id: status_url(base_url, handle, post.id),
+            kind: "Note".to_string(),
+            attributed_to: actor_url(base_url, handle),
+            content: Some(text_to_html(body, base_url, mentions)),
+            sensitive: summary.is_some(),
+            summary,
+            published: post.published_at.map(format_timestamp),
+            updated: post.edited_at.map(format_timestamp),
+            url: Some(Value::from(status_page_url(base_url, handle, post.id))),
+            in_reply_to: None,
+            to,
+            cc,
+            tag,
+            attachment: vec![],
+            one_of: vec![],
+            any_of: vec![],
+            end_time: None,
+            closed: None,
+            voters_count: None,
+            name: None,
+        }
+    }
+
+    /// Turn the note into the `Question` offering the options of `poll`, with
+    /// their current counts.
+    pub fn with_poll(mut self, poll: &Poll) -> Self {
+        let options = poll
+            .options
+            .iter()
+            .map(|option| {
+                serde_json::json!({
+                    "type": "Note",
+                    "name": option.name,
+                    "replies": { "type": "Collection", "totalItems": option.votes_count },
+                })
+            })
+            .collect();
+        if poll.poll.multiple {
+            self.any_of = options;
+        } else {
+            self.one_of = options;
+        }
+        self.kind = "Question".to_string();
+        self.end_time = poll.poll.expires_at.map(format_timestamp);
+        self.closed = poll.poll.closed_at.map(format_timestamp).map(Value::from);
+        self.voters_count = Some(poll.poll.voters_count);
+
+        self
+    }
+
+    /// The poll of the note, if it is a `Question`.
+    pub fn poll(&self) -> Option<RemotePoll> {
+        if self.kind != "Question" {
+            return None;
+        }
+        let (multiple, options) = match self.any_of.is_empty() {
+            true => (false, &self.one_of),
+            false => (true, &self.any_of),
+        };
+        let options: Vec<(String, i32)> = options
+            .iter()
+            .filter_map(|option| {
+                let name = option["name"].as_str()?.to_string();
+                let votes = option["replies"]["totalItems"].as_i64().unwrap_or_default();
+                Some((name, i32::try_from(votes).unwrap_or_default()))
+            })
+            .collect();
+        if options.is_empty() {
+            return None;
+        }
+        let expires_at = self.end_time.as_deref().and_then(parse_timestamp);
+        let closed_at = match &self.closed {
+            Some(Value::String(closed)) => parse_timestamp(closed),
+            Some(Value::Bool(true)) => expires_at.or_else(|| Some(chrono::Utc::now().naive_utc())),
+            _ => None,
+        };
+
+        Some(RemotePoll {
+            multiple,
+            options,
+            voters_count: self.voters_count,
+            expires_at,
+            closed_at,
+        })
+    }
+
+    /// Wrap the note in the `Create` activity that announces it.
+    pub fn into_create(self) -> Activity {
+        let mut create = Activity::new(
+            "Create",
+            format!("{}/activity", self.id),
+            self.attributed_to.clone(),
+            Value::Null,
+        );
+        create.to = self.to.clone();
+        create.cc = self.cc.clone();
+        create.published = self.published.clone();
+        create.object = serde_json::to_value(self).unwrap_or_default();
+
+        create
+    }
+
+    /// Wrap the note in an `Update` activity announcing its new version. The
+    /// note should carry the time of the edit in `updated`.
+    pub fn into_update(self) -> Activity {
+        let version = self.updated.as_deref().or(self.published.as_deref());
+        let mut update = Activity::new(
+            "Update",
+            format!("{}#updates/{}", self.id, version.unwrap_or_default()),
+            self.attributed_to.clone(),
+            Value::Null,
+        );
+        update.to = self.to.clone();
+        update.cc = self.cc.clone();
+        update.published = self.updated.clone();
+        update.object = serde_json::to_value(self).unwrap_or_default();
+
+        update
+    }
+
+    /// Wrap a tombstone of the note in the `Delete` activity announcing that
+    /// it is gone.
+    pub fn into_delete(self, deleted: Option<String>) -> Activity {
+        let tombstone = Tombstone {
+            context: Value::Null,
+            id: self.id.clone(),
+            kind: "Tombstone".to_string(),
+            former_type: self.kind,
+            deleted,
+        };
+        let mut delete = Activity::new(
+            "Delete",
+            format!("{}#delete", self.id),
+            self.attributed_to,
+            serde_json::to_value(tombstone).unwrap_or_default(),
+        );
+        delete.to = self.to;
+        delete.cc = self.cc;
+
+        delete
+    }
+
+    /// The human readable URL of the note, which some servers send as a
+    /// `Link` object instead of a plain string. Only http(s) URLs are
+    /// accepted.
+    pub fn url(&self) -> Option<String> {
+        let href = |link: &Value| match link {
+            Value::String(url) => Some(url.clone()),
+            link => link["href"].as_str().map(str::to_string),
+        };
+        match &self.url {
+            Some(Value::Array(links)) => links.iter().filter_map(href).find(|url| is_http_url(url)),
+            Some(link) => href(link).filter(|url| is_http_url(url)),
+            None => None,
+        }
+    }
+
+    /// `published` as a UTC timestamp, if it is present and well formed.
+    pub fn published_at(&self) -> Option<chrono::NaiveDateTime> {
+        self.published.as_deref().and_then(parse_timestamp)
+    }
+
+    /// `updated` as a UTC timestamp, if it is present and well formed.
+    pub fn updated_at(&self) -> Option<chrono::NaiveDateTime> {
+        self.updated.as_deref().and_then(parse_timestamp)
+    }
+
+    /// Whether the note is addressed to the public collection directly,
+    /// rather than only copied to it like unlisted posts are.
+    pub fn is_public(&self) -> bool {
+        self.to
+            .iter()
+            .any(|to| to == PUBLIC || to == "as:Public" || to == "Public")
+    }
+
+    /// The names of the hashtags the note is tagged with, without the `#`.
+    pub fn hashtags(&self) -> Vec<String> {
+        self.tag
+            .iter()
+            .filter(|t| t["type"] == "Hashtag")
+            .filter_map(|t| t["name"].as_str())
+            .map(|name| name.trim_start_matches('#').to_string())
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+
+    /// All actors the note is addressed to.
+    pub fn recipients(&self) -> impl Iterator<Item = &String> {
+        self.to.iter().chain(self.cc.iter())
+    }
+}
+
+/// What remains of a deleted object.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tombstone {
+    #[serde(rename = "@context", default, skip_serializing_if = "Value::is_null")]
+    pub context: Value,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub former_type: String,
+    pub deleted: Option<String>,
+}
+
+impl Tombstone {
+    /// The tombstone of the deleted local post `post`.
+    pub fn from_content(base_url: &str, handle: &str, post: &content::Model) -> Self {
+        Self {
+            context: Value::Null,
+            id: status_url(base_url, handle, post.id),
+            kind: "Tombstone".to_string(),
+            former_type: "Note".to_string(),
+            deleted: post.deleted_at.map(format_timestamp),
+        }
+    }
+}
+
+fn parse_timestamp(t: &str) -> Option<chrono::NaiveDateTime> {
+    chrono::DateTime::parse_from_rfc3339(t)
+        .ok()
+        .map(|t| t.naive_utc())
+}
+
+/// The `to` and `cc` recipients of a post with the given visibility.
+pub fn addressing(visibility: Visibility, followers: &str) -> (Vec<String>, Vec<String>) {
+    let public = PUBLIC.to_string();
+    let followers = followers.to_string();
+    match visibility {
+        Visibility::Public => (vec![public], vec![followers]),
+        Visibility::Unlisted => (vec![followers], vec![public]),
+        Visibility::Followers => (vec![followers], vec![]),
+        Visibility::Direct => (vec![], vec![]),
+    }
+}
+
+/// Render the plain text of a local post on the tenant at `base_url` as
+/// HTML. Blank lines separate paragraphs and single line breaks are kept.
+/// Mentions of the accounts in `mentions` become links to their profiles and
+/// hashtags links to their listings.
+pub fn text_to_html(text: &str, base_url: &str, mentions: &[mention::Model]) -> String {
+    text.replace("\r\n", "\n")
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("<p>{}</p>", link_entities(p, base_url, mentions)))
+        .collect()
+}
+
+fn link_entities(paragraph: &str, base_url: &str, mentions: &[mention::Model]) -> String {
+    let escape = |s: &str| escape_html(s).replace('\n', "<br>");
+
+    let mut links = vec![];
+    for found in ParsedMention::parse_all(paragraph) {
+        if let Some(mention) = mentions
+            .iter()
+            .find(|m| m.username.eq_ignore_ascii_case(&found.username) && m.domain == found.domain)
+        {
+            let link = format!(
+                r#"<span class="h-card"><a href="{}" class="u-url mention">{}</a></span>"#,
+                escape_html(&mention.url),
+                escape_html(&paragraph[found.range.clone()]),
+            );
+            links.push((found.range, link));
+        }
+    }
+    for found in ParsedHashtag::parse_all(paragraph) {
+        let link = format!(
+            r#"<a href="{}" class="mention hashtag" rel="tag">#<span>{}</span></a>"#,
+            escape_html(&tag_url(base_url, &found.folded())),
+            escape_html(&found.name),
+        );
+        links.push((found.range, link));
+    }
+    links.sort_by_key(|(range, _)| range.start);
+
+    let mut html = String::new();
+    let mut rest = 0;
+    for (range, link) in links {
+        if range.start < rest {
+            continue;
+        }
+        html.push_str(&escape(&paragraph[rest..range.start]));
+        html.push_str(&link);
+        rest = range.end;
+    }
+    html.push_str(&escape(&paragraph[rest..]));
+
+    html
+}
+
+/// The address of a mentioned account, as written in the post.
+fn acct(mention: &mention::Model) -> String {
+    match &mention.domain {
+        Some(domain) => format!("{}@{}", mention.username, domain),
+        None => mention.username.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{addressing, text_to_html, Note};
+    use crate::{
+        activitypub::PUBLIC,
+        domain::Visibility,
+        entities::{content, mention, poll, poll_option},
+        polls::Poll,
+    };
+
+    #[test]
+    fn mastodon_note_is_parsed() {
+        let note: Note = serde_json::from_value(serde_json::json!({
+            "id": "https://example.com/users/alice/statuses/1",
+            "type": "Note",
+            "attributedTo": "https://example.com/users/alice",
+            "content": "<p>Hello</p>",
+            "summary": "greeting",
+            "sensitive": true,
+            "published": "2023-01-02T03:04:05Z",
+            "url": { "type": "Link", "href": "https://example.com/@alice/1" },
+            "inReplyTo": null,
+            "to": ["https://www.w3.org/ns/activitystreams#Public"],
+            "cc": "https://example.com/users/alice/followers",
+        }))
+        .unwrap();
+
+        assert_eq!(note.url().as_deref(), Some("https://example.com/@alice/1"));
+        assert_eq!(
+            note.published_at().unwrap().to_string(),
+            "2023-01-02 03:04:05"
+        );
+        assert_eq!(note.recipients().count(), 2);
+        assert!(note.sensitive);
+    }
+
+    #[test]
+    fn only_http_urls_are_taken_from_notes() {
+        let note: Note = serde_json::from_value(serde_json::json!({
+            "id": "https://example.com/users/alice/statuses/1",
+            "type": "Note",
+            "attributedTo": "https://example.com/users/alice",
+            "url": "javascript:alert(1)",
+        }))
+        .unwrap();
+        assert_eq!(note.url(), None);
+
+        let note: Note = serde_json::from_value(serde_json::json!({
+            "id": "https://example.com/users/alice/statuses/1",
+            "type": "Note",
+            "attributedTo": "https://example.com/users/alice",
+            "url": ["javascript:alert(1)", { "href": "https://example.com/@alice/1" }],
+        }))
+        .unwrap();
+        assert_eq!(note.url().as_deref(), Some("https://example.com/@alice/1"));
+    }
+
+    #[test]
+    fn local_post_becomes_a_note() {
+        let post = content::Model {
+            id: 7,
+            publisher_id: 1,
+            cw: Some("spoilers".to_string()),
+            body: Some("Hello <world>\n\nbye".to_string()),
+            published: true,
+            published_at: Some(
+                chrono::NaiveDateTime::parse_from_str("2023-01-02 03:04:05", "%Y-%m-%d %H:%M:%S")
+                    .unwrap(),
+            ),
+            updated_at: chrono::Utc::now().naive_utc(),
+            visibility: "public".to_string(),
+            deleted_at: None,
+            edited_at: None,
+            scheduled_at: None,
+            base_url: None,
+        };
+
+        let note = Note::from_content("https://example.com", "alice", &post, &[]);
+        assert_eq!(note.id, "https://example.com/users/alice/statuses/7");
+        assert_eq!(note.attributed_to, "https://example.com/users/alice");
+        assert_eq!(note.url().as_deref(), Some("https://example.com/@alice/7"));
+        assert_eq!(
+            note.content.as_deref(),
+            Some("<p>Hello &lt;world&gt;</p><p>bye</p>")
+        );
+        assert_eq!(note.summary.as_deref(), Some("spoilers"));
+        assert!(note.sensitive);
+        assert_eq!(note.published.as_deref(), Some("2023-01-02T03:04:05Z"));
+
+        let create = note.into_create();
+        assert_eq!(create.kind, "Create");
+        assert_eq!(create.actor, "https://example.com/users/alice");
+        assert_eq!(create.object["type"], "Note");
+        assert_eq!(create.to, vec![PUBLIC]);
+    }
+
+    #[test]
+    fn visibility_decides_addressing() {
+        let followers = "https://example.com/users/alice/followers";
+        let cases = [
+            (Visibility::Public, vec![PUBLIC], vec![followers]),
+            (Visibility::Unlisted, vec![followers], vec![PUBLIC]),
+            (Visibility::Followers, vec![followers], vec![]),
+            (Visibility::Direct, vec![], vec![]),
+        ];
+        for (visibility, to, cc) in cases {
+            assert_eq!(
+                addressing(visibility, followers),
+                (
+                    to.iter().map(|s| s.to_string()).collect(),
+                    cc.iter().map(|s| s.to_string()).collect()
+                ),
+                "{}",
+                visibility
+            );
+        }
+    }
+
+    #[test]
+    fn line_breaks_are_kept() {
+        let base_url = "https://example.com";
+        assert_eq!(text_to_html("a\nb", base_url, &[]), "<p>a<br>b</p>");
+        assert_eq!(
+            text_to_html("a\r\n\r\nb", base_url, &[]),
+            "<p>a</p><p>b</p>"
+        );
+    }
+
+    #[test]
+    fn mentions_are_linked_tagged_and_addressed() {
+        let now = chrono::Utc::now().naive_utc();
+        let bob = mention::Model {
+            id: 1,
+            content_id: 7,
+            actor_id: "https://other.example/users/bob".to_string(),
+            username: "bob".to_string(),
+            domain: Some("other.example".to_string()),
+            url: "https://other.example/@bob".to_string(),
+            inbox: Some("https://other.example/users/bob/inbox".to_string()),
+            created_at: now,
+            updated_at: now,
+        };
+        let post = content::Model {
+            id: 7,
+            publisher_id: 1,
+            cw: None,
+            body: Some("hi @Bob@other.example & @nobody".to_string()),
+            published: true,
+            published_at: Some(now),
+            updated_at: now,
+            visibility: "direct".to_string(),
+            deleted_at: None,
+            edited_at: None,
+            scheduled_at: None,
+            base_url: None,
+        };
+
+        let note = Note::from_content("https://example.com", "alice", &post, &[bob]);
+        assert_eq!(
+            note.content.as_deref(),
+            Some(
+                r#"<p>hi <span class="h-card"><a href="https://other.example/@bob" class="u-url mention">@Bob@other.example</a></span> &amp; @nobody</p>"#
+            )
+        );
+        assert_eq!(note.to, vec!["https://other.example/users/bob"]);
+        assert!(note.cc.is_empty());
+        assert_eq!(note.tag[0]["type"], "Mention");
+        assert_eq!(note.tag[0]["name"], "@bob@other.example");
+    }
+
+    #[test]
+    fn hashtags_are_linked_and_tagged_once() {
+        let now = chrono::Utc::now().naive_utc();
+        let post = content::Model {
+            id: 7,
+            publisher_id: 1,
+            cw: None,
+            body: Some("#Rust is nice, #rust & #Été".to_string()),
+            published: true,
+            published_at: Some(now),
+            updated_at: now,
+            visibility: "public".to_string(),
+            deleted_at: None,
+            edited_at: None,
+            scheduled_at: None,
+            base_url: None,
+        };
+
+        let note = Note::from_content("https://example.com", "alice", &post, &[]);
+        assert_eq!(
+            note.content.as_deref(),
+            Some(
+                r#"<p><a href="https://example.com/tags/rust" class="mention hashtag" rel="tag">#<span>Rust</span></a> is nice, <a href="https://example.com/tags/rust" class="mention hashtag" rel="tag">#<span>rust</span></a> &amp; <a href="https://example.com/tags/%C3%A9t%C3%A9" class="mention hashtag" rel="tag">#<span>Été</span></a></p>"#
+            )
+        );
+        assert_eq!(note.tag.len(), 2);
+        assert_eq!(note.tag[0]["type"], "Hashtag");
+        assert_eq!(note.tag[0]["name"], "#rust");
+        assert_eq!(
+            note.tag[1]["href"],
+            "https://example.com/tags/%C3%A9t%C3%A9"
+        );
+        assert_eq!(note.hashtags(), vec!["rust", "été"]);
+    }
+
+    #[test]
+    fn polls_become_questions_and_back() {
+        let now = chrono::Utc::now().naive_utc();
+        let post = content::Model {
+            id: 7,
+            publisher_id: 1,
+            cw: None,
+            body: Some("Tea or coffee?".to_string()),
+            published: true,
+            published_at: Some(now),
+            updated_at: now,
+            visibility: "public".to_string(),
+            deleted_at: None,
+            edited_at: None,
+            scheduled_at: None,
+            base_url: None,
+        };
+        let option = |id: i64, name: &str, votes_count: i32| poll_option::Model {
+            id,
+            poll_id: 1,
+            position: id as i32,
+            name: name.to_string(),
+            votes_count,
+            created_at: now,
+            updated_at: now,
+        };
+        let poll = Poll {
+            poll: poll::Model {
+                id: 1,
+                content_id: Some(7),
+                remote_post_id: None,
+                multiple: false,
+                expires_at: Some(now),
+                closed_at: None,
+                voters_count: 3,
+                created_at: now,
+                updated_at: now,
+            },
+            options: vec![option(0, "tea", 2), option(1, "coffee", 1)],
+        };
+
+        let note = Note::from_content("https://example.com", "alice", &post, &[]).with_poll(&poll);
+        assert_eq!(note.kind, "Question");
+        assert!(note.any_of.is_empty());
+        assert_eq!(note.one_of[0]["name"], "tea");
+        assert_eq!(note.one_of[0]["replies"]["totalItems"], 2);
+        assert_eq!(note.voters_count, Some(3));
+
+        let json = serde_json::to_value(&note).unwrap();
+        assert!(json["endTime"].is_string());
+        let parsed: Note = serde_json::from_value(json).unwrap();
+        let remote = parsed.poll().unwrap();
+        assert!(!remote.multiple);
+        assert_eq!(
+            remote.options,
+            vec![("tea".to_string(), 2), ("coffee".to_string(), 1)]
+        );
+        assert_eq!(remote.voters_count, Some(3));
+        assert!(remote.closed_at.is_none());
+        assert!(
+            Note::from_content("https://example.com", "alice", &post, &[])
+                .poll()
+                .is_none()
+        );
+    }
+}
@@ -49,7 +49,7 @@ pub async fn password_ok(
     credentials: Credentials,
     conn: &DatabaseConnection,
 ) -> Result<bool, AuthError> {
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
     match validate_credentials(credentials, conn).await {
         Ok(_) => Ok(true),
         Err(e) => match e {
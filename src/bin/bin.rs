@@ -1,5 +1,6 @@
 use std::process::ExitCode;
 
+use librhodos::keys;
 use librhodos::serve;
 use librhodos::settings::{self, dotenv_override};
 use librhodos::startup;
@@ -22,6 +23,10 @@ async fn main() -> ExitCode {
         .unwrap();
 
     dotenv_override(&mut global_config);
+    if let Err(e) = keys::check_secret_key(&global_config.server.secret_key) {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
+    }
 
     // Process command line arguments
     let args: startup::Args = startup::process_command_line();
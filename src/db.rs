@@ -14,17 +14,32 @@ pub async fn connect(url: &String) -> Result<DatabaseConnection, DbErr> {
 
 pub mod content {
     use super::super::entities::{prelude::*, *};
-    use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait};
+    use crate::{
+        federation::queue,
+        polls,
+        routes::{all_tenants, tenant_base_url, AppState},
+        tags,
+    };
+    use sea_orm::{
+        ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, PaginatorTrait,
+        QueryFilter, QueryOrder, QuerySelect, Select, TransactionTrait,
+    };
+    use std::time::Duration;
 
-    pub async fn browse(db: &DatabaseConnection, id: i64) -> Result<Vec<content::Model>, String> {
-        let res = match Content::find_by_id(id).one(db).await {
-            Ok(res) => {
-                vec![res.unwrap()]
-            }
-            Err(e) => return Err(e.to_string()),
-        };
+    /// How often scheduled posts that are due are published.
+    const SCHEDULE_INTERVAL: Duration = Duration::from_secs(2);
+    /// How often tombstones past the retention period are purged.
+    const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
-        Ok(res)
+    /// The post `id`, if there is one.
+    pub async fn browse(
+        db: &DatabaseConnection,
+        id: i64,
+    ) -> Result<Option<content::Model>, String> {
+        Content::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(|e| e.to_string())
     }
 
     pub async fn create(
@@ -39,16 +54,439 @@ pub mod content {
         Ok(res)
     }
 
-    pub async fn publish(db: &DatabaseConnection, id: i64) -> Result<bool, String> {
-        let cnt = content::ActiveModel {
-            id: sea_orm::ActiveValue::Set(id),
-            published: sea_orm::ActiveValue::Set(Some(true)),
-            published_at: sea_orm::ActiveValue::Set(Some(chrono::Utc::now().naive_utc())),
+    /// Publish a post that is not published yet, tag it, start the clock of
+    /// its poll and queue its delivery to the followers of its author.
+    /// Returns whether the post was published, as it may have been published
+    /// or deleted in the meantime.
+    /// `base_url` is the externally visible URL of the tenant.
+    pub async fn publish(db: &DatabaseConnection, base_url: &str, id: i64) -> Result<bool, String> {
+        let now = chrono::Utc::now().naive_utc();
+        let txn = db.begin().await.map_err(|e| e.to_string())?;
+        let res = Content::update_many()
+            .col_expr(content::Column::Published, true.into())
+            .col_expr(content::Column::PublishedAt, Some(now).into())
+            .col_expr(
+                content::Column::ScheduledAt,
+                Option::<chrono::NaiveDateTime>::None.into(),
+            )
+            .filter(content::Column::Id.eq(id))
+            .filter(content::Column::Published.eq(false))
+            .filter(content::Column::DeletedAt.is_null())
+            .exec(&txn)
+            .await
+            .map_err(|e| e.to_string())?;
+        if res.rows_affected == 0 {
+            return Ok(false);
+        }
+        let post = Content::find_by_id(id)
+            .one(&txn)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("post {} vanished while publishing it", id))?;
+        tags::tag_post(&txn, post.id, post.body.as_deref().unwrap_or_default())
+            .await
+            .map_err(|e| e.to_string())?;
+        polls::restart(&txn, post.id)
+            .await
+            .map_err(|e| e.to_string())?;
+        queue::enqueue_post(&txn, base_url, &post)
+            .await
+            .map_err(|e| e.to_string())?;
+        txn.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(true)
+    }
+
+    /// Schedule the unpublished post `id` to be published at `at`, or turn
+    /// it back into a draft. `base_url` is kept for the scheduler, which has
+    /// no request to take it from. Returns whether there is such a post.
+    pub async fn schedule(
+        db: &DatabaseConnection,
+        base_url: &str,
+        id: i64,
+        at: Option<chrono::NaiveDateTime>,
+    ) -> Result<bool, String> {
+        let res = Content::update_many()
+            .col_expr(content::Column::ScheduledAt, at.into())
+            .col_expr(content::Column::BaseUrl, Some(base_url).into())
+            .filter(content::Column::Id.eq(id))
+            .filter(content::Column::Published.eq(false))
+            .filter(content::Column::DeletedAt.is_null())
+            .exec(db)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(res.rows_affected > 0)
+    }
+
+    /// Publish the scheduled posts that are due, under the base URL they
+    /// were scheduled with, or `default_base_url` for posts that have none.
+    /// Returns the number of posts published.
+    pub async fn publish_due(
+        db: &DatabaseConnection,
+        default_base_url: &str,
+    ) -> Result<usize, String> {
+        let due = Content::find()
+            .filter(content::Column::Published.eq(false))
+            .filter(content::Column::DeletedAt.is_null())
+            .filter(content::Column::ScheduledAt.lte(chrono::Utc::now().naive_utc()))
+            .order_by_asc(content::Column::ScheduledAt)
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut published = 0;
+        for post in due {
+            let base_url = post.base_url.as_deref().unwrap_or(default_base_url);
+            if publish(db, base_url, post.id).await? {
+                published += 1;
+            }
+        }
+
+        Ok(published)
+    }
+
+    /// Run the task publishing scheduled posts for all tenants until the
+    /// process exits.
+    pub fn spawn_scheduler(state: AppState) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SCHEDULE_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                for tenant in all_tenants(&state).await {
+                    let base_url = tenant_base_url(&tenant.domain, &state);
+                    if let Err(e) = publish_due(&tenant.db, &base_url).await {
+                        tracing::error!(
+                            "publishing scheduled posts of {} failed: {}",
+                            tenant.domain,
+                            e
+                        );
+                    }
+                }
+            }
+        });
+    }
+
+    /// Delete a post, leaving a tombstone in its place. Its text, mentions,
+    /// hashtags, poll and earlier versions are removed right away, and the
+    /// `Delete` of a published post is queued for everyone its `Create` went
+    /// to. Tombstones are purged after the retention period.
+    pub async fn delete(
+        db: &DatabaseConnection,
+        base_url: &str,
+        post: content::Model,
+    ) -> Result<(), String> {
+        let now = chrono::Utc::now().naive_utc();
+        let txn = db.begin().await.map_err(|e| e.to_string())?;
+        if post.published {
+            // The audience is taken from the mentions, so this comes first
+            let deleted = content::Model {
+                deleted_at: Some(now),
+                ..post.clone()
+            };
+            queue::enqueue_post_delete(&txn, base_url, &deleted)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        Mention::delete_many()
+            .filter(mention::Column::ContentId.eq(post.id))
+            .exec(&txn)
+            .await
+            .map_err(|e| e.to_string())?;
+        ContentTag::delete_many()
+            .filter(content_tag::Column::ContentId.eq(post.id))
+            .exec(&txn)
+            .await
+            .map_err(|e| e.to_string())?;
+        Poll::delete_many()
+            .filter(poll::Column::ContentId.eq(post.id))
+            .exec(&txn)
+            .await
+            .map_err(|e| e.to_string())?;
+        ContentRevision::delete_many()
+            .filter(content_revision::Column::ContentId.eq(post.id))
+            .exec(&txn)
+            .await
+            .map_err(|e| e.to_string())?;
+        content::ActiveModel {
+            id: sea_orm::ActiveValue::Set(post.id),
+            cw: sea_orm::ActiveValue::Set(None),
+            body: sea_orm::ActiveValue::Set(None),
+            updated_at: sea_orm::ActiveValue::Set(now),
+            deleted_at: sea_orm::ActiveValue::Set(Some(now)),
             ..Default::default()
-        };
-        match cnt.update(db).await {
-            Ok(_) => Ok(true),
-            Err(e) => Err(e.to_string()),
         }
+        .update(&txn)
+        .await
+        .map_err(|e| e.to_string())?;
+        txn.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    /// Forget the tombstones of posts deleted before `before`, local and
+    /// remote. Returns the number of tombstones purged.
+    pub async fn purge_deleted(
+        db: &DatabaseConnection,
+        before: chrono::NaiveDateTime,
+    ) -> Result<u64, String> {
+        let local = Content::delete_many()
+            .filter(content::Column::DeletedAt.lt(before))
+            .exec(db)
+            .await
+            .map_err(|e| e.to_string())?;
+        let remote = RemotePost::delete_many()
+            .filter(remote_post::Column::DeletedAt.lt(before))
+            .exec(db)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(local.rows_affected + remote.rows_affected)
+    }
+
+    /// Run the task purging old tombstones for all tenants until the process
+    /// exits. Tombstones are kept for `server.tombstone_retention_days`.
+    pub fn spawn_purger(state: AppState) {
+        let retention =
+            chrono::Duration::days(state.global_config.server.tombstone_retention_days.into());
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PURGE_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let before = chrono::Utc::now().naive_utc() - retention;
+                for tenant in all_tenants(&state).await {
+                    if let Err(e) = purge_deleted(&tenant.db, before).await {
+                        tracing::error!("purging tombstones of {} failed: {}", tenant.domain, e);
+                    }
+                }
+            }
+        });
+    }
+
+    /// The post `id` of the account `publisher_id`, whether it is published,
+    /// deleted or neither.
+    pub async fn find_by_publisher(
+        db: &DatabaseConnection,
+        publisher_id: i64,
+        id: i64,
+    ) -> Result<Option<content::Model>, String> {
+        Content::find_by_id(id)
+            .filter(content::Column::PublisherId.eq(publisher_id))
+            .one(db)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Published posts of an account that may be shown to anyone.
+    fn public_posts(publisher_id: i64) -> Select<Content> {
+        Content::find()
+            .filter(content::Column::PublisherId.eq(publisher_id))
+            .filter(content::Column::Published.eq(true))
+            .filter(content::Column::DeletedAt.is_null())
+            .filter(
+                Condition::any()
+                    .add(content::Column::Visibility.eq("public"))
+                    .add(content::Column::Visibility.eq("unlisted")),
+            )
+    }
+
+    /// Number of published posts by all local accounts.
+    pub async fn count_local(db: &DatabaseConnection) -> Result<u64, String> {
+        Content::find()
+            .filter(content::Column::Published.eq(true))
+            .filter(content::Column::DeletedAt.is_null())
+            .count(db)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn count_public(db: &DatabaseConnection, publisher_id: i64) -> Result<u64, String> {
+        public_posts(publisher_id)
+            .count(db)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Keyset bounds of a page of posts, by post id. `max_id` pages
+    /// backwards in time, `since_id` returns the newest posts after an id and
+    /// `min_id` the posts right after an id, which pages forwards.
+    #[derive(Debug, Default, Clone, Copy)]
+    pub struct PageBounds {
+        pub max_id: Option<i64>,
+        pub since_id: Option<i64>,
+        pub min_id: Option<i64>,
+    }
+
+    /// A page of an account's public posts, newest first. Without bounds the
+    /// newest posts are returned.
+    pub async fn public_page(
+        db: &DatabaseConnection,
+        publisher_id: i64,
+        bounds: PageBounds,
+        limit: u64,
+    ) -> Result<Vec<content::Model>, String> {
+        page(db, public_posts(publisher_id), bounds, limit).await
+    }
+
+    /// A page of the drafts of an account, the posts that are neither
+    /// published nor scheduled, newest first.
+    pub async fn drafts_page(
+        db: &DatabaseConnection,
+        publisher_id: i64,
+        bounds: PageBounds,
+        limit: u64,
+    ) -> Result<Vec<content::Model>, String> {
+        let drafts = unpublished_posts(publisher_id).filter(content::Column::ScheduledAt.is_null());
+
+        page(db, drafts, bounds, limit).await
+    }
+
+    /// A page of the scheduled posts of an account, newest first.
+    pub async fn scheduled_page(
+        db: &DatabaseConnection,
+        publisher_id: i64,
+        bounds: PageBounds,
+        limit: u64,
+    ) -> Result<Vec<content::Model>, String> {
+        let scheduled =
+            unpublished_posts(publisher_id).filter(content::Column::ScheduledAt.is_not_null());
+
+        page(db, scheduled, bounds, limit).await
+    }
+
+    fn unpublished_posts(publisher_id: i64) -> Select<Content> {
+        Content::find()
+            .filter(content::Column::PublisherId.eq(publisher_id))
+            .filter(content::Column::Published.eq(false))
+            .filter(content::Column::DeletedAt.is_null())
+    }
+
+    async fn page(
+        db: &DatabaseConnection,
+        mut query: Select<Content>,
+        bounds: PageBounds,
+        limit: u64,
+    ) -> Result<Vec<content::Model>, String> {
+        if let Some(max_id) = bounds.max_id {
+            query = query.filter(content::Column::Id.lt(max_id));
+        }
+        if let Some(since_id) = bounds.since_id {
+            query = query.filter(content::Column::Id.gt(since_id));
+        }
+        if let Some(min_id) = bounds.min_id {
+            // Take the posts right after min_id, then restore newest-first order
+            let mut page = query
+                .filter(content::Column::Id.gt(min_id))
+                .order_by_asc(content::Column::Id)
+                .limit(limit)
+                .all(db)
+                .await
+                .map_err(|e| e.to_string())?;
+            page.reverse();
+            return Ok(page);
+        }
+
+        query
+            .order_by_desc(content::Column::Id)
+            .limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+pub mod user {
+    use super::super::entities::{prelude::*, *};
+    use chrono::NaiveDateTime;
+    use sea_orm::{
+        ColumnTrait, DatabaseConnection, EntityTrait, JoinType, PaginatorTrait, QueryFilter,
+        QuerySelect, RelationTrait,
+    };
+
+    pub async fn count(db: &DatabaseConnection) -> Result<u64, String> {
+        User::find().count(db).await.map_err(|e| e.to_string())
+    }
+
+    /// Number of users who have published something since `since`.
+    pub async fn count_active_since(
+        db: &DatabaseConnection,
+        since: NaiveDateTime,
+    ) -> Result<u64, String> {
+        Account::find()
+            .select_only()
+            .column(account::Column::UserId)
+            .distinct()
+            .join(JoinType::InnerJoin, account::Relation::Content.def())
+            .filter(content::Column::Published.eq(true))
+            .filter(content::Column::PublishedAt.gte(since))
+            .count(db)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+pub mod follow {
+    use super::super::entities::{prelude::*, *};
+    use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter};
+
+    /// Number of accepted followers of the account `account_id`.
+    pub async fn count_followers(db: &DatabaseConnection, account_id: i64) -> Result<u64, String> {
+        Follower::find()
+            .filter(follower::Column::AccountId.eq(account_id))
+            .filter(follower::Column::Accepted.eq(true))
+            .count(db)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Number of accepted follows of the account `account_id`.
+    pub async fn count_following(db: &DatabaseConnection, account_id: i64) -> Result<u64, String> {
+        Following::find()
+            .filter(following::Column::AccountId.eq(account_id))
+            .filter(following::Column::Accepted.eq(true))
+            .count(db)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+pub mod microblog {
+    use super::super::entities::{prelude::*, *};
+    use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, Set};
+
+    /// Whether anyone can sign up to the tenant. Tenants without settings
+    /// are open.
+    pub async fn open_registrations(db: &DatabaseConnection) -> Result<bool, String> {
+        let open = Microblog::find()
+            .one(db)
+            .await
+            .map_err(|e| e.to_string())?
+            .map(|m| m.open_registrations)
+            .unwrap_or(true);
+
+        Ok(open)
+    }
+
+    /// Open or close the tenant to sign ups. The microblog settings are
+    /// created if the tenant has none yet, named after `default_name`.
+    pub async fn save_registrations(
+        db: &DatabaseConnection,
+        open: bool,
+        default_name: &str,
+    ) -> Result<(), String> {
+        let mut settings: microblog::ActiveModel =
+            match Microblog::find().one(db).await.map_err(|e| e.to_string())? {
+                Some(m) => m.into(),
+                None => microblog::ActiveModel {
+                    name: Set(default_name.to_string()),
+                    ..Default::default()
+                },
+            };
+        settings.open_registrations = Set(open);
+        settings.save(db).await.map_err(|e| e.to_string())?;
+
+        Ok(())
     }
 }
@@ -0,0 +1,134 @@
+use std::fmt;
+
+const MAX_HANDLE_CHARS: usize = 30;
+
+/// The local part of an account's fediverse address (the `name` in
+/// `acct:name@domain`).
+#[derive(Debug, Default, Clone, Eq, PartialEq, PartialOrd)]
+pub struct AccountHandle(String);
+
+impl AccountHandle {
+    pub fn parse(s: String) -> Result<AccountHandle, String> {
+        let is_empty = s.is_empty();
+        let is_long = s.chars().count() > MAX_HANDLE_CHARS;
+        let has_invalid_chars = s.chars().any(|c| !(c.is_ascii_alphanumeric() || c == '_'));
+
+        if !(is_empty || is_long || has_invalid_chars) {
+            return Ok(Self(s.to_lowercase()));
+        }
+
+        Err(format!("{} is not a valid account handle", s))
+    }
+
+    /// Derive a handle from the local part of an email address, replacing any
+    /// character that is not allowed in a handle with an underscore.
+    pub fn from_email(email: &str) -> Result<AccountHandle, String> {
+        let local_part = email.split('@').next().unwrap_or_default();
+        let candidate: String = local_part
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .take(MAX_HANDLE_CHARS)
+            .collect();
+
+        Self::parse(candidate)
+    }
+
+    /// This handle with `_<n>` appended, the way handles derived from the
+    /// same email local part are told apart. The handle is shortened if the
+    /// result would be too long.
+    pub fn with_suffix(&self, n: i64) -> AccountHandle {
+        let suffix = format!("_{}", n);
+        let base: String = self
+            .0
+            .chars()
+            .take(MAX_HANDLE_CHARS.saturating_sub(suffix.len()))
+            .collect();
+
+        Self(base + &suffix)
+    }
+}
+
+impl AsRef<str> for AccountHandle {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for AccountHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::AccountHandle;
+
+    #[test]
+    fn valid_handle_parsed_successfully() {
+        let handle = AccountHandle::parse("Bonita_Applebaum".to_string()).unwrap();
+        assert_eq!(
+            handle.as_ref(),
+            "bonita_applebaum",
+            "valid handles are parsed and lower-cased"
+        );
+    }
+
+    #[test]
+    fn empty_handle_is_invalid() {
+        assert!(
+            AccountHandle::parse("".to_string()).is_err(),
+            "empty handles are NOT valid"
+        );
+    }
+
+    #[test]
+    fn long_handle_31_is_invalid() {
+        assert!(
+            AccountHandle::parse("a".repeat(30)).is_ok(),
+            "handles up to 30 chars are valid"
+        );
+        assert!(
+            AccountHandle::parse("a".repeat(31)).is_err(),
+            "handles longer than 30 chars are NOT valid"
+        );
+    }
+
+    #[test]
+    fn invalid_chars_rejected() {
+        for case in ["ms.jackson", "ms jackson", "ms@jackson", "ms-jackson"] {
+            assert!(
+                AccountHandle::parse(case.to_string()).is_err(),
+                "{} is NOT a valid handle",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn handle_derived_from_email() {
+        let handle = AccountHandle::from_email("ms.jackson@example.com").unwrap();
+        assert_eq!(handle.as_ref(), "ms_jackson");
+
+        let handle = AccountHandle::from_email("admin").unwrap();
+        assert_eq!(handle.as_ref(), "admin");
+    }
+
+    #[test]
+    fn suffixed_handle_stays_valid() {
+        let handle = AccountHandle::from_email("alice@other.com").unwrap();
+        assert_eq!(handle.with_suffix(7).as_ref(), "alice_7");
+
+        let long = AccountHandle::parse("a".repeat(30))
+            .unwrap()
+            .with_suffix(123);
+        assert_eq!(long.as_ref(), format!("{}_123", "a".repeat(26)));
+        assert!(AccountHandle::parse(long.to_string()).is_ok());
+    }
+}
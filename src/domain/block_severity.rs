@@ -0,0 +1,75 @@
+use std::fmt;
+
+/// How far a domain block goes. Media and reports are rejected separately,
+/// with flags on the block.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum BlockSeverity {
+    /// Nothing is accepted from or sent to the domain
+    #[default]
+    Suspend,
+    /// The domain is kept off public timelines and cannot follow without
+    /// approval
+    Silence,
+    /// Only the flags of the block apply
+    Noop,
+}
+
+impl TryFrom<String> for BlockSeverity {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
+impl TryFrom<&str> for BlockSeverity {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim().to_lowercase().as_str() {
+            "suspend" => Ok(Self::Suspend),
+            "silence" => Ok(Self::Silence),
+            "noop" | "none" => Ok(Self::Noop),
+            other => Err(format!("Unknown block severity: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for BlockSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockSeverity::Suspend => write!(f, "suspend"),
+            BlockSeverity::Silence => write!(f, "silence"),
+            BlockSeverity::Noop => write!(f, "noop"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlockSeverity;
+
+    #[test]
+    fn severity_round_trips_through_strings() {
+        let cases = [
+            (BlockSeverity::Suspend, "suspend"),
+            (BlockSeverity::Silence, "silence"),
+            (BlockSeverity::Noop, "noop"),
+        ];
+
+        for (severity, str_ver) in cases {
+            assert_eq!(severity.to_string(), str_ver);
+            assert_eq!(BlockSeverity::try_from(str_ver), Ok(severity));
+            assert_eq!(
+                BlockSeverity::try_from(str_ver.to_uppercase()),
+                Ok(severity),
+                "parsing is case insensitive"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_severity_is_rejected() {
+        assert!(BlockSeverity::try_from("obliterate").is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use std::fmt;
+
+/// Which remote servers a tenant federates with.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum FederationMode {
+    /// Every server that is not suspended
+    #[default]
+    Open,
+    /// Only the servers on the allowlist of the tenant
+    Allowlist,
+}
+
+impl TryFrom<String> for FederationMode {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
+impl TryFrom<&str> for FederationMode {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim().to_lowercase().as_str() {
+            "open" => Ok(Self::Open),
+            "allowlist" => Ok(Self::Allowlist),
+            other => Err(format!("Unknown federation mode: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for FederationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FederationMode::Open => write!(f, "open"),
+            FederationMode::Allowlist => write!(f, "allowlist"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FederationMode;
+
+    #[test]
+    fn mode_round_trips_through_strings() {
+        for (mode, str_ver) in [
+            (FederationMode::Open, "open"),
+            (FederationMode::Allowlist, "allowlist"),
+        ] {
+            assert_eq!(mode.to_string(), str_ver);
+            assert_eq!(FederationMode::try_from(str_ver), Ok(mode));
+        }
+        assert!(FederationMode::try_from("closed").is_err());
+    }
+}
@@ -1,10 +1,24 @@
+pub mod account_handle;
+pub mod block_severity;
+pub mod federation_mode;
+pub mod new_poll;
 pub mod new_user;
+pub mod parsed_hashtag;
+pub mod parsed_mention;
 pub mod user_email;
 pub mod user_name;
 pub mod user_role;
+pub mod visibility;
 
 // Re-export
+pub use account_handle::AccountHandle;
+pub use block_severity::BlockSeverity;
+pub use federation_mode::FederationMode;
+pub use new_poll::NewPoll;
 pub use new_user::AppUser;
+pub use parsed_hashtag::ParsedHashtag;
+pub use parsed_mention::ParsedMention;
 pub use user_email::UserEmail;
 pub use user_name::UserName;
 pub use user_role::UserRole;
+pub use visibility::Visibility;
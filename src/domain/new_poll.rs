@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+/// The fewest options a poll can have.
+pub const MIN_POLL_OPTIONS: usize = 2;
+/// The most options a poll can have.
+pub const MAX_POLL_OPTIONS: usize = 8;
+const MAX_OPTION_CHARS: usize = 50;
+const MIN_EXPIRY: Duration = Duration::from_secs(5 * 60);
+const MAX_EXPIRY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// A valid poll to attach to a new post.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NewPoll {
+    options: Vec<String>,
+    multiple: bool,
+    expires_in: Duration,
+}
+
+impl NewPoll {
+    /// A poll offering `options`, of which voters pick one or, if `multiple`
+    /// is set, any number, and which closes after `expires_in`.
+    pub fn parse(
+        options: Vec<String>,
+        multiple: bool,
+        expires_in: Duration,
+    ) -> Result<NewPoll, String> {
+        let options: Vec<String> = options.iter().map(|o| o.trim().to_string()).collect();
+        if options.len() < MIN_POLL_OPTIONS || options.len() > MAX_POLL_OPTIONS {
+            return Err(format!(
+                "a poll needs {} to {} options",
+                MIN_POLL_OPTIONS, MAX_POLL_OPTIONS
+            ));
+        }
+        for (i, option) in options.iter().enumerate() {
+            if option.is_empty() || option.chars().count() > MAX_OPTION_CHARS {
+                return Err("empty poll option or too long".to_string());
+            }
+            if options[..i].contains(option) {
+                return Err(format!("duplicate poll option: {}", option));
+            }
+        }
+        if expires_in < MIN_EXPIRY || expires_in > MAX_EXPIRY {
+            return Err("polls last from five minutes to 30 days".to_string());
+        }
+
+        Ok(Self {
+            options,
+            multiple,
+            expires_in,
+        })
+    }
+
+    pub fn options(&self) -> &[String] {
+        &self.options
+    }
+
+    pub fn multiple(&self) -> bool {
+        self.multiple
+    }
+
+    pub fn expires_in(&self) -> Duration {
+        self.expires_in
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::NewPoll;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn options(n: usize) -> Vec<String> {
+        (1..=n).map(|i| format!("option {}", i)).collect()
+    }
+
+    #[test]
+    fn polls_have_two_to_eight_options() {
+        assert!(NewPoll::parse(options(1), false, DAY).is_err());
+        assert!(NewPoll::parse(options(2), false, DAY).is_ok());
+        assert!(NewPoll::parse(options(8), true, DAY).is_ok());
+        assert!(NewPoll::parse(options(9), false, DAY).is_err());
+    }
+
+    #[test]
+    fn options_must_be_distinct_and_not_blank() {
+        let duplicate = vec!["yes".to_string(), " yes ".to_string()];
+        assert!(NewPoll::parse(duplicate, false, DAY).is_err());
+        let blank = vec!["yes".to_string(), "  ".to_string()];
+        assert!(NewPoll::parse(blank, false, DAY).is_err());
+    }
+
+    #[test]
+    fn expiry_is_bounded() {
+        assert!(NewPoll::parse(options(2), false, Duration::from_secs(60)).is_err());
+        assert!(NewPoll::parse(options(2), false, DAY * 31).is_err());
+        let poll = NewPoll::parse(options(2), false, DAY).unwrap();
+        assert_eq!(poll.expires_in(), DAY);
+    }
+}
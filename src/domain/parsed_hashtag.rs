@@ -0,0 +1,120 @@
+use std::ops::Range;
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest hashtag that is recognised, in characters
+const MAX_LENGTH: usize = 100;
+
+/// A `#hashtag` found in the text of a post.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedHashtag {
+    /// The tag as written, without the leading `#`
+    pub name: String,
+    /// Where the hashtag, including the leading `#`, is in the text
+    pub range: Range<usize>,
+}
+
+impl ParsedHashtag {
+    /// Find all hashtags in `text`, in order. A `#` only starts a hashtag at
+    /// the beginning of a word, and tags made only of digits are ignored so
+    /// that "issue #12" is not a tag.
+    pub fn parse_all(text: &str) -> Vec<ParsedHashtag> {
+        let mut tags = vec![];
+        let mut prev: Option<char> = None;
+        let mut chars = text.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let at_word_start = prev
+                .map(|p| !is_tag_char(p) && !matches!(p, '/' | '&' | '#'))
+                .unwrap_or(true);
+            prev = Some(c);
+            if c != '#' || !at_word_start {
+                continue;
+            }
+
+            let rest = &text[start + 1..];
+            let len = rest
+                .char_indices()
+                .find(|(_, c)| !is_tag_char(*c))
+                .map(|(i, _)| i)
+                .unwrap_or(rest.len());
+            let name = &rest[..len];
+            if name.is_empty()
+                || name.chars().count() > MAX_LENGTH
+                || name.chars().all(|c| c.is_numeric() || c == '_')
+            {
+                continue;
+            }
+
+            let end = start + 1 + len;
+            while let Some(&(i, _)) = chars.peek() {
+                if i >= end {
+                    break;
+                }
+                prev = chars.next().map(|(_, c)| c);
+            }
+            tags.push(ParsedHashtag {
+                name: name.to_string(),
+                range: start..end,
+            });
+        }
+
+        tags
+    }
+
+    /// The case folded form of the tag, under which it is stored.
+    pub fn folded(&self) -> String {
+        fold(&self.name)
+    }
+}
+
+/// Fold `name` so that all spellings of a tag compare equal: compatibility
+/// characters are normalized (NFKC) and the result is lower-cased, which
+/// handles scripts beyond ASCII too.
+pub fn fold(name: &str) -> String {
+    let name = name.trim_start_matches('#');
+    name.nfkc()
+        .collect::<String>()
+        .to_lowercase()
+        .nfkc()
+        .collect()
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\u{b7}' || c == '\u{200c}'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fold, ParsedHashtag};
+
+    fn names(text: &str) -> Vec<String> {
+        ParsedHashtag::parse_all(text)
+            .into_iter()
+            .map(|t| t.name)
+            .collect()
+    }
+
+    #[test]
+    fn hashtags_are_found() {
+        let text = "#Rust and #日本語, also #café_au_lait!";
+        assert_eq!(names(text), vec!["Rust", "日本語", "café_au_lait"]);
+        let tags = ParsedHashtag::parse_all(text);
+        assert_eq!(&text[tags[1].range.clone()], "#日本語");
+    }
+
+    #[test]
+    fn numbers_anchors_and_entities_are_not_hashtags() {
+        assert!(names("see issue #12").is_empty());
+        assert!(names("https://example.com/page#section").is_empty());
+        assert!(names("a&#39;b and ##double and a lonely # sign").is_empty());
+    }
+
+    #[test]
+    fn spellings_of_a_tag_fold_together() {
+        assert_eq!(fold("Rust"), "rust");
+        assert_eq!(fold("#ÉTÉ"), "été");
+        // Decomposed and precomposed accents
+        assert_eq!(fold("Cafe\u{301}"), fold("CAFÉ"));
+        // Fullwidth letters are compatibility characters
+        assert_eq!(fold("ＲＵＳＴ"), "rust");
+    }
+}
@@ -0,0 +1,126 @@
+use std::ops::Range;
+
+/// A `@user` or `@user@domain` mention found in the text of a post.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedMention {
+    pub username: String,
+    /// Lower-cased, with the port if one was given
+    pub domain: Option<String>,
+    /// Where the mention, including the leading `@`, is in the text
+    pub range: Range<usize>,
+}
+
+impl ParsedMention {
+    /// Find all mentions in `text`, in order. An `@` only starts a mention
+    /// at the beginning of a word, so email addresses are not mistaken for
+    /// mentions.
+    pub fn parse_all(text: &str) -> Vec<ParsedMention> {
+        let mut mentions = vec![];
+        let mut prev: Option<char> = None;
+        let mut chars = text.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let at_word_start = prev.map(|p| !is_word(p) && p != '/').unwrap_or(true);
+            prev = Some(c);
+            if c != '@' || !at_word_start {
+                continue;
+            }
+
+            let username = scan(&text[start + 1..], |c| is_word(c) || c == '.' || c == '-');
+            if username.is_empty() {
+                continue;
+            }
+            let mut end = start + 1 + username.len();
+            let mut domain = None;
+            if text[end..].starts_with('@') {
+                let host = scan(&text[end + 1..], |c| is_word(c) || c == '.' || c == '-');
+                if !host.is_empty() {
+                    let mut len = host.len();
+                    let rest = &text[end + 1 + len..];
+                    if let Some(port) = rest.strip_prefix(':') {
+                        let digits = port.chars().take_while(char::is_ascii_digit).count();
+                        if digits > 0 {
+                            len += 1 + digits;
+                        }
+                    }
+                    domain = Some(text[end + 1..end + 1 + len].to_lowercase());
+                    end += 1 + len;
+                }
+            }
+
+            while let Some(&(i, _)) = chars.peek() {
+                if i >= end {
+                    break;
+                }
+                prev = chars.next().map(|(_, c)| c);
+            }
+            mentions.push(ParsedMention {
+                username: username.to_string(),
+                domain,
+                range: start..end,
+            });
+        }
+
+        mentions
+    }
+
+    /// The mention as written, without the leading `@`.
+    pub fn acct(&self) -> String {
+        match &self.domain {
+            Some(domain) => format!("{}@{}", self.username, domain),
+            None => self.username.clone(),
+        }
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The longest prefix of `s` made of characters accepted by `accept`, without
+/// trailing dots and dashes, which are more likely punctuation.
+fn scan(s: &str, accept: impl Fn(char) -> bool) -> &str {
+    let len = s
+        .char_indices()
+        .find(|(_, c)| !accept(*c))
+        .map(|(i, _)| i)
+        .unwrap_or(s.len());
+
+    s[..len].trim_end_matches(['.', '-'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParsedMention;
+
+    fn accts(text: &str) -> Vec<String> {
+        ParsedMention::parse_all(text)
+            .iter()
+            .map(ParsedMention::acct)
+            .collect()
+    }
+
+    #[test]
+    fn local_and_remote_mentions_are_found() {
+        let text = "hi @alice and @Bob@Example.COM!";
+        let mentions = ParsedMention::parse_all(text);
+        assert_eq!(accts(text), vec!["alice", "Bob@example.com"]);
+        assert_eq!(&text[mentions[1].range.clone()], "@Bob@Example.COM");
+    }
+
+    #[test]
+    fn trailing_punctuation_is_not_part_of_a_mention() {
+        assert_eq!(accts("thanks @alice."), vec!["alice"]);
+        assert_eq!(accts("(@bob@example.com.)"), vec!["bob@example.com"]);
+        assert_eq!(
+            accts("@carol@localhost:8080, hi"),
+            vec!["carol@localhost:8080"]
+        );
+    }
+
+    #[test]
+    fn email_addresses_and_urls_are_not_mentions() {
+        assert!(accts("write to bob@example.com").is_empty());
+        assert!(accts("see https://example.com/@alice").is_empty());
+        assert!(accts("a lonely @ sign").is_empty());
+    }
+}
@@ -56,7 +56,7 @@ mod tests {
     fn valid_emails_parsed_successfully() {
         let s = SafeEmail().fake();
         assert!(
-            !UserEmail::parse(s).is_err(),
+            UserEmail::parse(s).is_ok(),
             "correctly formatted emails are parsed successfully"
         )
     }
@@ -38,7 +38,7 @@ mod tests {
     fn long_name_256_is_valid() {
         let name = "a".repeat(256);
         assert!(
-            !UserName::parse(name).is_err(),
+            UserName::parse(name).is_ok(),
             "usernames up to 256 chars are valid"
         );
     }
@@ -0,0 +1,78 @@
+use std::fmt;
+
+/// Who a post is addressed to.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum Visibility {
+    /// Everyone, and listed on public timelines
+    #[default]
+    Public,
+    /// Everyone, but kept off public timelines
+    Unlisted,
+    /// Followers only
+    Followers,
+    /// Mentioned actors only
+    Direct,
+}
+
+impl TryFrom<String> for Visibility {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
+impl TryFrom<&str> for Visibility {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "public" => Ok(Self::Public),
+            "unlisted" => Ok(Self::Unlisted),
+            "followers" => Ok(Self::Followers),
+            "direct" => Ok(Self::Direct),
+            other => Err(format!("Unknown visibility: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for Visibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Visibility::Public => write!(f, "public"),
+            Visibility::Unlisted => write!(f, "unlisted"),
+            Visibility::Followers => write!(f, "followers"),
+            Visibility::Direct => write!(f, "direct"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Visibility;
+
+    #[test]
+    fn visibility_round_trips_through_strings() {
+        let cases = [
+            (Visibility::Public, "public"),
+            (Visibility::Unlisted, "unlisted"),
+            (Visibility::Followers, "followers"),
+            (Visibility::Direct, "direct"),
+        ];
+
+        for (visibility, str_ver) in cases {
+            assert_eq!(visibility.to_string(), str_ver);
+            assert_eq!(Visibility::try_from(str_ver), Ok(visibility));
+            assert_eq!(
+                Visibility::try_from(str_ver.to_uppercase()),
+                Ok(visibility),
+                "parsing is case insensitive"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_visibility_is_rejected() {
+        assert!(Visibility::try_from("friends").is_err());
+    }
+}
@@ -8,7 +8,17 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i64,
     pub user_id: i64,
+    #[sea_orm(unique)]
+    pub username: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub summary: Option<String>,
+    pub locked: bool,
     pub updated_at: DateTime,
+    pub also_known_as: Json,
+    pub moved_to: Option<String>,
+    pub moved_at: Option<DateTime>,
+    pub avatar_url: Option<String>,
+    pub fields: Json,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -21,8 +31,18 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     User,
+    #[sea_orm(has_many = "super::account_key::Entity")]
+    AccountKey,
     #[sea_orm(has_many = "super::content::Entity")]
     Content,
+    #[sea_orm(has_many = "super::delivery::Entity")]
+    Delivery,
+    #[sea_orm(has_many = "super::follower::Entity")]
+    Follower,
+    #[sea_orm(has_many = "super::following::Entity")]
+    Following,
+    #[sea_orm(has_many = "super::poll_vote::Entity")]
+    PollVote,
 }
 
 impl Related<super::user::Entity> for Entity {
@@ -31,10 +51,40 @@ impl Related<super::user::Entity> for Entity {
     }
 }
 
+impl Related<super::account_key::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AccountKey.def()
+    }
+}
+
 impl Related<super::content::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Content.def()
     }
 }
 
+impl Related<super::delivery::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Delivery.def()
+    }
+}
+
+impl Related<super::follower::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Follower.def()
+    }
+}
+
+impl Related<super::following::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Following.def()
+    }
+}
+
+impl Related<super::poll_vote::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PollVote.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -10,9 +10,14 @@ pub struct Model {
     pub publisher_id: i64,
     pub cw: Option<String>,
     pub body: Option<String>,
-    pub published: Option<bool>,
+    pub published: bool,
     pub published_at: Option<DateTime>,
     pub updated_at: DateTime,
+    pub visibility: String,
+    pub deleted_at: Option<DateTime>,
+    pub edited_at: Option<DateTime>,
+    pub scheduled_at: Option<DateTime>,
+    pub base_url: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -25,6 +30,14 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Account,
+    #[sea_orm(has_many = "super::mention::Entity")]
+    Mention,
+    #[sea_orm(has_many = "super::content_tag::Entity")]
+    ContentTag,
+    #[sea_orm(has_many = "super::content_revision::Entity")]
+    ContentRevision,
+    #[sea_orm(has_one = "super::poll::Entity")]
+    Poll,
 }
 
 impl Related<super::account::Entity> for Entity {
@@ -33,4 +46,28 @@ impl Related<super::account::Entity> for Entity {
     }
 }
 
+impl Related<super::mention::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Mention.def()
+    }
+}
+
+impl Related<super::content_tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ContentTag.def()
+    }
+}
+
+impl Related<super::content_revision::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ContentRevision.def()
+    }
+}
+
+impl Related<super::poll::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Poll.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
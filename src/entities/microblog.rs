@@ -12,6 +12,9 @@ pub struct Model {
     #[sea_orm(column_type = "Text", nullable)]
     pub about: Option<String>,
     pub updated_at: DateTime,
+    pub federation_mode: String,
+    pub authorized_fetch: bool,
+    pub open_registrations: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -3,8 +3,28 @@
 pub mod prelude;
 
 pub mod account;
+pub mod account_key;
 pub mod content;
+pub mod content_revision;
+pub mod content_tag;
+pub mod delivery;
+pub mod delivery_host;
+pub mod domain_allow;
+pub mod domain_block;
+pub mod follower;
+pub mod following;
+pub mod inbox_queue;
 pub mod instance;
+pub mod instance_key;
+pub mod mention;
 pub mod microblog;
+pub mod poll;
+pub mod poll_option;
+pub mod poll_vote;
+pub mod relay;
+pub mod remote_actor;
+pub mod remote_object;
+pub mod remote_post;
+pub mod tag;
 pub mod user;
 pub mod user_token;
@@ -1,8 +1,28 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.3
 
 pub use super::account::Entity as Account;
+pub use super::account_key::Entity as AccountKey;
 pub use super::content::Entity as Content;
+pub use super::content_revision::Entity as ContentRevision;
+pub use super::content_tag::Entity as ContentTag;
+pub use super::delivery::Entity as Delivery;
+pub use super::delivery_host::Entity as DeliveryHost;
+pub use super::domain_allow::Entity as DomainAllow;
+pub use super::domain_block::Entity as DomainBlock;
+pub use super::follower::Entity as Follower;
+pub use super::following::Entity as Following;
+pub use super::inbox_queue::Entity as InboxQueue;
 pub use super::instance::Entity as Instance;
+pub use super::instance_key::Entity as InstanceKey;
+pub use super::mention::Entity as Mention;
 pub use super::microblog::Entity as Microblog;
+pub use super::poll::Entity as Poll;
+pub use super::poll_option::Entity as PollOption;
+pub use super::poll_vote::Entity as PollVote;
+pub use super::relay::Entity as Relay;
+pub use super::remote_actor::Entity as RemoteActor;
+pub use super::remote_object::Entity as RemoteObject;
+pub use super::remote_post::Entity as RemotePost;
+pub use super::tag::Entity as Tag;
 pub use super::user::Entity as User;
 pub use super::user_token::Entity as UserToken;
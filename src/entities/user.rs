@@ -13,6 +13,7 @@ pub struct Model {
     pub password: String,
     pub role: String,
     pub confirmed: bool,
+    pub expand_cws: bool,
     pub updated_at: DateTime,
 }
 
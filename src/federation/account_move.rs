@@ -0,0 +1,299 @@
+//! Moving accounts between servers.
+//!
+//! An account lists the other accounts of its owner in `alsoKnownAs`. It may
+//! only move to an account that lists it there in turn, which proves both are
+//! held by the same person. The move is announced with a `Move` activity to
+//! the followers of the old account, whose servers then follow the new one
+//! instead. Local accounts following a remote account that moves are
+//! redirected the same way.
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, Set,
+};
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::{
+    fetch::{parse_actor, FetchError, Fetcher},
+    queue::{self, QueueError},
+    remote,
+};
+use crate::{
+    activitypub::{activity::Activity, actor_url, followers_url, handle_from_actor_url},
+    domain::ParsedMention,
+    entities::{account, following, prelude::*, remote_actor},
+    error::error_chain_fmt,
+};
+
+/// An account named by a user, e.g. as an alias or as the destination of a
+/// move.
+#[derive(Debug, Clone)]
+pub struct ResolvedAccount {
+    pub actor_id: String,
+    /// The accounts it is also known as
+    pub aliases: Vec<String>,
+}
+
+/// The accounts `account` is also known as.
+pub fn aliases(account: &account::Model) -> Vec<String> {
+    serde_json::from_value(account.also_known_as.clone()).unwrap_or_default()
+}
+
+/// Resolve `input`, an actor URL or a `user@domain` address with or without
+/// the leading `@`, for the tenant at `base_url`. Remote actors are always
+/// fetched again, so their aliases are current.
+#[tracing::instrument(name = "Resolve account", skip(fetcher, conn))]
+pub async fn resolve<C: ConnectionTrait>(
+    fetcher: &Fetcher<'_>,
+    conn: &C,
+    base_url: &str,
+    input: &str,
+) -> Result<ResolvedAccount, MoveError> {
+    let input = input.trim();
+    let (scheme, host) = base_url.split_once("://").unwrap_or(("https", base_url));
+    let unknown = || MoveError::Invalid(format!("unknown account: {}", input));
+
+    let actor_id = if input.starts_with("https://") || input.starts_with("http://") {
+        input.to_string()
+    } else {
+        let address = format!("@{}", input.trim_start_matches('@'));
+        let found = match ParsedMention::parse_all(&address).as_slice() {
+            [found] if found.range == (0..address.len()) => found.clone(),
+            _ => return Err(unknown()),
+        };
+        match &found.domain {
+            Some(domain) if !domain.eq_ignore_ascii_case(host) => {
+                fetcher
+                    .webfinger(conn, scheme, &found.username, domain)
+                    .await?
+            }
+            _ => actor_url(base_url, &found.username.to_lowercase()),
+        }
+    };
+
+    if let Some(handle) = handle_from_actor_url(base_url, &actor_id) {
+        let account = Account::find()
+            .filter(account::Column::Username.eq(handle.to_lowercase()))
+            .one(conn)
+            .await?
+            .ok_or_else(unknown)?;
+        return Ok(ResolvedAccount {
+            actor_id: actor_url(base_url, account.username.as_deref().unwrap_or_default()),
+            aliases: aliases(&account),
+        });
+    }
+    let actor = remote::refresh_actor(fetcher, conn, &actor_id).await?;
+
+    Ok(ResolvedAccount {
+        aliases: remote_aliases(&actor)?,
+        actor_id: actor.actor_id,
+    })
+}
+
+/// Add the account named by `input` to the aliases of `account`. Returns the
+/// aliases of the account.
+pub async fn add_alias<C: ConnectionTrait>(
+    fetcher: &Fetcher<'_>,
+    conn: &C,
+    base_url: &str,
+    account: &account::Model,
+    input: &str,
+) -> Result<Vec<String>, MoveError> {
+    let alias = resolve(fetcher, conn, base_url, input).await?;
+    let own = actor_url(base_url, account.username.as_deref().unwrap_or_default());
+    if alias.actor_id == own {
+        return Err(MoveError::Invalid(
+            "an account cannot be its own alias".to_string(),
+        ));
+    }
+
+    let mut list = aliases(account);
+    if !list.contains(&alias.actor_id) {
+        list.push(alias.actor_id);
+        save_aliases(conn, account, &list).await?;
+    }
+
+    Ok(list)
+}
+
+/// Remove the actor `actor_id` from the aliases of `account`. Returns the
+/// aliases of the account.
+pub async fn remove_alias<C: ConnectionTrait>(
+    conn: &C,
+    account: &account::Model,
+    actor_id: &str,
+) -> Result<Vec<String>, MoveError> {
+    let mut list = aliases(account);
+    let before = list.len();
+    list.retain(|a| a != actor_id);
+    if list.len() != before {
+        save_aliases(conn, account, &list).await?;
+    }
+
+    Ok(list)
+}
+
+/// Move `account` to the account named by `input`, which must list it as an
+/// alias, and tell its followers. Returns the actor id of the new account.
+pub async fn move_to<C: ConnectionTrait>(
+    fetcher: &Fetcher<'_>,
+    conn: &C,
+    base_url: &str,
+    account: &account::Model,
+    input: &str,
+) -> Result<String, MoveError> {
+    if let Some(moved_to) = &account.moved_to {
+        return Err(MoveError::Invalid(format!(
+            "this account has already moved to {}",
+            moved_to
+        )));
+    }
+    let handle = account.username.as_deref().unwrap_or_default();
+    let own = actor_url(base_url, handle);
+    let target = resolve(fetcher, conn, base_url, input).await?;
+    if target.actor_id == own {
+        return Err(MoveError::Invalid(
+            "an account cannot move to itself".to_string(),
+        ));
+    }
+    if !target.aliases.contains(&own) {
+        return Err(MoveError::Invalid(format!(
+            "{} does not list {} as an alias",
+            target.actor_id, own
+        )));
+    }
+
+    let now = Utc::now();
+    account::ActiveModel {
+        id: Set(account.id),
+        moved_to: Set(Some(target.actor_id.clone())),
+        moved_at: Set(Some(now.naive_utc())),
+        ..Default::default()
+    }
+    .update(conn)
+    .await?;
+
+    let mut activity = Activity::new(
+        "Move",
+        format!("{}#moves/{}", own, now.timestamp()),
+        own.clone(),
+        Value::from(own.clone()),
+    );
+    activity.target = Value::from(target.actor_id.clone());
+    activity.to = vec![followers_url(base_url, handle)];
+    let inboxes = queue::follower_inboxes(conn, account.id).await?;
+    queue::enqueue(conn, account.id, &own, &activity, inboxes).await?;
+
+    Ok(target.actor_id)
+}
+
+/// Have the local accounts following `origin`, a remote account that moved
+/// to `target`, follow `target` instead. The follows of `origin` are undone
+/// if its inbox is known. Returns the number of follows redirected.
+pub async fn redirect_follows<C: ConnectionTrait>(
+    conn: &C,
+    base_url: &str,
+    origin: &str,
+    origin_inbox: Option<String>,
+    target: &remote_actor::Model,
+) -> Result<usize, MoveError> {
+    let follows = Following::find()
+        .filter(following::Column::ActorId.eq(origin))
+        .find_also_related(Account)
+        .all(conn)
+        .await?;
+
+    let mut redirected = 0;
+    for (follow, account) in follows {
+        let Some(account) = account else {
+            continue;
+        };
+        let actor = actor_url(base_url, account.username.as_deref().unwrap_or_default());
+        Following::delete_by_id(follow.id).exec(conn).await?;
+        if let Some(inbox) = &origin_inbox {
+            let mut undo = Activity::new(
+                "Undo",
+                format!("{}/undo", follow.follow_id),
+                actor.clone(),
+                serde_json::json!({
+                    "id": follow.follow_id,
+                    "type": "Follow",
+                    "actor": actor,
+                    "object": origin,
+                }),
+            );
+            undo.to = vec![origin.to_string()];
+            queue::enqueue(conn, account.id, &actor, &undo, [inbox.clone()]).await?;
+        }
+
+        let already_following = Following::find()
+            .filter(following::Column::AccountId.eq(account.id))
+            .filter(following::Column::ActorId.eq(target.actor_id.as_str()))
+            .one(conn)
+            .await?
+            .is_some();
+        if already_following {
+            continue;
+        }
+        let follow_id = format!("{}#follows/{}", actor, Uuid::new_v4());
+        following::ActiveModel {
+            account_id: Set(account.id),
+            actor_id: Set(target.actor_id.clone()),
+            follow_id: Set(follow_id.clone()),
+            accepted: Set(false),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await?;
+        let mut follow = Activity::new(
+            "Follow",
+            follow_id,
+            actor.clone(),
+            Value::from(target.actor_id.clone()),
+        );
+        follow.to = vec![target.actor_id.clone()];
+        queue::enqueue(conn, account.id, &actor, &follow, [target.inbox.clone()]).await?;
+        redirected += 1;
+    }
+
+    Ok(redirected)
+}
+
+/// The accounts the cached remote actor `actor` is also known as.
+pub fn remote_aliases(actor: &remote_actor::Model) -> Result<Vec<String>, FetchError> {
+    Ok(parse_actor(actor.document.clone(), &actor.actor_id)?.also_known_as)
+}
+
+async fn save_aliases<C: ConnectionTrait>(
+    conn: &C,
+    account: &account::Model,
+    aliases: &[String],
+) -> Result<(), DbErr> {
+    account::ActiveModel {
+        id: Set(account.id),
+        also_known_as: Set(Value::from(aliases.to_vec())),
+        ..Default::default()
+    }
+    .update(conn)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(thiserror::Error)]
+pub enum MoveError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error(transparent)]
+    Fetch(#[from] FetchError),
+    #[error(transparent)]
+    Queue(#[from] QueueError),
+    #[error(transparent)]
+    Database(#[from] DbErr),
+}
+
+impl std::fmt::Debug for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
@@ -0,0 +1,53 @@
+//! Posting activities to remote inboxes.
+use serde::Serialize;
+
+use super::{FederationState, SignatureError, Signer};
+use crate::{activitypub::ACTIVITY_JSON, error::error_chain_fmt};
+
+/// Sign and POST `activity` to `inbox`, unless `federation` must not reach
+/// it.
+#[tracing::instrument(name = "Deliver activity", skip(federation, signer, activity), fields(key_id = %signer.key_id))]
+pub async fn deliver<T: Serialize>(
+    federation: &FederationState,
+    signer: &Signer,
+    inbox: &str,
+    activity: &T,
+) -> Result<(), DeliveryError> {
+    let body = serde_json::to_vec(activity)
+        .map_err(|e| DeliveryError::UnexpectedError(anyhow::anyhow!(e)))?;
+    let http = &federation.http;
+    let mut request = http
+        .post(inbox)
+        .header("Content-Type", ACTIVITY_JSON)
+        .body(body)
+        .build()?;
+    federation
+        .check_url(request.url())
+        .map_err(|e| DeliveryError::UnexpectedError(anyhow::anyhow!(e)))?;
+    signer.sign(&mut request)?;
+
+    let response = http.execute(request).await?;
+    if !response.status().is_success() {
+        return Err(DeliveryError::Rejected(response.status().as_u16()));
+    }
+
+    Ok(())
+}
+
+#[derive(thiserror::Error)]
+pub enum DeliveryError {
+    #[error("the inbox answered with status {0}")]
+    Rejected(u16),
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for DeliveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
@@ -0,0 +1,236 @@
+//! Dereferencing remote ActivityPub objects.
+use reqwest::{header, Response};
+use sea_orm::ConnectionTrait;
+use serde_json::Value;
+use url::Url;
+
+use super::{policy::DomainPolicies, same_origin, FederationState, SignatureError, Signer};
+use crate::{
+    activitypub::{actor::Person, ACTIVITY_JSON, JRD_JSON, LD_JSON},
+    error::error_chain_fmt,
+};
+
+/// Redirects followed before giving up on a document.
+const MAX_REDIRECTS: usize = 5;
+/// The largest document read, in bytes.
+const MAX_DOCUMENT_SIZE: usize = 1024 * 1024;
+
+/// Dereferences ActivityPub ids. Requests are signed when the fetcher has a
+/// key, which servers in authorized fetch mode insist on.
+#[derive(Clone, Copy)]
+pub struct Fetcher<'a> {
+    federation: &'a FederationState,
+    signer: Option<&'a Signer>,
+}
+
+impl<'a> Fetcher<'a> {
+    /// A fetcher making unsigned requests with the HTTP client of
+    /// `federation`, which only reaches the addresses it allows.
+    pub fn new(federation: &'a FederationState) -> Self {
+        Self {
+            federation,
+            signer: None,
+        }
+    }
+
+    /// Sign requests with `signer`, if there is one.
+    pub fn signed_by(mut self, signer: Option<&'a Signer>) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    /// GET `url` as ActivityPub JSON. The `id` of the document must be on the
+    /// same origin as the URL it was finally served from. Servers the tenant
+    /// behind `conn` does not federate with are not contacted, including
+    /// those a redirect points to, and neither are addresses the server must
+    /// not reach.
+    #[tracing::instrument(name = "Fetch remote object", skip(self, conn))]
+    pub async fn fetch_json<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        url: &str,
+    ) -> Result<Value, FetchError> {
+        let mut url =
+            Url::parse(url).map_err(|e| FetchError::InvalidUrl(format!("{}: {}", url, e)))?;
+
+        for _ in 0..=MAX_REDIRECTS {
+            check_policies(conn, url.as_str()).await?;
+            self.federation
+                .check_url(&url)
+                .map_err(FetchError::Unreachable)?;
+            let mut request = self
+                .federation
+                .http
+                .get(url.clone())
+                .header("Accept", format!("{}, {}", ACTIVITY_JSON, LD_JSON))
+                .build()?;
+            if let Some(signer) = self.signer {
+                signer.sign(&mut request)?;
+            }
+
+            let response = self.federation.http.execute(request).await?;
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|l| l.to_str().ok())
+                    .ok_or_else(|| {
+                        FetchError::InvalidDocument(format!("{} redirects nowhere", url))
+                    })?;
+                url = url
+                    .join(location)
+                    .map_err(|e| FetchError::InvalidUrl(format!("{}: {}", location, e)))?;
+                continue;
+            }
+
+            let document = read_json(response.error_for_status()?).await?;
+            let id = document["id"]
+                .as_str()
+                .ok_or_else(|| FetchError::InvalidDocument(format!("{} has no id", url)))?;
+            if !same_origin(id, url.as_str()) {
+                return Err(FetchError::InvalidDocument(format!(
+                    "{} was served by {}",
+                    id, url
+                )));
+            }
+
+            return Ok(document);
+        }
+
+        Err(FetchError::InvalidDocument(format!(
+            "too many redirects fetching {}",
+            url
+        )))
+    }
+
+    /// Look up the actor id of `username@domain` with WebFinger, reaching
+    /// `domain` with `scheme`. Servers the tenant behind `conn` does not
+    /// federate with are not asked.
+    #[tracing::instrument(name = "WebFinger lookup", skip(self, conn))]
+    pub async fn webfinger<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        scheme: &str,
+        username: &str,
+        domain: &str,
+    ) -> Result<String, FetchError> {
+        let endpoint = format!("{}://{}/.well-known/webfinger", scheme, domain);
+        check_policies(conn, &endpoint).await?;
+        let url = Url::parse_with_params(
+            &endpoint,
+            &[("resource", format!("acct:{}@{}", username, domain))],
+        )
+        .map_err(|e| FetchError::InvalidUrl(format!("{}: {}", endpoint, e)))?;
+        self.federation
+            .check_url(&url)
+            .map_err(FetchError::Unreachable)?;
+
+        let response = self
+            .federation
+            .http
+            .get(url.clone())
+            .header("Accept", JRD_JSON)
+            .send()
+            .await?;
+        let jrd = read_json(response.error_for_status()?).await?;
+        let actor = jrd["links"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|link| {
+                let kind = link["type"].as_str().unwrap_or_default();
+                link["rel"] == "self" && (kind == ACTIVITY_JSON || kind.starts_with(LD_JSON))
+            })
+            .and_then(|link| link["href"].as_str())
+            .ok_or_else(|| FetchError::InvalidDocument(format!("{} names no actor", url)))?;
+
+        Ok(actor.to_string())
+    }
+
+    /// Fetch the actor document at `id`.
+    pub async fn fetch_actor<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        id: &str,
+    ) -> Result<Person, FetchError> {
+        let document = self.fetch_json(conn, id).await?;
+
+        parse_actor(document, id)
+    }
+}
+
+/// Refuse to contact `url` if the tenant behind `conn` does not federate
+/// with its server.
+pub(super) async fn check_policies<C: ConnectionTrait>(
+    conn: &C,
+    url: &str,
+) -> Result<(), FetchError> {
+    if DomainPolicies::for_url(conn, url).await?.refuses(url) {
+        return Err(FetchError::Refused(url.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Read the body of `response` as JSON, giving up on bodies larger than
+/// `MAX_DOCUMENT_SIZE`.
+async fn read_json(mut response: Response) -> Result<Value, FetchError> {
+    let url = response.url().clone();
+    let too_large = || {
+        FetchError::InvalidDocument(format!(
+            "{} is larger than {} bytes",
+            url, MAX_DOCUMENT_SIZE
+        ))
+    };
+    if response.content_length().unwrap_or_default() > MAX_DOCUMENT_SIZE as u64 {
+        return Err(too_large());
+    }
+    let mut body = vec![];
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > MAX_DOCUMENT_SIZE {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    serde_json::from_slice(&body)
+        .map_err(|e| FetchError::InvalidDocument(format!("{} is not JSON: {}", url, e)))
+}
+
+/// Read `document` as the actor `id`.
+pub fn parse_actor(document: Value, id: &str) -> Result<Person, FetchError> {
+    let person: Person = serde_json::from_value(document)
+        .map_err(|e| FetchError::InvalidDocument(format!("{} is not an actor: {}", id, e)))?;
+    if person.id != id {
+        return Err(FetchError::InvalidDocument(format!(
+            "{} claims to be {}",
+            id, person.id
+        )));
+    }
+
+    Ok(person)
+}
+
+#[derive(thiserror::Error)]
+pub enum FetchError {
+    #[error("invalid URL {0}")]
+    InvalidUrl(String),
+    #[error("{0}")]
+    InvalidDocument(String),
+    #[error("this server does not federate with {0}")]
+    Refused(String),
+    #[error("refusing to contact {0}")]
+    Unreachable(String),
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Database(#[from] sea_orm::DbErr),
+}
+
+impl std::fmt::Debug for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
@@ -0,0 +1,111 @@
+//! Follow requests waiting for the followed account to approve them.
+//!
+//! Follows of locked accounts, and follows from silenced domains, are stored
+//! as followers that are not accepted yet. The account answers them with an
+//! `Accept`, which makes the follower a follower, or a `Reject`, which drops
+//! it.
+use anyhow::Context;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    Set,
+};
+
+use super::queue::{self, QueueError};
+use crate::{
+    activitypub::{activity::Activity, actor_url},
+    entities::{account, follower, prelude::*},
+};
+
+/// The follow requests of the account `account_id` not answered yet, oldest
+/// first.
+pub async fn pending<C: ConnectionTrait>(
+    conn: &C,
+    account_id: i64,
+) -> Result<Vec<follower::Model>, DbErr> {
+    Follower::find()
+        .filter(follower::Column::AccountId.eq(account_id))
+        .filter(follower::Column::Accepted.eq(false))
+        .order_by_asc(follower::Column::Id)
+        .all(conn)
+        .await
+}
+
+/// Accept or reject the pending follow request `id` of `account` and let the
+/// follower know. Returns whether there was such a request.
+pub async fn answer<C: ConnectionTrait>(
+    conn: &C,
+    base_url: &str,
+    account: &account::Model,
+    id: i64,
+    accept: bool,
+) -> Result<bool, QueueError> {
+    let Some(request) = Follower::find_by_id(id)
+        .filter(follower::Column::AccountId.eq(account.id))
+        .filter(follower::Column::Accepted.eq(false))
+        .one(conn)
+        .await?
+    else {
+        return Ok(false);
+    };
+
+    // Only the id of the Follow is kept, which is what servers match the
+    // answer on
+    let mut follow = Activity::new(
+        "Follow",
+        request.follow_id.clone(),
+        request.actor_id.clone(),
+        serde_json::Value::String(actor_url(
+            base_url,
+            account.username.as_deref().unwrap_or_default(),
+        )),
+    );
+    follow.context = serde_json::Value::Null;
+    send_answer(
+        conn,
+        base_url,
+        account,
+        &follow,
+        request.id,
+        request.inbox.clone(),
+        accept,
+    )
+    .await?;
+
+    if accept {
+        let mut model: follower::ActiveModel = request.into();
+        model.accepted = Set(true);
+        model.update(conn).await?;
+    } else {
+        Follower::delete_by_id(request.id).exec(conn).await?;
+    }
+
+    Ok(true)
+}
+
+/// Queue the `Accept` or the `Reject` of `follow` for the follower's `inbox`.
+pub async fn send_answer<C: ConnectionTrait>(
+    conn: &C,
+    base_url: &str,
+    account: &account::Model,
+    follow: &Activity,
+    follower_id: i64,
+    inbox: String,
+    accept: bool,
+) -> Result<(), QueueError> {
+    let actor = actor_url(base_url, account.username.as_deref().unwrap_or_default());
+    let (kind, fragment) = match accept {
+        true => ("Accept", "accepts"),
+        false => ("Reject", "rejects"),
+    };
+
+    let mut answer = Activity::new(
+        kind,
+        format!("{}#{}/follows/{}", actor, fragment, follower_id),
+        actor.clone(),
+        serde_json::to_value(follow).context("Failed to serialize Follow")?,
+    );
+    answer.to = vec![follow.actor.clone()];
+    queue::enqueue(conn, account.id, &actor, &answer, [inbox]).await?;
+
+    Ok(())
+}
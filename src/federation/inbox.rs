@@ -0,0 +1,893 @@
+//! Processing of activities delivered to our inboxes.
+use anyhow::Context;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    Set,
+};
+
+use super::{
+    account_move,
+    fetch::{parse_actor, FetchError, Fetcher},
+    follow_request, instance_signer,
+    policy::DomainPolicies,
+    queue, relay, remote, same_origin, signer_for_account, Signer,
+};
+use crate::{
+    activitypub::{
+        activity::Activity, actor_url, handle_from_actor_url, note::Note, status_from_url,
+    },
+    db,
+    entities::{
+        account, content_revision, content_tag, follower, following, prelude::*, remote_post,
+    },
+    error::{error_chain_fmt, TenantMapError},
+    orm,
+    polls::{self, Poll},
+    revisions,
+    routes::AppState,
+    tags,
+};
+
+/// Everything needed to process an activity on behalf of one tenant.
+pub struct InboxContext<'a> {
+    pub state: &'a AppState,
+    pub db: DatabaseConnection,
+    /// Externally visible URL of the tenant
+    pub base_url: String,
+}
+
+/// Parse an inbox request body.
+pub fn parse_activity(body: &[u8]) -> Result<Activity, InboxError> {
+    serde_json::from_slice(body)
+        .map_err(|e| InboxError::BadRequest(format!("malformed activity: {}", e)))
+}
+
+/// Check that `activity` may be accepted from `signed_by`, the owner of the
+/// key that signed the request delivering it: actors only act on their own
+/// behalf, from domains the tenant federates with. Returns the domain
+/// policies for the actor.
+pub async fn admit(
+    ctx: &InboxContext<'_>,
+    activity: &Activity,
+    signed_by: &str,
+) -> Result<DomainPolicies, InboxError> {
+    if activity.actor != signed_by {
+        return Err(InboxError::Unauthorized(format!(
+            "{} cannot act on behalf of {}",
+            signed_by, activity.actor
+        )));
+    }
+    if !same_origin(&activity.id, &activity.actor) {
+        return Err(InboxError::BadRequest(format!(
+            "activity {} is not hosted by its actor",
+            activity.id
+        )));
+    }
+    let policies = DomainPolicies::for_url(&ctx.db, &activity.actor)
+        .await
+        .context("Failed to look up domain policies")?;
+    if policies.refuses(&activity.actor) {
+        return Err(InboxError::Forbidden(format!(
+            "this server does not federate with the domain of {}",
+            activity.actor
+        )));
+    }
+    // A server that just reached us is up, whatever our deliveries said.
+    if let Some(host) = queue::host_of(&activity.actor) {
+        queue::mark_reachable(&ctx.db, &host)
+            .await
+            .context("Failed to update delivery host")?;
+    }
+
+    Ok(policies)
+}
+
+/// Apply `activity`, which was delivered in a request signed by `signed_by`.
+/// Activity types we do not support are accepted and ignored, and so is
+/// anything the domain policies of the tenant reject.
+#[tracing::instrument(
+    name = "Process inbox activity",
+    skip(ctx, activity),
+    fields(
+        activity_id = %activity.id,
+        kind = %activity.kind,
+    )
+)]
+pub async fn process(
+    ctx: &InboxContext<'_>,
+    activity: &Activity,
+    signed_by: &str,
+) -> Result<(), InboxError> {
+    // Policies may have changed since the activity was admitted
+    let policies = admit(ctx, activity, signed_by).await?;
+
+    match activity.kind.as_str() {
+        "Follow" => follow(ctx, activity, &policies).await,
+        "Accept" => answer_follow(ctx, activity, true).await,
+        "Reject" => answer_follow(ctx, activity, false).await,
+        "Undo" => undo(ctx, activity).await,
+        "Create" => create(ctx, activity, &policies).await,
+        "Announce" => announce(ctx, activity).await,
+        "Update" => update(ctx, activity, &policies).await,
+        "Delete" => delete(ctx, activity).await,
+        "Move" => move_account(ctx, activity).await,
+        "Flag" => flag(activity, &policies),
+        kind => {
+            tracing::debug!("ignoring unsupported activity type {}", kind);
+            Ok(())
+        }
+    }
+}
+
+async fn follow(
+    ctx: &InboxContext<'_>,
+    activity: &Activity,
+    policies: &DomainPolicies,
+) -> Result<(), InboxError> {
+    let object = activity
+        .object_id()
+        .ok_or_else(|| InboxError::BadRequest("Follow has no object".to_string()))?;
+    let account = local_account(ctx, object)
+        .await?
+        .ok_or_else(|| InboxError::NotFound(format!("{} is not a local actor", object)))?;
+    let signer = account_signer(ctx, &account).await?;
+    let fetcher = Fetcher::new(&ctx.state.federation).signed_by(signer.as_ref());
+    let remote = remote::actor(&fetcher, &ctx.db, &activity.actor)
+        .await
+        .context("Failed to fetch the follower's actor document")?;
+
+    let existing = Follower::find()
+        .filter(follower::Column::AccountId.eq(account.id))
+        .filter(follower::Column::ActorId.eq(activity.actor.as_str()))
+        .one(&ctx.db)
+        .await
+        .context("Failed to retrieve follower")?;
+    // Followers from silenced domains need approval even if the account is
+    // not locked
+    let approval_needed = account.locked || policies.is_silenced(&activity.actor);
+    let accepted = !approval_needed || existing.as_ref().map(|f| f.accepted).unwrap_or(false);
+    let mut model = match existing {
+        Some(f) => f.into(),
+        None => follower::ActiveModel {
+            account_id: Set(account.id),
+            actor_id: Set(activity.actor.clone()),
+            ..Default::default()
+        },
+    };
+    model.inbox = Set(remote.inbox.clone());
+    model.shared_inbox = Set(remote.shared_inbox.clone());
+    model.follow_id = Set(activity.id.clone());
+    model.accepted = Set(accepted);
+    let follower = model
+        .save(&ctx.db)
+        .await
+        .context("Failed to store follower")?;
+
+    if accepted {
+        let follower_id = follower.id.unwrap();
+        // The Accept goes through the delivery queue so the Follow can be
+        // answered right away
+        let mut follow = activity.clone();
+        follow.context = serde_json::Value::Null;
+        follow_request::send_answer(
+            &ctx.db,
+            &ctx.base_url,
+            &account,
+            &follow,
+            follower_id,
+            remote.inbox,
+            true,
+        )
+        .await
+        .context("Failed to queue Accept")?;
+    }
+
+    Ok(())
+}
+
+/// An Accept or Reject of a Follow one of our accounts or the instance actor
+/// sent.
+async fn answer_follow(
+    ctx: &InboxContext<'_>,
+    activity: &Activity,
+    accepted: bool,
+) -> Result<(), InboxError> {
+    if let Some(follow_id) = activity.object_id() {
+        if relay::answer(&ctx.db, follow_id, &activity.actor, accepted)
+            .await
+            .context("Failed to update relay")?
+        {
+            return Ok(());
+        }
+    }
+    let mut found = match activity.object_id() {
+        Some(follow_id) => Following::find()
+            .filter(following::Column::FollowId.eq(follow_id))
+            .filter(following::Column::ActorId.eq(activity.actor.as_str()))
+            .one(&ctx.db)
+            .await
+            .context("Failed to retrieve follow")?,
+        None => None,
+    };
+    // Some servers do not echo the id of the Follow, only its actor
+    if found.is_none() {
+        if let Some(local) = activity.object["actor"].as_str() {
+            if let Some(account) = local_account(ctx, local).await? {
+                found = Following::find()
+                    .filter(following::Column::AccountId.eq(account.id))
+                    .filter(following::Column::ActorId.eq(activity.actor.as_str()))
+                    .one(&ctx.db)
+                    .await
+                    .context("Failed to retrieve follow")?;
+            }
+        }
+    }
+    let found = match found {
+        Some(f) => f,
+        None => {
+            tracing::debug!("no pending follow matches {}", activity.id);
+            return Ok(());
+        }
+    };
+
+    if accepted {
+        let mut model: following::ActiveModel = found.into();
+        model.accepted = Set(true);
+        model
+            .update(&ctx.db)
+            .await
+            .context("Failed to accept follow")?;
+    } else {
+        Following::delete_by_id(found.id)
+            .exec(&ctx.db)
+            .await
+            .context("Failed to remove rejected follow")?;
+    }
+
+    Ok(())
+}
+
+async fn undo(ctx: &InboxContext<'_>, activity: &Activity) -> Result<(), InboxError> {
+    match activity.object_type() {
+        Some("Follow") | None => {}
+        Some(kind) => {
+            tracing::debug!("ignoring Undo of {}", kind);
+            return Ok(());
+        }
+    }
+    let follow_id = activity
+        .object_id()
+        .ok_or_else(|| InboxError::BadRequest("Undo has no object".to_string()))?;
+
+    let res = Follower::delete_many()
+        .filter(follower::Column::ActorId.eq(activity.actor.as_str()))
+        .filter(follower::Column::FollowId.eq(follow_id))
+        .exec(&ctx.db)
+        .await
+        .context("Failed to remove follower")?;
+    if res.rows_affected > 0 {
+        return Ok(());
+    }
+
+    // The Follow may have been re-sent with a different id
+    if let Some(followed) = activity.object["object"].as_str() {
+        if let Some(account) = local_account(ctx, followed).await? {
+            Follower::delete_many()
+                .filter(follower::Column::AccountId.eq(account.id))
+                .filter(follower::Column::ActorId.eq(activity.actor.as_str()))
+                .exec(&ctx.db)
+                .await
+                .context("Failed to remove follower")?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn create(
+    ctx: &InboxContext<'_>,
+    activity: &Activity,
+    policies: &DomainPolicies,
+) -> Result<(), InboxError> {
+    // Some servers only send the id of the created object
+    let object = match &activity.object {
+        serde_json::Value::String(id) => {
+            let signer = instance_signer(
+                &ctx.db,
+                &ctx.base_url,
+                &ctx.state.global_config.server.secret_key,
+            )
+            .await
+            .map_err(|e| InboxError::UnexpectedError(e.into()))?;
+            let fetcher = Fetcher::new(&ctx.state.federation).signed_by(signer.as_ref());
+            remote::object(&fetcher, &ctx.db, id)
+                .await
+                .map_err(|e| fetch_failed(id, e))?
+                .document
+        }
+        object => object.clone(),
+    };
+    if object["type"] != "Note" && object["type"] != "Question" {
+        tracing::debug!("ignoring Create of {:?}", object["type"]);
+        return Ok(());
+    }
+    let note: Note = serde_json::from_value(object)
+        .map_err(|e| InboxError::BadRequest(format!("malformed Note: {}", e)))?;
+    if note.attributed_to != activity.actor || !same_origin(&note.id, &activity.actor) {
+        return Err(InboxError::Unauthorized(format!(
+            "{} cannot create {}",
+            activity.actor, note.id
+        )));
+    }
+    // Votes are notes naming an option, in reply to the poll
+    if let (Some(name), Some(parent)) = (&note.name, &note.in_reply_to) {
+        if let Some(poll) = local_poll(ctx, parent).await? {
+            let counted =
+                polls::record_remote_vote(&ctx.db, &poll, &activity.actor, name, &activity.id)
+                    .await
+                    .context("Failed to record vote")?;
+            if !counted {
+                tracing::debug!("ignoring vote of {} in {}", activity.actor, parent);
+            }
+            return Ok(());
+        }
+    }
+    if !is_relevant(ctx, &note, policies.is_silenced(&activity.actor)).await? {
+        tracing::debug!("nobody on this tenant is interested in {}", note.id);
+        return Ok(());
+    }
+
+    store_note(ctx, note, policies).await
+}
+
+/// A public post rebroadcast by a relay the tenant subscribes to. The post is
+/// fetched from its origin, as the relay cannot vouch for it. `Announce`s by
+/// anyone else are boosts, which are not supported.
+async fn announce(ctx: &InboxContext<'_>, activity: &Activity) -> Result<(), InboxError> {
+    let subscribed = relay::accepted(&ctx.db, &activity.actor)
+        .await
+        .context("Failed to look up relay")?
+        .is_some();
+    if !subscribed {
+        tracing::debug!("ignoring Announce by {}", activity.actor);
+        return Ok(());
+    }
+    let id = activity
+        .object_id()
+        .ok_or_else(|| InboxError::BadRequest("Announce has no object".to_string()))?;
+
+    let signer = instance_signer(
+        &ctx.db,
+        &ctx.base_url,
+        &ctx.state.global_config.server.secret_key,
+    )
+    .await
+    .map_err(|e| InboxError::UnexpectedError(e.into()))?;
+    let fetcher = Fetcher::new(&ctx.state.federation).signed_by(signer.as_ref());
+    let object = remote::object(&fetcher, &ctx.db, id)
+        .await
+        .map_err(|e| fetch_failed(id, e))?
+        .document;
+    if object["type"] != "Note" && object["type"] != "Question" {
+        tracing::debug!("ignoring relayed {:?}", object["type"]);
+        return Ok(());
+    }
+    let note: Note = serde_json::from_value(object)
+        .map_err(|e| InboxError::BadRequest(format!("malformed Note: {}", e)))?;
+    if !same_origin(&note.id, &note.attributed_to) {
+        return Err(InboxError::BadRequest(format!(
+            "{} is not hosted by its author",
+            note.id
+        )));
+    }
+    // Silenced domains are kept off public timelines, which is all a relay
+    // is good for
+    let policies = DomainPolicies::for_url(&ctx.db, &note.attributed_to)
+        .await
+        .context("Failed to look up domain policies")?;
+    if !note.is_public()
+        || policies.refuses(&note.attributed_to)
+        || policies.is_silenced(&note.attributed_to)
+    {
+        tracing::debug!("not storing relayed note {}", note.id);
+        return Ok(());
+    }
+
+    store_note(ctx, note, &policies).await
+}
+
+/// Store a remote note unless it is already known. `policies` are those of
+/// the domain of its author.
+async fn store_note(
+    ctx: &InboxContext<'_>,
+    mut note: Note,
+    policies: &DomainPolicies,
+) -> Result<(), InboxError> {
+    let exists = RemotePost::find()
+        .filter(remote_post::Column::ObjectId.eq(note.id.as_str()))
+        .one(&ctx.db)
+        .await
+        .context("Failed to look up remote post")?
+        .is_some();
+    if exists {
+        return Ok(());
+    }
+    if policies.rejects_media(&note.attributed_to) {
+        note.attachment.clear();
+    }
+    // Only public posts are listed under their tags
+    let hashtags = if note.is_public() {
+        note.hashtags()
+    } else {
+        vec![]
+    };
+    let poll = note.poll();
+    let post = remote_post::ActiveModel {
+        object_id: Set(note.id.clone()),
+        actor_id: Set(note.attributed_to.clone()),
+        url: Set(note.url()),
+        in_reply_to: Set(note.in_reply_to.clone()),
+        summary: Set(note.summary.clone()),
+        sensitive: Set(note.sensitive),
+        content: Set(note.content.clone()),
+        published_at: Set(note.published_at()),
+        attachment: Set(serde_json::Value::from(note.attachment)),
+        ..Default::default()
+    }
+    .insert(&ctx.db)
+    .await
+    .context("Failed to store remote post")?;
+    tags::tag_remote_post(&ctx.db, post.id, &hashtags)
+        .await
+        .context("Failed to store hashtags")?;
+    if let Some(poll) = poll {
+        polls::store_remote(&ctx.db, post.id, &poll)
+            .await
+            .context("Failed to store poll")?;
+    }
+
+    Ok(())
+}
+
+/// A remote note is stored if it is addressed to or replies to someone on this
+/// tenant, or if its author is followed from here. Replies from silenced
+/// domains have to be addressed to be kept.
+async fn is_relevant(
+    ctx: &InboxContext<'_>,
+    note: &Note,
+    silenced: bool,
+) -> Result<bool, InboxError> {
+    if let Some(parent) = &note.in_reply_to {
+        if parent.starts_with(&ctx.base_url) && !silenced {
+            return Ok(true);
+        }
+    }
+    for recipient in note.recipients() {
+        if local_account(ctx, recipient).await?.is_some() {
+            return Ok(true);
+        }
+    }
+    let followed = Following::find()
+        .filter(following::Column::ActorId.eq(note.attributed_to.as_str()))
+        .filter(following::Column::Accepted.eq(true))
+        .one(&ctx.db)
+        .await
+        .context("Failed to look up follows")?
+        .is_some();
+
+    Ok(followed)
+}
+
+/// An actor updating its own actor document or one of its notes. The cached
+/// copy of the actor is replaced, and so is the stored copy of the note if
+/// there is one.
+async fn update(
+    ctx: &InboxContext<'_>,
+    activity: &Activity,
+    policies: &DomainPolicies,
+) -> Result<(), InboxError> {
+    if matches!(activity.object_type(), Some("Note" | "Question")) {
+        return update_note(ctx, activity, policies).await;
+    }
+    if activity.object_id() != Some(activity.actor.as_str()) {
+        tracing::debug!("ignoring Update of {:?}", activity.object_id());
+        return Ok(());
+    }
+
+    match &activity.object {
+        // Only the id was sent, the document has to be fetched
+        serde_json::Value::String(id) => {
+            let signer = instance_signer(
+                &ctx.db,
+                &ctx.base_url,
+                &ctx.state.global_config.server.secret_key,
+            )
+            .await
+            .map_err(|e| InboxError::UnexpectedError(e.into()))?;
+            let fetcher = Fetcher::new(&ctx.state.federation).signed_by(signer.as_ref());
+            remote::refresh_actor(&fetcher, &ctx.db, id)
+                .await
+                .map_err(|e| fetch_failed(id, e))?;
+        }
+        document => {
+            let person = parse_actor(document.clone(), &activity.actor)
+                .map_err(|e| InboxError::BadRequest(e.to_string()))?;
+            remote::store_actor(&ctx.db, &person, document.clone())
+                .await
+                .context("Failed to store remote actor")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// An edited remote note. Notes that were never stored here are ignored.
+async fn update_note(
+    ctx: &InboxContext<'_>,
+    activity: &Activity,
+    policies: &DomainPolicies,
+) -> Result<(), InboxError> {
+    let mut note: Note = serde_json::from_value(activity.object.clone())
+        .map_err(|e| InboxError::BadRequest(format!("malformed Note: {}", e)))?;
+    if note.attributed_to != activity.actor || !same_origin(&note.id, &activity.actor) {
+        return Err(InboxError::Unauthorized(format!(
+            "{} cannot update {}",
+            activity.actor, note.id
+        )));
+    }
+    let Some(post) = RemotePost::find()
+        .filter(remote_post::Column::ObjectId.eq(note.id.as_str()))
+        .one(&ctx.db)
+        .await
+        .context("Failed to look up remote post")?
+    else {
+        tracing::debug!("ignoring Update of unknown note {}", note.id);
+        return Ok(());
+    };
+
+    if policies.rejects_media(&activity.actor) {
+        note.attachment.clear();
+    }
+    let hashtags = if note.is_public() {
+        note.hashtags()
+    } else {
+        vec![]
+    };
+    let poll = note.poll();
+    // Updates that leave the text alone, like new poll counts, are no edits
+    let edited = post.content != note.content || post.summary != note.summary;
+    if edited {
+        revisions::record_remote(&ctx.db, &post)
+            .await
+            .context("Failed to keep the previous version")?;
+    }
+    let mut model: remote_post::ActiveModel = post.into();
+    if edited {
+        model.edited_at = Set(Some(
+            note.updated_at()
+                .unwrap_or_else(|| chrono::Utc::now().naive_utc()),
+        ));
+    }
+    model.url = Set(note.url());
+    model.summary = Set(note.summary.clone());
+    model.sensitive = Set(note.sensitive);
+    model.content = Set(note.content.clone());
+    model.attachment = Set(serde_json::Value::from(note.attachment));
+    let post = model
+        .update(&ctx.db)
+        .await
+        .context("Failed to update remote post")?;
+    tags::tag_remote_post(&ctx.db, post.id, &hashtags)
+        .await
+        .context("Failed to store hashtags")?;
+    if let Some(poll) = poll {
+        polls::store_remote(&ctx.db, post.id, &poll)
+            .await
+            .context("Failed to store poll")?;
+    }
+
+    Ok(())
+}
+
+/// A remote account moving to another account. Local accounts following it
+/// follow the new account instead, provided the new account confirms the
+/// move by listing the old one as an alias.
+async fn move_account(ctx: &InboxContext<'_>, activity: &Activity) -> Result<(), InboxError> {
+    if activity.object_id() != Some(activity.actor.as_str()) {
+        return Err(InboxError::Unauthorized(format!(
+            "{} can only move itself",
+            activity.actor
+        )));
+    }
+    let target = activity
+        .target_id()
+        .ok_or_else(|| InboxError::BadRequest("Move has no target".to_string()))?;
+    let policies = DomainPolicies::for_url(&ctx.db, target)
+        .await
+        .context("Failed to look up domain policies")?;
+    if policies.refuses(target) {
+        return Err(InboxError::Forbidden(format!(
+            "this server does not federate with the domain of {}",
+            target
+        )));
+    }
+
+    let signer = instance_signer(
+        &ctx.db,
+        &ctx.base_url,
+        &ctx.state.global_config.server.secret_key,
+    )
+    .await
+    .map_err(|e| InboxError::UnexpectedError(e.into()))?;
+    let fetcher = Fetcher::new(&ctx.state.federation).signed_by(signer.as_ref());
+    let target = remote::refresh_actor(&fetcher, &ctx.db, target)
+        .await
+        .map_err(|e| fetch_failed(target, e))?;
+    let aliases =
+        account_move::remote_aliases(&target).map_err(|e| InboxError::BadRequest(e.to_string()))?;
+    if !aliases.contains(&activity.actor) {
+        return Err(InboxError::Unauthorized(format!(
+            "{} does not list {} as an alias",
+            target.actor_id, activity.actor
+        )));
+    }
+
+    let origin_inbox = remote::actor(&fetcher, &ctx.db, &activity.actor)
+        .await
+        .ok()
+        .map(|origin| origin.inbox);
+    let redirected = account_move::redirect_follows(
+        &ctx.db,
+        &ctx.base_url,
+        &activity.actor,
+        origin_inbox,
+        &target,
+    )
+    .await
+    .context("Failed to redirect follows")?;
+    tracing::info!(
+        "{} moved to {}, {} follows redirected",
+        activity.actor,
+        target.actor_id,
+        redirected
+    );
+
+    Ok(())
+}
+
+async fn delete(ctx: &InboxContext<'_>, activity: &Activity) -> Result<(), InboxError> {
+    let object = activity
+        .object_id()
+        .ok_or_else(|| InboxError::BadRequest("Delete has no object".to_string()))?;
+
+    if object == activity.actor {
+        // The actor deleted itself
+        Follower::delete_many()
+            .filter(follower::Column::ActorId.eq(object))
+            .exec(&ctx.db)
+            .await
+            .context("Failed to remove deleted follower")?;
+        Following::delete_many()
+            .filter(following::Column::ActorId.eq(object))
+            .exec(&ctx.db)
+            .await
+            .context("Failed to remove deleted followee")?;
+        remote::forget_actor(&ctx.db, object)
+            .await
+            .context("Failed to forget deleted actor")?;
+    }
+
+    let mut deleted = RemotePost::find()
+        .filter(remote_post::Column::ActorId.eq(activity.actor.as_str()))
+        .filter(remote_post::Column::DeletedAt.is_null());
+    if object != activity.actor {
+        deleted = deleted.filter(remote_post::Column::ObjectId.eq(object));
+    }
+    let ids: Vec<i64> = deleted
+        .all(&ctx.db)
+        .await
+        .context("Failed to look up deleted remote posts")?
+        .into_iter()
+        .map(|post| post.id)
+        .collect();
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    RemotePost::update_many()
+        .col_expr(
+            remote_post::Column::DeletedAt,
+            Expr::value(chrono::Utc::now().naive_utc()),
+        )
+        .col_expr(
+            remote_post::Column::Content,
+            Expr::value(Option::<String>::None),
+        )
+        .col_expr(
+            remote_post::Column::Summary,
+            Expr::value(Option::<String>::None),
+        )
+        .filter(remote_post::Column::Id.is_in(ids.clone()))
+        .exec(&ctx.db)
+        .await
+        .context("Failed to tombstone remote post")?;
+    // Earlier versions and hashtags would keep the deleted text around
+    ContentRevision::delete_many()
+        .filter(content_revision::Column::RemotePostId.is_in(ids.clone()))
+        .exec(&ctx.db)
+        .await
+        .context("Failed to remove earlier versions of deleted remote post")?;
+    ContentTag::delete_many()
+        .filter(content_tag::Column::RemotePostId.is_in(ids))
+        .exec(&ctx.db)
+        .await
+        .context("Failed to remove hashtags of deleted remote post")?;
+
+    Ok(())
+}
+
+/// A report of local content. Reports are not stored yet, so all that
+/// happens is that moderators find them in the logs.
+fn flag(activity: &Activity, policies: &DomainPolicies) -> Result<(), InboxError> {
+    if policies.rejects_reports(&activity.actor) {
+        tracing::info!(
+            "dropping report {} from a domain whose reports are rejected",
+            activity.id
+        );
+        return Ok(());
+    }
+    tracing::warn!(
+        "report {} by {} about {:?}",
+        activity.id,
+        activity.actor,
+        activity.object
+    );
+
+    Ok(())
+}
+
+/// The key requests made on behalf of `account` are signed with.
+async fn account_signer(
+    ctx: &InboxContext<'_>,
+    account: &account::Model,
+) -> Result<Option<Signer>, InboxError> {
+    let actor = actor_url(
+        &ctx.base_url,
+        account.username.as_deref().unwrap_or_default(),
+    );
+    let signer = signer_for_account(
+        &ctx.db,
+        account.id,
+        &actor,
+        &ctx.state.global_config.server.secret_key,
+    )
+    .await
+    .map_err(|e| InboxError::UnexpectedError(e.into()))?;
+
+    Ok(signer)
+}
+
+/// The local account behind an actor URL of this tenant.
+async fn local_account(
+    ctx: &InboxContext<'_>,
+    url: &str,
+) -> Result<Option<account::Model>, InboxError> {
+    let handle = match handle_from_actor_url(&ctx.base_url, url) {
+        Some(h) => h,
+        None => return Ok(None),
+    };
+    let account = orm::get_account_by_handle(handle, &ctx.db)
+        .await
+        .map_err(|e| InboxError::UnexpectedError(e.into()))?
+        .map(|(account, _)| account);
+
+    Ok(account)
+}
+
+/// The poll of the published local post at `url`, if there is one.
+async fn local_poll(ctx: &InboxContext<'_>, url: &str) -> Result<Option<Poll>, InboxError> {
+    let Some((handle, id)) = status_from_url(&ctx.base_url, url) else {
+        return Ok(None);
+    };
+    let Some(account) = local_account(ctx, &actor_url(&ctx.base_url, handle)).await? else {
+        return Ok(None);
+    };
+    let post = db::content::find_by_publisher(&ctx.db, account.id, id)
+        .await
+        .map_err(|e| InboxError::UnexpectedError(anyhow::anyhow!(e)))?
+        .filter(|post| post.published && post.deleted_at.is_none());
+    let Some(post) = post else {
+        return Ok(None);
+    };
+
+    Ok(polls::for_post(&ctx.db, post.id)
+        .await
+        .context("Failed to look up poll")?)
+}
+
+/// Map a failure to fetch `url` to the error of the activity that needed it.
+/// Timeouts, refused connections, rate limits and server errors may pass, so
+/// they leave the activity to be retried; anything else is the sender's
+/// problem.
+fn fetch_failed(url: &str, e: FetchError) -> InboxError {
+    let transient = match &e {
+        FetchError::Http(e) => e
+            .status()
+            .is_none_or(|s| s.is_server_error() || s.as_u16() == 429),
+        FetchError::Database(_) => true,
+        _ => false,
+    };
+    let message = format!("failed to fetch {}: {}", url, e);
+    if transient {
+        InboxError::Unavailable(message)
+    } else {
+        InboxError::BadRequest(message)
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum InboxError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    /// Something the activity depends on could not be reached for now
+    #[error("{0}")]
+    Unavailable(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl From<TenantMapError> for InboxError {
+    fn from(e: TenantMapError) -> Self {
+        match e {
+            TenantMapError::NotFound(s) => Self::NotFound(s),
+            TenantMapError::UnexpectedError(s) => Self::UnexpectedError(anyhow::anyhow!(s)),
+        }
+    }
+}
+
+impl std::fmt::Debug for InboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for InboxError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::BadRequest(s) => {
+                tracing::info!("rejected activity: {s:?}");
+                (StatusCode::BAD_REQUEST, s).into_response()
+            }
+            Self::Unauthorized(s) => {
+                tracing::info!("unauthorized activity: {s:?}");
+                (StatusCode::UNAUTHORIZED, s).into_response()
+            }
+            Self::Forbidden(s) => {
+                tracing::info!("refused activity: {s:?}");
+                (StatusCode::FORBIDDEN, s).into_response()
+            }
+            Self::NotFound(s) => {
+                tracing::info!("inbox target not found: {s:?}");
+                (StatusCode::NOT_FOUND, s).into_response()
+            }
+            Self::Unavailable(s) => {
+                tracing::info!("activity cannot be applied yet: {s:?}");
+                (StatusCode::SERVICE_UNAVAILABLE, s).into_response()
+            }
+            Self::UnexpectedError(e) => {
+                tracing::error!("an unexpected error occurred: {e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")).into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! Asynchronous processing of the activities delivered to our inboxes.
+//!
+//! Once the signature of a delivery is verified and its sender admitted, the
+//! activity is stored in the `inbox_queue` table of the tenant and the
+//! request is answered right away. Workers claim stored activities and apply
+//! them, leasing rather than locking them like the delivery queue does. An
+//! activity is stored once per id, so an activity delivered to several of our
+//! inboxes, or delivered again by a server that is retrying, is only applied
+//! once. Activities that fail for reasons that may go away are retried with a
+//! backoff. Those that keep failing, or that are invalid, are kept as dead
+//! letters. Processed activities are kept for a while, so they can be
+//! replayed when debugging.
+use chrono::NaiveDateTime;
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    DbBackend, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect, Set, Statement,
+};
+use std::time::Duration;
+
+use super::inbox::{self, InboxContext, InboxError};
+use crate::{
+    activitypub::activity::Activity,
+    entities::{inbox_queue, prelude::*},
+    routes::{all_tenants, AppState, TenantData},
+};
+
+pub const PENDING: &str = "pending";
+pub const PROCESSED: &str = "processed";
+pub const DEAD: &str = "dead";
+
+/// How many workers process activities concurrently.
+const WORKERS: usize = 4;
+/// How often an idle worker looks for new activities.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// How many activities a worker claims at a time.
+const BATCH_SIZE: i64 = 10;
+/// How long a claimed activity is hidden from other workers.
+const LEASE: Duration = Duration::from_secs(5 * 60);
+/// The delay after the first failure. It doubles with every further failure.
+const BASE_BACKOFF: Duration = Duration::from_secs(10);
+/// Attempts after which an activity becomes a dead letter.
+pub const MAX_ATTEMPTS: i32 = 5;
+/// How long processed activities are kept.
+const RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Store `activity`, delivered to the tenant at `base_url` in a request
+/// signed by `signed_by`, for processing. Returns whether it was new.
+pub async fn enqueue<C: ConnectionTrait>(
+    conn: &C,
+    base_url: &str,
+    activity_id: &str,
+    signed_by: &str,
+    activity: serde_json::Value,
+) -> Result<bool, DbErr> {
+    let inserted = InboxQueue::insert(inbox_queue::ActiveModel {
+        activity_id: Set(activity_id.to_string()),
+        signed_by: Set(signed_by.to_string()),
+        base_url: Set(base_url.to_string()),
+        activity: Set(activity),
+        status: Set(PENDING.to_string()),
+        next_attempt_at: Set(now()),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::column(inbox_queue::Column::ActivityId)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(conn)
+    .await?;
+
+    Ok(inserted > 0)
+}
+
+/// Process the stored activity `id` again, whatever became of it before.
+/// Returns whether there is such an activity.
+pub async fn replay<C: ConnectionTrait>(conn: &C, id: i64) -> Result<bool, DbErr> {
+    let res = InboxQueue::update_many()
+        .col_expr(inbox_queue::Column::Status, PENDING.into())
+        .col_expr(inbox_queue::Column::Attempts, 0.into())
+        .col_expr(inbox_queue::Column::NextAttemptAt, now().into())
+        .col_expr(
+            inbox_queue::Column::LastError,
+            Option::<String>::None.into(),
+        )
+        .filter(inbox_queue::Column::Id.eq(id))
+        .exec(conn)
+        .await?;
+
+    Ok(res.rows_affected > 0)
+}
+
+/// The most recent dead letters, newest first.
+pub async fn dead_letters<C: ConnectionTrait>(
+    conn: &C,
+    limit: u64,
+) -> Result<Vec<inbox_queue::Model>, DbErr> {
+    InboxQueue::find()
+        .filter(inbox_queue::Column::Status.eq(DEAD))
+        .order_by_desc(inbox_queue::Column::UpdatedAt)
+        .limit(limit)
+        .all(conn)
+        .await
+}
+
+/// Run the inbox workers for all tenants until the process exits.
+pub fn spawn_workers(state: AppState) {
+    for _ in 0..WORKERS {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                for tenant in all_tenants(&state).await {
+                    if let Err(e) = run_once(&state, &tenant).await {
+                        tracing::error!("inbox processing for {} failed: {:?}", tenant.domain, e);
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Process the activities of `tenant` that are currently due. Returns the
+/// number of activities processed.
+pub async fn run_once(state: &AppState, tenant: &TenantData) -> Result<usize, DbErr> {
+    let jobs = claim(&tenant.db).await?;
+    if jobs.is_empty() {
+        return Ok(0);
+    }
+
+    let processed = jobs.len();
+    for job in jobs {
+        let ctx = InboxContext {
+            state,
+            db: tenant.db.clone(),
+            base_url: job.base_url.clone(),
+        };
+        apply(&ctx, job).await?;
+    }
+    purge(&tenant.db).await?;
+
+    Ok(processed)
+}
+
+/// Lease a batch of due activities, oldest first.
+async fn claim(db: &DatabaseConnection) -> Result<Vec<inbox_queue::Model>, DbErr> {
+    let now = now();
+    let lease_until = now + chrono::Duration::from_std(LEASE).unwrap();
+
+    InboxQueue::find()
+        .from_raw_sql(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+UPDATE inbox_queue SET next_attempt_at = $2
+WHERE id IN (
+    SELECT id FROM inbox_queue
+    WHERE status = 'pending' AND next_attempt_at <= $1
+    ORDER BY next_attempt_at
+    LIMIT $3
+    FOR UPDATE SKIP LOCKED
+)
+RETURNING *"#,
+            vec![now.into(), lease_until.into(), BATCH_SIZE.into()],
+        ))
+        .all(db)
+        .await
+}
+
+/// Apply one stored activity and record the outcome.
+#[tracing::instrument(name = "Apply inbox activity", skip(ctx, job), fields(id = job.id, activity_id = %job.activity_id))]
+async fn apply(ctx: &InboxContext<'_>, job: inbox_queue::Model) -> Result<(), DbErr> {
+    let result = match serde_json::from_value::<Activity>(job.activity.clone()) {
+        Ok(activity) => inbox::process(ctx, &activity, &job.signed_by).await,
+        Err(e) => Err(InboxError::BadRequest(format!("malformed activity: {}", e))),
+    };
+
+    let now = now();
+    let attempts = job.attempts + 1;
+    let mut job: inbox_queue::ActiveModel = job.into();
+    job.attempts = Set(attempts);
+    match result {
+        Ok(()) => {
+            job.status = Set(PROCESSED.to_string());
+            job.processed_at = Set(Some(now));
+            job.last_error = Set(None);
+        }
+        Err(e) if is_transient(&e) && attempts < MAX_ATTEMPTS => {
+            tracing::info!("processing failed, will retry: {:?}", e);
+            job.next_attempt_at = Set(now + chrono::Duration::from_std(backoff(attempts)).unwrap());
+            job.last_error = Set(Some(e.to_string()));
+        }
+        Err(e) => {
+            tracing::warn!("giving up on activity: {:?}", e);
+            job.status = Set(DEAD.to_string());
+            job.last_error = Set(Some(e.to_string()));
+        }
+    }
+    job.update(&ctx.db).await?;
+
+    Ok(())
+}
+
+/// Forget processed activities that are past the retention period.
+async fn purge(db: &DatabaseConnection) -> Result<(), DbErr> {
+    let cutoff = now() - chrono::Duration::from_std(RETENTION).unwrap();
+    InboxQueue::delete_many()
+        .filter(inbox_queue::Column::Status.eq(PROCESSED))
+        .filter(inbox_queue::Column::ProcessedAt.lt(cutoff))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Errors that may go away by trying again, as opposed to activities we
+/// will never accept.
+fn is_transient(e: &InboxError) -> bool {
+    matches!(
+        e,
+        InboxError::Unavailable(_) | InboxError::UnexpectedError(_)
+    )
+}
+
+/// How long to wait before the next attempt after `failures` failures.
+pub fn backoff(failures: i32) -> Duration {
+    BASE_BACKOFF * 2u32.pow(failures.clamp(1, 16) as u32 - 1)
+}
+
+fn now() -> NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::backoff;
+
+    #[test]
+    fn backoff_doubles() {
+        assert_eq!(backoff(1), Duration::from_secs(10));
+        assert_eq!(backoff(2), Duration::from_secs(20));
+        assert_eq!(backoff(4), Duration::from_secs(80));
+    }
+}
@@ -0,0 +1,194 @@
+//! Mentions of local and remote accounts in local posts.
+//!
+//! Mentions are resolved when a post is written: local handles are looked up
+//! directly and remote ones with WebFinger, followed by a fetch of the actor
+//! document. Mentions that cannot be resolved stay plain text.
+use futures::future::join_all;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    Set,
+};
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    fetch::{FetchError, Fetcher},
+    remote,
+};
+use crate::{
+    activitypub::actor_url,
+    domain::ParsedMention,
+    entities::{mention, prelude::*},
+    orm,
+};
+
+/// Remote accounts looked up for a single post. Mentions beyond these stay
+/// plain text.
+pub const MAX_REMOTE_MENTIONS: usize = 10;
+
+/// Resolve the mentions in `text`, written on the tenant at `base_url`. Each
+/// account is only mentioned once. Remote accounts are looked up
+/// concurrently, and only the first [`MAX_REMOTE_MENTIONS`] of them.
+#[tracing::instrument(name = "Resolve mentions", skip(fetcher, conn, text))]
+pub async fn resolve(
+    fetcher: &Fetcher<'_>,
+    conn: &DatabaseConnection,
+    base_url: &str,
+    text: &str,
+) -> Result<Vec<mention::ActiveModel>, anyhow::Error> {
+    let (scheme, host) = base_url.split_once("://").unwrap_or(("https", base_url));
+    let found = ParsedMention::parse_all(text);
+    let is_local = |found: &ParsedMention| match &found.domain {
+        Some(domain) => domain.eq_ignore_ascii_case(host),
+        None => true,
+    };
+
+    let mut accts = HashSet::new();
+    let lookups = found
+        .iter()
+        .filter(|found| !is_local(found))
+        .filter(|found| accts.insert(found.acct().to_lowercase()))
+        .take(MAX_REMOTE_MENTIONS)
+        .map(|found| async move {
+            let target = match remote_target(fetcher, conn, scheme, found).await {
+                Ok(target) => Some(target),
+                Err(e) => {
+                    tracing::info!("could not resolve @{}: {}", found.acct(), e);
+                    None
+                }
+            };
+            (found.acct().to_lowercase(), target)
+        });
+    let mut remote: HashMap<String, Option<Target>> = join_all(lookups).await.into_iter().collect();
+
+    let mut resolved = vec![];
+    let mut seen = HashSet::new();
+    for found in found {
+        let target = if is_local(&found) {
+            local_target(conn, base_url, &found).await?
+        } else {
+            remote
+                .get_mut(&found.acct().to_lowercase())
+                .and_then(Option::take)
+        };
+        let Some(target) = target else {
+            continue;
+        };
+        if seen.insert(target.actor_id.clone()) {
+            resolved.push(mention::ActiveModel {
+                actor_id: Set(target.actor_id),
+                username: Set(found.username),
+                domain: Set(found.domain),
+                url: Set(target.url),
+                inbox: Set(target.inbox),
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Link `mentions` to the post `content_id`.
+pub async fn store<C: ConnectionTrait>(
+    conn: &C,
+    content_id: i64,
+    mentions: Vec<mention::ActiveModel>,
+) -> Result<(), DbErr> {
+    if mentions.is_empty() {
+        return Ok(());
+    }
+    let rows = mentions.into_iter().map(|mut m| {
+        m.content_id = Set(content_id);
+        m
+    });
+    Mention::insert_many(rows).exec(conn).await?;
+
+    Ok(())
+}
+
+/// Replace the mentions of the post `content_id` with `mentions`, e.g.
+/// because it was edited.
+pub async fn replace<C: ConnectionTrait>(
+    conn: &C,
+    content_id: i64,
+    mentions: Vec<mention::ActiveModel>,
+) -> Result<(), DbErr> {
+    Mention::delete_many()
+        .filter(mention::Column::ContentId.eq(content_id))
+        .exec(conn)
+        .await?;
+
+    store(conn, content_id, mentions).await
+}
+
+/// The mentions in the post `content_id`.
+pub async fn for_post<C: ConnectionTrait>(
+    conn: &C,
+    content_id: i64,
+) -> Result<Vec<mention::Model>, DbErr> {
+    Mention::find()
+        .filter(mention::Column::ContentId.eq(content_id))
+        .order_by_asc(mention::Column::Id)
+        .all(conn)
+        .await
+}
+
+/// The mentions in each of the posts `content_ids`.
+pub async fn for_posts<C: ConnectionTrait>(
+    conn: &C,
+    content_ids: Vec<i64>,
+) -> Result<HashMap<i64, Vec<mention::Model>>, DbErr> {
+    let mut by_post: HashMap<i64, Vec<mention::Model>> = HashMap::new();
+    for mention in Mention::find()
+        .filter(mention::Column::ContentId.is_in(content_ids))
+        .order_by_asc(mention::Column::Id)
+        .all(conn)
+        .await?
+    {
+        by_post.entry(mention.content_id).or_default().push(mention);
+    }
+
+    Ok(by_post)
+}
+
+struct Target {
+    actor_id: String,
+    url: String,
+    inbox: Option<String>,
+}
+
+async fn local_target(
+    conn: &DatabaseConnection,
+    base_url: &str,
+    found: &ParsedMention,
+) -> Result<Option<Target>, anyhow::Error> {
+    let account = orm::get_account_by_handle(&found.username.to_lowercase(), conn).await?;
+
+    Ok(account.map(|(account, _)| {
+        let actor_id = actor_url(base_url, account.username.as_deref().unwrap_or_default());
+        Target {
+            url: actor_id.clone(),
+            actor_id,
+            inbox: None,
+        }
+    }))
+}
+
+async fn remote_target<C: ConnectionTrait>(
+    fetcher: &Fetcher<'_>,
+    conn: &C,
+    scheme: &str,
+    found: &ParsedMention,
+) -> Result<Target, FetchError> {
+    let domain = found.domain.as_deref().unwrap_or_default();
+    let actor_id = fetcher
+        .webfinger(conn, scheme, &found.username, domain)
+        .await?;
+    let actor = remote::actor(fetcher, conn, &actor_id).await?;
+
+    Ok(Target {
+        url: actor.url.unwrap_or_else(|| actor.actor_id.clone()),
+        actor_id: actor.actor_id,
+        inbox: Some(actor.inbox),
+    })
+}
@@ -0,0 +1,164 @@
+//! Server-to-server plumbing: HTTP signatures, remote key lookup, the
+//! delivery queue and the shared HTTP client used for outbound federation
+//! traffic.
+use reqwest::Client;
+use sea_orm::ConnectionTrait;
+use secrecy::Secret;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+use crate::{activitypub::instance_actor_url, keys, APP_NAME};
+
+pub mod account_move;
+pub mod address;
+pub mod delivery;
+pub mod fetch;
+pub mod follow_request;
+pub mod inbox;
+pub mod inbox_queue;
+pub mod mention;
+pub mod policy;
+pub mod queue;
+pub mod relay;
+pub mod remote;
+pub mod signature;
+pub mod verify;
+
+pub use signature::{SignatureError, Signer};
+pub use verify::{authorize_fetch, authorize_page_fetch, require_signature, VerifiedSignature};
+
+/// How long a remote public key is trusted before it is fetched again.
+pub const KEY_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Federation state shared by all tenants of a server.
+#[derive(Clone, Debug)]
+pub struct FederationState {
+    pub http: Client,
+    /// Whether loopback, link-local and private addresses may be contacted
+    allow_private_addresses: bool,
+    key_cache: Arc<RwLock<HashMap<String, CachedKey>>>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct CachedKey {
+    pub owner: String,
+    pub public_key_pem: String,
+    pub fetched_at: Instant,
+}
+
+impl FederationState {
+    /// Federation state whose HTTP client only reaches public addresses,
+    /// unless `allow_private_addresses` is set.
+    pub fn new(allow_private_addresses: bool) -> Result<Self, reqwest::Error> {
+        let mut builder = Client::builder()
+            .user_agent(format!("{}/{}", APP_NAME, env!("CARGO_PKG_VERSION")))
+            .timeout(HTTP_TIMEOUT)
+            // The fetcher follows redirects itself so it can sign every hop
+            .redirect(reqwest::redirect::Policy::none());
+        if !allow_private_addresses {
+            builder = builder.dns_resolver(Arc::new(address::PublicResolver));
+        }
+
+        Ok(Self {
+            http: builder.build()?,
+            allow_private_addresses,
+            key_cache: Arc::new(RwLock::new(HashMap::new())),
+        })
+    }
+
+    /// Refuse to contact `url` if its host is an address the server must not
+    /// reach.
+    pub fn check_url(&self, url: &url::Url) -> Result<(), String> {
+        match self.allow_private_addresses {
+            true => Ok(()),
+            false => address::check_url(url),
+        }
+    }
+
+    pub(crate) async fn cached_key(&self, key_id: &str) -> Option<CachedKey> {
+        self.key_cache
+            .read()
+            .await
+            .get(key_id)
+            .filter(|k| k.fetched_at.elapsed() < KEY_CACHE_TTL)
+            .cloned()
+    }
+
+    pub(crate) async fn cache_key(&self, key_id: &str, key: CachedKey) {
+        self.key_cache.write().await.insert(key_id.to_string(), key);
+    }
+
+    pub(crate) async fn evict_key(&self, key_id: &str) {
+        self.key_cache.write().await.remove(key_id);
+    }
+}
+
+/// Whether two URLs share scheme, host and port.
+pub fn same_origin(a: &str, b: &str) -> bool {
+    match (url::Url::parse(a), url::Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+/// A signer for requests made on behalf of a local account whose actor
+/// document lives at `actor_url`. Returns `None` if the account has no key.
+pub async fn signer_for_account<C: ConnectionTrait>(
+    conn: &C,
+    account_id: i64,
+    actor_url: &str,
+    secret_key: &Secret<String>,
+) -> Result<Option<Signer>, keys::KeyError> {
+    let signer = keys::signing_key(conn, account_id, secret_key)
+        .await?
+        .map(|(key_id, private_key)| Signer::new(format!("{}#{}", actor_url, key_id), private_key));
+
+    Ok(signer)
+}
+
+/// A signer for requests the tenant at `base_url` makes on its own behalf.
+/// Returns `None` if the instance actor has no key.
+pub async fn instance_signer<C: ConnectionTrait>(
+    conn: &C,
+    base_url: &str,
+    secret_key: &Secret<String>,
+) -> Result<Option<Signer>, keys::KeyError> {
+    let signer =
+        keys::instance_signing_key(conn, secret_key)
+            .await?
+            .map(|(key_id, private_key)| {
+                Signer::new(
+                    format!("{}#{}", instance_actor_url(base_url), key_id),
+                    private_key,
+                )
+            });
+
+    Ok(signer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::same_origin;
+
+    #[test]
+    fn origins_are_compared_by_scheme_host_and_port() {
+        assert!(same_origin(
+            "https://example.com/users/alice",
+            "https://example.com/users/alice#main-key"
+        ));
+        assert!(!same_origin(
+            "https://evil.example/users/alice",
+            "https://example.com/users/alice#main-key"
+        ));
+        assert!(!same_origin(
+            "http://example.com:8080/a",
+            "http://example.com:8081/a"
+        ));
+    }
+}
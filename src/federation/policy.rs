@@ -0,0 +1,659 @@
+//! Moderation policies for remote domains.
+//!
+//! Admins of a tenant block domains with one of three severities. Suspended
+//! domains are cut off entirely: their requests are refused, nothing is
+//! delivered to them and existing follows are severed when the block is
+//! created. Silenced domains may still talk to us, but their follows need
+//! approval and their posts are only kept when someone here follows the
+//! author or is addressed. Independently of the severity a block can reject
+//! media attachments and reports. A block applies to the domain and all of
+//! its subdomains; the most specific block wins.
+//!
+//! Tenants in allowlist mode only federate with the domains on their
+//! allowlist, and their subdomains. Everyone else is treated as if they were
+//! suspended, except that nothing is severed.
+//!
+//! In authorized fetch mode a tenant only serves the ActivityPub
+//! representations of its actors and objects to requests signed by a server it
+//! federates with, so blocked servers cannot read public posts anonymously.
+//!
+//! Block lists are exchanged in the CSV format Mastodon uses for its
+//! exports.
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, Set,
+};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{
+    domain::{BlockSeverity, FederationMode},
+    entities::{
+        delivery, domain_allow, domain_block, follower, following, microblog, prelude::*,
+        remote_post,
+    },
+};
+
+use super::queue;
+
+/// The columns of an exported block list, in order.
+pub const CSV_HEADER: [&str; 6] = [
+    "#domain",
+    "#severity",
+    "#reject_media",
+    "#reject_reports",
+    "#public_comment",
+    "#obfuscate",
+];
+
+/// A block as entered by an admin or read from a block list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockEntry {
+    pub domain: String,
+    pub severity: BlockSeverity,
+    pub reject_media: bool,
+    pub reject_reports: bool,
+    pub public_comment: Option<String>,
+    pub obfuscate: bool,
+}
+
+/// The federation mode of a tenant and the blocks and allowlist entries that
+/// may apply to a set of URLs.
+#[derive(Debug, Default, Clone)]
+pub struct DomainPolicies {
+    mode: FederationMode,
+    blocks: HashMap<String, domain_block::Model>,
+    allowed: HashSet<String>,
+}
+
+impl DomainPolicies {
+    /// All policies of the tenant, for checking many URLs at once.
+    pub async fn load<C: ConnectionTrait>(conn: &C) -> Result<Self, DbErr> {
+        let mode = federation_mode(conn).await?;
+        let blocks = DomainBlock::find().all(conn).await?;
+        let allowed = match mode {
+            FederationMode::Open => vec![],
+            FederationMode::Allowlist => DomainAllow::find().all(conn).await?,
+        };
+
+        Ok(Self::new(mode, blocks, allowed))
+    }
+
+    /// Only the policies that may apply to `url`.
+    pub async fn for_url<C: ConnectionTrait>(conn: &C, url: &str) -> Result<Self, DbErr> {
+        let mode = federation_mode(conn).await?;
+        let candidates = match domain_of(url) {
+            Some(domain) => candidates(&domain),
+            None => return Ok(Self::new(mode, vec![], vec![])),
+        };
+        let blocks = DomainBlock::find()
+            .filter(domain_block::Column::Domain.is_in(candidates.clone()))
+            .all(conn)
+            .await?;
+        let allowed = match mode {
+            FederationMode::Open => vec![],
+            FederationMode::Allowlist => {
+                DomainAllow::find()
+                    .filter(domain_allow::Column::Domain.is_in(candidates))
+                    .all(conn)
+                    .await?
+            }
+        };
+
+        Ok(Self::new(mode, blocks, allowed))
+    }
+
+    fn new(
+        mode: FederationMode,
+        blocks: Vec<domain_block::Model>,
+        allowed: Vec<domain_allow::Model>,
+    ) -> Self {
+        Self {
+            mode,
+            blocks: blocks.into_iter().map(|b| (b.domain.clone(), b)).collect(),
+            allowed: allowed.into_iter().map(|a| a.domain).collect(),
+        }
+    }
+
+    /// Whether the tenant refuses to talk to the server at `url` at all,
+    /// because it is suspended or not on the allowlist.
+    pub fn refuses(&self, url: &str) -> bool {
+        self.is_suspended(url) || !self.is_allowed(url)
+    }
+
+    /// Whether `url` passes the allowlist. Everything does in open mode.
+    pub fn is_allowed(&self, url: &str) -> bool {
+        match self.mode {
+            FederationMode::Open => true,
+            FederationMode::Allowlist => match domain_of(url) {
+                Some(domain) => candidates(&domain)
+                    .iter()
+                    .any(|candidate| self.allowed.contains(candidate)),
+                None => false,
+            },
+        }
+    }
+
+    /// The most specific block covering the host of `url`, which may also be
+    /// a bare host with an optional port.
+    pub fn block_for(&self, url: &str) -> Option<&domain_block::Model> {
+        if self.blocks.is_empty() {
+            return None;
+        }
+        let domain = domain_of(url)?;
+        candidates(&domain)
+            .iter()
+            .find_map(|candidate| self.blocks.get(candidate))
+    }
+
+    pub fn severity(&self, url: &str) -> Option<BlockSeverity> {
+        self.block_for(url).map(severity_of)
+    }
+
+    pub fn is_suspended(&self, url: &str) -> bool {
+        self.severity(url) == Some(BlockSeverity::Suspend)
+    }
+
+    pub fn is_silenced(&self, url: &str) -> bool {
+        self.severity(url) == Some(BlockSeverity::Silence)
+    }
+
+    pub fn rejects_media(&self, url: &str) -> bool {
+        self.block_for(url)
+            .map(|b| b.reject_media || severity_of(b) == BlockSeverity::Suspend)
+            .unwrap_or(false)
+    }
+
+    pub fn rejects_reports(&self, url: &str) -> bool {
+        self.block_for(url)
+            .map(|b| b.reject_reports || severity_of(b) == BlockSeverity::Suspend)
+            .unwrap_or(false)
+    }
+}
+
+fn severity_of(block: &domain_block::Model) -> BlockSeverity {
+    BlockSeverity::try_from(block.severity.as_str()).unwrap_or_default()
+}
+
+/// Create or replace the block of `entry.domain` and apply it to what we
+/// already have from the domain.
+pub async fn save_block<C: ConnectionTrait>(
+    conn: &C,
+    entry: &BlockEntry,
+) -> Result<domain_block::Model, DbErr> {
+    let model = domain_block::ActiveModel {
+        domain: Set(entry.domain.clone()),
+        severity: Set(entry.severity.to_string()),
+        reject_media: Set(entry.reject_media),
+        reject_reports: Set(entry.reject_reports),
+        public_comment: Set(entry.public_comment.clone()),
+        obfuscate: Set(entry.obfuscate),
+        ..Default::default()
+    };
+    DomainBlock::insert(model)
+        .on_conflict(
+            OnConflict::column(domain_block::Column::Domain)
+                .update_columns([
+                    domain_block::Column::Severity,
+                    domain_block::Column::RejectMedia,
+                    domain_block::Column::RejectReports,
+                    domain_block::Column::PublicComment,
+                    domain_block::Column::Obfuscate,
+                ])
+                .to_owned(),
+        )
+        .exec(conn)
+        .await?;
+    if entry.severity == BlockSeverity::Suspend {
+        sever(conn, &entry.domain).await?;
+    }
+
+    DomainBlock::find()
+        .filter(domain_block::Column::Domain.eq(entry.domain.as_str()))
+        .one(conn)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound(entry.domain.clone()))
+}
+
+/// The federation mode of the tenant. Tenants without microblog settings are
+/// open.
+pub async fn federation_mode<C: ConnectionTrait>(conn: &C) -> Result<FederationMode, DbErr> {
+    let mode = Microblog::find()
+        .one(conn)
+        .await?
+        .map(|m| FederationMode::try_from(m.federation_mode).unwrap_or_default())
+        .unwrap_or_default();
+
+    Ok(mode)
+}
+
+/// Whether the tenant only serves its actors and objects to signed requests.
+pub async fn authorized_fetch<C: ConnectionTrait>(conn: &C) -> Result<bool, DbErr> {
+    let authorized_fetch = Microblog::find()
+        .one(conn)
+        .await?
+        .map(|m| m.authorized_fetch)
+        .unwrap_or_default();
+
+    Ok(authorized_fetch)
+}
+
+/// The allowlist of the tenant, ordered by domain.
+pub async fn allowed_domains<C: ConnectionTrait>(conn: &C) -> Result<Vec<String>, DbErr> {
+    let domains = DomainAllow::find()
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|a| a.domain)
+        .collect::<BTreeSet<_>>();
+
+    Ok(domains.into_iter().collect())
+}
+
+/// Switch the tenant to `mode`, turn authorized fetch mode on or off and
+/// replace the allowlist with `domains`. The microblog settings are created if
+/// the tenant has none yet, named after `default_name`.
+pub async fn save_federation<C: ConnectionTrait>(
+    conn: &C,
+    mode: FederationMode,
+    authorized_fetch: bool,
+    domains: &[String],
+    default_name: &str,
+) -> Result<(), DbErr> {
+    let mut settings: microblog::ActiveModel = match Microblog::find().one(conn).await? {
+        Some(m) => m.into(),
+        None => microblog::ActiveModel {
+            name: Set(default_name.to_string()),
+            ..Default::default()
+        },
+    };
+    settings.federation_mode = Set(mode.to_string());
+    settings.authorized_fetch = Set(authorized_fetch);
+    settings.save(conn).await?;
+
+    DomainAllow::delete_many().exec(conn).await?;
+    let rows: Vec<_> = domains
+        .iter()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|domain| domain_allow::ActiveModel {
+            domain: Set(domain.clone()),
+            ..Default::default()
+        })
+        .collect();
+    if !rows.is_empty() {
+        DomainAllow::insert_many(rows).exec(conn).await?;
+    }
+
+    Ok(())
+}
+
+/// Lift the block `id`. Severed follows are not restored.
+pub async fn remove_block<C: ConnectionTrait>(conn: &C, id: i64) -> Result<bool, DbErr> {
+    let res = DomainBlock::delete_by_id(id).exec(conn).await?;
+
+    Ok(res.rows_affected > 0)
+}
+
+/// Drop follows, posts and pending deliveries involving `domain`.
+async fn sever<C: ConnectionTrait>(conn: &C, domain: &str) -> Result<(), DbErr> {
+    let followers = Follower::find()
+        .filter(follower::Column::ActorId.contains(domain))
+        .all(conn)
+        .await?
+        .into_iter()
+        .filter(|f| covers(domain, &f.actor_id))
+        .map(|f| f.id);
+    Follower::delete_many()
+        .filter(follower::Column::Id.is_in(followers))
+        .exec(conn)
+        .await?;
+
+    let followees = Following::find()
+        .filter(following::Column::ActorId.contains(domain))
+        .all(conn)
+        .await?
+        .into_iter()
+        .filter(|f| covers(domain, &f.actor_id))
+        .map(|f| f.id);
+    Following::delete_many()
+        .filter(following::Column::Id.is_in(followees))
+        .exec(conn)
+        .await?;
+
+    let posts = RemotePost::find()
+        .filter(remote_post::Column::ActorId.contains(domain))
+        .all(conn)
+        .await?
+        .into_iter()
+        .filter(|p| covers(domain, &p.actor_id))
+        .map(|p| p.id);
+    RemotePost::delete_many()
+        .filter(remote_post::Column::Id.is_in(posts))
+        .exec(conn)
+        .await?;
+
+    let deliveries = Delivery::find()
+        .filter(delivery::Column::Status.eq(queue::PENDING))
+        .filter(delivery::Column::Host.contains(domain))
+        .all(conn)
+        .await?
+        .into_iter()
+        .filter(|d| covers(domain, &d.host))
+        .map(|d| d.id);
+    Delivery::update_many()
+        .col_expr(delivery::Column::Status, Expr::value(queue::FAILED))
+        .col_expr(
+            delivery::Column::LastError,
+            Expr::value(format!("{} is suspended", domain)),
+        )
+        .filter(delivery::Column::Id.is_in(deliveries))
+        .exec(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Whether a block of `domain` applies to `url`.
+pub fn covers(domain: &str, url: &str) -> bool {
+    match domain_of(url) {
+        Some(host) => candidates(&host).iter().any(|c| c == domain),
+        None => false,
+    }
+}
+
+/// The lower-case host of `url`, which may also be a bare host with an
+/// optional port.
+pub fn domain_of(url: &str) -> Option<String> {
+    let host = if url.contains("://") {
+        url::Url::parse(url).ok()?.host_str()?.to_string()
+    } else {
+        match url.rsplit_once(':') {
+            Some((host, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => {
+                host.to_string()
+            }
+            _ => url.to_string(),
+        }
+    };
+    let host = host.trim_end_matches('.').to_lowercase();
+
+    (!host.is_empty()).then_some(host)
+}
+
+/// `domain` and its parent domains, most specific first.
+fn candidates(domain: &str) -> Vec<String> {
+    if domain.starts_with('[') || domain.parse::<std::net::IpAddr>().is_ok() {
+        return vec![domain.to_string()];
+    }
+    let labels: Vec<&str> = domain.split('.').collect();
+    (0..labels.len()).map(|i| labels[i..].join(".")).collect()
+}
+
+/// Turn what an admin typed, a domain or a URL on it, into the form blocks
+/// are stored in.
+pub fn normalize_domain(input: &str) -> Result<String, String> {
+    let input = input.trim();
+    let domain = domain_of(input).ok_or_else(|| "A domain is required".to_string())?;
+    if domain.contains(['*', '/', '@', ' ']) {
+        return Err(format!("Invalid domain: {}", input));
+    }
+    match url::Host::parse(&domain) {
+        Ok(host) => Ok(host.to_string()),
+        Err(_) => Err(format!("Invalid domain: {}", input)),
+    }
+}
+
+/// Read a block list. Lists with a header row may order their columns
+/// freely and leave out all but `#domain`; lists without one are read in the
+/// order of [`CSV_HEADER`], so a plain list of domains suspends each of them.
+pub fn parse_csv(text: &str) -> Result<Vec<BlockEntry>, String> {
+    let mut rows = csv_rows(text)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, row)| row.iter().any(|field| !field.trim().is_empty()))
+        .peekable();
+
+    let default_columns: Vec<String> = CSV_HEADER.iter().map(|c| column_name(c)).collect();
+    let columns = match rows.peek() {
+        Some((_, header))
+            if header[0].trim().starts_with('#') || column_name(&header[0]) == "domain" =>
+        {
+            let columns = header.iter().map(|c| column_name(c)).collect();
+            rows.next();
+            columns
+        }
+        _ => default_columns,
+    };
+
+    let mut entries = vec![];
+    for (line, row) in rows {
+        let field = |name: &str| {
+            columns
+                .iter()
+                .position(|c| c == name)
+                .and_then(|i| row.get(i))
+                .map(|f| f.trim())
+                .filter(|f| !f.is_empty())
+        };
+        let error = |e: String| format!("line {}: {}", line + 1, e);
+
+        let domain = normalize_domain(field("domain").unwrap_or_default()).map_err(error)?;
+        let severity = match field("severity") {
+            Some(s) => BlockSeverity::try_from(s).map_err(error)?,
+            None => BlockSeverity::Suspend,
+        };
+        entries.push(BlockEntry {
+            domain,
+            severity,
+            reject_media: parse_bool(field("reject_media")).map_err(error)?,
+            reject_reports: parse_bool(field("reject_reports")).map_err(error)?,
+            public_comment: field("public_comment").map(str::to_string),
+            obfuscate: parse_bool(field("obfuscate")).map_err(error)?,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Write `blocks` as a block list other servers can import.
+pub fn to_csv(blocks: &[domain_block::Model]) -> String {
+    let mut csv = CSV_HEADER.join(",");
+    csv.push('\n');
+    for block in blocks {
+        let fields = [
+            block.domain.clone(),
+            block.severity.clone(),
+            block.reject_media.to_string(),
+            block.reject_reports.to_string(),
+            block.public_comment.clone().unwrap_or_default(),
+            block.obfuscate.to_string(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+fn column_name(header: &str) -> String {
+    header.trim().trim_start_matches('#').to_lowercase()
+}
+
+fn parse_bool(value: Option<&str>) -> Result<bool, String> {
+    match value.map(str::to_lowercase).as_deref() {
+        None | Some("false") | Some("0") | Some("no") => Ok(false),
+        Some("true") | Some("1") | Some("yes") => Ok(true),
+        Some(other) => Err(format!("Invalid boolean: {}", other)),
+    }
+}
+
+/// Split CSV text into rows of fields. Quoted fields may contain commas,
+/// line breaks and doubled quotes.
+fn csv_rows(text: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        covers, domain_of, normalize_domain, parse_csv, to_csv, BlockEntry, DomainPolicies,
+    };
+    use crate::{
+        domain::{BlockSeverity, FederationMode},
+        entities::{domain_allow, domain_block},
+    };
+
+    #[test]
+    fn allowlist_admits_listed_domains_and_their_subdomains() {
+        let now = chrono::Utc::now().naive_utc();
+        let allowed = domain_allow::Model {
+            id: 1,
+            domain: "partner.example".to_string(),
+            created_at: now,
+            updated_at: now,
+        };
+
+        let open = DomainPolicies::new(FederationMode::Open, vec![], vec![allowed.clone()]);
+        assert!(!open.refuses("https://anyone.example/inbox"));
+
+        let closed = DomainPolicies::new(FederationMode::Allowlist, vec![], vec![allowed]);
+        assert!(!closed.refuses("https://partner.example/inbox"));
+        assert!(!closed.refuses("social.partner.example:443"));
+        assert!(closed.refuses("https://anyone.example/inbox"));
+        assert!(closed.refuses("not a url"));
+    }
+
+    #[test]
+    fn blocks_cover_subdomains() {
+        assert!(covers("example.com", "https://example.com/users/alice"));
+        assert!(covers("example.com", "https://social.example.com/inbox"));
+        assert!(covers("example.com", "social.example.com:8443"));
+        assert!(!covers("example.com", "https://notexample.com/inbox"));
+        assert!(!covers("social.example.com", "https://example.com/inbox"));
+    }
+
+    #[test]
+    fn domains_are_extracted_from_urls_and_hosts() {
+        assert_eq!(
+            domain_of("https://Example.COM/users/alice").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(domain_of("127.0.0.1:8080").as_deref(), Some("127.0.0.1"));
+        assert_eq!(domain_of("localhost:8080").as_deref(), Some("localhost"));
+        assert_eq!(
+            normalize_domain(" https://bad.example/about ").as_deref(),
+            Ok("bad.example")
+        );
+        assert!(normalize_domain("*.example.com").is_err());
+        assert!(normalize_domain("").is_err());
+    }
+
+    #[test]
+    fn mastodon_export_is_parsed() {
+        let csv = "#domain,#severity,#reject_media,#reject_reports,#public_comment,#obfuscate\n\
+                   spam.example,suspend,false,false,Spam,false\n\
+                   Loud.Example,silence,true,true,\"rude, often\",true\n";
+
+        let entries = parse_csv(csv).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                BlockEntry {
+                    domain: "spam.example".to_string(),
+                    severity: BlockSeverity::Suspend,
+                    reject_media: false,
+                    reject_reports: false,
+                    public_comment: Some("Spam".to_string()),
+                    obfuscate: false,
+                },
+                BlockEntry {
+                    domain: "loud.example".to_string(),
+                    severity: BlockSeverity::Silence,
+                    reject_media: true,
+                    reject_reports: true,
+                    public_comment: Some("rude, often".to_string()),
+                    obfuscate: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn partial_and_headerless_lists_are_parsed() {
+        let entries = parse_csv("#severity,#domain\r\nnoop,a.example\r\n").unwrap();
+        assert_eq!(entries[0].domain, "a.example");
+        assert_eq!(entries[0].severity, BlockSeverity::Noop);
+
+        let entries = parse_csv("a.example\n\nb.example").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|e| e.severity == BlockSeverity::Suspend));
+    }
+
+    #[test]
+    fn invalid_rows_are_reported_with_their_line() {
+        let err =
+            parse_csv("#domain,#severity\nok.example,suspend\nbad.example,nuke\n").unwrap_err();
+        assert!(err.starts_with("line 3:"), "{}", err);
+    }
+
+    #[test]
+    fn exported_list_can_be_imported_again() {
+        let now = chrono::Utc::now().naive_utc();
+        let block = domain_block::Model {
+            id: 1,
+            domain: "loud.example".to_string(),
+            severity: "silence".to_string(),
+            reject_media: true,
+            reject_reports: false,
+            public_comment: Some("says \"hi\", loudly".to_string()),
+            obfuscate: false,
+            created_at: now,
+            updated_at: now,
+        };
+
+        let entries = parse_csv(&to_csv(&[block])).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].domain, "loud.example");
+        assert_eq!(entries[0].severity, BlockSeverity::Silence);
+        assert!(entries[0].reject_media);
+        assert_eq!(
+            entries[0].public_comment.as_deref(),
+            Some("says \"hi\", loudly")
+        );
+    }
+}
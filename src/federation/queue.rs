@@ -0,0 +1,739 @@
+//! Durable delivery of outbound activities.
+//!
+//! Every (activity, inbox) pair becomes a row in the `delivery` table of the
+//! tenant that sent it. A background worker claims due rows and hands them to
+//! a task per destination host, which signs and POSTs them and records the
+//! outcome. Failures back off per destination host, and a host that keeps
+//! failing is marked unreachable and only probed occasionally until it
+//! answers again. Claimed rows are leased rather than locked for the duration
+//! of the request, so deliveries interrupted by a restart are picked up again
+//! once the lease runs out. Nothing is queued for or sent to domains the
+//! tenant does not federate with.
+use chrono::NaiveDateTime;
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    DbBackend, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect, Set, Statement,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::Semaphore;
+
+use super::{
+    delivery::{deliver, DeliveryError},
+    mention,
+    policy::DomainPolicies,
+    relay, signer_for_account, Signer,
+};
+use crate::{
+    activitypub::{
+        activity::Activity, actor::Person, actor_url, followers_url, format_timestamp,
+        instance_actor_url, note::Note, PUBLIC,
+    },
+    domain::Visibility,
+    entities::{
+        account, content, delivery, delivery_host, follower, prelude::*, remote_actor, user,
+    },
+    error::error_chain_fmt,
+    keys,
+    polls::{self, Poll},
+    routes::{all_tenants, AppState, TenantData},
+};
+
+pub const PENDING: &str = "pending";
+pub const DELIVERED: &str = "delivered";
+pub const FAILED: &str = "failed";
+
+/// How often the worker looks for due deliveries.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How many deliveries a worker claims at a time.
+const BATCH_SIZE: i64 = 20;
+/// How many hosts are delivered to at the same time, across all tenants.
+const CONCURRENT_HOSTS: usize = 16;
+/// How long a claimed delivery is hidden from other workers.
+const LEASE: Duration = Duration::from_secs(5 * 60);
+/// The delay after the first failure. It doubles with every further failure.
+const BASE_BACKOFF: Duration = Duration::from_secs(30);
+/// The longest a host is left alone, which is also how often an unreachable
+/// host is probed.
+const MAX_BACKOFF: Duration = Duration::from_secs(6 * 60 * 60);
+/// Consecutive failures after which a host is considered unreachable.
+pub const UNREACHABLE_AFTER: i32 = 10;
+/// Deliveries that have not gone through after this long are given up.
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Queue `activity`, signed by the account `account_id` whose actor document
+/// is `actor_id`, for delivery to each of `inboxes`. Returns the number of
+/// deliveries queued.
+pub async fn enqueue<C, T, I>(
+    conn: &C,
+    account_id: i64,
+    actor_id: &str,
+    activity: &T,
+    inboxes: I,
+) -> Result<usize, QueueError>
+where
+    C: ConnectionTrait,
+    T: Serialize,
+    I: IntoIterator<Item = String>,
+{
+    insert(conn, Some(account_id), actor_id, activity, inboxes).await
+}
+
+/// Queue `activity`, signed by the instance actor of the tenant at
+/// `base_url`, for delivery to each of `inboxes`.
+pub async fn enqueue_as_instance<C, T, I>(
+    conn: &C,
+    base_url: &str,
+    activity: &T,
+    inboxes: I,
+) -> Result<usize, QueueError>
+where
+    C: ConnectionTrait,
+    T: Serialize,
+    I: IntoIterator<Item = String>,
+{
+    insert(conn, None, &instance_actor_url(base_url), activity, inboxes).await
+}
+
+/// Queue deliveries signed by `account_id`, or by the instance actor if there
+/// is no account.
+async fn insert<C, T, I>(
+    conn: &C,
+    account_id: Option<i64>,
+    actor_id: &str,
+    activity: &T,
+    inboxes: I,
+) -> Result<usize, QueueError>
+where
+    C: ConnectionTrait,
+    T: Serialize,
+    I: IntoIterator<Item = String>,
+{
+    let activity = serde_json::to_value(activity)
+        .map_err(|e| QueueError::UnexpectedError(anyhow::anyhow!(e)))?;
+    let now = now();
+    let policies = DomainPolicies::load(conn).await?;
+
+    let mut rows = vec![];
+    for inbox in inboxes.into_iter().collect::<BTreeSet<_>>() {
+        let Some(host) = host_of(&inbox) else {
+            tracing::warn!("not delivering to invalid inbox {}", inbox);
+            continue;
+        };
+        if policies.refuses(&inbox) {
+            tracing::debug!("not delivering to refused inbox {}", inbox);
+            continue;
+        }
+        rows.push(delivery::ActiveModel {
+            account_id: Set(account_id),
+            actor_id: Set(actor_id.to_string()),
+            inbox: Set(inbox),
+            host: Set(host),
+            activity: Set(activity.clone()),
+            status: Set(PENDING.to_string()),
+            next_attempt_at: Set(now),
+            ..Default::default()
+        });
+    }
+
+    let queued = rows.len();
+    if queued > 0 {
+        Delivery::insert_many(rows).exec(conn).await?;
+    }
+
+    Ok(queued)
+}
+
+/// The inboxes reaching every accepted follower of an account. Followers on
+/// the same server share a single delivery when the server has a shared inbox.
+pub async fn follower_inboxes<C: ConnectionTrait>(
+    conn: &C,
+    account_id: i64,
+) -> Result<Vec<String>, DbErr> {
+    let inboxes = Follower::find()
+        .filter(follower::Column::AccountId.eq(account_id))
+        .filter(follower::Column::Accepted.eq(true))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|f| f.shared_inbox.unwrap_or(f.inbox))
+        .collect::<BTreeSet<_>>();
+
+    Ok(inboxes.into_iter().collect())
+}
+
+/// Queue the `Create` of a newly published local post for its audience: the
+/// followers of its author, unless it is a direct post, the remote accounts
+/// it mentions and, for public posts, the relays the tenant subscribes to.
+pub async fn enqueue_post<C: ConnectionTrait>(
+    conn: &C,
+    base_url: &str,
+    post: &content::Model,
+) -> Result<usize, QueueError> {
+    enqueue_for_audience(conn, base_url, post, vec![], Note::into_create).await
+}
+
+/// Queue the `Update` of an edited local post for the audience its `Create`
+/// went to.
+pub async fn enqueue_post_update<C: ConnectionTrait>(
+    conn: &C,
+    base_url: &str,
+    post: &content::Model,
+) -> Result<usize, QueueError> {
+    let updated = format_timestamp(post.edited_at.unwrap_or(post.updated_at));
+    enqueue_for_audience(conn, base_url, post, vec![], |mut note| {
+        note.updated = Some(updated);
+        note.into_update()
+    })
+    .await
+}
+
+/// Queue the `Delete` of the deleted local post `post` for the audience its
+/// `Create` went to.
+pub async fn enqueue_post_delete<C: ConnectionTrait>(
+    conn: &C,
+    base_url: &str,
+    post: &content::Model,
+) -> Result<usize, QueueError> {
+    let deleted = post.deleted_at.map(format_timestamp);
+    enqueue_for_audience(conn, base_url, post, vec![], |note| {
+        note.into_delete(deleted)
+    })
+    .await
+}
+
+/// Queue the `Update` carrying the final counts of the closed poll `poll` of
+/// a local post, for the audience of the post and the remote accounts that
+/// voted in it.
+pub async fn enqueue_poll_update<C: ConnectionTrait>(
+    conn: &C,
+    base_url: &str,
+    post: &content::Model,
+    poll: &Poll,
+) -> Result<usize, QueueError> {
+    let voters = polls::remote_voters(conn, poll).await?;
+    let inboxes = RemoteActor::find()
+        .filter(remote_actor::Column::ActorId.is_in(voters))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|actor| actor.shared_inbox.unwrap_or(actor.inbox))
+        .collect();
+    enqueue_for_audience(conn, base_url, post, inboxes, |note| {
+        let id = format!("{}#updates/closed", note.id);
+        let mut update = note.into_update();
+        update.id = id;
+        update
+    })
+    .await
+}
+
+/// Queue the activity `wrap` makes of the note of `post` for the audience of
+/// the post and `extra_inboxes`.
+async fn enqueue_for_audience<C: ConnectionTrait>(
+    conn: &C,
+    base_url: &str,
+    post: &content::Model,
+    extra_inboxes: Vec<String>,
+    wrap: impl FnOnce(Note) -> Activity,
+) -> Result<usize, QueueError> {
+    let visibility = Visibility::try_from(post.visibility.as_str()).unwrap_or_default();
+    let account = Account::find_by_id(post.publisher_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("post {} has no publisher", post.id))?;
+    let handle = account.username.unwrap_or_default();
+
+    let mentions = mention::for_post(conn, post.id).await?;
+    let mut note = Note::from_content(base_url, &handle, post, &mentions);
+    if let Some(poll) = polls::for_post(conn, post.id).await? {
+        note = note.with_poll(&poll);
+    }
+    let activity = wrap(note);
+    let mut inboxes = match visibility {
+        Visibility::Direct => vec![],
+        _ => follower_inboxes(conn, account.id).await?,
+    };
+    inboxes.extend(mentions.into_iter().filter_map(|m| m.inbox));
+    if visibility == Visibility::Public {
+        inboxes.extend(relay::inboxes(conn).await?);
+    }
+    inboxes.extend(extra_inboxes);
+
+    enqueue(
+        conn,
+        account.id,
+        &actor_url(base_url, &handle),
+        &activity,
+        inboxes,
+    )
+    .await
+}
+
+/// Queue an `Update` of the actor document of `account`, owned by `user`, for
+/// the followers of the account, so remote servers refresh their copy.
+pub async fn enqueue_actor_update<C: ConnectionTrait>(
+    conn: &C,
+    base_url: &str,
+    account: &account::Model,
+    user: &user::Model,
+) -> Result<usize, QueueError> {
+    let mut person = Person::from_account(base_url, account, user);
+    if let Some(key) = keys::active_key(conn, account.id)
+        .await
+        .map_err(|e| QueueError::UnexpectedError(e.into()))?
+    {
+        person = person.with_public_key(&key);
+    }
+    person.context = serde_json::Value::Null;
+    let handle = account.username.as_deref().unwrap_or_default();
+    let actor = person.id.clone();
+
+    let mut update = Activity::new(
+        "Update",
+        format!(
+            "{}#updates/{}",
+            actor,
+            account.updated_at.and_utc().timestamp()
+        ),
+        actor.clone(),
+        serde_json::to_value(person).map_err(|e| QueueError::UnexpectedError(e.into()))?,
+    );
+    update.to = vec![PUBLIC.to_string()];
+    update.cc = vec![followers_url(base_url, handle)];
+    let inboxes = follower_inboxes(conn, account.id).await?;
+
+    enqueue(conn, account.id, &actor, &update, inboxes).await
+}
+
+/// Forget past failures of `host`, e.g. because it just sent us something.
+pub async fn mark_reachable<C: ConnectionTrait>(conn: &C, host: &str) -> Result<(), DbErr> {
+    let now = now();
+    DeliveryHost::update_many()
+        .col_expr(delivery_host::Column::Failures, 0.into())
+        .col_expr(
+            delivery_host::Column::UnreachableSince,
+            Option::<NaiveDateTime>::None.into(),
+        )
+        .col_expr(delivery_host::Column::NextAttemptAt, now.into())
+        .filter(delivery_host::Column::Host.eq(host))
+        .filter(delivery_host::Column::Failures.gt(0))
+        .exec(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// The deliveries still waiting to go out, oldest first.
+pub async fn pending<C: ConnectionTrait>(
+    conn: &C,
+    limit: u64,
+) -> Result<Vec<delivery::Model>, DbErr> {
+    Delivery::find()
+        .filter(delivery::Column::Status.eq(PENDING))
+        .order_by_asc(delivery::Column::CreatedAt)
+        .limit(limit)
+        .all(conn)
+        .await
+}
+
+/// The most recent deliveries that were given up, newest first.
+pub async fn failed<C: ConnectionTrait>(
+    conn: &C,
+    limit: u64,
+) -> Result<Vec<delivery::Model>, DbErr> {
+    Delivery::find()
+        .filter(delivery::Column::Status.eq(FAILED))
+        .order_by_desc(delivery::Column::UpdatedAt)
+        .limit(limit)
+        .all(conn)
+        .await
+}
+
+/// The hosts whose last deliveries failed, the longest failing first.
+pub async fn failing_hosts<C: ConnectionTrait>(
+    conn: &C,
+) -> Result<Vec<delivery_host::Model>, DbErr> {
+    DeliveryHost::find()
+        .filter(delivery_host::Column::Failures.gt(0))
+        .order_by_desc(delivery_host::Column::Failures)
+        .all(conn)
+        .await
+}
+
+/// The hosts deliveries are currently being made to, per tenant, and the
+/// permits bounding how many there are at a time.
+#[derive(Clone)]
+struct InFlight {
+    hosts: Arc<Mutex<HashSet<(String, String)>>>,
+    permits: Arc<Semaphore>,
+}
+
+/// Run the delivery worker for all tenants until the process exits.
+pub fn spawn_worker(state: AppState) {
+    let in_flight = InFlight {
+        hosts: Arc::new(Mutex::new(HashSet::new())),
+        permits: Arc::new(Semaphore::new(CONCURRENT_HOSTS)),
+    };
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            for tenant in all_tenants(&state).await {
+                if let Err(e) = run_once(&state, &tenant, &in_flight).await {
+                    tracing::error!("delivery to {} failed: {:?}", tenant.domain, e);
+                }
+            }
+        }
+    });
+}
+
+/// Start the deliveries of `tenant` that are currently due. Each host is
+/// delivered to by a task of its own, so a slow host only holds up its own
+/// deliveries; deliveries to a host that is still busy, or beyond the
+/// `CONCURRENT_HOSTS` limit, are put back for a later run. Returns the number
+/// of hosts delivered to.
+async fn run_once(
+    state: &AppState,
+    tenant: &TenantData,
+    in_flight: &InFlight,
+) -> Result<usize, QueueError> {
+    if in_flight.permits.available_permits() == 0 {
+        return Ok(0);
+    }
+    let db = &tenant.db;
+    let jobs = claim(db).await?;
+    if jobs.is_empty() {
+        return Ok(0);
+    }
+    let policies = DomainPolicies::load(db).await?;
+
+    let mut by_host: BTreeMap<String, Vec<delivery::Model>> = BTreeMap::new();
+    for job in jobs {
+        if policies.refuses(&job.inbox) {
+            // The domain was blocked after the delivery was queued
+            let mut job: delivery::ActiveModel = job.into();
+            job.status = Set(FAILED.to_string());
+            job.last_error = Set(Some("the domain is not federated with".to_string()));
+            job.update(db).await?;
+            continue;
+        }
+        by_host.entry(job.host.clone()).or_default().push(job);
+    }
+
+    let mut started = 0;
+    for (host, jobs) in by_host {
+        let key = (tenant.domain.clone(), host);
+        let permit = match in_flight.permits.clone().try_acquire_owned() {
+            Ok(permit) if in_flight.hosts.lock().unwrap().insert(key.clone()) => permit,
+            _ => {
+                for job in jobs {
+                    release(db, job).await?;
+                }
+                continue;
+            }
+        };
+        started += 1;
+
+        let state = state.clone();
+        let db = db.clone();
+        let hosts = in_flight.hosts.clone();
+        tokio::spawn(async move {
+            if let Err(e) = attempt_host(&state, &db, jobs).await {
+                tracing::error!("delivery to {} failed: {:?}", key.1, e);
+            }
+            hosts.lock().unwrap().remove(&key);
+            drop(permit);
+        });
+    }
+
+    Ok(started)
+}
+
+/// Attempt the deliveries to a single host in order, stopping at the first
+/// one the host does not answer.
+async fn attempt_host(
+    state: &AppState,
+    db: &DatabaseConnection,
+    jobs: Vec<delivery::Model>,
+) -> Result<(), QueueError> {
+    let mut jobs = jobs.into_iter();
+    for job in jobs.by_ref() {
+        if !attempt(state, db, job).await? {
+            break;
+        }
+    }
+    // The host just failed; wait for its backoff like everyone else.
+    for job in jobs {
+        release(db, job).await?;
+    }
+
+    Ok(())
+}
+
+/// Lease a batch of due deliveries whose host is not backing off.
+async fn claim(db: &DatabaseConnection) -> Result<Vec<delivery::Model>, DbErr> {
+    let now = now();
+    let lease_until = now + chrono::Duration::from_std(LEASE).unwrap();
+
+    Delivery::find()
+        .from_raw_sql(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+UPDATE delivery SET next_attempt_at = $2
+WHERE id IN (
+    SELECT d.id FROM delivery d
+    LEFT JOIN delivery_host h ON h.host = d.host
+    WHERE d.status = 'pending'
+        AND d.next_attempt_at <= $1
+        AND (h.next_attempt_at IS NULL OR h.next_attempt_at <= $1)
+    ORDER BY d.next_attempt_at
+    LIMIT $3
+    FOR UPDATE OF d SKIP LOCKED
+)
+RETURNING *"#,
+            vec![now.into(), lease_until.into(), BATCH_SIZE.into()],
+        ))
+        .all(db)
+        .await
+}
+
+/// Put a claimed delivery back until its host may be tried again.
+async fn release(db: &DatabaseConnection, job: delivery::Model) -> Result<(), DbErr> {
+    let retry_at = host_retry_at(db, &job.host).await?.unwrap_or_else(now);
+    let mut job: delivery::ActiveModel = job.into();
+    job.next_attempt_at = Set(retry_at);
+    job.update(db).await?;
+
+    Ok(())
+}
+
+/// POST one delivery and record the outcome. Returns whether the host
+/// answered.
+#[tracing::instrument(name = "Attempt delivery", skip(state, db, job), fields(id = job.id, inbox = %job.inbox))]
+async fn attempt(
+    state: &AppState,
+    db: &DatabaseConnection,
+    job: delivery::Model,
+) -> Result<bool, QueueError> {
+    let secret_key = &state.global_config.server.secret_key;
+    let signer = match job.account_id {
+        Some(account_id) => signer_for_account(db, account_id, &job.actor_id, secret_key).await,
+        None => keys::instance_signing_key(db, secret_key).await.map(|key| {
+            key.map(|(key_id, private_key)| {
+                Signer::new(format!("{}#{}", job.actor_id, key_id), private_key)
+            })
+        }),
+    }
+    .map_err(|e| QueueError::UnexpectedError(e.into()))?;
+
+    let result = match &signer {
+        Some(signer) => deliver(&state.federation, signer, &job.inbox, &job.activity).await,
+        None => Err(DeliveryError::UnexpectedError(anyhow::anyhow!(
+            "{} has no signing key",
+            job.actor_id
+        ))),
+    };
+
+    let now = now();
+    let host = job.host.clone();
+    let created_at = job.created_at;
+    let mut job: delivery::ActiveModel = job.into();
+    job.attempts = Set(job.attempts.as_ref() + 1);
+
+    let answered = match result {
+        Ok(()) => {
+            job.status = Set(DELIVERED.to_string());
+            job.delivered_at = Set(Some(now));
+            job.last_error = Set(None);
+            record_success(db, &host).await?;
+            true
+        }
+        Err(e) if is_permanent(&e) => {
+            tracing::warn!("giving up delivery: {}", e);
+            job.status = Set(FAILED.to_string());
+            job.last_error = Set(Some(e.to_string()));
+            if matches!(e, DeliveryError::Rejected(_)) {
+                record_success(db, &host).await?;
+            }
+            true
+        }
+        Err(e) => {
+            tracing::info!("delivery failed, will retry: {}", e);
+            let retry_at = record_failure(db, &host).await?;
+            job.last_error = Set(Some(e.to_string()));
+            if now - created_at > chrono::Duration::from_std(MAX_AGE).unwrap() {
+                job.status = Set(FAILED.to_string());
+            } else {
+                job.next_attempt_at = Set(retry_at);
+            }
+            false
+        }
+    };
+    job.update(db).await?;
+
+    Ok(answered)
+}
+
+/// Errors that will not go away by trying again: the receiving server refused
+/// the activity, or we are unable to sign it.
+fn is_permanent(e: &DeliveryError) -> bool {
+    match e {
+        DeliveryError::Rejected(status) => {
+            (400..500).contains(status) && *status != 408 && *status != 429
+        }
+        DeliveryError::Signature(_) | DeliveryError::UnexpectedError(_) => true,
+        DeliveryError::Http(_) => false,
+    }
+}
+
+async fn host_retry_at(
+    db: &DatabaseConnection,
+    host: &str,
+) -> Result<Option<NaiveDateTime>, DbErr> {
+    let host = DeliveryHost::find()
+        .filter(delivery_host::Column::Host.eq(host))
+        .one(db)
+        .await?;
+
+    Ok(host.map(|h| h.next_attempt_at))
+}
+
+async fn record_success(db: &DatabaseConnection, host: &str) -> Result<(), DbErr> {
+    let now = now();
+    let model = delivery_host::ActiveModel {
+        host: Set(host.to_string()),
+        failures: Set(0),
+        next_attempt_at: Set(now),
+        unreachable_since: Set(None),
+        last_success_at: Set(Some(now)),
+        ..Default::default()
+    };
+    DeliveryHost::insert(model)
+        .on_conflict(
+            OnConflict::column(delivery_host::Column::Host)
+                .update_columns([
+                    delivery_host::Column::Failures,
+                    delivery_host::Column::NextAttemptAt,
+                    delivery_host::Column::UnreachableSince,
+                    delivery_host::Column::LastSuccessAt,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Count a failure against `host` and return when it may be tried again.
+async fn record_failure(db: &DatabaseConnection, host: &str) -> Result<NaiveDateTime, DbErr> {
+    let now = now();
+    let previous = DeliveryHost::find()
+        .filter(delivery_host::Column::Host.eq(host))
+        .one(db)
+        .await?;
+    let failures = previous.as_ref().map(|h| h.failures).unwrap_or(0) + 1;
+    let unreachable_since = match previous.and_then(|h| h.unreachable_since) {
+        Some(since) => Some(since),
+        None if failures >= UNREACHABLE_AFTER => {
+            tracing::warn!("marking {} unreachable after {} failures", host, failures);
+            Some(now)
+        }
+        None => None,
+    };
+    let retry_at = now + chrono::Duration::from_std(backoff(failures)).unwrap();
+
+    let model = delivery_host::ActiveModel {
+        host: Set(host.to_string()),
+        failures: Set(failures),
+        next_attempt_at: Set(retry_at),
+        unreachable_since: Set(unreachable_since),
+        ..Default::default()
+    };
+    DeliveryHost::insert(model)
+        .on_conflict(
+            OnConflict::column(delivery_host::Column::Host)
+                .update_columns([
+                    delivery_host::Column::Failures,
+                    delivery_host::Column::NextAttemptAt,
+                    delivery_host::Column::UnreachableSince,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+
+    Ok(retry_at)
+}
+
+/// How long to leave a host alone after `failures` consecutive failures.
+pub fn backoff(failures: i32) -> Duration {
+    let exponent = failures.clamp(1, 32) as u32 - 1;
+    BASE_BACKOFF
+        .checked_mul(2u32.saturating_pow(exponent))
+        .unwrap_or(MAX_BACKOFF)
+        .min(MAX_BACKOFF)
+}
+
+/// The authority (host and port) deliveries to `inbox` are grouped by.
+pub fn host_of(inbox: &str) -> Option<String> {
+    let url = url::Url::parse(inbox).ok()?;
+    let host = url.host_str()?;
+
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+fn now() -> NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+#[derive(thiserror::Error)]
+pub enum QueueError {
+    #[error(transparent)]
+    Database(#[from] DbErr),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for QueueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backoff, host_of, MAX_BACKOFF};
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_doubles_up_to_a_limit() {
+        assert_eq!(backoff(1), Duration::from_secs(30));
+        assert_eq!(backoff(2), Duration::from_secs(60));
+        assert_eq!(backoff(4), Duration::from_secs(240));
+        assert_eq!(backoff(20), MAX_BACKOFF);
+        assert_eq!(backoff(i32::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn deliveries_are_grouped_by_authority() {
+        assert_eq!(
+            host_of("https://example.com/inbox").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(
+            host_of("http://localhost:8080/users/a/inbox").as_deref(),
+            Some("localhost:8080")
+        );
+        assert_eq!(host_of("not a url"), None);
+    }
+}
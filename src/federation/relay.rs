@@ -0,0 +1,229 @@
+//! Subscriptions of the tenant to relays.
+//!
+//! A relay rebroadcasts the public posts of every server subscribed to it.
+//! The instance actor subscribes by following the relay. LitePub relays are
+//! given as their actor and followed like any other actor. Mastodon-style
+//! relays are given as their inbox and followed with the public collection as
+//! object. Once a relay accepts, it `Announce`s the public posts of the other
+//! subscribers to us, and our own public posts are delivered to its inbox.
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    Set,
+};
+use uuid::Uuid;
+
+use super::{
+    fetch::{FetchError, Fetcher},
+    policy::DomainPolicies,
+    queue::{self, QueueError},
+    remote,
+};
+use crate::{
+    activitypub::{activity::Activity, instance_actor_url, PUBLIC},
+    entities::{prelude::*, relay},
+    error::error_chain_fmt,
+};
+
+pub const PENDING: &str = "pending";
+pub const ACCEPTED: &str = "accepted";
+pub const REJECTED: &str = "rejected";
+
+/// Subscribe to the relay at `url`, which is either the actor of a LitePub
+/// relay or the inbox of a Mastodon-style relay, by sending it a `Follow`.
+pub async fn subscribe<C: ConnectionTrait>(
+    fetcher: &Fetcher<'_>,
+    conn: &C,
+    base_url: &str,
+    url: &str,
+) -> Result<relay::Model, RelayError> {
+    let url = url.trim();
+    match url::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+        _ => return Err(RelayError::Invalid(format!("not a relay URL: {}", url))),
+    }
+    let policies = DomainPolicies::for_url(conn, url).await?;
+    if policies.refuses(url) {
+        return Err(RelayError::Invalid(format!(
+            "this server does not federate with the domain of {}",
+            url
+        )));
+    }
+    let existing = Relay::find()
+        .filter(relay::Column::Url.eq(url))
+        .one(conn)
+        .await?;
+    if existing.is_some() {
+        return Err(RelayError::Invalid(format!(
+            "already subscribed to {}",
+            url
+        )));
+    }
+
+    let (inbox, actor_id) = if is_inbox(url) {
+        (url.to_string(), None)
+    } else {
+        let actor = remote::actor(fetcher, conn, url).await?;
+        (actor.inbox, Some(actor.actor_id))
+    };
+    let relay = relay::ActiveModel {
+        url: Set(url.to_string()),
+        inbox: Set(inbox),
+        actor_id: Set(actor_id),
+        follow_id: Set(format!(
+            "{}#relays/{}",
+            instance_actor_url(base_url),
+            Uuid::new_v4()
+        )),
+        state: Set(PENDING.to_string()),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?;
+
+    let follow = follow_activity(base_url, &relay);
+    queue::enqueue_as_instance(conn, base_url, &follow, [relay.inbox.clone()]).await?;
+
+    Ok(relay)
+}
+
+/// Unsubscribe from the relay `id` by undoing its `Follow`. Returns whether
+/// there was such a relay.
+pub async fn unsubscribe<C: ConnectionTrait>(
+    conn: &C,
+    base_url: &str,
+    id: i64,
+) -> Result<bool, RelayError> {
+    let Some(relay) = Relay::find_by_id(id).one(conn).await? else {
+        return Ok(false);
+    };
+
+    let actor = instance_actor_url(base_url);
+    let mut follow = follow_activity(base_url, &relay);
+    follow.context = serde_json::Value::Null;
+    let mut undo = Activity::new(
+        "Undo",
+        format!("{}/undo", relay.follow_id),
+        actor,
+        serde_json::to_value(&follow).map_err(|e| QueueError::UnexpectedError(e.into()))?,
+    );
+    undo.to = follow.to.clone();
+    queue::enqueue_as_instance(conn, base_url, &undo, [relay.inbox.clone()]).await?;
+    Relay::delete_by_id(relay.id).exec(conn).await?;
+
+    Ok(true)
+}
+
+/// Record the answer of `actor` to the relay subscription `follow_id`.
+/// Returns whether `follow_id` was a relay subscription. Mastodon-style
+/// relays are only known by their inbox until they answer.
+pub async fn answer<C: ConnectionTrait>(
+    conn: &C,
+    follow_id: &str,
+    actor: &str,
+    accepted: bool,
+) -> Result<bool, DbErr> {
+    let Some(relay) = Relay::find()
+        .filter(relay::Column::FollowId.eq(follow_id))
+        .one(conn)
+        .await?
+    else {
+        return Ok(false);
+    };
+    if relay.actor_id.as_deref().is_some_and(|id| id != actor) {
+        tracing::warn!("{} answered the subscription to {}", actor, relay.url);
+        return Ok(false);
+    }
+
+    let mut model: relay::ActiveModel = relay.into();
+    model.actor_id = Set(Some(actor.to_string()));
+    model.state = Set(if accepted { ACCEPTED } else { REJECTED }.to_string());
+    model.update(conn).await?;
+
+    Ok(true)
+}
+
+/// The relay `actor` is, if the tenant is subscribed to it.
+pub async fn accepted<C: ConnectionTrait>(
+    conn: &C,
+    actor: &str,
+) -> Result<Option<relay::Model>, DbErr> {
+    Relay::find()
+        .filter(relay::Column::ActorId.eq(actor))
+        .filter(relay::Column::State.eq(ACCEPTED))
+        .one(conn)
+        .await
+}
+
+/// The inboxes of the relays that accepted the subscription of the tenant.
+pub async fn inboxes<C: ConnectionTrait>(conn: &C) -> Result<Vec<String>, DbErr> {
+    let inboxes = Relay::find()
+        .filter(relay::Column::State.eq(ACCEPTED))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|relay| relay.inbox)
+        .collect();
+
+    Ok(inboxes)
+}
+
+/// Every relay subscription of the tenant, in the order they were made.
+pub async fn all<C: ConnectionTrait>(conn: &C) -> Result<Vec<relay::Model>, DbErr> {
+    Relay::find()
+        .order_by_asc(relay::Column::Id)
+        .all(conn)
+        .await
+}
+
+/// The `Follow` subscribing the instance actor to `relay`.
+fn follow_activity(base_url: &str, relay: &relay::Model) -> Activity {
+    let object = if is_inbox(&relay.url) {
+        PUBLIC.to_string()
+    } else {
+        relay.actor_id.clone().unwrap_or_else(|| relay.url.clone())
+    };
+    let mut follow = Activity::new(
+        "Follow",
+        relay.follow_id.clone(),
+        instance_actor_url(base_url),
+        serde_json::Value::String(object.clone()),
+    );
+    follow.to = vec![object];
+
+    follow
+}
+
+/// Mastodon-style relays are named by their inbox.
+fn is_inbox(url: &str) -> bool {
+    url.trim_end_matches('/').ends_with("/inbox")
+}
+
+#[derive(thiserror::Error)]
+pub enum RelayError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error(transparent)]
+    Fetch(#[from] FetchError),
+    #[error(transparent)]
+    Queue(#[from] QueueError),
+    #[error(transparent)]
+    Database(#[from] DbErr),
+}
+
+impl std::fmt::Debug for RelayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_inbox;
+
+    #[test]
+    fn mastodon_relays_are_named_by_their_inbox() {
+        assert!(is_inbox("https://relay.example/inbox"));
+        assert!(is_inbox("https://relay.example/inbox/"));
+        assert!(!is_inbox("https://relay.example/actor"));
+    }
+}
@@ -0,0 +1,225 @@
+//! Local copies of remote actors and objects.
+//!
+//! Cached documents are used as long as they are younger than their TTL and
+//! fetched again afterwards. Callers that have reason to believe a copy is
+//! outdated, e.g. because a signature no longer verifies against the cached
+//! key, refresh it explicitly. Nothing is fetched from servers the tenant does
+//! not federate with.
+use chrono::NaiveDateTime;
+use sea_orm::{
+    sea_query::OnConflict, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, Set,
+};
+use serde_json::Value;
+use std::time::Duration;
+
+use super::fetch::{parse_actor, FetchError, Fetcher};
+use crate::{
+    activitypub::actor::Person,
+    entities::{prelude::*, remote_actor, remote_object},
+};
+
+/// How long a cached actor is trusted.
+pub const ACTOR_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long a cached object is trusted.
+pub const OBJECT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The actor `id`, from the cache if it is fresh enough.
+pub async fn actor<C: ConnectionTrait>(
+    fetcher: &Fetcher<'_>,
+    conn: &C,
+    id: &str,
+) -> Result<remote_actor::Model, FetchError> {
+    let cached = RemoteActor::find()
+        .filter(remote_actor::Column::ActorId.eq(id))
+        .one(conn)
+        .await?;
+    match cached {
+        Some(actor) if is_fresh(actor.fetched_at, ACTOR_TTL) => Ok(actor),
+        _ => refresh_actor(fetcher, conn, id).await,
+    }
+}
+
+/// Fetch the actor `id` and update the cached copy.
+pub async fn refresh_actor<C: ConnectionTrait>(
+    fetcher: &Fetcher<'_>,
+    conn: &C,
+    id: &str,
+) -> Result<remote_actor::Model, FetchError> {
+    let document = fetcher.fetch_json(conn, id).await?;
+    let person = parse_actor(document.clone(), id)?;
+
+    Ok(store_actor(conn, &person, document).await?)
+}
+
+/// The fresh cached actor publishing the key `key_id`, if there is one.
+pub async fn actor_by_key<C: ConnectionTrait>(
+    conn: &C,
+    key_id: &str,
+) -> Result<Option<remote_actor::Model>, DbErr> {
+    let actor = RemoteActor::find()
+        .filter(remote_actor::Column::PublicKeyId.eq(key_id))
+        .one(conn)
+        .await?
+        .filter(|a| is_fresh(a.fetched_at, ACTOR_TTL));
+
+    Ok(actor)
+}
+
+/// Cache `person`, whose full actor document is `document`.
+pub async fn store_actor<C: ConnectionTrait>(
+    conn: &C,
+    person: &Person,
+    document: Value,
+) -> Result<remote_actor::Model, DbErr> {
+    let (key_id, key_pem) = match &person.public_key {
+        Some(key) => (Some(key.id.clone()), Some(key.public_key_pem.clone())),
+        None => (None, None),
+    };
+    let model = remote_actor::ActiveModel {
+        actor_id: Set(person.id.clone()),
+        kind: Set(person.kind.clone()),
+        username: Set(person.preferred_username.clone()),
+        name: Set(person.name.clone()),
+        summary: Set(person.summary.clone()),
+        url: Set(person.url.clone()),
+        inbox: Set(person.inbox.clone()),
+        shared_inbox: Set(person
+            .endpoints
+            .as_ref()
+            .and_then(|e| e.shared_inbox.clone())),
+        outbox: Set(person.outbox.clone()),
+        followers: Set(person.followers.clone()),
+        following: Set(person.following.clone()),
+        locked: Set(person.manually_approves_followers),
+        public_key_id: Set(key_id),
+        public_key_pem: Set(key_pem),
+        document: Set(document),
+        fetched_at: Set(now()),
+        ..Default::default()
+    };
+    RemoteActor::insert(model)
+        .on_conflict(
+            OnConflict::column(remote_actor::Column::ActorId)
+                .update_columns([
+                    remote_actor::Column::Kind,
+                    remote_actor::Column::Username,
+                    remote_actor::Column::Name,
+                    remote_actor::Column::Summary,
+                    remote_actor::Column::Url,
+                    remote_actor::Column::Inbox,
+                    remote_actor::Column::SharedInbox,
+                    remote_actor::Column::Outbox,
+                    remote_actor::Column::Followers,
+                    remote_actor::Column::Following,
+                    remote_actor::Column::Locked,
+                    remote_actor::Column::PublicKeyId,
+                    remote_actor::Column::PublicKeyPem,
+                    remote_actor::Column::Document,
+                    remote_actor::Column::FetchedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(conn)
+        .await?;
+
+    RemoteActor::find()
+        .filter(remote_actor::Column::ActorId.eq(person.id.as_str()))
+        .one(conn)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound(person.id.clone()))
+}
+
+/// Forget the actor `id`, e.g. because it was deleted.
+pub async fn forget_actor<C: ConnectionTrait>(conn: &C, id: &str) -> Result<(), DbErr> {
+    RemoteActor::delete_many()
+        .filter(remote_actor::Column::ActorId.eq(id))
+        .exec(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// The object `id`, from the cache if it is fresh enough.
+pub async fn object<C: ConnectionTrait>(
+    fetcher: &Fetcher<'_>,
+    conn: &C,
+    id: &str,
+) -> Result<remote_object::Model, FetchError> {
+    let cached = RemoteObject::find()
+        .filter(remote_object::Column::ObjectId.eq(id))
+        .one(conn)
+        .await?;
+    match cached {
+        Some(object) if is_fresh(object.fetched_at, OBJECT_TTL) => Ok(object),
+        _ => refresh_object(fetcher, conn, id).await,
+    }
+}
+
+/// Fetch the object `id` and update the cached copy.
+pub async fn refresh_object<C: ConnectionTrait>(
+    fetcher: &Fetcher<'_>,
+    conn: &C,
+    id: &str,
+) -> Result<remote_object::Model, FetchError> {
+    let document = fetcher.fetch_json(conn, id).await?;
+    let object_id = document["id"].as_str().unwrap_or(id).to_string();
+    let attributed_to = match &document["attributedTo"] {
+        Value::String(actor) => Some(actor.clone()),
+        actor => actor["id"].as_str().map(str::to_string),
+    };
+    let model = remote_object::ActiveModel {
+        object_id: Set(object_id.clone()),
+        kind: Set(document["type"].as_str().unwrap_or_default().to_string()),
+        attributed_to: Set(attributed_to),
+        document: Set(document),
+        fetched_at: Set(now()),
+        ..Default::default()
+    };
+    RemoteObject::insert(model)
+        .on_conflict(
+            OnConflict::column(remote_object::Column::ObjectId)
+                .update_columns([
+                    remote_object::Column::Kind,
+                    remote_object::Column::AttributedTo,
+                    remote_object::Column::Document,
+                    remote_object::Column::FetchedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(conn)
+        .await?;
+
+    let object = RemoteObject::find()
+        .filter(remote_object::Column::ObjectId.eq(object_id.as_str()))
+        .one(conn)
+        .await?
+        .ok_or(DbErr::RecordNotFound(object_id))?;
+
+    Ok(object)
+}
+
+/// Refuse to fetch from servers the tenant does not federate with.
+fn is_fresh(fetched_at: NaiveDateTime, ttl: Duration) -> bool {
+    match chrono::Duration::from_std(ttl) {
+        Ok(ttl) => now() - fetched_at < ttl,
+        Err(_) => true,
+    }
+}
+
+fn now() -> NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_fresh;
+    use std::time::Duration;
+
+    #[test]
+    fn entries_expire_after_their_ttl() {
+        let ttl = Duration::from_secs(60);
+        let now = chrono::Utc::now().naive_utc();
+        assert!(is_fresh(now - chrono::Duration::seconds(30), ttl));
+        assert!(!is_fresh(now - chrono::Duration::seconds(90), ttl));
+    }
+}
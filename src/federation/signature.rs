@@ -0,0 +1,803 @@
+//! HTTP message signatures as used between fediverse servers.
+//!
+//! Outbound requests are signed following draft-cavage-http-signatures, which
+//! is what the wider fediverse expects. Inbound requests are accepted with
+//! either a draft-cavage `Signature` header or an RFC 9421
+//! `Signature-Input`/`Signature` pair.
+use axum::http::{HeaderMap, Method};
+use rsa::{
+    pkcs1::DecodeRsaPublicKey,
+    pkcs1v15::{Signature, SigningKey, VerifyingKey},
+    pkcs8::DecodePublicKey,
+    signature::{SignatureEncoding, Signer as _, Verifier},
+    RsaPrivateKey, RsaPublicKey,
+};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, SystemTime};
+
+use crate::error::error_chain_fmt;
+
+/// Requests whose `Date` (or `created` parameter) is further than this from
+/// our clock are rejected.
+pub const MAX_CLOCK_SKEW: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// The parts of an inbound request that take part in signature verification.
+#[derive(Debug)]
+pub struct RequestParts<'a> {
+    pub method: &'a Method,
+    /// Scheme and authority the request was addressed to, e.g. `https://example.com`
+    pub base_url: &'a str,
+    pub path_and_query: &'a str,
+    pub headers: &'a HeaderMap,
+    pub body: &'a [u8],
+}
+
+/// A parsed signature, independent of the specification it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureInput {
+    pub key_id: String,
+    /// The covered components, lower-cased
+    pub components: Vec<String>,
+    pub signature: Vec<u8>,
+    pub created: Option<u64>,
+    pub scheme: SignatureScheme,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureScheme {
+    /// draft-cavage-http-signatures
+    Cavage,
+    /// RFC 9421. The serialized `@signature-params` value is kept because it
+    /// is part of the signature base.
+    Rfc9421 { params: String },
+}
+
+/// Find and parse the signature of an inbound request.
+pub fn parse_signature(headers: &HeaderMap) -> Result<SignatureInput, SignatureError> {
+    if let Some(input) = header_str(headers, "signature-input")? {
+        let signature = header_str(headers, "signature")?
+            .ok_or_else(|| SignatureError::Malformed("Signature-Input without Signature".into()))?;
+        return parse_rfc9421(input, signature);
+    }
+
+    if let Some(signature) = header_str(headers, "signature")? {
+        return parse_cavage(signature);
+    }
+    if let Some(authorization) = header_str(headers, "authorization")? {
+        if let Some(signature) = authorization.strip_prefix("Signature ") {
+            return parse_cavage(signature);
+        }
+    }
+
+    Err(SignatureError::Missing)
+}
+
+fn parse_cavage(value: &str) -> Result<SignatureInput, SignatureError> {
+    let mut key_id = None;
+    let mut headers = None;
+    let mut signature = None;
+    let mut created = None;
+    for (name, val) in split_params(value) {
+        match name.as_str() {
+            "keyid" => key_id = Some(val),
+            "headers" => headers = Some(val),
+            "signature" => signature = Some(val),
+            "created" => created = val.parse().ok(),
+            "algorithm" if !matches!(val.as_str(), "rsa-sha256" | "hs2019") => {
+                return Err(SignatureError::Unsupported(val));
+            }
+            _ => {}
+        }
+    }
+
+    let key_id = key_id.ok_or_else(|| SignatureError::Malformed("missing keyId".into()))?;
+    let signature =
+        signature.ok_or_else(|| SignatureError::Malformed("missing signature".into()))?;
+    let components = headers
+        .unwrap_or_else(|| "date".to_string())
+        .split_whitespace()
+        .map(|h| h.to_lowercase())
+        .collect();
+    let signature = base64::decode(signature)
+        .map_err(|e| SignatureError::Malformed(format!("signature is not base64: {}", e)))?;
+
+    Ok(SignatureInput {
+        key_id,
+        components,
+        signature,
+        created,
+        scheme: SignatureScheme::Cavage,
+    })
+}
+
+fn parse_rfc9421(input: &str, signature: &str) -> Result<SignatureInput, SignatureError> {
+    // Only the first signature is considered
+    let (label, params) = input
+        .split_once('=')
+        .ok_or_else(|| SignatureError::Malformed("invalid Signature-Input".into()))?;
+    let label = label.trim();
+    let params = params.trim();
+
+    let list_end = params
+        .find(')')
+        .ok_or_else(|| SignatureError::Malformed("invalid component list".into()))?;
+    let components = params[..list_end]
+        .trim_start_matches('(')
+        .split_whitespace()
+        .map(|c| c.trim_matches('"').to_lowercase())
+        .collect();
+
+    let mut key_id = None;
+    let mut created = None;
+    for param in params[list_end + 1..].split(';').filter(|p| !p.is_empty()) {
+        let (name, val) = param.split_once('=').unwrap_or((param, ""));
+        let val = val.trim_matches('"');
+        match name.trim() {
+            "keyid" => key_id = Some(val.to_string()),
+            "created" => created = val.parse().ok(),
+            "alg" if val != "rsa-v1_5-sha256" => {
+                return Err(SignatureError::Unsupported(val.to_string()));
+            }
+            _ => {}
+        }
+    }
+    let key_id = key_id.ok_or_else(|| SignatureError::Malformed("missing keyid".into()))?;
+
+    let encoded = signature
+        .split(',')
+        .filter_map(|s| s.trim().split_once('='))
+        .find(|(l, _)| l.trim() == label)
+        .map(|(_, v)| v.trim().trim_matches(':').to_string())
+        .ok_or_else(|| SignatureError::Malformed(format!("no signature labelled {}", label)))?;
+    let signature = base64::decode(encoded)
+        .map_err(|e| SignatureError::Malformed(format!("signature is not base64: {}", e)))?;
+
+    Ok(SignatureInput {
+        key_id,
+        components,
+        signature,
+        created,
+        scheme: SignatureScheme::Rfc9421 {
+            params: params.to_string(),
+        },
+    })
+}
+
+/// Split `a="b",c="d"` into lower-cased names and unquoted values.
+fn split_params(value: &str) -> Vec<(String, String)> {
+    let mut params = vec![];
+    let mut rest = value.trim();
+    while let Some((name, tail)) = rest.split_once('=') {
+        let name = name.trim().trim_start_matches(',').trim().to_lowercase();
+        let tail = tail.trim_start();
+        let (val, remaining) = if let Some(quoted) = tail.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            }
+        } else {
+            match tail.find(',') {
+                Some(end) => (&tail[..end], &tail[end..]),
+                None => (tail, ""),
+            }
+        };
+        params.push((name, val.to_string()));
+        rest = remaining.trim_start_matches(',').trim();
+    }
+
+    params
+}
+
+/// Check everything about a signed request that does not need the signer's
+/// key: required components, including the target authority, are covered, the body digest matches and the
+/// request is fresh.
+pub fn check_request(
+    input: &SignatureInput,
+    request: &RequestParts<'_>,
+    now: SystemTime,
+) -> Result<(), SignatureError> {
+    let covers = |c: &str| input.components.iter().any(|x| x == c);
+    match input.scheme {
+        // The authority is part of what is signed, so that a request cannot
+        // be replayed against another tenant or server.
+        SignatureScheme::Cavage => {
+            if !covers("(request-target)") || !covers("host") {
+                return Err(SignatureError::Malformed(
+                    "(request-target) and host are not signed".into(),
+                ));
+            }
+        }
+        SignatureScheme::Rfc9421 { .. } => {
+            if !covers("@method")
+                || !(covers("@target-uri") || (covers("@authority") && covers("@path")))
+            {
+                return Err(SignatureError::Malformed(
+                    "@method and @target-uri are not signed".into(),
+                ));
+            }
+        }
+    }
+
+    // Freshness. The created parameter of a draft-cavage signature is only
+    // trustworthy if the signature covers it.
+    let created = match input.scheme {
+        SignatureScheme::Cavage => input.created.filter(|_| covers("(created)")),
+        SignatureScheme::Rfc9421 { .. } => input.created,
+    };
+    let signed_at = match (created, header_str(request.headers, "date")?) {
+        (Some(created), _) => SystemTime::UNIX_EPOCH + Duration::from_secs(created),
+        (None, Some(date)) if covers("date") => httpdate::parse_http_date(date)
+            .map_err(|_| SignatureError::Malformed(format!("invalid Date: {}", date)))?,
+        _ => {
+            return Err(SignatureError::Malformed(
+                "neither date nor created is signed".into(),
+            ))
+        }
+    };
+    let skew = match now.duration_since(signed_at) {
+        Ok(d) => d,
+        Err(e) => e.duration(),
+    };
+    if skew > MAX_CLOCK_SKEW {
+        return Err(SignatureError::Expired);
+    }
+
+    // Body integrity
+    if !request.body.is_empty() || request.method == Method::POST {
+        match input.scheme {
+            SignatureScheme::Cavage => {
+                if !covers("digest") {
+                    return Err(SignatureError::Malformed("Digest is not signed".into()));
+                }
+                let digest = header_str(request.headers, "digest")?
+                    .ok_or_else(|| SignatureError::Malformed("missing Digest".into()))?;
+                if !digest_matches(digest, request.body) {
+                    return Err(SignatureError::DigestMismatch);
+                }
+            }
+            SignatureScheme::Rfc9421 { .. } => {
+                if !covers("content-digest") {
+                    return Err(SignatureError::Malformed(
+                        "Content-Digest is not signed".into(),
+                    ));
+                }
+                let digest = header_str(request.headers, "content-digest")?
+                    .ok_or_else(|| SignatureError::Malformed("missing Content-Digest".into()))?;
+                if !content_digest_matches(digest, request.body) {
+                    return Err(SignatureError::DigestMismatch);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify the signature itself against the signer's public key.
+pub fn verify_signature(
+    input: &SignatureInput,
+    request: &RequestParts<'_>,
+    public_key_pem: &str,
+) -> Result<(), SignatureError> {
+    let base = match &input.scheme {
+        SignatureScheme::Cavage => cavage_signing_string(input, request)?,
+        SignatureScheme::Rfc9421 { params } => rfc9421_signature_base(input, params, request)?,
+    };
+    let public_key = parse_public_key(public_key_pem)?;
+    let signature = Signature::try_from(input.signature.as_slice())
+        .map_err(|_| SignatureError::Malformed("invalid signature bytes".into()))?;
+
+    VerifyingKey::<Sha256>::new(public_key)
+        .verify(base.as_bytes(), &signature)
+        .map_err(|_| SignatureError::Invalid)
+}
+
+pub fn parse_public_key(pem: &str) -> Result<RsaPublicKey, SignatureError> {
+    RsaPublicKey::from_public_key_pem(pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        .map_err(|e| SignatureError::Malformed(format!("invalid public key: {}", e)))
+}
+
+fn cavage_signing_string(
+    input: &SignatureInput,
+    request: &RequestParts<'_>,
+) -> Result<String, SignatureError> {
+    let mut lines = vec![];
+    for component in &input.components {
+        let line = match component.as_str() {
+            "(request-target)" => format!(
+                "(request-target): {} {}",
+                request.method.as_str().to_lowercase(),
+                request.path_and_query
+            ),
+            "(created)" => format!(
+                "(created): {}",
+                input
+                    .created
+                    .ok_or_else(|| SignatureError::Malformed("missing created".into()))?
+            ),
+            name => format!("{}: {}", name, header_values(request.headers, name)?),
+        };
+        lines.push(line);
+    }
+
+    Ok(lines.join("\n"))
+}
+
+fn rfc9421_signature_base(
+    input: &SignatureInput,
+    params: &str,
+    request: &RequestParts<'_>,
+) -> Result<String, SignatureError> {
+    let (path, query) = match request.path_and_query.split_once('?') {
+        Some((p, q)) => (p, format!("?{}", q)),
+        None => (request.path_and_query, "?".to_string()),
+    };
+    let (scheme, authority) = request
+        .base_url
+        .split_once("://")
+        .unwrap_or(("https", request.base_url));
+
+    let mut lines = vec![];
+    for component in &input.components {
+        let value = match component.as_str() {
+            "@method" => request.method.as_str().to_string(),
+            "@target-uri" => format!("{}{}", request.base_url, request.path_and_query),
+            "@authority" => authority.to_lowercase(),
+            "@scheme" => scheme.to_string(),
+            "@request-target" => request.path_and_query.to_string(),
+            "@path" => path.to_string(),
+            "@query" => query.clone(),
+            name if name.starts_with('@') => {
+                return Err(SignatureError::Unsupported(name.to_string()))
+            }
+            name => header_values(request.headers, name)?,
+        };
+        lines.push(format!("\"{}\": {}", component, value));
+    }
+    lines.push(format!("\"@signature-params\": {}", params));
+
+    Ok(lines.join("\n"))
+}
+
+fn header_values(headers: &HeaderMap, name: &str) -> Result<String, SignatureError> {
+    let values = headers
+        .get_all(name)
+        .iter()
+        .map(|v| v.to_str().map(|s| s.trim().to_string()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| SignatureError::Malformed(format!("header {} is not valid text", name)))?;
+    if values.is_empty() {
+        return Err(SignatureError::Malformed(format!(
+            "signed header {} is missing",
+            name
+        )));
+    }
+
+    Ok(values.join(", "))
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Result<Option<&'a str>, SignatureError> {
+    headers
+        .get(name)
+        .map(|v| {
+            v.to_str().map_err(|_| {
+                SignatureError::Malformed(format!("header {} is not valid text", name))
+            })
+        })
+        .transpose()
+}
+
+/// `Digest` header value (RFC 3230) for `body`.
+pub fn digest(body: &[u8]) -> String {
+    format!("SHA-256={}", base64::encode(Sha256::digest(body)))
+}
+
+/// `Content-Digest` header value (RFC 9530) for `body`.
+pub fn content_digest(body: &[u8]) -> String {
+    format!("sha-256=:{}:", base64::encode(Sha256::digest(body)))
+}
+
+fn digest_matches(header: &str, body: &[u8]) -> bool {
+    let expected = base64::encode(Sha256::digest(body));
+    header
+        .split(',')
+        .filter_map(|d| d.trim().split_once('='))
+        .any(|(alg, val)| alg.eq_ignore_ascii_case("sha-256") && val == expected)
+}
+
+fn content_digest_matches(header: &str, body: &[u8]) -> bool {
+    let expected = base64::encode(Sha256::digest(body));
+    header
+        .split(',')
+        .filter_map(|d| d.trim().split_once('='))
+        .any(|(alg, val)| alg.eq_ignore_ascii_case("sha-256") && val.trim_matches(':') == expected)
+}
+
+/// Signs outbound requests on behalf of an actor.
+#[derive(Clone)]
+pub struct Signer {
+    pub key_id: String,
+    private_key: RsaPrivateKey,
+}
+
+impl std::fmt::Debug for Signer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Signer")
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
+impl Signer {
+    pub fn new(key_id: String, private_key: RsaPrivateKey) -> Self {
+        Self {
+            key_id,
+            private_key,
+        }
+    }
+
+    /// Add `Host`, `Date`, `Digest` and a draft-cavage `Signature` header to
+    /// `request`.
+    pub fn sign(&self, request: &mut reqwest::Request) -> Result<(), SignatureError> {
+        self.sign_at(request, SystemTime::now())
+    }
+
+    /// Like [`Signer::sign`], with an explicit `Date`.
+    pub fn sign_at(
+        &self,
+        request: &mut reqwest::Request,
+        date: SystemTime,
+    ) -> Result<(), SignatureError> {
+        let body = request_body(request);
+        let host = request_authority(request)?;
+        let date = httpdate::fmt_http_date(date);
+        let target = request_target(request);
+
+        let mut headers = vec!["(request-target)", "host", "date"];
+        let mut lines = vec![
+            format!(
+                "(request-target): {} {}",
+                request.method().as_str().to_lowercase(),
+                target
+            ),
+            format!("host: {}", host),
+            format!("date: {}", date),
+        ];
+        let digest = match body {
+            Some(body) => {
+                let digest = digest(&body);
+                headers.push("digest");
+                lines.push(format!("digest: {}", digest));
+                Some(digest)
+            }
+            None => None,
+        };
+
+        let signature = self.sign_bytes(lines.join("\n").as_bytes());
+        let value = format!(
+            r#"keyId="{}",algorithm="rsa-sha256",headers="{}",signature="{}""#,
+            self.key_id,
+            headers.join(" "),
+            signature
+        );
+
+        let h = request.headers_mut();
+        h.insert("host", header_value(&host)?);
+        h.insert("date", header_value(&date)?);
+        if let Some(digest) = digest {
+            h.insert("digest", header_value(&digest)?);
+        }
+        h.insert("signature", header_value(&value)?);
+
+        Ok(())
+    }
+
+    /// Sign `request` following RFC 9421 instead of draft-cavage.
+    pub fn sign_rfc9421(&self, request: &mut reqwest::Request) -> Result<(), SignatureError> {
+        let body = request_body(request);
+        let created = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut components = vec![
+            ("@method", request.method().as_str().to_string()),
+            ("@target-uri", request.url().to_string()),
+        ];
+        let digest = body.map(|b| content_digest(&b));
+        if let Some(digest) = &digest {
+            components.push(("content-digest", digest.clone()));
+        }
+        let params = format!(
+            r#"({});created={};keyid="{}";alg="rsa-v1_5-sha256""#,
+            components
+                .iter()
+                .map(|(c, _)| format!("\"{}\"", c))
+                .collect::<Vec<_>>()
+                .join(" "),
+            created,
+            self.key_id
+        );
+        let mut lines: Vec<String> = components
+            .iter()
+            .map(|(c, v)| format!("\"{}\": {}", c, v))
+            .collect();
+        lines.push(format!("\"@signature-params\": {}", params));
+        let signature = self.sign_bytes(lines.join("\n").as_bytes());
+
+        let h = request.headers_mut();
+        if let Some(digest) = digest {
+            h.insert("content-digest", header_value(&digest)?);
+        }
+        h.insert(
+            "signature-input",
+            header_value(&format!("sig1={}", params))?,
+        );
+        h.insert("signature", header_value(&format!("sig1=:{}:", signature))?);
+
+        Ok(())
+    }
+
+    fn sign_bytes(&self, data: &[u8]) -> String {
+        let signing_key = SigningKey::<Sha256>::new(self.private_key.clone());
+        base64::encode(signing_key.sign(data).to_bytes())
+    }
+}
+
+fn request_body(request: &reqwest::Request) -> Option<Vec<u8>> {
+    request
+        .body()
+        .and_then(|b| b.as_bytes())
+        .map(|b| b.to_vec())
+}
+
+fn request_authority(request: &reqwest::Request) -> Result<String, SignatureError> {
+    let url = request.url();
+    let host = url
+        .host_str()
+        .ok_or_else(|| SignatureError::Malformed(format!("{} has no host", url)))?;
+
+    Ok(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+fn request_target(request: &reqwest::Request) -> String {
+    let url = request.url();
+    match url.query() {
+        Some(q) => format!("{}?{}", url.path(), q),
+        None => url.path().to_string(),
+    }
+}
+
+fn header_value(s: &str) -> Result<reqwest::header::HeaderValue, SignatureError> {
+    reqwest::header::HeaderValue::from_str(s)
+        .map_err(|e| SignatureError::Malformed(format!("invalid header value: {}", e)))
+}
+
+#[derive(thiserror::Error)]
+pub enum SignatureError {
+    #[error("the request is not signed")]
+    Missing,
+    #[error("malformed signature: {0}")]
+    Malformed(String),
+    #[error("unsupported signature algorithm or component: {0}")]
+    Unsupported(String),
+    #[error("the request date is outside the accepted window")]
+    Expired,
+    #[error("the body does not match its digest")]
+    DigestMismatch,
+    #[error("the signature does not verify")]
+    Invalid,
+}
+
+impl std::fmt::Debug for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{HeaderMap, HeaderValue, Method};
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    #[test]
+    fn cavage_header_is_parsed() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "signature",
+            HeaderValue::from_static(
+                r#"keyId="https://example.com/users/alice#main-key",algorithm="rsa-sha256",headers="(request-target) host date digest",signature="YWJj""#,
+            ),
+        );
+
+        let input = parse_signature(&headers).unwrap();
+        assert_eq!(input.key_id, "https://example.com/users/alice#main-key");
+        assert_eq!(
+            input.components,
+            vec!["(request-target)", "host", "date", "digest"]
+        );
+        assert_eq!(input.signature, b"abc");
+        assert_eq!(input.scheme, SignatureScheme::Cavage);
+    }
+
+    #[test]
+    fn rfc9421_headers_are_parsed() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "signature-input",
+            HeaderValue::from_static(
+                r#"sig1=("@method" "@target-uri" "content-digest");created=1618884473;keyid="test-key";alg="rsa-v1_5-sha256""#,
+            ),
+        );
+        headers.insert("signature", HeaderValue::from_static("sig1=:YWJj:"));
+
+        let input = parse_signature(&headers).unwrap();
+        assert_eq!(input.key_id, "test-key");
+        assert_eq!(
+            input.components,
+            vec!["@method", "@target-uri", "content-digest"]
+        );
+        assert_eq!(input.created, Some(1618884473));
+        assert_eq!(input.signature, b"abc");
+    }
+
+    #[test]
+    fn unsigned_request_is_rejected() {
+        assert!(matches!(
+            parse_signature(&HeaderMap::new()),
+            Err(SignatureError::Missing)
+        ));
+    }
+
+    #[test]
+    fn stale_date_is_rejected() {
+        let mut headers = HeaderMap::new();
+        let date = httpdate::fmt_http_date(SystemTime::now() - Duration::from_secs(13 * 60 * 60));
+        headers.insert("host", HeaderValue::from_static("example.com"));
+        headers.insert("date", HeaderValue::from_str(&date).unwrap());
+        let input = SignatureInput {
+            key_id: "k".to_string(),
+            components: vec![
+                "(request-target)".to_string(),
+                "host".to_string(),
+                "date".to_string(),
+            ],
+            signature: vec![],
+            created: None,
+            scheme: SignatureScheme::Cavage,
+        };
+        let request = RequestParts {
+            method: &Method::GET,
+            base_url: "https://example.com",
+            path_and_query: "/users/alice",
+            headers: &headers,
+            body: &[],
+        };
+
+        assert!(matches!(
+            check_request(&input, &request, SystemTime::now()),
+            Err(SignatureError::Expired)
+        ));
+        assert!(check_request(
+            &input,
+            &request,
+            SystemTime::now() - Duration::from_secs(13 * 60 * 60)
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn unsigned_created_does_not_refresh_a_stale_date() {
+        let mut headers = HeaderMap::new();
+        let date = httpdate::fmt_http_date(SystemTime::now() - Duration::from_secs(13 * 60 * 60));
+        headers.insert("host", HeaderValue::from_static("example.com"));
+        headers.insert("date", HeaderValue::from_str(&date).unwrap());
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut input = SignatureInput {
+            key_id: "k".to_string(),
+            components: vec![
+                "(request-target)".to_string(),
+                "host".to_string(),
+                "date".to_string(),
+            ],
+            signature: vec![],
+            created: Some(now),
+            scheme: SignatureScheme::Cavage,
+        };
+        let request = RequestParts {
+            method: &Method::GET,
+            base_url: "https://example.com",
+            path_and_query: "/users/alice",
+            headers: &headers,
+            body: &[],
+        };
+
+        assert!(matches!(
+            check_request(&input, &request, SystemTime::now()),
+            Err(SignatureError::Expired)
+        ));
+        input.components.push("(created)".to_string());
+        assert!(check_request(&input, &request, SystemTime::now()).is_ok());
+    }
+
+    #[test]
+    fn unsigned_authority_is_rejected() {
+        let mut headers = HeaderMap::new();
+        headers.insert("host", HeaderValue::from_static("example.com"));
+        headers.insert(
+            "date",
+            HeaderValue::from_str(&httpdate::fmt_http_date(SystemTime::now())).unwrap(),
+        );
+        let request = RequestParts {
+            method: &Method::GET,
+            base_url: "https://example.com",
+            path_and_query: "/users/alice",
+            headers: &headers,
+            body: &[],
+        };
+        let created = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let input = |components: &[&str], scheme: SignatureScheme| SignatureInput {
+            key_id: "k".to_string(),
+            components: components.iter().map(|c| c.to_string()).collect(),
+            signature: vec![],
+            created: Some(created),
+            scheme,
+        };
+        let rfc9421 = || SignatureScheme::Rfc9421 {
+            params: String::new(),
+        };
+
+        for (components, scheme) in [
+            (vec!["(request-target)", "date"], SignatureScheme::Cavage),
+            (vec!["@method", "@path"], rfc9421()),
+            (vec!["@method", "@authority"], rfc9421()),
+            (vec!["@method", "@request-target"], rfc9421()),
+        ] {
+            assert!(
+                matches!(
+                    check_request(&input(&components, scheme), &request, SystemTime::now()),
+                    Err(SignatureError::Malformed(_))
+                ),
+                "{:?}",
+                components
+            );
+        }
+        for (components, scheme) in [
+            (
+                vec!["(request-target)", "host", "date"],
+                SignatureScheme::Cavage,
+            ),
+            (vec!["@method", "@target-uri"], rfc9421()),
+            (vec!["@method", "@authority", "@path"], rfc9421()),
+        ] {
+            assert!(
+                check_request(&input(&components, scheme), &request, SystemTime::now()).is_ok(),
+                "{:?}",
+                components
+            );
+        }
+    }
+
+    #[test]
+    fn digest_is_checked() {
+        assert!(digest_matches(&digest(b"hello"), b"hello"));
+        assert!(!digest_matches(&digest(b"hello"), b"goodbye"));
+        assert!(content_digest_matches(&content_digest(b"hello"), b"hello"));
+        assert!(!content_digest_matches(
+            &content_digest(b"hello"),
+            b"goodbye"
+        ));
+    }
+}
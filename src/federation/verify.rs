@@ -0,0 +1,461 @@
+//! Verification of signed inbound requests.
+use axum::{
+    body::{Body, Bytes},
+    extract::{FromRequest, Host, State},
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::time::{Instant, SystemTime};
+use url::Url;
+
+use super::{
+    fetch::{parse_actor, Fetcher},
+    instance_signer,
+    policy::{self, DomainPolicies},
+    remote, same_origin,
+    signature::{self, RequestParts, SignatureError, SignatureInput},
+    CachedKey,
+};
+use crate::{
+    activitypub::{handle_from_actor_url, instance_actor_url, wants_html},
+    error::{error_chain_fmt, TenantMapError},
+    keys, orm,
+    routes::{get_tenant_from_host, tenant_base_url, AppState, TenantData},
+};
+
+/// The signer of a request that passed [`require_signature`], available to
+/// handlers as a request extension.
+#[derive(Clone, Debug)]
+pub struct VerifiedSignature {
+    pub key_id: String,
+    /// The actor that owns the key
+    pub actor_id: String,
+}
+
+/// Middleware that rejects requests without a valid HTTP signature and
+/// attaches a [`VerifiedSignature`] to those that have one.
+pub async fn require_signature(
+    State(state): State<AppState>,
+    Host(host): Host,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let tenant = match get_tenant_from_host(&host, &state).await {
+        Ok(t) => t,
+        Err(e) => return VerifyError::from(e).into_response(),
+    };
+
+    match verified_request(&state, &tenant, &host, request).await {
+        Ok(request) => next.run(request).await,
+        Err(response) => response,
+    }
+}
+
+/// Middleware for the actors and objects of a tenant. In authorized fetch
+/// mode only servers the tenant federates with may read them, and they have
+/// to prove who they are with a signature. Otherwise anyone may read them,
+/// but requests signed on behalf of a server the tenant does not federate
+/// with are refused. Their signature is not checked: a forged claim only gets
+/// its sender refused.
+pub async fn authorize_fetch(
+    State(state): State<AppState>,
+    Host(host): Host,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    authorize(&state, &host, request, next, false).await
+}
+
+/// Like [`authorize_fetch`], for endpoints that also render HTML. Browsers
+/// cannot sign their requests, so requests that explicitly ask for HTML stay
+/// public in authorized fetch mode. Every other read has to be signed, so
+/// servers cannot fall back to scraping the pages.
+pub async fn authorize_page_fetch(
+    State(state): State<AppState>,
+    Host(host): Host,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    authorize(&state, &host, request, next, true).await
+}
+
+async fn authorize(
+    state: &AppState,
+    host: &str,
+    request: Request<Body>,
+    next: Next<Body>,
+    public_html: bool,
+) -> Response {
+    let tenant = match get_tenant_from_host(host, state).await {
+        Ok(t) => t,
+        Err(e) => return VerifyError::from(e).into_response(),
+    };
+    let authorized_fetch = match policy::authorized_fetch(&tenant.db).await {
+        Ok(a) => a,
+        Err(e) => return VerifyError::UnexpectedError(e.into()).into_response(),
+    };
+    let is_page = public_html && wants_html(request.headers());
+    if authorized_fetch && !is_page {
+        return match verified_request(state, &tenant, host, request).await {
+            Ok(request) => next.run(request).await,
+            Err(response) => response,
+        };
+    }
+
+    let key_id = match signature::parse_signature(request.headers()) {
+        Ok(input) => input.key_id,
+        Err(_) => return next.run(request).await,
+    };
+    match DomainPolicies::for_url(&tenant.db, &key_id).await {
+        Ok(policies) if policies.refuses(&key_id) => VerifyError::Forbidden(format!(
+            "this server does not federate with the domain of {}",
+            key_id
+        ))
+        .into_response(),
+        Ok(_) => next.run(request).await,
+        Err(e) => VerifyError::UnexpectedError(e.into()).into_response(),
+    }
+}
+
+/// Verify the signature of `request` and attach the signer to it.
+async fn verified_request(
+    state: &AppState,
+    tenant: &TenantData,
+    host: &str,
+    request: Request<Body>,
+) -> Result<Request<Body>, Response> {
+    let (parts, body) = request.into_parts();
+    let body = Bytes::from_request(Request::new(body), state)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let base_url = tenant_base_url(host, state);
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    let request_parts = RequestParts {
+        method: &parts.method,
+        base_url: &base_url,
+        path_and_query,
+        headers: &parts.headers,
+        body: &body,
+    };
+
+    let verified = verify_request(state, tenant, &request_parts)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    tracing::debug!("verified signature of {}", verified.key_id);
+
+    let mut request = Request::from_parts(parts, Body::from(body));
+    request.extensions_mut().insert(verified);
+
+    Ok(request)
+}
+
+#[tracing::instrument(name = "Verify HTTP signature", skip(state, tenant, request))]
+async fn verify_request(
+    state: &AppState,
+    tenant: &TenantData,
+    request: &RequestParts<'_>,
+) -> Result<VerifiedSignature, VerifyError> {
+    let input = signature::parse_signature(request.headers)?;
+    signature::check_request(&input, request, SystemTime::now())?;
+
+    // Keys of our own accounts are looked up directly. This is also the only
+    // way a rotated key that is no longer published can still be honoured.
+    if let Some(key) = local_key(tenant, request.base_url, &input.key_id).await? {
+        signature::verify_signature(&input, request, &key.public_key_pem)?;
+        return Ok(verified(&input, key.owner));
+    }
+
+    let policies = DomainPolicies::for_url(&tenant.db, &input.key_id)
+        .await
+        .map_err(|e| VerifyError::UnexpectedError(e.into()))?;
+    if policies.refuses(&input.key_id) {
+        return Err(VerifyError::Forbidden(format!(
+            "this server does not federate with the domain of {}",
+            input.key_id
+        )));
+    }
+
+    let (key, from_cache) = match cached_key(state, tenant, &input.key_id).await? {
+        Some(key) => (key, true),
+        None => (
+            fetch_key(state, tenant, request.base_url, &input.key_id).await?,
+            false,
+        ),
+    };
+    match signature::verify_signature(&input, request, &key.public_key_pem) {
+        Ok(()) => Ok(verified(&input, key.owner)),
+        // The remote actor may have rotated its key since we cached it
+        Err(SignatureError::Invalid) if from_cache => {
+            state.federation.evict_key(&input.key_id).await;
+            let key = fetch_key(state, tenant, request.base_url, &input.key_id).await?;
+            signature::verify_signature(&input, request, &key.public_key_pem)?;
+            Ok(verified(&input, key.owner))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A remote key we already know, either from memory or from the actor cache
+/// of the tenant.
+async fn cached_key(
+    state: &AppState,
+    tenant: &TenantData,
+    key_id: &str,
+) -> Result<Option<CachedKey>, VerifyError> {
+    if let Some(key) = state.federation.cached_key(key_id).await {
+        return Ok(Some(key));
+    }
+    let actor = remote::actor_by_key(&tenant.db, key_id)
+        .await
+        .map_err(|e| VerifyError::UnexpectedError(e.into()))?;
+
+    Ok(actor.and_then(|a| {
+        Some(CachedKey {
+            owner: a.actor_id,
+            public_key_pem: a.public_key_pem?,
+            fetched_at: Instant::now(),
+        })
+    }))
+}
+
+fn verified(input: &SignatureInput, owner: String) -> VerifiedSignature {
+    VerifiedSignature {
+        key_id: input.key_id.clone(),
+        actor_id: owner,
+    }
+}
+
+async fn local_key(
+    tenant: &TenantData,
+    base_url: &str,
+    key_id: &str,
+) -> Result<Option<CachedKey>, VerifyError> {
+    let (actor, fragment) = match key_id.split_once('#') {
+        Some(split) => split,
+        None => return Ok(None),
+    };
+    if actor == instance_actor_url(base_url) {
+        let key = keys::instance_key(&tenant.db)
+            .await
+            .map_err(|e| VerifyError::UnexpectedError(e.into()))?
+            .filter(|k| k.key_id == fragment)
+            .ok_or_else(|| VerifyError::Unauthorized(format!("unknown key {}", key_id)))?;
+        return Ok(Some(CachedKey {
+            owner: actor.to_string(),
+            public_key_pem: key.public_key_pem,
+            fetched_at: Instant::now(),
+        }));
+    }
+    let handle = match handle_from_actor_url(base_url, actor) {
+        Some(h) => h,
+        None => return Ok(None),
+    };
+
+    let (account, _) = match orm::get_account_by_handle(handle, &tenant.db)
+        .await
+        .map_err(|e| VerifyError::UnexpectedError(e.into()))?
+    {
+        Some(found) => found,
+        None => return Err(VerifyError::Unauthorized(format!("unknown key {}", key_id))),
+    };
+    let key = keys::verification_key(&tenant.db, account.id, fragment)
+        .await
+        .map_err(|e| VerifyError::UnexpectedError(e.into()))?
+        .ok_or_else(|| VerifyError::Unauthorized(format!("unknown or expired key {}", key_id)))?;
+
+    Ok(Some(CachedKey {
+        owner: actor.to_string(),
+        public_key_pem: key.public_key_pem,
+        fetched_at: Instant::now(),
+    }))
+}
+
+/// Dereference `key_id` and extract the public key from the returned
+/// document, which may be the owning actor or a standalone key object. Actor
+/// documents also refresh the actor cache of the tenant. The request is
+/// signed by the instance actor of the tenant at `base_url`, as the remote
+/// server may be in authorized fetch mode.
+#[tracing::instrument(name = "Fetch remote public key", skip(state, tenant))]
+async fn fetch_key(
+    state: &AppState,
+    tenant: &TenantData,
+    base_url: &str,
+    key_id: &str,
+) -> Result<CachedKey, VerifyError> {
+    let mut url = Url::parse(key_id)
+        .map_err(|e| VerifyError::BadRequest(format!("invalid keyId {}: {}", key_id, e)))?;
+    url.set_fragment(None);
+
+    let signer = instance_signer(&tenant.db, base_url, &state.global_config.server.secret_key)
+        .await
+        .map_err(|e| VerifyError::UnexpectedError(e.into()))?;
+    let document = Fetcher::new(&state.federation)
+        .signed_by(signer.as_ref())
+        .fetch_json(&tenant.db, url.as_str())
+        .await
+        .map_err(|e| VerifyError::Unauthorized(format!("failed to fetch {}: {}", url, e)))?;
+    let key = extract_public_key(&document, key_id)
+        .ok_or_else(|| VerifyError::Unauthorized(format!("{} does not publish {}", url, key_id)))?;
+    if !same_origin(&key.owner, key_id) {
+        return Err(VerifyError::Unauthorized(format!(
+            "key {} is not owned by an actor on the same host",
+            key_id
+        )));
+    }
+    if let Ok(person) = parse_actor(document.clone(), &key.owner) {
+        remote::store_actor(&tenant.db, &person, document)
+            .await
+            .map_err(|e| VerifyError::UnexpectedError(e.into()))?;
+    }
+    state.federation.cache_key(key_id, key.clone()).await;
+
+    Ok(key)
+}
+
+fn extract_public_key(document: &serde_json::Value, key_id: &str) -> Option<CachedKey> {
+    let key_from = |value: &serde_json::Value, default_owner: Option<&str>| {
+        let pem = value["publicKeyPem"].as_str()?;
+        let owner = value["owner"].as_str().or(default_owner)?;
+        Some(CachedKey {
+            owner: owner.to_string(),
+            public_key_pem: pem.to_string(),
+            fetched_at: Instant::now(),
+        })
+    };
+
+    // A standalone key document
+    if document["id"] == key_id && document.get("publicKeyPem").is_some() {
+        return key_from(document, None);
+    }
+
+    // An actor document with one or several keys
+    let owner = document["id"].as_str();
+    match &document["publicKey"] {
+        serde_json::Value::Array(keys) => keys
+            .iter()
+            .find(|k| k["id"] == key_id)
+            .and_then(|k| key_from(k, owner)),
+        key @ serde_json::Value::Object(_) if key["id"] == key_id => key_from(key, owner),
+        _ => None,
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum VerifyError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl From<SignatureError> for VerifyError {
+    fn from(e: SignatureError) -> Self {
+        match e {
+            SignatureError::Malformed(_) | SignatureError::Unsupported(_) => {
+                Self::BadRequest(e.to_string())
+            }
+            SignatureError::Missing
+            | SignatureError::Expired
+            | SignatureError::DigestMismatch
+            | SignatureError::Invalid => Self::Unauthorized(e.to_string()),
+        }
+    }
+}
+
+impl From<TenantMapError> for VerifyError {
+    fn from(e: TenantMapError) -> Self {
+        match e {
+            TenantMapError::NotFound(s) => Self::NotFound(s),
+            TenantMapError::UnexpectedError(s) => Self::UnexpectedError(anyhow::anyhow!(s)),
+        }
+    }
+}
+
+impl std::fmt::Debug for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for VerifyError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::BadRequest(s) => {
+                tracing::info!("malformed signature: {s:?}");
+                (StatusCode::BAD_REQUEST, s).into_response()
+            }
+            Self::Unauthorized(s) => {
+                tracing::info!("signature rejected: {s:?}");
+                (StatusCode::UNAUTHORIZED, s).into_response()
+            }
+            Self::Forbidden(s) => {
+                tracing::info!("signer refused: {s:?}");
+                (StatusCode::FORBIDDEN, s).into_response()
+            }
+            Self::NotFound(s) => {
+                tracing::info!("tenant not found: {s:?}");
+                (StatusCode::NOT_FOUND, s).into_response()
+            }
+            Self::UnexpectedError(e) => {
+                tracing::error!("an unexpected error occurred: {e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")).into_response()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extract_public_key;
+
+    #[test]
+    fn key_is_extracted_from_actor_document() {
+        let actor = serde_json::json!({
+            "id": "https://example.com/users/alice",
+            "publicKey": {
+                "id": "https://example.com/users/alice#main-key",
+                "owner": "https://example.com/users/alice",
+                "publicKeyPem": "PEM"
+            }
+        });
+
+        let key = extract_public_key(&actor, "https://example.com/users/alice#main-key").unwrap();
+        assert_eq!(key.owner, "https://example.com/users/alice");
+        assert_eq!(key.public_key_pem, "PEM");
+        assert!(extract_public_key(&actor, "https://example.com/users/alice#other").is_none());
+    }
+
+    #[test]
+    fn key_is_extracted_from_key_array_and_key_document() {
+        let actor = serde_json::json!({
+            "id": "https://example.com/users/alice",
+            "publicKey": [
+                { "id": "https://example.com/users/alice#k1", "publicKeyPem": "ONE" },
+                { "id": "https://example.com/users/alice#k2", "publicKeyPem": "TWO" }
+            ]
+        });
+        let key = extract_public_key(&actor, "https://example.com/users/alice#k2").unwrap();
+        assert_eq!(key.public_key_pem, "TWO");
+        assert_eq!(key.owner, "https://example.com/users/alice");
+
+        let key_doc = serde_json::json!({
+            "id": "https://example.com/keys/1",
+            "owner": "https://example.com/users/alice",
+            "publicKeyPem": "PEM"
+        });
+        let key = extract_public_key(&key_doc, "https://example.com/keys/1").unwrap();
+        assert_eq!(key.owner, "https://example.com/users/alice");
+    }
+}
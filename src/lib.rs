@@ -3,6 +3,7 @@ use sea_orm::{Database, DatabaseConnection};
 use settings::Settings;
 use std::net::TcpListener;
 
+pub mod activitypub;
 pub mod authentication;
 pub mod cookies;
 pub mod db;
@@ -10,14 +11,19 @@ pub mod domain;
 pub mod email_client;
 pub mod entities;
 pub mod error;
+pub mod federation;
+pub mod keys;
 pub mod migration;
 pub mod migrator;
 pub mod orm;
+pub mod polls;
+pub mod revisions;
 pub mod routes;
 pub mod session_state;
 pub mod settings;
 pub mod smtp_client;
 pub mod startup;
+pub mod tags;
 pub mod telemetry;
 
 pub const APP_NAME: &str = "rhodos";
@@ -3,6 +3,7 @@ use sea_orm_migration::prelude::*;
 use secrecy::{ExposeSecret, Secret};
 
 use super::db;
+use super::keys;
 use super::migrator::Migrator;
 
 #[derive(Debug)]
@@ -12,7 +13,7 @@ pub struct DbUri {
     pub db_name: String,
 }
 
-pub async fn init<'a>(server_url: &Secret<String>, db_name: &String) -> Result<(), DbErr> {
+pub async fn init(server_url: &Secret<String>, db_name: &String) -> Result<(), DbErr> {
     let db = Database::connect(server_url.expose_secret()).await?;
     let _db = &match db.get_database_backend() {
         DbBackend::Postgres => {
@@ -34,14 +35,24 @@ pub async fn init<'a>(server_url: &Secret<String>, db_name: &String) -> Result<(
     Ok(())
 }
 
-pub async fn migrate(db: &DatabaseConnection) -> Result<(), DbErr> {
+pub async fn migrate(db: &DatabaseConnection, secret_key: &Secret<String>) -> Result<(), DbErr> {
     tracing::info!("Starting migration");
     Migrator::up(db, None).await?;
 
+    // Accounts created before signing keys existed need a keypair
+    keys::backfill_account_keys(db, secret_key)
+        .await
+        .map_err(|e| DbErr::Custom(format!("failed to backfill account keys: {}", e)))?;
+    keys::ensure_instance_key(db, secret_key)
+        .await
+        .map_err(|e| DbErr::Custom(format!("failed to generate the instance key: {}", e)))?;
+
     let schema_manager = SchemaManager::new(db);
     assert!(schema_manager.has_table("account").await?);
+    assert!(schema_manager.has_table("account_key").await?);
     assert!(schema_manager.has_table("content").await?);
     assert!(schema_manager.has_table("instance").await?);
+    assert!(schema_manager.has_table("instance_key").await?);
     assert!(schema_manager.has_table("microblog").await?);
     assert!(schema_manager.has_table("user").await?);
     assert!(schema_manager.has_table("user_token").await?);
@@ -49,7 +60,10 @@ pub async fn migrate(db: &DatabaseConnection) -> Result<(), DbErr> {
     Ok(())
 }
 
-pub async fn initialize_and_migrate_database(uri: &DbUri) -> Result<(), String> {
+pub async fn initialize_and_migrate_database(
+    uri: &DbUri,
+    secret_key: &Secret<String>,
+) -> Result<(), String> {
     // Initialize DB
     tracing::debug!("Initializing database: postgres://****/{}", uri.db_name);
 
@@ -64,7 +78,7 @@ pub async fn initialize_and_migrate_database(uri: &DbUri) -> Result<(), String>
             uri.db_name, e
         )
     })?;
-    migrate(&db_conn)
+    migrate(&db_conn, secret_key)
         .await
         .map_err(|e| format!("Migration of {} failed: {}", uri.db_name, e))?;
 
@@ -0,0 +1,54 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220101_000010_add_account_username"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Define how to apply this migration
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = r#"ALTER TABLE account ADD COLUMN username VARCHAR UNIQUE;"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+
+        // Existing accounts get a handle derived from the local part of the
+        // owner's email address. Collisions are disambiguated with the account id.
+        let sql = r#"
+UPDATE account SET username = CASE WHEN c.n = 1 THEN c.base ELSE c.base || '_' || c.id END
+    FROM (
+        SELECT a.id,
+            lower(regexp_replace(split_part(u.email, '@', 1), '[^a-zA-Z0-9_]', '_', 'g')) AS base,
+            row_number() OVER (
+                PARTITION BY lower(regexp_replace(split_part(u.email, '@', 1), '[^a-zA-Z0-9_]', '_', 'g'))
+                ORDER BY a.id
+            ) AS n
+        FROM account a JOIN "user" u ON u.id = a.user_id
+    ) c
+    WHERE account.id = c.id AND account.username IS NULL
+;"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Define how to rollback this migration
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "ALTER TABLE account DROP COLUMN username;";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
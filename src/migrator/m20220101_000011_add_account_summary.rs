@@ -0,0 +1,33 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220101_000011_add_account_summary"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Define how to apply this migration
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = r#"ALTER TABLE account ADD COLUMN summary TEXT;"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Define how to rollback this migration
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "ALTER TABLE account DROP COLUMN summary;";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
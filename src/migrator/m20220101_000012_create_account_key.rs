@@ -0,0 +1,65 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220101_000012_create_account_key"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Define how to apply this migration
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // An account has exactly one active key (expires_at IS NULL). Keys that
+        // have been rotated out remain verifiable until they expire.
+        let sql = r#"
+CREATE TABLE account_key (
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    account_id BIGINT NOT NULL,
+    key_id VARCHAR NOT NULL,
+    public_key_pem TEXT NOT NULL,
+    private_key_enc TEXT NOT NULL,
+    expires_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (account_id, key_id),
+    CONSTRAINT fk_account
+        FOREIGN KEY(account_id)
+            REFERENCES account
+            ON DELETE CASCADE
+);"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+
+        let sql = r#"
+CREATE UNIQUE INDEX account_key_active_idx ON account_key (account_id) WHERE expires_at IS NULL;"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+
+        let sql = r#"SELECT rhodos_manage_updated_at('account_key');"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Define how to rollback this migration
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "DROP TABLE account_key;";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
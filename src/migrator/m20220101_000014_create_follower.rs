@@ -0,0 +1,58 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220101_000014_create_follower"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Define how to apply this migration
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Remote actors following a local account. A follow request to a locked
+        // account stays pending (accepted = false) until it is approved.
+        let sql = r#"
+CREATE TABLE follower (
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    account_id BIGINT NOT NULL,
+    actor_id VARCHAR NOT NULL,
+    inbox VARCHAR NOT NULL,
+    shared_inbox VARCHAR,
+    follow_id VARCHAR NOT NULL,
+    accepted BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (account_id, actor_id),
+    CONSTRAINT fk_account
+        FOREIGN KEY(account_id)
+            REFERENCES account
+            ON DELETE CASCADE
+);"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+
+        let sql = r#"SELECT rhodos_manage_updated_at('follower');"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Define how to rollback this migration
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "DROP TABLE follower;";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
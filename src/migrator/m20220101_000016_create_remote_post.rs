@@ -0,0 +1,63 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220101_000016_create_remote_post"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Define how to apply this migration
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Notes received from remote servers. A deleted note is kept as a
+        // tombstone (deleted_at IS NOT NULL) so that it is not re-ingested.
+        let sql = r#"
+CREATE TABLE remote_post (
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    object_id VARCHAR NOT NULL UNIQUE,
+    actor_id VARCHAR NOT NULL,
+    url VARCHAR,
+    in_reply_to VARCHAR,
+    summary TEXT,
+    sensitive BOOLEAN NOT NULL DEFAULT false,
+    content TEXT,
+    published_at TIMESTAMP,
+    deleted_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+
+        let sql = r#"CREATE INDEX remote_post_actor_idx ON remote_post (actor_id);"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+
+        let sql = r#"SELECT rhodos_manage_updated_at('remote_post');"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Define how to rollback this migration
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "DROP TABLE remote_post;";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220101_000019_create_remote_actor"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Define how to apply this migration
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Local copies of remote actor documents. The columns hold what we need
+        // to address and verify the actor; `document` keeps the whole thing.
+        let sql = r#"
+CREATE TABLE remote_actor (
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    actor_id VARCHAR NOT NULL UNIQUE,
+    kind VARCHAR NOT NULL,
+    username VARCHAR,
+    name VARCHAR,
+    summary VARCHAR,
+    url VARCHAR,
+    inbox VARCHAR NOT NULL,
+    shared_inbox VARCHAR,
+    outbox VARCHAR,
+    followers VARCHAR,
+    following VARCHAR,
+    locked BOOLEAN NOT NULL DEFAULT false,
+    public_key_id VARCHAR,
+    public_key_pem VARCHAR,
+    document JSONB NOT NULL,
+    fetched_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+
+        let sql = r#"CREATE INDEX remote_actor_public_key_id_idx ON remote_actor (public_key_id);"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+
+        let sql = r#"SELECT rhodos_manage_updated_at('remote_actor');"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Define how to rollback this migration
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "DROP TABLE remote_actor;";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
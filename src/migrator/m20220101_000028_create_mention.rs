@@ -0,0 +1,59 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220101_000028_create_mention"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Define how to apply this migration
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Accounts mentioned in a local post. `username` and `domain` are kept as
+        // written in the post, `domain` being NULL for a bare `@username`. Only
+        // remote accounts have an `inbox`.
+        let sql = r#"
+CREATE TABLE mention (
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    content_id BIGINT NOT NULL,
+    actor_id VARCHAR NOT NULL,
+    username VARCHAR NOT NULL,
+    domain VARCHAR,
+    url VARCHAR NOT NULL,
+    inbox VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (content_id, actor_id),
+    CONSTRAINT fk_content
+        FOREIGN KEY(content_id)
+            REFERENCES content
+            ON DELETE CASCADE
+);"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+
+        let sql = r#"SELECT rhodos_manage_updated_at('mention');"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Define how to rollback this migration
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "DROP TABLE mention;";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220101_000030_create_content_tag"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Define how to apply this migration
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Posts tagged with a hashtag, each row linking a tag to either a local post
+        // or a remote one.
+        let sql = r#"
+CREATE TABLE content_tag (
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    tag_id BIGINT NOT NULL,
+    content_id BIGINT,
+    remote_post_id BIGINT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (num_nonnulls(content_id, remote_post_id) = 1),
+    UNIQUE (tag_id, content_id),
+    UNIQUE (tag_id, remote_post_id),
+    CONSTRAINT fk_tag
+        FOREIGN KEY(tag_id)
+            REFERENCES tag
+            ON DELETE CASCADE,
+    CONSTRAINT fk_content
+        FOREIGN KEY(content_id)
+            REFERENCES content
+            ON DELETE CASCADE,
+    CONSTRAINT fk_remote_post
+        FOREIGN KEY(remote_post_id)
+            REFERENCES remote_post
+            ON DELETE CASCADE
+);"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+
+        let sql = r#"SELECT rhodos_manage_updated_at('content_tag');"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Define how to rollback this migration
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "DROP TABLE content_tag;";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
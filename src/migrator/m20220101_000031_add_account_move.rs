@@ -0,0 +1,39 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220101_000031_add_account_move"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Define how to apply this migration
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Accounts list the other accounts of their owner in `also_known_as`, an
+        // array of actor ids. `moved_to` is the account an account moved to.
+        let sql = r#"
+ALTER TABLE account
+    ADD COLUMN also_known_as JSONB NOT NULL DEFAULT '[]',
+    ADD COLUMN moved_to VARCHAR,
+    ADD COLUMN moved_at TIMESTAMP;"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Define how to rollback this migration
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "ALTER TABLE account DROP COLUMN also_known_as, DROP COLUMN moved_to, DROP COLUMN moved_at;";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
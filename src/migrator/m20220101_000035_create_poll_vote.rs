@@ -0,0 +1,72 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220101_000035_create_poll_vote"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Define how to apply this migration
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // One row per option picked by a voter, local or remote. Votes of local
+        // accounts also name the account.
+        let sql = r#"
+CREATE TABLE poll_vote (
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    poll_id BIGINT NOT NULL,
+    option_id BIGINT NOT NULL,
+    actor_id VARCHAR NOT NULL,
+    account_id BIGINT,
+    activity_id VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (option_id, actor_id),
+    CONSTRAINT fk_poll
+        FOREIGN KEY(poll_id)
+            REFERENCES poll
+            ON DELETE CASCADE,
+    CONSTRAINT fk_option
+        FOREIGN KEY(option_id)
+            REFERENCES poll_option
+            ON DELETE CASCADE,
+    CONSTRAINT fk_account
+        FOREIGN KEY(account_id)
+            REFERENCES account
+            ON DELETE CASCADE
+);"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+
+        let sql = r#"CREATE INDEX poll_vote_voter_idx ON poll_vote (poll_id, actor_id);"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+
+        let sql = r#"SELECT rhodos_manage_updated_at('poll_vote');"#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Define how to rollback this migration
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "DROP TABLE poll_vote;";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        match manager.get_connection().execute(stmt).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
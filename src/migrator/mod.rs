@@ -9,6 +9,43 @@ mod m20220101_000006_create_instance;
 mod m20220101_000007_create_microblog;
 mod m20220101_000008_create_user_token;
 mod m20220101_000009_create_admin;
+mod m20220101_000010_add_account_username;
+mod m20220101_000011_add_account_summary;
+mod m20220101_000012_create_account_key;
+mod m20220101_000013_add_account_locked;
+mod m20220101_000014_create_follower;
+mod m20220101_000015_create_following;
+mod m20220101_000016_create_remote_post;
+mod m20220101_000017_add_content_visibility;
+mod m20220101_000018_create_delivery;
+mod m20220101_000019_create_remote_actor;
+mod m20220101_000020_create_remote_object;
+mod m20220101_000021_add_content_deleted_at;
+mod m20220101_000022_create_domain_block;
+mod m20220101_000023_add_remote_post_attachment;
+mod m20220101_000024_add_microblog_federation_mode;
+mod m20220101_000025_create_domain_allow;
+mod m20220101_000026_add_microblog_authorized_fetch;
+mod m20220101_000027_create_instance_key;
+mod m20220101_000028_create_mention;
+mod m20220101_000029_create_tag;
+mod m20220101_000030_create_content_tag;
+mod m20220101_000031_add_account_move;
+mod m20220101_000032_add_account_profile;
+mod m20220101_000033_create_poll;
+mod m20220101_000034_create_poll_option;
+mod m20220101_000035_create_poll_vote;
+mod m20220101_000036_create_relay;
+mod m20220101_000037_add_instance_deliveries;
+mod m20220101_000038_create_inbox_queue;
+mod m20220101_000039_create_content_revision;
+mod m20220101_000040_add_content_edited_at;
+mod m20220101_000041_add_remote_post_edited_at;
+mod m20220101_000042_make_content_published_not_null;
+mod m20220101_000043_add_content_scheduled_at;
+mod m20220101_000044_add_user_expand_cws;
+mod m20220101_000045_add_microblog_open_registrations;
+mod m20220101_000046_add_content_base_url;
 
 pub struct Migrator;
 
@@ -25,6 +62,43 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000007_create_microblog::Migration),
             Box::new(m20220101_000008_create_user_token::Migration),
             Box::new(m20220101_000009_create_admin::Migration),
+            Box::new(m20220101_000010_add_account_username::Migration),
+            Box::new(m20220101_000011_add_account_summary::Migration),
+            Box::new(m20220101_000012_create_account_key::Migration),
+            Box::new(m20220101_000013_add_account_locked::Migration),
+            Box::new(m20220101_000014_create_follower::Migration),
+            Box::new(m20220101_000015_create_following::Migration),
+            Box::new(m20220101_000016_create_remote_post::Migration),
+            Box::new(m20220101_000017_add_content_visibility::Migration),
+            Box::new(m20220101_000018_create_delivery::Migration),
+            Box::new(m20220101_000019_create_remote_actor::Migration),
+            Box::new(m20220101_000020_create_remote_object::Migration),
+            Box::new(m20220101_000021_add_content_deleted_at::Migration),
+            Box::new(m20220101_000022_create_domain_block::Migration),
+            Box::new(m20220101_000023_add_remote_post_attachment::Migration),
+            Box::new(m20220101_000024_add_microblog_federation_mode::Migration),
+            Box::new(m20220101_000025_create_domain_allow::Migration),
+            Box::new(m20220101_000026_add_microblog_authorized_fetch::Migration),
+            Box::new(m20220101_000027_create_instance_key::Migration),
+            Box::new(m20220101_000028_create_mention::Migration),
+            Box::new(m20220101_000029_create_tag::Migration),
+            Box::new(m20220101_000030_create_content_tag::Migration),
+            Box::new(m20220101_000031_add_account_move::Migration),
+            Box::new(m20220101_000032_add_account_profile::Migration),
+            Box::new(m20220101_000033_create_poll::Migration),
+            Box::new(m20220101_000034_create_poll_option::Migration),
+            Box::new(m20220101_000035_create_poll_vote::Migration),
+            Box::new(m20220101_000036_create_relay::Migration),
+            Box::new(m20220101_000037_add_instance_deliveries::Migration),
+            Box::new(m20220101_000038_create_inbox_queue::Migration),
+            Box::new(m20220101_000039_create_content_revision::Migration),
+            Box::new(m20220101_000040_add_content_edited_at::Migration),
+            Box::new(m20220101_000041_add_remote_post_edited_at::Migration),
+            Box::new(m20220101_000042_make_content_published_not_null::Migration),
+            Box::new(m20220101_000043_add_content_scheduled_at::Migration),
+            Box::new(m20220101_000044_add_user_expand_cws::Migration),
+            Box::new(m20220101_000045_add_microblog_open_registrations::Migration),
+            Box::new(m20220101_000046_add_content_base_url::Migration),
         ]
     }
 }
@@ -5,6 +5,7 @@ use secrecy::Secret;
 use crate::{
     domain::{AppUser, UserEmail, UserName, UserRole},
     entities::{
+        account,
         prelude::*,
         user::{self, Model as UserModel},
     },
@@ -49,6 +50,24 @@ pub async fn get_orm_model_by_id(
     })
 }
 
+#[tracing::instrument(name = "Get account by handle", skip(conn))]
+pub async fn get_account_by_handle(
+    handle: &str,
+    conn: &DatabaseConnection,
+) -> Result<Option<(account::Model, UserModel)>, OrmError> {
+    let found = Account::find()
+        .filter(account::Column::Username.eq(handle))
+        .find_also_related(User)
+        .one(conn)
+        .await
+        .context("Failed to retrieve account by handle")?;
+
+    Ok(match found {
+        Some((account, Some(user))) => Some((account, user)),
+        _ => None,
+    })
+}
+
 #[tracing::instrument(name = "Get credential", skip(username, conn))]
 pub async fn get_credential(
     username: &str,
@@ -0,0 +1,484 @@
+//! Polls attached to local and remote posts.
+//!
+//! A poll belongs to either a local post or a remote one and offers its
+//! options in a fixed order. Every vote is kept, one row per chosen option and
+//! voter, and the counts on the options and the poll are updated with it so
+//! they can be served without counting. Remote polls carry the counts their
+//! server last told us about, plus the votes cast from here since. Polls stop
+//! taking votes once they expire; a background task marks them closed and
+//! tells the audience of local polls about the final counts.
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, Set, TryIntoModel,
+};
+use std::{collections::HashMap, time::Duration};
+
+use crate::{
+    domain::NewPoll,
+    entities::{content, poll, poll_option, poll_vote},
+    error::error_chain_fmt,
+    federation::queue,
+    routes::{all_tenants, tenant_base_url, AppState},
+};
+
+/// How often expired polls are looked for.
+const CLOSE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A poll with its options, in order.
+#[derive(Debug, Clone)]
+pub struct Poll {
+    pub poll: poll::Model,
+    pub options: Vec<poll_option::Model>,
+}
+
+impl Poll {
+    /// Whether the poll stopped taking votes by `now`.
+    pub fn is_closed(&self, now: NaiveDateTime) -> bool {
+        self.poll.closed_at.is_some() || self.poll.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// The options and counts of a poll as another server describes it.
+#[derive(Debug, Clone)]
+pub struct RemotePoll {
+    pub multiple: bool,
+    pub options: Vec<(String, i32)>,
+    pub voters_count: Option<i32>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub closed_at: Option<NaiveDateTime>,
+}
+
+/// Attach `new` to the local post `content_id`.
+pub async fn create<C: ConnectionTrait>(
+    conn: &C,
+    content_id: i64,
+    new: &NewPoll,
+) -> Result<Poll, DbErr> {
+    let expires_at = Utc::now().naive_utc()
+        + chrono::Duration::from_std(new.expires_in())
+            .unwrap_or_else(|_| chrono::Duration::days(1));
+    let poll = poll::ActiveModel {
+        content_id: Set(Some(content_id)),
+        multiple: Set(new.multiple()),
+        expires_at: Set(Some(expires_at)),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?;
+    let mut options = vec![];
+    for (position, name) in new.options().iter().enumerate() {
+        let option = poll_option::ActiveModel {
+            poll_id: Set(poll.id),
+            position: Set(position as i32),
+            name: Set(name.clone()),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await?;
+        options.push(option);
+    }
+
+    Ok(Poll { poll, options })
+}
+
+/// Reopen the poll of the local post `content_id`, if it has one, for as long
+/// as it was meant to run. Polls of drafts and scheduled posts only start
+/// running once the post is published.
+pub async fn restart<C: ConnectionTrait>(conn: &C, content_id: i64) -> Result<(), DbErr> {
+    let Some(poll) = poll::Entity::find()
+        .filter(poll::Column::ContentId.eq(content_id))
+        .one(conn)
+        .await?
+    else {
+        return Ok(());
+    };
+    let now = Utc::now().naive_utc();
+    let expires_at = poll.expires_at.map(|at| now + (at - poll.created_at));
+    let mut model = poll::ActiveModel::from(poll);
+    model.expires_at = Set(expires_at);
+    model.closed_at = Set(None);
+    model.update(conn).await?;
+
+    Ok(())
+}
+
+/// Store or refresh the poll of the remote post `remote_post_id`. Counts are
+/// taken as given; options are only replaced if they changed, which drops
+/// the votes cast for them.
+pub async fn store_remote<C: ConnectionTrait>(
+    conn: &C,
+    remote_post_id: i64,
+    remote: &RemotePoll,
+) -> Result<Poll, DbErr> {
+    let existing = poll::Entity::find()
+        .filter(poll::Column::RemotePostId.eq(remote_post_id))
+        .one(conn)
+        .await?;
+    let mut model = match existing {
+        Some(poll) => poll::ActiveModel::from(poll),
+        None => poll::ActiveModel {
+            remote_post_id: Set(Some(remote_post_id)),
+            ..Default::default()
+        },
+    };
+    model.multiple = Set(remote.multiple);
+    model.expires_at = Set(remote.expires_at);
+    model.closed_at = Set(remote.closed_at);
+    if let Some(voters_count) = remote.voters_count {
+        model.voters_count = Set(voters_count);
+    }
+    let poll = model.save(conn).await?.try_into_model()?;
+
+    let options = options_of(conn, poll.id).await?;
+    let unchanged = options.len() == remote.options.len()
+        && options
+            .iter()
+            .zip(&remote.options)
+            .all(|(option, (name, _))| &option.name == name);
+    if !unchanged {
+        poll_option::Entity::delete_many()
+            .filter(poll_option::Column::PollId.eq(poll.id))
+            .exec(conn)
+            .await?;
+    }
+    let mut stored = vec![];
+    for (position, (name, votes_count)) in remote.options.iter().enumerate() {
+        let mut option = match options.get(position) {
+            Some(option) if unchanged => poll_option::ActiveModel::from(option.clone()),
+            _ => poll_option::ActiveModel {
+                poll_id: Set(poll.id),
+                position: Set(position as i32),
+                name: Set(name.clone()),
+                ..Default::default()
+            },
+        };
+        option.votes_count = Set(*votes_count);
+        stored.push(option.save(conn).await?.try_into_model()?);
+    }
+
+    Ok(Poll {
+        poll,
+        options: stored,
+    })
+}
+
+/// The poll `id`.
+pub async fn find<C: ConnectionTrait>(conn: &C, id: i64) -> Result<Option<Poll>, DbErr> {
+    match poll::Entity::find_by_id(id).one(conn).await? {
+        Some(poll) => Ok(Some(with_options(conn, poll).await?)),
+        None => Ok(None),
+    }
+}
+
+/// The poll of the local post `content_id`, if it has one.
+pub async fn for_post<C: ConnectionTrait>(
+    conn: &C,
+    content_id: i64,
+) -> Result<Option<Poll>, DbErr> {
+    Ok(for_posts(conn, vec![content_id]).await?.remove(&content_id))
+}
+
+/// The polls of those of the local posts `content_ids` that have one.
+pub async fn for_posts<C: ConnectionTrait>(
+    conn: &C,
+    content_ids: Vec<i64>,
+) -> Result<HashMap<i64, Poll>, DbErr> {
+    let polls = poll::Entity::find()
+        .filter(poll::Column::ContentId.is_in(content_ids))
+        .all(conn)
+        .await?;
+    let mut by_post = HashMap::new();
+    for poll in polls {
+        let content_id = poll.content_id.unwrap_or_default();
+        by_post.insert(content_id, with_options(conn, poll).await?);
+    }
+
+    Ok(by_post)
+}
+
+/// The polls of those of the remote posts `remote_post_ids` that have one.
+pub async fn for_remote_posts<C: ConnectionTrait>(
+    conn: &C,
+    remote_post_ids: Vec<i64>,
+) -> Result<HashMap<i64, Poll>, DbErr> {
+    let polls = poll::Entity::find()
+        .filter(poll::Column::RemotePostId.is_in(remote_post_ids))
+        .all(conn)
+        .await?;
+    let mut by_post = HashMap::new();
+    for poll in polls {
+        let remote_post_id = poll.remote_post_id.unwrap_or_default();
+        by_post.insert(remote_post_id, with_options(conn, poll).await?);
+    }
+
+    Ok(by_post)
+}
+
+/// The positions of the options of `poll` the actor `voter` voted for.
+pub async fn choices_of<C: ConnectionTrait>(
+    conn: &C,
+    poll: &Poll,
+    voter: &str,
+) -> Result<Vec<usize>, DbErr> {
+    let votes = poll_vote::Entity::find()
+        .filter(poll_vote::Column::PollId.eq(poll.poll.id))
+        .filter(poll_vote::Column::ActorId.eq(voter))
+        .all(conn)
+        .await?;
+
+    Ok(poll
+        .options
+        .iter()
+        .enumerate()
+        .filter(|(_, option)| votes.iter().any(|v| v.option_id == option.id))
+        .map(|(position, _)| position)
+        .collect())
+}
+
+/// Record the vote of the actor `voter`, the local account `account_id` if it
+/// is one of ours, for the options at `choices`. Returns the poll with its
+/// new counts.
+pub async fn vote<C: ConnectionTrait>(
+    conn: &C,
+    poll: &Poll,
+    voter: &str,
+    account_id: Option<i64>,
+    choices: &[usize],
+) -> Result<Poll, PollError> {
+    if poll.is_closed(Utc::now().naive_utc()) {
+        return Err(PollError::Invalid("the poll is closed".to_string()));
+    }
+    let mut chosen = choices.to_vec();
+    chosen.sort_unstable();
+    chosen.dedup();
+    if chosen.is_empty() || (!poll.poll.multiple && chosen.len() > 1) {
+        return Err(PollError::Invalid(
+            "pick one option, or more if the poll allows it".to_string(),
+        ));
+    }
+    let options = chosen
+        .iter()
+        .map(|&i| {
+            poll.options
+                .get(i)
+                .ok_or_else(|| PollError::Invalid(format!("no such option: {}", i)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if has_voted(conn, poll.poll.id, voter).await? {
+        return Err(PollError::Invalid("already voted".to_string()));
+    }
+
+    for option in options {
+        add_vote(conn, poll, option, voter, account_id, None).await?;
+    }
+    count_voter(conn, poll.poll.id).await?;
+
+    Ok(with_options(conn, reload(conn, poll.poll.id).await?).await?)
+}
+
+/// Record a vote for the option `name` of the local poll `poll`, received from
+/// the remote actor `voter` in the activity `activity_id`. Votes for closed
+/// polls, for unknown options and further votes on single choice polls are
+/// ignored. Returns whether the vote was counted.
+pub async fn record_remote_vote<C: ConnectionTrait>(
+    conn: &C,
+    poll: &Poll,
+    voter: &str,
+    name: &str,
+    activity_id: &str,
+) -> Result<bool, DbErr> {
+    if poll.is_closed(Utc::now().naive_utc()) {
+        return Ok(false);
+    }
+    let Some(option) = poll.options.iter().find(|o| o.name == name) else {
+        return Ok(false);
+    };
+    let voted = poll_vote::Entity::find()
+        .filter(poll_vote::Column::PollId.eq(poll.poll.id))
+        .filter(poll_vote::Column::ActorId.eq(voter))
+        .all(conn)
+        .await?;
+    if voted.iter().any(|v| v.option_id == option.id) || (!poll.poll.multiple && !voted.is_empty())
+    {
+        return Ok(false);
+    }
+
+    add_vote(
+        conn,
+        poll,
+        option,
+        voter,
+        None,
+        Some(activity_id.to_string()),
+    )
+    .await?;
+    if voted.is_empty() {
+        count_voter(conn, poll.poll.id).await?;
+    }
+
+    Ok(true)
+}
+
+/// The actors outside this tenant who voted in `poll`.
+pub async fn remote_voters<C: ConnectionTrait>(
+    conn: &C,
+    poll: &Poll,
+) -> Result<Vec<String>, DbErr> {
+    let mut voters: Vec<String> = poll_vote::Entity::find()
+        .filter(poll_vote::Column::PollId.eq(poll.poll.id))
+        .filter(poll_vote::Column::AccountId.is_null())
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|v| v.actor_id)
+        .collect();
+    voters.sort();
+    voters.dedup();
+
+    Ok(voters)
+}
+
+/// Close the polls that expired. The audience of each closed local poll, and
+/// the remote accounts that voted in it, are sent the final counts, under the
+/// base URL its post was written with, or `default_base_url` for posts that
+/// have none. Returns the number of polls closed.
+pub async fn close_expired(
+    db: &DatabaseConnection,
+    default_base_url: &str,
+) -> Result<usize, PollError> {
+    let now = Utc::now().naive_utc();
+    let expired = poll::Entity::find()
+        .filter(poll::Column::ClosedAt.is_null())
+        .filter(poll::Column::ExpiresAt.lte(now))
+        .order_by_asc(poll::Column::Id)
+        .all(db)
+        .await?;
+
+    let closed = expired.len();
+    for poll in expired {
+        let mut model = poll::ActiveModel::from(poll.clone());
+        model.closed_at = Set(Some(now));
+        let poll = with_options(db, model.update(db).await?).await?;
+        let Some(content_id) = poll.poll.content_id else {
+            continue;
+        };
+        if let Some(post) = content::Entity::find_by_id(content_id).one(db).await? {
+            if post.published && post.deleted_at.is_none() {
+                let base_url = post.base_url.as_deref().unwrap_or(default_base_url);
+                queue::enqueue_poll_update(db, base_url, &post, &poll).await?;
+            }
+        }
+    }
+
+    Ok(closed)
+}
+
+/// Run the task closing expired polls for all tenants until the process exits.
+pub fn spawn_closer(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLOSE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            for tenant in all_tenants(&state).await {
+                let base_url = tenant_base_url(&tenant.domain, &state);
+                if let Err(e) = close_expired(&tenant.db, &base_url).await {
+                    tracing::error!("closing polls of {} failed: {:?}", tenant.domain, e);
+                }
+            }
+        }
+    });
+}
+
+async fn has_voted<C: ConnectionTrait>(conn: &C, poll_id: i64, voter: &str) -> Result<bool, DbErr> {
+    Ok(poll_vote::Entity::find()
+        .filter(poll_vote::Column::PollId.eq(poll_id))
+        .filter(poll_vote::Column::ActorId.eq(voter))
+        .count(conn)
+        .await?
+        > 0)
+}
+
+async fn add_vote<C: ConnectionTrait>(
+    conn: &C,
+    poll: &Poll,
+    option: &poll_option::Model,
+    voter: &str,
+    account_id: Option<i64>,
+    activity_id: Option<String>,
+) -> Result<(), DbErr> {
+    poll_vote::ActiveModel {
+        poll_id: Set(poll.poll.id),
+        option_id: Set(option.id),
+        actor_id: Set(voter.to_string()),
+        account_id: Set(account_id),
+        activity_id: Set(activity_id),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?;
+    poll_option::Entity::update_many()
+        .col_expr(
+            poll_option::Column::VotesCount,
+            Expr::col(poll_option::Column::VotesCount).add(1),
+        )
+        .filter(poll_option::Column::Id.eq(option.id))
+        .exec(conn)
+        .await?;
+
+    Ok(())
+}
+
+async fn count_voter<C: ConnectionTrait>(conn: &C, poll_id: i64) -> Result<(), DbErr> {
+    poll::Entity::update_many()
+        .col_expr(
+            poll::Column::VotersCount,
+            Expr::col(poll::Column::VotersCount).add(1),
+        )
+        .filter(poll::Column::Id.eq(poll_id))
+        .exec(conn)
+        .await?;
+
+    Ok(())
+}
+
+async fn reload<C: ConnectionTrait>(conn: &C, poll_id: i64) -> Result<poll::Model, DbErr> {
+    poll::Entity::find_by_id(poll_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound(format!("poll {}", poll_id)))
+}
+
+async fn with_options<C: ConnectionTrait>(conn: &C, poll: poll::Model) -> Result<Poll, DbErr> {
+    let options = options_of(conn, poll.id).await?;
+
+    Ok(Poll { poll, options })
+}
+
+async fn options_of<C: ConnectionTrait>(
+    conn: &C,
+    poll_id: i64,
+) -> Result<Vec<poll_option::Model>, DbErr> {
+    poll_option::Entity::find()
+        .filter(poll_option::Column::PollId.eq(poll_id))
+        .order_by_asc(poll_option::Column::Position)
+        .all(conn)
+        .await
+}
+
+#[derive(thiserror::Error)]
+pub enum PollError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error(transparent)]
+    Queue(#[from] queue::QueueError),
+    #[error(transparent)]
+    Database(#[from] DbErr),
+}
+
+impl std::fmt::Debug for PollError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
@@ -0,0 +1,168 @@
+//! Earlier versions of edited posts, local and remote.
+//!
+//! Editing a published post keeps the version it replaces as a revision,
+//! along with the time that version was written. The history of a post lists
+//! its revisions and then its current version, each with the changes from
+//! the version before it.
+use chrono::NaiveDateTime;
+use sea_orm::{ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+
+use crate::entities::{content, content_revision, prelude::*, remote_post};
+
+/// A version of a post.
+#[derive(Debug, Clone)]
+pub struct Version {
+    pub cw: Option<String>,
+    pub body: String,
+    pub written_at: NaiveDateTime,
+    /// The changes to the body from the previous version. The first version
+    /// is a single insertion.
+    pub diff: Vec<Change>,
+}
+
+/// A run of text that a version kept, added or removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "op", content = "text", rename_all = "lowercase")]
+pub enum Change {
+    Equal(String),
+    Insert(String),
+    Delete(String),
+}
+
+/// Keep the current version of the local post `post` before it is edited.
+pub async fn record_local<C: ConnectionTrait>(
+    conn: &C,
+    post: &content::Model,
+) -> Result<(), DbErr> {
+    ContentRevision::insert(content_revision::ActiveModel {
+        content_id: Set(Some(post.id)),
+        cw: Set(post.cw.clone()),
+        body: Set(post.body.clone().unwrap_or_default()),
+        written_at: Set(local_written_at(post)),
+        ..Default::default()
+    })
+    .exec_without_returning(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Keep the current version of the remote post `post` before it is updated.
+pub async fn record_remote<C: ConnectionTrait>(
+    conn: &C,
+    post: &remote_post::Model,
+) -> Result<(), DbErr> {
+    ContentRevision::insert(content_revision::ActiveModel {
+        remote_post_id: Set(Some(post.id)),
+        cw: Set(post.summary.clone()),
+        body: Set(post.content.clone().unwrap_or_default()),
+        written_at: Set(remote_written_at(post)),
+        ..Default::default()
+    })
+    .exec_without_returning(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// All versions of the local post `post`, oldest first.
+pub async fn local_history<C: ConnectionTrait>(
+    conn: &C,
+    post: &content::Model,
+) -> Result<Vec<Version>, DbErr> {
+    let revisions = ContentRevision::find()
+        .filter(content_revision::Column::ContentId.eq(post.id))
+        .order_by_asc(content_revision::Column::Id)
+        .all(conn)
+        .await?;
+
+    Ok(history(
+        revisions,
+        post.cw.clone(),
+        post.body.clone().unwrap_or_default(),
+        local_written_at(post),
+    ))
+}
+
+fn history(
+    revisions: Vec<content_revision::Model>,
+    cw: Option<String>,
+    body: String,
+    written_at: NaiveDateTime,
+) -> Vec<Version> {
+    let mut previous = String::new();
+    revisions
+        .into_iter()
+        .map(|r| (r.cw, r.body, r.written_at))
+        .chain(std::iter::once((cw, body, written_at)))
+        .map(|(cw, body, written_at)| {
+            let diff = diff(&previous, &body);
+            previous = body.clone();
+            Version {
+                cw,
+                body,
+                written_at,
+                diff,
+            }
+        })
+        .collect()
+}
+
+/// The word by word changes from `old` to `new`. Consecutive words with the
+/// same fate are merged into one change.
+pub fn diff(old: &str, new: &str) -> Vec<Change> {
+    let mut changes: Vec<Change> = vec![];
+    for change in TextDiff::from_words(old, new).iter_all_changes() {
+        let text = change.value();
+        match (changes.last_mut(), change.tag()) {
+            (Some(Change::Equal(run)), ChangeTag::Equal)
+            | (Some(Change::Insert(run)), ChangeTag::Insert)
+            | (Some(Change::Delete(run)), ChangeTag::Delete) => run.push_str(text),
+            (_, ChangeTag::Equal) => changes.push(Change::Equal(text.to_string())),
+            (_, ChangeTag::Insert) => changes.push(Change::Insert(text.to_string())),
+            (_, ChangeTag::Delete) => changes.push(Change::Delete(text.to_string())),
+        }
+    }
+
+    changes
+}
+
+/// When the current version of a local post was written.
+fn local_written_at(post: &content::Model) -> NaiveDateTime {
+    post.edited_at
+        .or(post.published_at)
+        .unwrap_or(post.updated_at)
+}
+
+/// When the current version of a remote post was written, as far as we know.
+fn remote_written_at(post: &remote_post::Model) -> NaiveDateTime {
+    post.edited_at
+        .or(post.published_at)
+        .unwrap_or(post.created_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, Change};
+
+    #[test]
+    fn diff_merges_runs_of_words() {
+        assert_eq!(
+            diff("teh quick fox", "the quick brown fox"),
+            vec![
+                Change::Delete("teh".to_string()),
+                Change::Insert("the".to_string()),
+                Change::Equal(" quick ".to_string()),
+                Change::Insert("brown ".to_string()),
+                Change::Equal("fox".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn first_version_is_an_insertion() {
+        assert_eq!(diff("", "hello"), vec![Change::Insert("hello".to_string())]);
+    }
+}
@@ -0,0 +1,79 @@
+use axum::{
+    extract::{Host, Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    activitypub::{collection::OrderedCollection, followers_url, following_url, ACTIVITY_JSON},
+    db, orm,
+    routes::{get_db_from_host, tenant_base_url, AppState},
+};
+
+use super::ActorError;
+
+/// The followers of an account. Only their number is published, not who they
+/// are.
+#[tracing::instrument(
+    name = "Get followers",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn followers(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(handle): Path<String>,
+) -> Result<Response, ActorError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+
+    let (account, _) = orm::get_account_by_handle(&handle.to_lowercase(), &conn)
+        .await
+        .map_err(|e| ActorError::UnexpectedError(e.into()))?
+        .ok_or_else(|| ActorError::NotFound(format!("no such account: {}", handle)))?;
+    let total = db::follow::count_followers(&conn, account.id)
+        .await
+        .map_err(|e| ActorError::UnexpectedError(anyhow::anyhow!(e)))?;
+    let id = followers_url(&base_url, account.username.as_deref().unwrap_or_default());
+
+    Ok(activity_json(OrderedCollection::count_only(id, total)))
+}
+
+/// The accounts an account follows. Only their number is published, not who
+/// they are.
+#[tracing::instrument(
+    name = "Get following",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn following(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(handle): Path<String>,
+) -> Result<Response, ActorError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+
+    let (account, _) = orm::get_account_by_handle(&handle.to_lowercase(), &conn)
+        .await
+        .map_err(|e| ActorError::UnexpectedError(e.into()))?
+        .ok_or_else(|| ActorError::NotFound(format!("no such account: {}", handle)))?;
+    let total = db::follow::count_following(&conn, account.id)
+        .await
+        .map_err(|e| ActorError::UnexpectedError(anyhow::anyhow!(e)))?;
+    let id = following_url(&base_url, account.username.as_deref().unwrap_or_default());
+
+    Ok(activity_json(OrderedCollection::count_only(id, total)))
+}
+
+fn activity_json<T: serde::Serialize>(body: T) -> Response {
+    ([(header::CONTENT_TYPE, ACTIVITY_JSON)], Json(body)).into_response()
+}
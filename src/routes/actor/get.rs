@@ -0,0 +1,83 @@
+use axum::{
+    extract::{Host, Path, State},
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    activitypub::{actor::Person, wants_activity_json, ACTIVITY_JSON},
+    keys, orm,
+    routes::{escape_html, get_db_from_host, tenant_base_url, AppState},
+};
+
+use super::ActorError;
+
+#[tracing::instrument(
+    name = "Get actor",
+    skip(state, headers),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn actor(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(handle): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ActorError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+
+    let (account, user) = orm::get_account_by_handle(&handle.to_lowercase(), &conn)
+        .await
+        .map_err(|e| ActorError::UnexpectedError(e.into()))?
+        .ok_or_else(|| ActorError::NotFound(format!("no such account: {}", handle)))?;
+    let mut person = Person::from_account(&base_url, &account, &user);
+    // Only the active key: servers expect a single publicKey, so keys in
+    // their grace period after a rotation are not served.
+    if let Some(key) = keys::active_key(&conn, account.id)
+        .await
+        .map_err(|e| ActorError::UnexpectedError(e.into()))?
+    {
+        person = person.with_public_key(&key);
+    }
+
+    if wants_activity_json(&headers) {
+        return Ok((
+            [
+                (header::CONTENT_TYPE, ACTIVITY_JSON),
+                (header::VARY, "Accept"),
+            ],
+            Json(person),
+        )
+            .into_response());
+    }
+
+    Ok(([(header::VARY, "Accept")], profile_html(&person)).into_response())
+}
+
+fn profile_html(person: &Person) -> Html<String> {
+    let name = escape_html(person.name.as_deref().unwrap_or_default());
+    let handle = escape_html(person.preferred_username.as_deref().unwrap_or_default());
+    let summary = escape_html(person.summary.as_deref().unwrap_or_default());
+
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8">
+        <title>{name} (@{handle})</title>
+        <link rel="alternate" type="application/activity+json" href="{id}">
+    </head>
+    <body>
+        <h1>{name}</h1>
+        <p>@{handle}</p>
+        <p>{summary}</p>
+    </body>
+</html>"#,
+        id = escape_html(&person.id),
+    ))
+}
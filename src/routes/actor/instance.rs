@@ -0,0 +1,45 @@
+use axum::{
+    extract::{Host, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    activitypub::{actor::Person, ACTIVITY_JSON},
+    keys,
+    routes::{get_db_from_host, tenant_base_url, AppState},
+};
+
+use super::ActorError;
+
+/// The instance actor of the tenant. Servers fetch it to verify requests we
+/// sign on our own behalf, so it is readable without a signature even in
+/// authorized fetch mode.
+#[tracing::instrument(
+    name = "Get instance actor",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn instance_actor(
+    Host(host): Host,
+    State(state): State<AppState>,
+) -> Result<Response, ActorError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+
+    let key = keys::instance_key(&conn)
+        .await
+        .map_err(|e| ActorError::UnexpectedError(e.into()))?
+        .ok_or_else(|| ActorError::NotFound("the instance actor has no key".to_string()))?;
+
+    Ok((
+        [(header::CONTENT_TYPE, ACTIVITY_JSON)],
+        Json(Person::instance_actor(&base_url, &hst, &key)),
+    )
+        .into_response())
+}
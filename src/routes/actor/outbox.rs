@@ -0,0 +1,128 @@
+use axum::{
+    extract::{Host, Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    activitypub::{
+        collection::{OrderedCollection, OrderedCollectionPage},
+        note::Note,
+        outbox_url, ACTIVITY_JSON,
+    },
+    db,
+    federation::mention,
+    orm, polls,
+    routes::{get_db_from_host, tenant_base_url, AppState},
+};
+
+use super::ActorError;
+
+const PAGE_SIZE: u64 = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct QueryParameters {
+    #[serde(default)]
+    page: bool,
+    max_id: Option<i64>,
+    min_id: Option<i64>,
+}
+
+#[tracing::instrument(
+    name = "Get outbox",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn outbox(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(handle): Path<String>,
+    Query(query_params): Query<QueryParameters>,
+) -> Result<Response, ActorError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+
+    let (account, _) = orm::get_account_by_handle(&handle.to_lowercase(), &conn)
+        .await
+        .map_err(|e| ActorError::UnexpectedError(e.into()))?
+        .ok_or_else(|| ActorError::NotFound(format!("no such account: {}", handle)))?;
+    let handle = account.username.unwrap_or_default();
+    let id = outbox_url(&base_url, &handle);
+
+    if !query_params.page {
+        let total = db::content::count_public(&conn, account.id)
+            .await
+            .map_err(|e| ActorError::UnexpectedError(anyhow::anyhow!(e)))?;
+        let collection = OrderedCollection::new(
+            id.clone(),
+            total,
+            format!("{}?page=true", id),
+            Some(format!("{}?page=true&min_id=0", id)),
+        );
+        return Ok(activity_json(collection));
+    }
+
+    let posts = db::content::public_page(
+        &conn,
+        account.id,
+        db::content::PageBounds {
+            max_id: query_params.max_id,
+            min_id: query_params.min_id,
+            ..Default::default()
+        },
+        PAGE_SIZE,
+    )
+    .await
+    .map_err(|e| ActorError::UnexpectedError(anyhow::anyhow!(e)))?;
+
+    let page_id = match (query_params.max_id, query_params.min_id) {
+        (Some(max_id), _) => format!("{}?page=true&max_id={}", id, max_id),
+        (None, Some(min_id)) => format!("{}?page=true&min_id={}", id, min_id),
+        (None, None) => format!("{}?page=true", id),
+    };
+    let next = match posts.last() {
+        Some(last) if posts.len() as u64 == PAGE_SIZE => {
+            Some(format!("{}?page=true&max_id={}", id, last.id))
+        }
+        _ => None,
+    };
+    let prev = posts
+        .first()
+        .map(|first| format!("{}?page=true&min_id={}", id, first.id));
+    let mentions = mention::for_posts(&conn, posts.iter().map(|p| p.id).collect())
+        .await
+        .map_err(|e| ActorError::UnexpectedError(e.into()))?;
+    let polls = polls::for_posts(&conn, posts.iter().map(|p| p.id).collect())
+        .await
+        .map_err(|e| ActorError::UnexpectedError(e.into()))?;
+    let items = posts
+        .iter()
+        .map(|post| {
+            let mentions = mentions
+                .get(&post.id)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let note = Note::from_content(&base_url, &handle, post, mentions);
+            match polls.get(&post.id) {
+                Some(poll) => note.with_poll(poll).into_create(),
+                None => note.into_create(),
+            }
+        })
+        .collect();
+
+    let mut page = OrderedCollectionPage::new(page_id, id, items);
+    page.next = next;
+    page.prev = prev;
+
+    Ok(activity_json(page))
+}
+
+fn activity_json<T: serde::Serialize>(body: T) -> Response {
+    ([(header::CONTENT_TYPE, ACTIVITY_JSON)], Json(body)).into_response()
+}
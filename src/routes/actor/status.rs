@@ -0,0 +1,174 @@
+use axum::{
+    extract::{Host, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    activitypub::{
+        actor::Person,
+        note::{Note, Tombstone},
+        wants_activity_json, ACTIVITYSTREAMS_CONTEXT, ACTIVITY_JSON,
+    },
+    db,
+    domain::Visibility,
+    federation::mention,
+    orm,
+    polls::{self, Poll},
+    routes::{
+        cw_html, escape_html, expands_cws, get_db_from_host, poll_html, tenant_base_url, AppState,
+    },
+    session_state::AuthContext,
+};
+
+use super::ActorError;
+
+/// A single post, at both its ActivityPub id and its human readable URL.
+/// Only posts anyone may see are served: drafts, followers-only and direct
+/// posts are indistinguishable from posts that never existed.
+#[tracing::instrument(
+    name = "Get status",
+    skip(state, headers),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn status(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path((handle, id)): Path<(String, i64)>,
+    auth: AuthContext,
+    headers: HeaderMap,
+) -> Result<Response, ActorError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+
+    let (account, user) = orm::get_account_by_handle(&handle.to_lowercase(), &conn)
+        .await
+        .map_err(|e| ActorError::UnexpectedError(e.into()))?
+        .ok_or_else(|| ActorError::NotFound(format!("no such account: {}", handle)))?;
+    let not_found = || ActorError::NotFound(format!("no such status: {}", id));
+    let post = db::content::find_by_publisher(&conn, account.id, id)
+        .await
+        .map_err(|e| ActorError::UnexpectedError(anyhow::anyhow!(e)))?
+        .ok_or_else(not_found)?;
+    let visibility = Visibility::try_from(post.visibility.as_str()).unwrap_or_default();
+    if !post.published || !matches!(visibility, Visibility::Public | Visibility::Unlisted) {
+        return Err(not_found());
+    }
+    let handle = account.username.clone().unwrap_or_default();
+
+    if post.deleted_at.is_some() {
+        let mut tombstone = Tombstone::from_content(&base_url, &handle, &post);
+        if wants_activity_json(&headers) {
+            tombstone.context = Value::from(ACTIVITYSTREAMS_CONTEXT);
+            return Ok((
+                StatusCode::GONE,
+                [
+                    (header::CONTENT_TYPE, ACTIVITY_JSON),
+                    (header::VARY, "Accept"),
+                ],
+                Json(tombstone),
+            )
+                .into_response());
+        }
+
+        return Ok((
+            StatusCode::GONE,
+            [(header::VARY, "Accept")],
+            tombstone_html(&tombstone),
+        )
+            .into_response());
+    }
+
+    let mentions = mention::for_post(&conn, post.id)
+        .await
+        .map_err(|e| ActorError::UnexpectedError(e.into()))?;
+    let mut note = Note::from_content(&base_url, &handle, &post, &mentions);
+    let poll = polls::for_post(&conn, post.id)
+        .await
+        .map_err(|e| ActorError::UnexpectedError(e.into()))?;
+    if let Some(poll) = &poll {
+        note = note.with_poll(poll);
+    }
+    if wants_activity_json(&headers) {
+        note.context = Value::from(ACTIVITYSTREAMS_CONTEXT);
+        return Ok((
+            [
+                (header::CONTENT_TYPE, ACTIVITY_JSON),
+                (header::VARY, "Accept"),
+            ],
+            Json(note),
+        )
+            .into_response());
+    }
+
+    let author = Person::from_account(&base_url, &account, &user);
+    let expand_cw = expands_cws(&conn, auth.current_user.as_ref())
+        .await
+        .map_err(|e| ActorError::UnexpectedError(e.into()))?;
+    Ok((
+        [(header::VARY, "Accept")],
+        status_html(&author, &note, poll.as_ref(), expand_cw),
+    )
+        .into_response())
+}
+
+fn status_html(author: &Person, note: &Note, poll: Option<&Poll>, expand_cw: bool) -> Html<String> {
+    let name = escape_html(author.name.as_deref().unwrap_or_default());
+    let handle = escape_html(author.preferred_username.as_deref().unwrap_or_default());
+    // The content is rendered from escaped plain text
+    let content = note.content.as_deref().unwrap_or_default();
+    let body = cw_html(note.summary.as_deref(), content, expand_cw);
+    let poll = poll.map(poll_html).unwrap_or_default();
+    let edited = match &note.updated {
+        Some(updated) => format!(
+            r#", edited <time datetime="{updated}">{updated}</time>"#,
+            updated = escape_html(updated)
+        ),
+        None => String::new(),
+    };
+
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8">
+        <title>{name} (@{handle})</title>
+        <link rel="alternate" type="application/activity+json" href="{id}">
+    </head>
+    <body>
+        <p><a href="{author}">{name}</a> @{handle}</p>
+        {body}{poll}
+        <p><time datetime="{published}">{published}</time>{edited}</p>
+    </body>
+</html>"#,
+        id = escape_html(&note.id),
+        author = escape_html(&author.id),
+        published = escape_html(note.published.as_deref().unwrap_or_default()),
+    ))
+}
+
+fn tombstone_html(tombstone: &Tombstone) -> Html<String> {
+    let deleted = match &tombstone.deleted {
+        Some(deleted) => format!(" on {}", escape_html(deleted)),
+        None => String::new(),
+    };
+
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8">
+        <title>Deleted</title>
+    </head>
+    <body>
+        <p>This post was deleted{deleted}.</p>
+    </body>
+</html>"#
+    ))
+}
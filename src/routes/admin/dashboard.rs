@@ -7,10 +7,12 @@ use axum::{
 use axum_macros::debug_handler;
 
 use crate::{
-    domain::AppUser,
+    db,
+    domain::{AppUser, FederationMode},
     error::{error_chain_fmt, SessionError},
+    federation::policy,
     orm,
-    routes::{get_db_from_host, AppState},
+    routes::{escape_html, get_db_from_host, AppState},
 };
 
 #[tracing::instrument(name = "Admin dashboard", skip(state))]
@@ -30,6 +32,37 @@ pub async fn admin_dashboard(
         .await
         .map_err(|e| AdminError::UnexpectedError(e.into()))?;
     let user_name = model.name;
+    let mode = policy::federation_mode(&conn)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    let authorized_fetch = match policy::authorized_fetch(&conn)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?
+    {
+        true => " checked",
+        false => "",
+    };
+    let open_registrations = match db::microblog::open_registrations(&conn)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(anyhow::anyhow!(e)))?
+    {
+        true => " checked",
+        false => "",
+    };
+    let allowed_domains = policy::allowed_domains(&conn)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    let mode_options: String = [
+        (FederationMode::Open, "Open"),
+        (FederationMode::Allowlist, "Allowlisted domains only"),
+    ]
+    .iter()
+    .map(|(m, label)| {
+        let selected = if *m == mode { " selected" } else { "" };
+        format!(r#"<option value="{m}"{selected}>{label}</option>"#)
+    })
+    .collect();
+    let allowed_domains = escape_html(&allowed_domains.join("\n"));
 
     Ok(Html(format!(
         r#"<!DOCTYPE html>
@@ -43,12 +76,36 @@ pub async fn admin_dashboard(
         <p>Actions:</p>
         <ol>
             <li><a href="/user/change-password">Change your password</a></li>
+            <li><a href="/admin/domain-blocks">Manage domain blocks</a></li>
+            <li><a href="/admin/relays">Manage relays</a></li>
+            <li><a href="/admin/deliveries">Deliveries that are pending or failed</a></li>
+            <li><a href="/admin/inbox-queue">Activities that could not be processed</a></li>
             <li>
                 <form name="logoutForm" action="/user/logout" method="post">
                     <input type="submit" value="Logout" />
                 </form>
             </li>
         </ol>
+        <h2>Federation</h2>
+        <form name="federationForm" action="/admin/federation" method="post">
+            <label>Federate with <select name="mode">{mode_options}</select></label>
+            <label>
+                <input type="checkbox" name="authorized_fetch"{authorized_fetch} />
+                Require signatures to fetch actors and posts
+            </label>
+            <label>Allowed domains, one per line
+                <textarea name="allowed_domains" rows="10" cols="40">{allowed_domains}</textarea>
+            </label>
+            <input type="submit" value="Save" />
+        </form>
+        <h2>Registrations</h2>
+        <form name="registrationsForm" action="/admin/registrations" method="post">
+            <label>
+                <input type="checkbox" name="open_registrations"{open_registrations} />
+                Anyone can sign up
+            </label>
+            <input type="submit" value="Save" />
+        </form>
     </body>
 </html>"#
     )))
@@ -58,6 +115,10 @@ pub async fn admin_dashboard(
 pub enum AdminError {
     #[error("session creation failed")]
     SessionError(#[from] SessionError),
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("{0}")]
+    NotFound(String),
     #[error("an unexpected error occurred")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -75,8 +136,16 @@ impl IntoResponse for AdminError {
                 tracing::error!("failed to instantiate session: {}", e.to_string());
                 (StatusCode::from_u16(303).unwrap(), Redirect::to("/login")).into_response()
             }
+            Self::ValidationError(s) => {
+                tracing::info!("validation error {s:?}");
+                (StatusCode::BAD_REQUEST, s).into_response()
+            }
+            Self::NotFound(s) => {
+                tracing::info!("not found: {s:?}");
+                (StatusCode::NOT_FOUND, s).into_response()
+            }
             Self::UnexpectedError(e) => {
-                tracing::error!("an unexpected error occurred during session creation");
+                tracing::error!("an unexpected error occurred: {e}");
                 (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")).into_response()
             }
         }
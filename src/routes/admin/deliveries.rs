@@ -0,0 +1,121 @@
+use axum::{
+    extract::{Host, State},
+    response::Html,
+};
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::{
+    entities::delivery,
+    federation::queue,
+    routes::{escape_html, get_db_from_host, AppState},
+};
+
+use super::dashboard::AdminError;
+
+/// How many pending and how many failed deliveries are listed.
+const LISTED: u64 = 50;
+
+/// Outbound deliveries that have not gone through yet or were given up, and
+/// the hosts that are failing.
+#[tracing::instrument(
+    name = "List deliveries",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn list(
+    Host(host): Host,
+    State(state): State<AppState>,
+) -> Result<Html<String>, AdminError> {
+    let conn = get_db_from_host(&host, &state)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    let pending = queue::pending(&conn, LISTED)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    let failed = queue::failed(&conn, LISTED)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    let hosts = queue::failing_hosts(&conn)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    let host_rows: String = hosts
+        .iter()
+        .map(|h| {
+            format!(
+                r#"
+            <tr>
+                <td>{host}</td>
+                <td>{failures}</td>
+                <td>{next_attempt_at}</td>
+                <td>{unreachable_since}</td>
+            </tr>"#,
+                host = escape_html(&h.host),
+                failures = h.failures,
+                next_attempt_at = format_time(h.next_attempt_at),
+                unreachable_since = h.unreachable_since.map(format_time).unwrap_or_default(),
+            )
+        })
+        .collect();
+    let pending_rows: String = pending
+        .iter()
+        .map(|d| delivery_row(d, d.next_attempt_at))
+        .collect();
+    let failed_rows: String = failed
+        .iter()
+        .map(|d| delivery_row(d, d.updated_at))
+        .collect();
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8">
+        <title>Deliveries</title>
+    </head>
+    <body>
+        <h1>Deliveries</h1>
+        <h2>Failing hosts</h2>
+        <table>
+            <tr><th>Host</th><th>Failures</th><th>Next attempt</th><th>Unreachable since</th></tr>{host_rows}
+        </table>
+        <h2>Pending</h2>
+        <table>
+            <tr><th>Inbox</th><th>Type</th><th>Attempts</th><th>Next attempt</th><th>Error</th></tr>{pending_rows}
+        </table>
+        <h2>Failed</h2>
+        <table>
+            <tr><th>Inbox</th><th>Type</th><th>Attempts</th><th>Given up</th><th>Error</th></tr>{failed_rows}
+        </table>
+        <p><a href="/admin/dashboard">Back to the dashboard</a></p>
+    </body>
+</html>"#
+    )))
+}
+
+/// A table row for the delivery `d`, with the time `at` that matters for its
+/// status.
+fn delivery_row(d: &delivery::Model, at: NaiveDateTime) -> String {
+    format!(
+        r#"
+            <tr>
+                <td>{inbox}</td>
+                <td>{kind}</td>
+                <td>{attempts}</td>
+                <td>{at}</td>
+                <td>{error}</td>
+            </tr>"#,
+        inbox = escape_html(&d.inbox),
+        kind = escape_html(d.activity["type"].as_str().unwrap_or_default()),
+        attempts = d.attempts,
+        at = format_time(at),
+        error = escape_html(d.last_error.as_deref().unwrap_or_default()),
+    )
+}
+
+fn format_time(t: NaiveDateTime) -> String {
+    t.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
@@ -0,0 +1,273 @@
+use axum::{
+    extract::{Host, Path, State},
+    http::header,
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use sea_orm::{EntityTrait, QueryOrder, TransactionTrait};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    domain::BlockSeverity,
+    entities::{domain_block, prelude::*},
+    federation::policy::{self, BlockEntry},
+    routes::{escape_html, get_db_from_host, AppState},
+};
+
+use super::dashboard::AdminError;
+
+#[derive(Debug, Deserialize)]
+pub struct BlockForm {
+    domain: String,
+    #[serde(default)]
+    severity: Option<String>,
+    #[serde(default)]
+    reject_media: Option<String>,
+    #[serde(default)]
+    reject_reports: Option<String>,
+    #[serde(default)]
+    public_comment: Option<String>,
+    #[serde(default)]
+    obfuscate: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportForm {
+    csv: String,
+}
+
+#[tracing::instrument(
+    name = "List domain blocks",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn list(
+    Host(host): Host,
+    State(state): State<AppState>,
+) -> Result<Html<String>, AdminError> {
+    let blocks = all_blocks(&host, &state).await?;
+
+    let rows: String = blocks
+        .iter()
+        .map(|b| {
+            let mut flags = vec![];
+            if b.reject_media {
+                flags.push("reject media");
+            }
+            if b.reject_reports {
+                flags.push("reject reports");
+            }
+            if b.obfuscate {
+                flags.push("obfuscate");
+            }
+            format!(
+                r#"
+            <tr>
+                <td>{domain}</td>
+                <td>{severity}</td>
+                <td>{flags}</td>
+                <td>{comment}</td>
+                <td>
+                    <form action="/admin/domain-blocks/{id}/delete" method="post">
+                        <input type="submit" value="Remove" />
+                    </form>
+                </td>
+            </tr>"#,
+                domain = escape_html(&b.domain),
+                severity = escape_html(&b.severity),
+                flags = flags.join(", "),
+                comment = escape_html(b.public_comment.as_deref().unwrap_or_default()),
+                id = b.id,
+            )
+        })
+        .collect();
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8">
+        <title>Domain blocks</title>
+    </head>
+    <body>
+        <h1>Domain blocks</h1>
+        <table>
+            <tr><th>Domain</th><th>Severity</th><th>Policies</th><th>Public comment</th><th></th></tr>{rows}
+        </table>
+        <h2>Block a domain</h2>
+        <form name="blockForm" action="/admin/domain-blocks" method="post">
+            <label>Domain <input type="text" name="domain" required /></label>
+            <label>Severity
+                <select name="severity">
+                    <option value="suspend">Suspend</option>
+                    <option value="silence">Silence</option>
+                    <option value="noop">None</option>
+                </select>
+            </label>
+            <label><input type="checkbox" name="reject_media" /> Reject media</label>
+            <label><input type="checkbox" name="reject_reports" /> Reject reports</label>
+            <label><input type="checkbox" name="obfuscate" /> Obfuscate</label>
+            <label>Public comment <input type="text" name="public_comment" /></label>
+            <input type="submit" value="Block" />
+        </form>
+        <h2>Import</h2>
+        <form name="importForm" action="/admin/domain-blocks/import" method="post">
+            <textarea name="csv" rows="10" cols="80"></textarea>
+            <input type="submit" value="Import" />
+        </form>
+        <p><a href="/admin/domain-blocks/export">Export as CSV</a></p>
+        <p><a href="/admin/dashboard">Back to the dashboard</a></p>
+    </body>
+</html>"#
+    )))
+}
+
+#[tracing::instrument(
+    name = "Block a domain",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn create(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Form(form): Form<BlockForm>,
+) -> Result<Redirect, AdminError> {
+    let conn = get_db_from_host(&host, &state)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    let severity = match form.severity.as_deref() {
+        Some(s) if !s.is_empty() => {
+            BlockSeverity::try_from(s).map_err(AdminError::ValidationError)?
+        }
+        _ => BlockSeverity::default(),
+    };
+    let entry = BlockEntry {
+        domain: policy::normalize_domain(&form.domain).map_err(AdminError::ValidationError)?,
+        severity,
+        reject_media: form.reject_media.is_some(),
+        reject_reports: form.reject_reports.is_some(),
+        public_comment: form.public_comment.filter(|c| !c.trim().is_empty()),
+        obfuscate: form.obfuscate.is_some(),
+    };
+    let txn = conn
+        .begin()
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    policy::save_block(&txn, &entry)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    txn.commit()
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    Ok(Redirect::to("/admin/domain-blocks"))
+}
+
+#[tracing::instrument(
+    name = "Remove a domain block",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn delete(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Redirect, AdminError> {
+    let conn = get_db_from_host(&host, &state)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    if !policy::remove_block(&conn, id)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?
+    {
+        return Err(AdminError::NotFound(format!(
+            "no such domain block: {}",
+            id
+        )));
+    }
+
+    Ok(Redirect::to("/admin/domain-blocks"))
+}
+
+/// Replace the blocks of the domains in an uploaded block list. Nothing is
+/// imported unless every row is valid.
+#[tracing::instrument(
+    name = "Import domain blocks",
+    skip(state, form),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn import(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Form(form): Form<ImportForm>,
+) -> Result<Redirect, AdminError> {
+    let conn = get_db_from_host(&host, &state)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    let entries = policy::parse_csv(&form.csv).map_err(AdminError::ValidationError)?;
+    let txn = conn
+        .begin()
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    for entry in &entries {
+        policy::save_block(&txn, entry)
+            .await
+            .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    }
+    txn.commit()
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    tracing::info!("imported {} domain blocks", entries.len());
+
+    Ok(Redirect::to("/admin/domain-blocks"))
+}
+
+#[tracing::instrument(
+    name = "Export domain blocks",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn export(
+    Host(host): Host,
+    State(state): State<AppState>,
+) -> Result<Response, AdminError> {
+    let blocks = all_blocks(&host, &state).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                r#"attachment; filename="domain_blocks.csv""#,
+            ),
+        ],
+        policy::to_csv(&blocks),
+    )
+        .into_response())
+}
+
+async fn all_blocks(host: &str, state: &AppState) -> Result<Vec<domain_block::Model>, AdminError> {
+    let conn = get_db_from_host(host, state)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    DomainBlock::find()
+        .order_by_asc(domain_block::Column::Domain)
+        .all(&conn)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))
+}
@@ -0,0 +1,74 @@
+use axum::{
+    extract::{Host, State},
+    response::Redirect,
+    Form,
+};
+use sea_orm::TransactionTrait;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    domain::FederationMode,
+    federation::policy,
+    routes::{get_tenant_from_host, AppState},
+};
+
+use super::dashboard::AdminError;
+
+#[derive(Debug, Deserialize)]
+pub struct FederationForm {
+    mode: String,
+    #[serde(default)]
+    authorized_fetch: Option<String>,
+    /// One domain per line
+    #[serde(default)]
+    allowed_domains: String,
+}
+
+/// Change the federation mode, authorized fetch mode and allowlist of the
+/// tenant.
+#[tracing::instrument(
+    name = "Update federation settings",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn update(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Form(form): Form<FederationForm>,
+) -> Result<Redirect, AdminError> {
+    let tenant = get_tenant_from_host(&host, &state)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    let mode = FederationMode::try_from(form.mode).map_err(AdminError::ValidationError)?;
+    let domains = form
+        .allowed_domains
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(policy::normalize_domain)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(AdminError::ValidationError)?;
+
+    let txn = tenant
+        .db
+        .begin()
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    policy::save_federation(
+        &txn,
+        mode,
+        form.authorized_fetch.is_some(),
+        &domains,
+        &tenant.domain,
+    )
+    .await
+    .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    txn.commit()
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    Ok(Redirect::to("/admin/dashboard"))
+}
@@ -0,0 +1,102 @@
+use axum::{
+    extract::{Host, Path, State},
+    response::{Html, Redirect},
+};
+use uuid::Uuid;
+
+use crate::{
+    federation::inbox_queue,
+    routes::{escape_html, get_db_from_host, AppState},
+};
+
+use super::dashboard::AdminError;
+
+/// How many dead letters are listed.
+const DEAD_LETTERS: u64 = 50;
+
+/// Activities that could not be processed, with the reason.
+#[tracing::instrument(
+    name = "List dead letters",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn list(
+    Host(host): Host,
+    State(state): State<AppState>,
+) -> Result<Html<String>, AdminError> {
+    let conn = get_db_from_host(&host, &state)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    let dead = inbox_queue::dead_letters(&conn, DEAD_LETTERS)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    let rows: String = dead
+        .iter()
+        .map(|a| {
+            format!(
+                r#"
+            <tr>
+                <td>{activity_id}</td>
+                <td>{kind}</td>
+                <td>{error}</td>
+                <td>
+                    <form action="/admin/inbox-queue/{id}/replay" method="post">
+                        <input type="submit" value="Replay" />
+                    </form>
+                </td>
+            </tr>"#,
+                activity_id = escape_html(&a.activity_id),
+                kind = escape_html(a.activity["type"].as_str().unwrap_or_default()),
+                error = escape_html(a.last_error.as_deref().unwrap_or_default()),
+                id = a.id,
+            )
+        })
+        .collect();
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8">
+        <title>Dead letters</title>
+    </head>
+    <body>
+        <h1>Activities that could not be processed</h1>
+        <table>
+            <tr><th>Activity</th><th>Type</th><th>Error</th><th></th></tr>{rows}
+        </table>
+        <p><a href="/admin/dashboard">Back to the dashboard</a></p>
+    </body>
+</html>"#
+    )))
+}
+
+/// Process a stored activity again.
+#[tracing::instrument(
+    name = "Replay activity",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn replay(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Redirect, AdminError> {
+    let conn = get_db_from_host(&host, &state)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    if !inbox_queue::replay(&conn, id)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?
+    {
+        return Err(AdminError::NotFound(format!("no such activity: {}", id)));
+    }
+
+    Ok(Redirect::to("/admin/inbox-queue"))
+}
@@ -1 +1,7 @@
 pub(crate) mod dashboard;
+pub(crate) mod deliveries;
+pub(crate) mod domain_blocks;
+pub(crate) mod federation;
+pub(crate) mod inbox_queue;
+pub(crate) mod registrations;
+pub(crate) mod relays;
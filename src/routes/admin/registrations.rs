@@ -0,0 +1,48 @@
+use axum::{
+    extract::{Host, State},
+    response::Redirect,
+    Form,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    db,
+    routes::{get_tenant_from_host, AppState},
+};
+
+use super::dashboard::AdminError;
+
+#[derive(Debug, Deserialize)]
+pub struct RegistrationsForm {
+    #[serde(default)]
+    open_registrations: Option<String>,
+}
+
+/// Open or close the tenant to sign ups through `POST /user`.
+#[tracing::instrument(
+    name = "Update registrations",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn update(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Form(form): Form<RegistrationsForm>,
+) -> Result<Redirect, AdminError> {
+    let tenant = get_tenant_from_host(&host, &state)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    db::microblog::save_registrations(
+        &tenant.db,
+        form.open_registrations.is_some(),
+        &tenant.domain,
+    )
+    .await
+    .map_err(|e| AdminError::UnexpectedError(anyhow::anyhow!(e)))?;
+
+    Ok(Redirect::to("/admin/dashboard"))
+}
@@ -0,0 +1,164 @@
+use axum::{
+    extract::{Host, Path, State},
+    response::{Html, Redirect},
+    Form,
+};
+use sea_orm::TransactionTrait;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    federation::{
+        fetch::Fetcher,
+        instance_signer,
+        relay::{self, RelayError},
+    },
+    routes::{escape_html, get_db_from_host, tenant_base_url, AppState},
+};
+
+use super::dashboard::AdminError;
+
+#[derive(Debug, Deserialize)]
+pub struct RelayForm {
+    /// The actor of a LitePub relay or the inbox of a Mastodon-style relay
+    url: String,
+}
+
+#[tracing::instrument(
+    name = "List relays",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn list(
+    Host(host): Host,
+    State(state): State<AppState>,
+) -> Result<Html<String>, AdminError> {
+    let conn = get_db_from_host(&host, &state)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    let relays = relay::all(&conn)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    let rows: String = relays
+        .iter()
+        .map(|r| {
+            format!(
+                r#"
+            <tr>
+                <td>{url}</td>
+                <td>{state}</td>
+                <td>
+                    <form action="/admin/relays/{id}/delete" method="post">
+                        <input type="submit" value="Unsubscribe" />
+                    </form>
+                </td>
+            </tr>"#,
+                url = escape_html(&r.url),
+                state = escape_html(&r.state),
+                id = r.id,
+            )
+        })
+        .collect();
+
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8">
+        <title>Relays</title>
+    </head>
+    <body>
+        <h1>Relays</h1>
+        <table>
+            <tr><th>Relay</th><th>State</th><th></th></tr>{rows}
+        </table>
+        <h2>Subscribe to a relay</h2>
+        <form name="relayForm" action="/admin/relays" method="post">
+            <label>Relay actor or inbox URL <input type="url" name="url" required /></label>
+            <input type="submit" value="Subscribe" />
+        </form>
+        <p><a href="/admin/dashboard">Back to the dashboard</a></p>
+    </body>
+</html>"#
+    )))
+}
+
+/// Subscribe the tenant to a relay. The relay shows up as pending until it
+/// accepts.
+#[tracing::instrument(
+    name = "Subscribe to a relay",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn create(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Form(form): Form<RelayForm>,
+) -> Result<Redirect, AdminError> {
+    let conn = get_db_from_host(&host, &state)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    let base_url = tenant_base_url(&host, &state);
+
+    let signer = instance_signer(&conn, &base_url, &state.global_config.server.secret_key)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    let fetcher = Fetcher::new(&state.federation).signed_by(signer.as_ref());
+    let txn = conn
+        .begin()
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    relay::subscribe(&fetcher, &txn, &base_url, &form.url)
+        .await
+        .map_err(|e| match e {
+            RelayError::Invalid(s) => AdminError::ValidationError(s),
+            RelayError::Fetch(e) => {
+                AdminError::ValidationError(format!("failed to fetch the relay: {}", e))
+            }
+            e => AdminError::UnexpectedError(e.into()),
+        })?;
+    txn.commit()
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    Ok(Redirect::to("/admin/relays"))
+}
+
+#[tracing::instrument(
+    name = "Unsubscribe from a relay",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn delete(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Redirect, AdminError> {
+    let conn = get_db_from_host(&host, &state)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    let base_url = tenant_base_url(&host, &state);
+
+    let txn = conn
+        .begin()
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+    if !relay::unsubscribe(&txn, &base_url, id)
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?
+    {
+        return Err(AdminError::NotFound(format!("no such relay: {}", id)));
+    }
+    txn.commit()
+        .await
+        .map_err(|e| AdminError::UnexpectedError(e.into()))?;
+
+    Ok(Redirect::to("/admin/relays"))
+}
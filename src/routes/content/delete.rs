@@ -0,0 +1,45 @@
+use axum::{
+    extract::{Host, Path, State},
+    Extension,
+};
+use uuid::Uuid;
+
+use crate::{
+    db,
+    domain::AppUser,
+    routes::{tenant_base_url, AppState},
+};
+
+use super::{current_account, tenant_db, ContentError};
+
+/// Delete one of the current user's posts. The post becomes a tombstone and
+/// its deletion is sent wherever it was delivered.
+#[tracing::instrument(
+    name = "Delete a microblog",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn delete(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<(), ContentError> {
+    let hst = host.to_string();
+    let conn = tenant_db(&hst, &state).await?;
+    let account = current_account(&user, &conn).await?;
+    let post = db::content::find_by_publisher(&conn, account.id, id)
+        .await
+        .map_err(|e| ContentError::UnexpectedError(anyhow::anyhow!(e)))?
+        .filter(|post| post.deleted_at.is_none())
+        .ok_or_else(|| ContentError::NotFound(format!("no such post: {}", id)))?;
+
+    let base_url = tenant_base_url(&hst, &state);
+    db::content::delete(&conn, &base_url, post)
+        .await
+        .map_err(|e| ContentError::UnexpectedError(anyhow::anyhow!(e)))?;
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use anyhow::Context;
+use axum::{
+    extract::{Host, Path, State},
+    Extension, Json,
+};
+use sea_orm::{ActiveModelTrait, NotSet, Set, TransactionTrait};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    db,
+    domain::AppUser,
+    entities::content,
+    error::TenantMapError,
+    federation::{mention, queue},
+    revisions,
+    routes::{get_db_from_host, tenant_base_url, AppState},
+    tags,
+};
+
+use super::{
+    post::{parse_cw, process_content, resolve_mentions},
+    ContentError,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct BodyData {
+    pub content: EditedPost,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EditedPost {
+    text: String,
+    /// The new content warning. The post has none if it is missing or blank.
+    #[serde(default)]
+    cw: Option<String>,
+}
+
+/// Replace the text and content warning of one of the current user's posts.
+/// Mentions and hashtags are taken from the new text. The version a
+/// published post had before is kept in its history, and the post is updated
+/// wherever it was delivered.
+#[tracing::instrument(
+    name = "Edit a microblog",
+    skip(state, body),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn edit(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(body): Json<BodyData>,
+) -> Result<(), ContentError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state).await.map_err(|e| match e {
+        TenantMapError::NotFound(s) => ContentError::ValidationError(s),
+        TenantMapError::UnexpectedError(s) => ContentError::UnexpectedError(anyhow::anyhow!(s)),
+    })?;
+
+    let account_id = process_content(&user, &body.content.text, &conn).await?;
+    let post = db::content::find_by_publisher(&conn, account_id, id)
+        .await
+        .map_err(|e| ContentError::UnexpectedError(anyhow::anyhow!(e)))?
+        .filter(|post| post.deleted_at.is_none())
+        .ok_or_else(|| ContentError::NotFound(format!("no such post: {}", id)))?;
+
+    let cw = parse_cw(body.content.cw)?;
+    if post.body.as_deref() == Some(body.content.text.as_str()) && post.cw == cw {
+        return Ok(());
+    }
+
+    let base_url = tenant_base_url(&hst, &state);
+    let mentions = resolve_mentions(&state, &base_url, &conn, &body.content.text).await?;
+
+    let published = post.published;
+    let now = chrono::Utc::now().naive_utc();
+    let txn = conn.begin().await.context("failed to start transaction")?;
+    if published {
+        revisions::record_local(&txn, &post)
+            .await
+            .context("failed to keep the previous version")?;
+    }
+    let post = content::ActiveModel {
+        id: Set(post.id),
+        cw: Set(cw),
+        body: Set(Some(body.content.text)),
+        updated_at: Set(now),
+        edited_at: if published { Set(Some(now)) } else { NotSet },
+        ..Default::default()
+    }
+    .update(&txn)
+    .await
+    .context("failed to update content")?;
+    mention::replace(&txn, post.id, mentions)
+        .await
+        .context("failed to store mentions")?;
+    tags::tag_post(&txn, post.id, post.body.as_deref().unwrap_or_default())
+        .await
+        .context("failed to store hashtags")?;
+    if published {
+        queue::enqueue_post_update(&txn, &base_url, &post)
+            .await
+            .context("failed to queue delivery of the edit")?;
+    }
+    txn.commit().await.context("failed to edit content")?;
+
+    Ok(())
+}
@@ -6,26 +6,87 @@ use axum::{
 use serde::Serialize;
 
 use crate::{
+    activitypub::format_timestamp,
+    db::{self, content::PageBounds},
     domain::AppUser,
-    routes::{get_db_from_host, AppState},
+    entities::content,
+    routes::{escape_html, get_db_from_host, AppState},
 };
 
-use super::ContentError;
+use super::{current_account, ContentError};
+
+/// How many drafts and scheduled posts are listed below the form.
+const UNPUBLISHED_LIMIT: u64 = 40;
 
 #[derive(Debug, Serialize)]
 pub struct FormData {
     pub content: String,
 }
 
+/// The compose form, followed by the current user's drafts and scheduled
+/// posts, each with a button to publish it right away.
 pub async fn form(
     Host(host): Host,
     State(state): State<AppState>,
-    Extension(_user): Extension<AppUser>,
-) -> Result<Html<&'static str>, ContentError> {
+    Extension(user): Extension<AppUser>,
+) -> Result<Html<String>, ContentError> {
     let hst = host.to_string();
-    let _conn = get_db_from_host(&hst, &state)
+    let conn = get_db_from_host(&hst, &state)
         .await
         .map_err(|e| ContentError::UnexpectedError(e.into()))?;
+    let account = current_account(&user, &conn).await?;
+
+    let drafts =
+        db::content::drafts_page(&conn, account.id, PageBounds::default(), UNPUBLISHED_LIMIT)
+            .await
+            .map_err(|e| ContentError::UnexpectedError(anyhow::anyhow!(e)))?;
+    let scheduled =
+        db::content::scheduled_page(&conn, account.id, PageBounds::default(), UNPUBLISHED_LIMIT)
+            .await
+            .map_err(|e| ContentError::UnexpectedError(anyhow::anyhow!(e)))?;
+    let unpublished = format!(
+        "{}{}",
+        unpublished_list("Drafts", &drafts),
+        unpublished_list("Scheduled", &scheduled)
+    );
+
+    Ok(Html(
+        include_str!("content.html").replace("<!-- unpublished -->", &unpublished),
+    ))
+}
+
+fn unpublished_list(title: &str, posts: &[content::Model]) -> String {
+    if posts.is_empty() {
+        return String::new();
+    }
+    let items = posts
+        .iter()
+        .map(|post| {
+            let scheduled = post
+                .scheduled_at
+                .map(|at| {
+                    let at = format_timestamp(at);
+                    format!(r#" <time datetime="{at}">{at}</time>"#)
+                })
+                .unwrap_or_default();
+            format!(
+                r#"
+        <li>
+            <p>{text}</p>{scheduled}
+            <form action="/content/form/{id}/publish" method="post">
+                <button type="submit">Publish now</button>
+            </form>
+        </li>"#,
+                text = escape_html(post.body.as_deref().unwrap_or_default()),
+                id = post.id,
+            )
+        })
+        .collect::<String>();
 
-    Ok(Html(include_str!("content.html")))
+    format!(
+        r#"<h2>{title}</h2>
+    <ul>{items}
+    </ul>
+    "#
+    )
 }
@@ -1,9 +1,21 @@
+use anyhow::Context;
 use axum::{http::StatusCode, response::IntoResponse};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
 
-use crate::error::error_chain_fmt;
+use crate::{
+    domain::AppUser,
+    entities::{account, prelude::*},
+    error::{error_chain_fmt, TenantMapError},
+    routes::{get_db_from_host, AppState},
+};
 
+pub mod delete;
+pub mod edit;
 pub mod get;
+pub mod poll;
 pub mod post;
+pub mod publish;
+pub mod read;
 
 #[derive(thiserror::Error)]
 pub enum ContentError {
@@ -11,6 +23,8 @@ pub enum ContentError {
     UnexpectedError(#[from] anyhow::Error),
     #[error("{0}")]
     ValidationError(String),
+    #[error("{0}")]
+    NotFound(String),
 }
 
 impl IntoResponse for ContentError {
@@ -24,6 +38,10 @@ impl IntoResponse for ContentError {
                 tracing::info!("validation error {s:?}");
                 (StatusCode::BAD_REQUEST, s).into_response()
             }
+            Self::NotFound(s) => {
+                tracing::info!("content not found: {s:?}");
+                (StatusCode::NOT_FOUND, s).into_response()
+            }
         }
     }
 }
@@ -39,3 +57,26 @@ impl std::fmt::Debug for ContentError {
         error_chain_fmt(self, f)
     }
 }
+
+async fn tenant_db(host: &str, state: &AppState) -> Result<DatabaseConnection, ContentError> {
+    get_db_from_host(host, state).await.map_err(|e| match e {
+        TenantMapError::NotFound(s) => ContentError::ValidationError(s),
+        TenantMapError::UnexpectedError(s) => ContentError::UnexpectedError(anyhow::anyhow!(s)),
+    })
+}
+
+async fn current_account(
+    user: &AppUser,
+    conn: &DatabaseConnection,
+) -> Result<account::Model, ContentError> {
+    Account::find()
+        .filter(account::Column::UserId.eq(user.id.unwrap_or_default()))
+        .one(conn)
+        .await
+        .context("Unable to retrieve account associated with current user")?
+        .ok_or_else(|| {
+            ContentError::UnexpectedError(anyhow::anyhow!(
+                "There is no account associated with current user"
+            ))
+        })
+}
@@ -0,0 +1,218 @@
+use anyhow::Context;
+use axum::{
+    extract::{Host, Path, State},
+    Extension, Json,
+};
+use sea_orm::{DatabaseConnection, EntityTrait, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    activitypub::{activity::Activity, actor_url, format_timestamp},
+    domain::AppUser,
+    entities::{content, prelude::*, remote_post},
+    federation::{fetch::Fetcher, instance_signer, queue, remote},
+    polls::{self, Poll, PollError},
+    routes::{tenant_base_url, AppState},
+};
+
+use super::{current_account, tenant_db, ContentError};
+
+#[derive(Debug, Deserialize)]
+pub struct VoteData {
+    /// The positions of the chosen options, starting at 0
+    pub choices: Vec<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PollView {
+    pub id: i64,
+    pub multiple: bool,
+    pub expires_at: Option<String>,
+    pub closed: bool,
+    pub voters_count: i32,
+    pub options: Vec<OptionView>,
+    /// The options the current user voted for
+    pub own_votes: Vec<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OptionView {
+    pub title: String,
+    pub votes_count: i32,
+}
+
+/// The post a poll is attached to.
+enum PolledPost {
+    Local(content::Model),
+    Remote(remote_post::Model),
+}
+
+/// A poll of a local or remote post, with its current counts.
+#[tracing::instrument(
+    name = "Get poll",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn show(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<PollView>, ContentError> {
+    let hst = host.to_string();
+    let conn = tenant_db(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+    let account = current_account(&user, &conn).await?;
+    let (poll, _) = find_poll(&conn, id).await?;
+
+    let voter = actor_url(&base_url, account.username.as_deref().unwrap_or_default());
+    Ok(Json(view(&conn, poll, &voter).await?))
+}
+
+/// Vote in a poll. Votes in remote polls are sent to the author of the poll,
+/// one `Create` of a `Note` naming the option per chosen option.
+#[tracing::instrument(
+    name = "Vote in poll",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn vote(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(body): Json<VoteData>,
+) -> Result<Json<PollView>, ContentError> {
+    let hst = host.to_string();
+    let conn = tenant_db(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+    let account = current_account(&user, &conn).await?;
+    let (poll, post) = find_poll(&conn, id).await?;
+    let voter = actor_url(&base_url, account.username.as_deref().unwrap_or_default());
+
+    // The inbox of the author of a remote poll, found before anything is stored
+    let remote_author = match &post {
+        PolledPost::Local(post) if post.publisher_id == account.id => {
+            return Err(ContentError::ValidationError(
+                "you cannot vote in your own poll".to_string(),
+            ));
+        }
+        PolledPost::Local(_) => None,
+        PolledPost::Remote(post) => {
+            let signer = instance_signer(&conn, &base_url, &state.global_config.server.secret_key)
+                .await
+                .context("failed to load the instance key")?;
+            let fetcher = Fetcher::new(&state.federation).signed_by(signer.as_ref());
+            let author = remote::actor(&fetcher, &conn, &post.actor_id)
+                .await
+                .context("failed to fetch the author of the poll")?;
+            Some((post.object_id.clone(), author))
+        }
+    };
+
+    let txn = conn.begin().await.context("failed to start transaction")?;
+    let voted = polls::vote(&txn, &poll, &voter, Some(account.id), &body.choices)
+        .await
+        .map_err(|e| match e {
+            PollError::Invalid(s) => ContentError::ValidationError(s),
+            e => ContentError::UnexpectedError(e.into()),
+        })?;
+    if let Some((question, author)) = remote_author {
+        for option in voted
+            .options
+            .iter()
+            .enumerate()
+            .filter(|(position, _)| body.choices.contains(position))
+            .map(|(_, option)| option)
+        {
+            let activity = vote_activity(&voter, &question, &author.actor_id, &option.name);
+            queue::enqueue(&txn, account.id, &voter, &activity, [author.inbox.clone()])
+                .await
+                .context("failed to queue the vote")?;
+        }
+    }
+    txn.commit().await.context("failed to vote")?;
+
+    Ok(Json(view(&conn, voted, &voter).await?))
+}
+
+/// The `Create` of the vote of `voter` for the option `name` of the remote
+/// `Question` `question` by `author`.
+fn vote_activity(voter: &str, question: &str, author: &str, name: &str) -> Activity {
+    let id = format!("{}#votes/{}", voter, Uuid::new_v4());
+    let mut create = Activity::new(
+        "Create",
+        format!("{}/activity", id),
+        voter.to_string(),
+        serde_json::json!({
+            "id": id,
+            "type": "Note",
+            "name": name,
+            "attributedTo": voter,
+            "inReplyTo": question,
+            "to": [author],
+        }),
+    );
+    create.to = vec![author.to_string()];
+
+    create
+}
+
+/// The poll `id`, if the post it belongs to may be seen.
+async fn find_poll(conn: &DatabaseConnection, id: i64) -> Result<(Poll, PolledPost), ContentError> {
+    let not_found = || ContentError::NotFound(format!("no such poll: {}", id));
+    let poll = polls::find(conn, id)
+        .await
+        .context("failed to look up poll")?
+        .ok_or_else(not_found)?;
+
+    let post = match (poll.poll.content_id, poll.poll.remote_post_id) {
+        (Some(content_id), _) => Content::find_by_id(content_id)
+            .one(conn)
+            .await
+            .context("failed to look up post")?
+            .filter(|post| post.published && post.deleted_at.is_none())
+            .map(PolledPost::Local),
+        (None, Some(remote_post_id)) => RemotePost::find_by_id(remote_post_id)
+            .one(conn)
+            .await
+            .context("failed to look up post")?
+            .filter(|post| post.deleted_at.is_none())
+            .map(PolledPost::Remote),
+        (None, None) => None,
+    };
+
+    Ok((poll, post.ok_or_else(not_found)?))
+}
+
+async fn view(
+    conn: &DatabaseConnection,
+    poll: Poll,
+    voter: &str,
+) -> Result<PollView, ContentError> {
+    let own_votes = polls::choices_of(conn, &poll, voter)
+        .await
+        .context("failed to look up votes")?;
+
+    Ok(PollView {
+        id: poll.poll.id,
+        multiple: poll.poll.multiple,
+        expires_at: poll.poll.expires_at.map(format_timestamp),
+        closed: poll.is_closed(chrono::Utc::now().naive_utc()),
+        voters_count: poll.poll.voters_count,
+        options: poll
+            .options
+            .into_iter()
+            .map(|option| OptionView {
+                title: option.name,
+                votes_count: option.votes_count,
+            })
+            .collect(),
+        own_votes,
+    })
+}
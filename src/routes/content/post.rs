@@ -4,20 +4,29 @@ use axum::{
     response::Redirect,
     Extension, Form, Json,
 };
-use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set,
+    TransactionTrait,
+};
 use serde::Deserialize;
+use std::time::Duration;
 use uuid::Uuid;
 
 use crate::{
-    domain::AppUser,
-    entities::{account, content, prelude::*},
+    domain::{AppUser, NewPoll, Visibility},
+    entities::{self, account, content, prelude::*},
     error::TenantMapError,
-    routes::{get_db_from_host, AppState},
+    federation::{fetch::Fetcher, instance_signer, mention, queue},
+    polls,
+    routes::{get_db_from_host, tenant_base_url, AppState},
+    tags,
 };
 
 use super::ContentError;
 
 const MAX_POST_CHARS: usize = 500;
+const MAX_CW_CHARS: usize = 100;
 
 #[derive(Debug, Deserialize)]
 pub struct BodyData {
@@ -27,6 +36,75 @@ pub struct BodyData {
 #[derive(Debug, Deserialize)]
 pub struct NewPost {
     text: String,
+    /// The content warning shown in place of the text until readers expand it
+    #[serde(default)]
+    cw: Option<String>,
+    #[serde(default)]
+    visibility: Option<String>,
+    #[serde(default)]
+    poll: Option<PollData>,
+    /// Keep the post as a draft instead of publishing it
+    #[serde(default)]
+    draft: bool,
+    /// When to publish the post, as an RFC 3339 timestamp in the future
+    #[serde(default)]
+    scheduled_at: Option<String>,
+}
+
+/// When a new post is published.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Publication {
+    Now,
+    /// Not until its author publishes it
+    Draft,
+    /// By the scheduler, once the time has come
+    At(NaiveDateTime),
+}
+
+impl Publication {
+    fn parse(draft: bool, scheduled_at: Option<&str>) -> Result<Self, ContentError> {
+        match (draft, scheduled_at.filter(|at| !at.trim().is_empty())) {
+            (true, Some(_)) => Err(ContentError::ValidationError(
+                "a post is either a draft or scheduled".to_string(),
+            )),
+            (true, None) => Ok(Self::Draft),
+            (false, Some(at)) => parse_schedule(at).map(Self::At),
+            (false, None) => Ok(Self::Now),
+        }
+    }
+}
+
+/// Parse the time a post is scheduled for, either an RFC 3339 timestamp or
+/// the value of a `datetime-local` input, which is taken as UTC. It must be
+/// in the future.
+pub(super) fn parse_schedule(at: &str) -> Result<NaiveDateTime, ContentError> {
+    let at = at.trim();
+    let at = DateTime::parse_from_rfc3339(at)
+        .map(|at| at.with_timezone(&Utc).naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(at, "%Y-%m-%dT%H:%M"))
+        .or_else(|_| NaiveDateTime::parse_from_str(at, "%Y-%m-%dT%H:%M:%S"))
+        .map_err(|_| ContentError::ValidationError(format!("invalid time: {}", at)))?;
+    if at <= Utc::now().naive_utc() {
+        return Err(ContentError::ValidationError(
+            "a post can only be scheduled in the future".to_string(),
+        ));
+    }
+
+    Ok(at)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PollData {
+    options: Vec<String>,
+    #[serde(default)]
+    multiple: bool,
+    /// Seconds until the poll closes
+    #[serde(default = "default_poll_expiry")]
+    expires_in: u64,
+}
+
+fn default_poll_expiry() -> u64 {
+    24 * 60 * 60
 }
 
 #[tracing::instrument(
@@ -51,8 +129,24 @@ pub async fn create(
     })?;
 
     let account_id = process_content(&user, &body.content.text, &conn).await?;
+    let visibility = parse_visibility(body.content.visibility.as_deref())?;
+    let poll = body
+        .content
+        .poll
+        .map(|p| NewPoll::parse(p.options, p.multiple, Duration::from_secs(p.expires_in)))
+        .transpose()?;
+    let publication = Publication::parse(body.content.draft, body.content.scheduled_at.as_deref())?;
+    let cw = parse_cw(body.content.cw)?;
 
-    post_content(account_id, body.content.text, &conn).await?;
+    let base_url = tenant_base_url(&hst, &state);
+    let post = Composed {
+        text: body.content.text,
+        cw,
+        visibility,
+        poll,
+        publication,
+    };
+    post_content(account_id, post, &state, &base_url, &conn).await?;
 
     Ok(())
 }
@@ -60,6 +154,46 @@ pub async fn create(
 #[derive(Debug, Deserialize)]
 pub struct FormData {
     content: String,
+    #[serde(default)]
+    cw: Option<String>,
+    #[serde(default)]
+    visibility: Option<String>,
+    /// Poll options, one per line. No poll is attached if there are none.
+    #[serde(default)]
+    poll_options: String,
+    #[serde(default)]
+    poll_multiple: Option<String>,
+    #[serde(default)]
+    poll_expires_in: Option<u64>,
+    /// When to publish the post. It is published right away if empty.
+    #[serde(default)]
+    scheduled_at: Option<String>,
+    /// Set by the button saving the post as a draft
+    #[serde(default)]
+    draft: Option<String>,
+}
+
+impl FormData {
+    fn poll(&self) -> Result<Option<NewPoll>, String> {
+        let options: Vec<String> = self
+            .poll_options
+            .lines()
+            .map(str::trim)
+            .filter(|o| !o.is_empty())
+            .map(str::to_string)
+            .collect();
+        if options.is_empty() {
+            return Ok(None);
+        }
+        let expires_in = self.poll_expires_in.unwrap_or_else(default_poll_expiry);
+
+        NewPoll::parse(
+            options,
+            self.poll_multiple.is_some(),
+            Duration::from_secs(expires_in),
+        )
+        .map(Some)
+    }
 }
 
 #[tracing::instrument(
@@ -83,16 +217,31 @@ pub async fn new(
     })?;
 
     let account_id = process_content(&user, &body.content, &conn).await?;
+    let visibility = parse_visibility(body.visibility.as_deref())?;
+    let poll = body.poll()?;
+    let publication = Publication::parse(body.draft.is_some(), body.scheduled_at.as_deref())?;
+    let cw = parse_cw(body.cw)?;
 
-    post_content(account_id, body.content, &conn).await?;
+    let base_url = tenant_base_url(&hst, &state);
+    let post = Composed {
+        text: body.content,
+        cw,
+        visibility,
+        poll,
+        publication,
+    };
+    post_content(account_id, post, &state, &base_url, &conn).await?;
 
-    Ok(Redirect::to("/home"))
+    match publication {
+        Publication::Now => Ok(Redirect::to("/home")),
+        Publication::Draft | Publication::At(_) => Ok(Redirect::to("/content/form")),
+    }
 }
 
 #[tracing::instrument(name = "Process content", skip(content, conn))]
-async fn process_content(
+pub(super) async fn process_content(
     user: &AppUser,
-    content: &String,
+    content: &str,
     conn: &DatabaseConnection,
 ) -> Result<i64, ContentError> {
     let account = Account::find()
@@ -101,6 +250,11 @@ async fn process_content(
         .await
         .context("Unable to retrieve account associated with current user")?;
     let account_id = match account {
+        Some(model) if model.moved_to.is_some() => {
+            return Err(ContentError::ValidationError(
+                "this account has moved".to_string(),
+            ))
+        }
         Some(model) => model.id,
         None => {
             return Err(ContentError::UnexpectedError(anyhow!(
@@ -119,24 +273,120 @@ async fn process_content(
     Ok(account_id)
 }
 
+/// The content warning of a post, if it is not blank. Surrounding whitespace
+/// is dropped.
+pub(super) fn parse_cw(cw: Option<String>) -> Result<Option<String>, ContentError> {
+    let Some(cw) = cw
+        .map(|cw| cw.trim().to_string())
+        .filter(|cw| !cw.is_empty())
+    else {
+        return Ok(None);
+    };
+    if cw.chars().count() > MAX_CW_CHARS {
+        return Err(ContentError::ValidationError(
+            "content warning too long".to_string(),
+        ));
+    }
+
+    Ok(Some(cw))
+}
+
+fn parse_visibility(visibility: Option<&str>) -> Result<Visibility, ContentError> {
+    match visibility {
+        Some(v) if !v.is_empty() => Visibility::try_from(v).map_err(ContentError::ValidationError),
+        _ => Ok(Visibility::default()),
+    }
+}
+
+/// A new post that passed validation.
+struct Composed {
+    text: String,
+    cw: Option<String>,
+    visibility: Visibility,
+    poll: Option<NewPoll>,
+    publication: Publication,
+}
+
+/// Store a new post along with its mentions, hashtags and poll and, if it is
+/// published right away, queue its delivery in the same transaction, so a
+/// post is never published without its audience hearing about it. Drafts and
+/// scheduled posts are delivered once they are published. Mentions are
+/// resolved beforehand, as that may take a few requests to remote servers.
 #[tracing::instrument(
     name = "Post content"
-    skip(content, conn),
+    skip(post, state, conn),
 )]
 async fn post_content(
     account_id: i64,
-    content: String,
+    post: Composed,
+    state: &AppState,
+    base_url: &str,
     conn: &DatabaseConnection,
 ) -> Result<(), ContentError> {
+    let Composed {
+        text,
+        cw,
+        visibility,
+        poll,
+        publication,
+    } = post;
+    let mentions = resolve_mentions(state, base_url, conn, &text).await?;
+
     let data = content::ActiveModel {
         publisher_id: Set(account_id),
-        body: Set(Some(content)),
+        cw: Set(cw),
+        body: Set(Some(text)),
+        published: Set(publication == Publication::Now),
+        published_at: Set((publication == Publication::Now).then(|| Utc::now().naive_utc())),
+        scheduled_at: Set(match publication {
+            Publication::At(at) => Some(at),
+            Publication::Now | Publication::Draft => None,
+        }),
+        visibility: Set(visibility.to_string()),
+        base_url: Set(Some(base_url.to_string())),
         ..Default::default()
     };
-    let _ = Content::insert(data)
-        .exec(conn)
+    let txn = conn.begin().await.context("failed to start transaction")?;
+    let post = data
+        .insert(&txn)
         .await
         .context("failed to post new content")?;
+    mention::store(&txn, post.id, mentions)
+        .await
+        .context("failed to store mentions")?;
+    tags::tag_post(&txn, post.id, post.body.as_deref().unwrap_or_default())
+        .await
+        .context("failed to store hashtags")?;
+    if let Some(poll) = &poll {
+        polls::create(&txn, post.id, poll)
+            .await
+            .context("failed to store poll")?;
+    }
+    if post.published {
+        queue::enqueue_post(&txn, base_url, &post)
+            .await
+            .context("failed to queue delivery of new content")?;
+    }
+    txn.commit().await.context("failed to post new content")?;
 
     Ok(())
 }
+
+/// Resolve the mentions in `text`, fetching remote accounts as the instance
+/// actor.
+pub(super) async fn resolve_mentions(
+    state: &AppState,
+    base_url: &str,
+    conn: &DatabaseConnection,
+    text: &str,
+) -> Result<Vec<entities::mention::ActiveModel>, ContentError> {
+    let signer = instance_signer(conn, base_url, &state.global_config.server.secret_key)
+        .await
+        .context("failed to load the instance key")?;
+    let fetcher = Fetcher::new(&state.federation).signed_by(signer.as_ref());
+    let mentions = mention::resolve(&fetcher, conn, base_url, text)
+        .await
+        .context("failed to resolve mentions")?;
+
+    Ok(mentions)
+}
@@ -0,0 +1,108 @@
+use axum::{
+    body::Bytes,
+    extract::{Host, Path, State},
+    response::Redirect,
+    Extension, Form,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    db,
+    domain::AppUser,
+    routes::{tenant_base_url, AppState},
+};
+
+use super::{current_account, post::parse_schedule, tenant_db, ContentError};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PublishData {
+    /// When to publish the post, as an RFC 3339 timestamp in the future. The
+    /// post is published right away if it is missing or blank.
+    #[serde(default)]
+    scheduled_at: Option<String>,
+}
+
+/// Publish one of the current user's drafts or scheduled posts, right away or
+/// at the time given, which reschedules a scheduled post. Only an empty body
+/// publishes right away: a body that doesn't parse is rejected rather than
+/// taken as a request to publish now.
+#[tracing::instrument(
+    name = "Publish a microblog",
+    skip(state, body),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn publish(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    body: Bytes,
+) -> Result<(), ContentError> {
+    let body = if body.is_empty() {
+        PublishData::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| ContentError::ValidationError(e.to_string()))?
+    };
+    publish_post(&user, &host, &state, id, body).await
+}
+
+/// Publish one of the current user's drafts or scheduled posts from the
+/// compose form.
+#[tracing::instrument(
+    name = "Publish a microblog form",
+    skip(state, body),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn publish_form(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(body): Form<PublishData>,
+) -> Result<Redirect, ContentError> {
+    publish_post(&user, &host, &state, id, body).await?;
+
+    Ok(Redirect::to("/content/form"))
+}
+
+async fn publish_post(
+    user: &AppUser,
+    host: &str,
+    state: &AppState,
+    id: i64,
+    body: PublishData,
+) -> Result<(), ContentError> {
+    let conn = tenant_db(host, state).await?;
+    let account = current_account(user, &conn).await?;
+    let post = db::content::find_by_publisher(&conn, account.id, id)
+        .await
+        .map_err(|e| ContentError::UnexpectedError(anyhow::anyhow!(e)))?
+        .filter(|post| post.deleted_at.is_none())
+        .ok_or_else(|| ContentError::NotFound(format!("no such post: {}", id)))?;
+    if post.published {
+        return Err(ContentError::ValidationError(
+            "the post is already published".to_string(),
+        ));
+    }
+
+    let base_url = tenant_base_url(host, state);
+    let published = match body.scheduled_at.filter(|at| !at.trim().is_empty()) {
+        Some(at) => {
+            db::content::schedule(&conn, &base_url, post.id, Some(parse_schedule(&at)?)).await
+        }
+        None => db::content::publish(&conn, &base_url, post.id).await,
+    }
+    .map_err(|e| ContentError::UnexpectedError(anyhow::anyhow!(e)))?;
+    if !published {
+        return Err(ContentError::ValidationError(
+            "the post is already published".to_string(),
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,313 @@
+use anyhow::Context;
+use axum::{
+    extract::{Host, Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use sea_orm::{DatabaseConnection, EntityTrait};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    activitypub::{format_timestamp, status_url},
+    db::{self, content::PageBounds},
+    domain::{AppUser, Visibility},
+    entities::{account, content, prelude::*},
+    orm,
+    revisions::{self, Change, Version},
+    routes::{tenant_base_url, AppState},
+};
+
+use super::{current_account, tenant_db, ContentError};
+
+const DEFAULT_LIMIT: u64 = 20;
+const MAX_LIMIT: u64 = 40;
+
+#[derive(Debug, Deserialize)]
+pub struct PageParameters {
+    limit: Option<u64>,
+    max_id: Option<i64>,
+    since_id: Option<i64>,
+    min_id: Option<i64>,
+}
+
+impl PageParameters {
+    fn bounds(&self) -> PageBounds {
+        PageBounds {
+            max_id: self.max_id,
+            since_id: self.since_id,
+            min_id: self.min_id,
+        }
+    }
+
+    fn limit(&self) -> u64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PostView {
+    pub id: i64,
+    /// The handle of the author
+    pub account: String,
+    pub cw: Option<String>,
+    pub text: String,
+    pub visibility: String,
+    pub published: bool,
+    pub published_at: Option<String>,
+    pub updated_at: String,
+    /// When the published post was last edited
+    pub edited_at: Option<String>,
+    /// When a scheduled post is going to be published
+    pub scheduled_at: Option<String>,
+    /// The ActivityPub id of the post, once it is published
+    pub url: Option<String>,
+}
+
+impl PostView {
+    fn new(base_url: &str, handle: &str, post: content::Model) -> Self {
+        let published = post.published;
+        Self {
+            id: post.id,
+            account: handle.to_string(),
+            cw: post.cw,
+            text: post.body.unwrap_or_default(),
+            visibility: post.visibility,
+            published,
+            published_at: post.published_at.map(format_timestamp),
+            updated_at: format_timestamp(post.updated_at),
+            edited_at: post.edited_at.map(format_timestamp),
+            scheduled_at: post.scheduled_at.map(format_timestamp),
+            url: published.then(|| status_url(base_url, handle, post.id)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionView {
+    pub cw: Option<String>,
+    pub text: String,
+    pub written_at: String,
+    /// The changes to the text from the previous version
+    pub diff: Vec<Change>,
+}
+
+impl From<Version> for VersionView {
+    fn from(version: Version) -> Self {
+        Self {
+            cw: version.cw,
+            text: version.body,
+            written_at: format_timestamp(version.written_at),
+            diff: version.diff,
+        }
+    }
+}
+
+/// A single post. Authors see all their posts, drafts included; others only
+/// see published posts that anyone may see.
+#[tracing::instrument(
+    name = "Get a microblog",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn show(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<PostView>, ContentError> {
+    let hst = host.to_string();
+    let conn = tenant_db(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+    let account = current_account(&user, &conn).await?;
+
+    let post = visible_post(&conn, &account, id).await?;
+    let handle = author_handle(&conn, post.publisher_id).await?;
+    Ok(Json(PostView::new(&base_url, &handle, post)))
+}
+
+/// All versions of a post, oldest first, each with the changes from the
+/// version before it. The post must be one the current user may see.
+#[tracing::instrument(
+    name = "Get the history of a microblog",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn history(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<VersionView>>, ContentError> {
+    let hst = host.to_string();
+    let conn = tenant_db(&hst, &state).await?;
+    let account = current_account(&user, &conn).await?;
+    let post = visible_post(&conn, &account, id).await?;
+
+    let versions = revisions::local_history(&conn, &post)
+        .await
+        .context("failed to look up revisions")?;
+    Ok(Json(versions.into_iter().map(VersionView::from).collect()))
+}
+
+/// The public posts of a local account, newest first.
+#[tracing::instrument(
+    name = "List posts of an account",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn account_posts(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(handle): Path<String>,
+    Query(params): Query<PageParameters>,
+) -> Result<Response, ContentError> {
+    let hst = host.to_string();
+    let conn = tenant_db(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+
+    let (account, _) = orm::get_account_by_handle(&handle.to_lowercase(), &conn)
+        .await
+        .context("failed to look up account")?
+        .ok_or_else(|| ContentError::NotFound(format!("no such account: {}", handle)))?;
+    let handle = account.username.unwrap_or_default();
+    let posts = db::content::public_page(&conn, account.id, params.bounds(), params.limit())
+        .await
+        .map_err(|e| ContentError::UnexpectedError(anyhow::anyhow!(e)))?;
+
+    let url = format!("{}/content/accounts/{}", base_url, handle);
+    Ok(page(&base_url, &handle, &url, posts, params.limit()))
+}
+
+/// The current user's drafts, the posts that are neither published nor
+/// scheduled, newest first.
+#[tracing::instrument(
+    name = "List drafts",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn drafts(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Query(params): Query<PageParameters>,
+) -> Result<Response, ContentError> {
+    let hst = host.to_string();
+    let conn = tenant_db(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+    let account = current_account(&user, &conn).await?;
+
+    let handle = account.username.unwrap_or_default();
+    let posts = db::content::drafts_page(&conn, account.id, params.bounds(), params.limit())
+        .await
+        .map_err(|e| ContentError::UnexpectedError(anyhow::anyhow!(e)))?;
+
+    let url = format!("{}/content/drafts", base_url);
+    Ok(page(&base_url, &handle, &url, posts, params.limit()))
+}
+
+/// The current user's scheduled posts, newest first.
+#[tracing::instrument(
+    name = "List scheduled posts",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn scheduled(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Query(params): Query<PageParameters>,
+) -> Result<Response, ContentError> {
+    let hst = host.to_string();
+    let conn = tenant_db(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+    let account = current_account(&user, &conn).await?;
+
+    let handle = account.username.unwrap_or_default();
+    let posts = db::content::scheduled_page(&conn, account.id, params.bounds(), params.limit())
+        .await
+        .map_err(|e| ContentError::UnexpectedError(anyhow::anyhow!(e)))?;
+
+    let url = format!("{}/content/scheduled", base_url);
+    Ok(page(&base_url, &handle, &url, posts, params.limit()))
+}
+
+/// The post `id`, if `account` may see it. Authors see all their posts that
+/// are not deleted, others only published posts that anyone may see.
+async fn visible_post(
+    conn: &DatabaseConnection,
+    account: &account::Model,
+    id: i64,
+) -> Result<content::Model, ContentError> {
+    let not_found = || ContentError::NotFound(format!("no such post: {}", id));
+    let post = db::content::browse(conn, id)
+        .await
+        .map_err(|e| ContentError::UnexpectedError(anyhow::anyhow!(e)))?
+        .filter(|post| post.deleted_at.is_none())
+        .ok_or_else(not_found)?;
+    if post.publisher_id != account.id && !is_public(&post) {
+        return Err(not_found());
+    }
+
+    Ok(post)
+}
+
+fn is_public(post: &content::Model) -> bool {
+    let visibility = Visibility::try_from(post.visibility.as_str()).unwrap_or_default();
+    post.published && matches!(visibility, Visibility::Public | Visibility::Unlisted)
+}
+
+async fn author_handle(conn: &DatabaseConnection, account_id: i64) -> Result<String, ContentError> {
+    let account = Account::find_by_id(account_id)
+        .one(conn)
+        .await
+        .context("failed to look up the author")?
+        .ok_or_else(|| {
+            ContentError::UnexpectedError(anyhow::anyhow!("the author of the post is missing"))
+        })?;
+
+    Ok(account.username.unwrap_or_default())
+}
+
+/// A page of posts of `handle`, listed at `url`, with links to the pages
+/// before and after it.
+fn page(
+    base_url: &str,
+    handle: &str,
+    url: &str,
+    posts: Vec<content::Model>,
+    limit: u64,
+) -> Response {
+    let link = link_header(url, &posts, limit);
+    let posts: Vec<PostView> = posts
+        .into_iter()
+        .map(|post| PostView::new(base_url, handle, post))
+        .collect();
+
+    match link {
+        Some(link) => ([(header::LINK, link)], Json(posts)).into_response(),
+        None => Json(posts).into_response(),
+    }
+}
+
+/// The RFC 8288 `Link` header of a page of posts, newest first. Empty pages
+/// have no links, as there is no id to page from.
+fn link_header(url: &str, posts: &[content::Model], limit: u64) -> Option<String> {
+    let (newest, oldest) = (posts.first()?, posts.last()?);
+
+    Some(format!(
+        r#"<{url}?limit={limit}&max_id={}>; rel="next", <{url}?limit={limit}&min_id={}>; rel="prev""#,
+        oldest.id, newest.id
+    ))
+}
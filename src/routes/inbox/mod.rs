@@ -0,0 +1,103 @@
+use axum::{
+    body::Bytes,
+    extract::{Host, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+};
+use uuid::Uuid;
+
+use crate::{
+    federation::{
+        inbox::{self, InboxContext, InboxError},
+        inbox_queue, VerifiedSignature,
+    },
+    orm,
+    routes::{get_db_from_host, tenant_base_url, AppState},
+};
+
+/// The shared inbox. Deliveries only reach this handler once their HTTP
+/// signature has been verified. Activities are applied later by the inbox
+/// workers.
+#[tracing::instrument(
+    name = "Shared inbox",
+    skip(state, signature, body),
+    fields(
+        request_id = %Uuid::new_v4(),
+        actor = %signature.actor_id,
+    )
+)]
+pub async fn shared_inbox(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Extension(signature): Extension<VerifiedSignature>,
+    body: Bytes,
+) -> Result<impl IntoResponse, InboxError> {
+    let ctx = InboxContext {
+        state: &state,
+        db: get_db_from_host(&host, &state).await?,
+        base_url: tenant_base_url(&host, &state),
+    };
+    receive(&ctx, &body, &signature).await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// The inbox of a single account.
+#[tracing::instrument(
+    name = "Account inbox",
+    skip(state, signature, body),
+    fields(
+        request_id = %Uuid::new_v4(),
+        actor = %signature.actor_id,
+    )
+)]
+pub async fn account_inbox(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(handle): Path<String>,
+    Extension(signature): Extension<VerifiedSignature>,
+    body: Bytes,
+) -> Result<impl IntoResponse, InboxError> {
+    let ctx = InboxContext {
+        state: &state,
+        db: get_db_from_host(&host, &state).await?,
+        base_url: tenant_base_url(&host, &state),
+    };
+    orm::get_account_by_handle(&handle.to_lowercase(), &ctx.db)
+        .await
+        .map_err(|e| InboxError::UnexpectedError(e.into()))?
+        .ok_or_else(|| InboxError::NotFound(format!("no such account: {}", handle)))?;
+
+    receive(&ctx, &body, &signature).await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Admit the activity in `body` and store it for the inbox workers. An
+/// activity that was already received is not stored again.
+async fn receive(
+    ctx: &InboxContext<'_>,
+    body: &[u8],
+    signature: &VerifiedSignature,
+) -> Result<(), InboxError> {
+    let activity = inbox::parse_activity(body)?;
+    inbox::admit(ctx, &activity, &signature.actor_id).await?;
+
+    let document = serde_json::from_slice(body)
+        .map_err(|e| InboxError::BadRequest(format!("malformed activity: {}", e)))?;
+    let stored = inbox_queue::enqueue(
+        &ctx.db,
+        &ctx.base_url,
+        &activity.id,
+        &signature.actor_id,
+        document,
+    )
+    .await
+    .map_err(|e| InboxError::UnexpectedError(e.into()))?;
+    if !stored {
+        tracing::debug!("{} was already received", activity.id);
+    }
+
+    Ok(())
+}
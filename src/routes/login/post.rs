@@ -45,7 +45,7 @@ pub async fn login(
         username: form.username,
         password: form.password,
     };
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
 
     let user_id = validate_credentials(credentials, &conn)
         .await
@@ -63,7 +63,7 @@ pub async fn login(
 
     auth.login(&orm_user).await.unwrap();
 
-    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
     Ok(Redirect::to("/home"))
 }
 
@@ -1,28 +1,32 @@
 use async_redis_session::RedisSessionStore;
 use axum::{
     http::StatusCode,
-    middleware::map_response,
+    middleware::{from_fn_with_state, map_response},
     response::{IntoResponse, Redirect, Response},
     routing::{get, post},
     Router,
 };
 use axum_login::AuthLayer;
-use axum_sessions::{SameSite, SessionLayer};
+use axum_sessions::{PersistencePolicy, SameSite, SessionLayer};
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
-use sea_orm::{ColumnTrait, Database, DatabaseConnection, EntityTrait, QueryFilter};
+use sea_orm::{ColumnTrait, Database, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
 use secrecy::ExposeSecret;
 use std::{collections::HashMap, sync::Arc};
 use tokio::sync::RwLock;
 use tower_cookies::{CookieManagerLayer, Key};
 use tower_http::trace::TraceLayer;
 
+pub mod actor;
 pub mod admin;
 pub mod content;
 pub mod health_check;
 pub mod home;
+pub mod inbox;
 pub mod index;
 pub mod login;
+pub mod tags;
 pub mod user;
+pub mod well_known;
 
 use admin::dashboard::admin_dashboard;
 use health_check::health_check;
@@ -33,26 +37,34 @@ use user::change_password::post::change;
 use user::logout::logout;
 
 use crate::{
+    activitypub::format_timestamp,
     cookies::FLASH_KEY,
-    domain::UserRole,
+    db,
+    domain::{AppUser, UserRole},
     entities::{instance, prelude::*},
     error::TenantMapError,
+    federation::{
+        authorize_fetch, authorize_page_fetch, inbox_queue, queue, require_signature,
+        FederationState,
+    },
+    polls::{self, Poll},
     session_state::{RequireAuth, SeaOrmStore},
     settings::Settings,
 };
 
 #[derive(Clone, Debug)]
 pub struct TenantData {
-    domain: String,
-    db: DatabaseConnection,
+    pub domain: String,
+    pub db: DatabaseConnection,
 }
 
 #[derive(Clone, Debug)]
 pub struct AppState {
     domain: String,
     rhodos_db: Option<DatabaseConnection>,
-    global_config: Settings,
+    pub(crate) global_config: Settings,
     host_db_map: Arc<RwLock<HashMap<String, TenantData>>>,
+    pub(crate) federation: FederationState,
 }
 
 pub async fn create_routes(
@@ -80,21 +92,69 @@ pub async fn create_routes(
         .with_session_ttl(Some(std::time::Duration::from_secs(60 * 60 * 24 * 7)))
         .with_secure(false);
 
+    // Public pages know about readers who are logged in, without starting a
+    // session for everyone else.
+    let reader_auth_layer = auth_layer.clone();
+    let reader_session_layer = session_layer
+        .clone()
+        .with_persistence_policy(PersistencePolicy::ExistingOnly);
+
     let shared_state = AppState {
         domain: global_config.server.domain.clone(),
         rhodos_db: Some(db),
         global_config: global_config.clone(),
         host_db_map: Arc::new(RwLock::new(HashMap::new())),
+        federation: FederationState::new(global_config.server.allow_private_addresses)
+            .map_err(|e| e.to_string())?,
     };
+    queue::spawn_worker(shared_state.clone());
+    inbox_queue::spawn_workers(shared_state.clone());
+    polls::spawn_closer(shared_state.clone());
+    db::content::spawn_purger(shared_state.clone());
+    db::content::spawn_scheduler(shared_state.clone());
 
     let router = Router::new()
         .route("/home", get(home))
         .route("/content", post(content::post::create))
+        .route("/content/:id", get(content::read::show))
+        .route("/content/:id/edit", post(content::edit::edit))
+        .route("/content/:id/delete", post(content::delete::delete))
+        .route("/content/:id/history", get(content::read::history))
+        .route("/content/:id/publish", post(content::publish::publish))
+        .route("/content/drafts", get(content::read::drafts))
+        .route("/content/scheduled", get(content::read::scheduled))
+        .route(
+            "/content/accounts/:handle",
+            get(content::read::account_posts),
+        )
+        .route("/content/polls/:id", get(content::poll::show))
+        .route("/content/polls/:id/votes", post(content::poll::vote))
         .route(
             "/content/form",
             get(content::get::form).post(content::post::new),
         )
+        .route(
+            "/content/form/:id/publish",
+            post(content::publish::publish_form),
+        )
         .route("/user/change-password", get(password_reset).post(change))
+        .route("/user/keys/rotate", post(user::keys::rotate))
+        .route("/user/follow-requests", get(user::follow_requests::list))
+        .route(
+            "/user/follow-requests/:id/accept",
+            post(user::follow_requests::accept),
+        )
+        .route(
+            "/user/follow-requests/:id/reject",
+            post(user::follow_requests::reject),
+        )
+        .route("/user/aliases", post(user::moving::add_alias))
+        .route("/user/aliases/remove", post(user::moving::remove_alias))
+        .route("/user/move", post(user::moving::move_account))
+        .route(
+            "/user/profile",
+            get(user::profile::form).post(user::profile::update),
+        )
         .layer(RequireAuth::login_with_role(UserRole::User..))
         .route(
             "/login",
@@ -105,6 +165,7 @@ pub async fn create_routes(
             "/admin/dashboard",
             get(admin_dashboard).route_layer(RequireAuth::login_with_role(UserRole::SuperAdmin..)),
         )
+        .merge(admin_routes())
         .layer(auth_layer)
         .layer(map_response(redirect_to_login))
         .layer(session_layer)
@@ -113,12 +174,100 @@ pub async fn create_routes(
         .route("/health_check", get(health_check))
         .route("/user", post(user::create::create))
         .route("/user/confirm", get(user::confirm::confirm))
+        .route("/actor", get(actor::instance::instance_actor))
+        .merge(public_object_routes(
+            shared_state.clone(),
+            reader_auth_layer,
+            reader_session_layer,
+        ))
+        .route(
+            "/inbox",
+            post(inbox::shared_inbox)
+                .route_layer(from_fn_with_state(shared_state.clone(), require_signature)),
+        )
+        .route(
+            "/users/:handle/inbox",
+            post(inbox::account_inbox)
+                .route_layer(from_fn_with_state(shared_state.clone(), require_signature)),
+        )
+        .route(
+            "/.well-known/webfinger",
+            get(well_known::webfinger::webfinger),
+        )
+        .route(
+            "/.well-known/nodeinfo",
+            get(well_known::nodeinfo::nodeinfo_links),
+        )
+        .route("/nodeinfo/2.1", get(well_known::nodeinfo::nodeinfo))
         .layer(TraceLayer::new_for_http())
         .with_state(shared_state);
 
     Ok(router)
 }
 
+/// The actors and objects of a tenant, readable by anyone the tenant
+/// federates with. In authorized fetch mode readers have to sign their
+/// requests, except for the HTML pages.
+fn public_object_routes(
+    state: AppState,
+    auth_layer: AuthLayer<SeaOrmStore, AppUser, UserRole>,
+    session_layer: SessionLayer<RedisSessionStore>,
+) -> Router<AppState> {
+    Router::new()
+        .merge(
+            Router::new()
+                .route("/users/:handle/outbox", get(actor::outbox::outbox))
+                .route("/users/:handle/followers", get(actor::follows::followers))
+                .route("/users/:handle/following", get(actor::follows::following))
+                .route_layer(from_fn_with_state(state.clone(), authorize_fetch)),
+        )
+        .merge(
+            Router::new()
+                .route("/users/:handle", get(actor::get::actor))
+                .route("/users/:handle/statuses/:id", get(actor::status::status))
+                .route("/@:handle/:id", get(actor::status::status))
+                .route("/tags/:name", get(tags::get::tag))
+                .route_layer(from_fn_with_state(state, authorize_page_fetch))
+                .layer(auth_layer)
+                .layer(session_layer),
+        )
+}
+
+/// Tenant administration, for super admins only.
+fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/admin/domain-blocks",
+            get(admin::domain_blocks::list).post(admin::domain_blocks::create),
+        )
+        .route(
+            "/admin/domain-blocks/:id/delete",
+            post(admin::domain_blocks::delete),
+        )
+        .route(
+            "/admin/domain-blocks/import",
+            post(admin::domain_blocks::import),
+        )
+        .route(
+            "/admin/domain-blocks/export",
+            get(admin::domain_blocks::export),
+        )
+        .route("/admin/federation", post(admin::federation::update))
+        .route("/admin/registrations", post(admin::registrations::update))
+        .route(
+            "/admin/relays",
+            get(admin::relays::list).post(admin::relays::create),
+        )
+        .route("/admin/relays/:id/delete", post(admin::relays::delete))
+        .route("/admin/deliveries", get(admin::deliveries::list))
+        .route("/admin/inbox-queue", get(admin::inbox_queue::list))
+        .route(
+            "/admin/inbox-queue/:id/replay",
+            post(admin::inbox_queue::replay),
+        )
+        .route_layer(RequireAuth::login_with_role(UserRole::SuperAdmin..))
+}
+
 async fn redirect_to_login(response: Response) -> impl IntoResponse {
     if response.status() == StatusCode::UNAUTHORIZED {
         Redirect::to("/login").into_response()
@@ -131,6 +280,17 @@ pub async fn get_db_from_host(
     host: &str,
     state: &AppState,
 ) -> Result<DatabaseConnection, TenantMapError> {
+    let res = get_tenant_from_host(host, state).await;
+    match res {
+        Ok(td) => Ok(td.db),
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn get_tenant_from_host(
+    host: &str,
+    state: &AppState,
+) -> Result<TenantData, TenantMapError> {
     let mut split = host.split(':');
     let mut key = "".to_string();
     if let Some(i) = split.next() {
@@ -139,14 +299,53 @@ pub async fn get_db_from_host(
 
     if key == state.domain {
         if let Some(dbconn) = &state.rhodos_db {
-            return Ok(dbconn.clone());
+            return Ok(TenantData {
+                domain: state.domain.clone(),
+                db: dbconn.clone(),
+            });
         }
     }
-    let res = map_get(&key, state).await;
-    match res {
-        Ok(td) => Ok(td.db),
-        Err(e) => Err(e),
+    map_get(&key, state).await
+}
+
+/// All tenants served by this server, starting with the main one. Tenants
+/// whose database cannot be reached are skipped.
+pub(crate) async fn all_tenants(state: &AppState) -> Vec<TenantData> {
+    let Some(db) = state.rhodos_db.clone() else {
+        return vec![];
+    };
+    let instances = match Instance::find().all(&db).await {
+        Ok(instances) => instances,
+        Err(e) => {
+            tracing::error!("failed to list instances: {}", e);
+            vec![]
+        }
+    };
+
+    let mut tenants = vec![TenantData {
+        domain: state.domain.clone(),
+        db,
+    }];
+    for inst in instances.iter().filter(|i| i.domain != state.domain) {
+        match map_get(&inst.domain, state).await {
+            Ok(td) => tenants.push(td),
+            Err(e) => tracing::warn!("skipping tenant {}: {}", inst.domain, e),
+        }
     }
+
+    tenants
+}
+
+/// The externally visible base URL of the tenant serving `host`. Only the
+/// scheme is taken from the configured `server.base_url`; the authority is the
+/// one the request was addressed to.
+pub fn tenant_base_url(host: &str, state: &AppState) -> String {
+    let scheme = match state.global_config.server.base_url.starts_with("https://") {
+        true => "https",
+        false => "http",
+    };
+
+    format!("{}://{}", scheme, host)
 }
 
 async fn map_get(key: &String, state: &AppState) -> Result<TenantData, TenantMapError> {
@@ -174,7 +373,7 @@ async fn map_get(key: &String, state: &AppState) -> Result<TenantData, TenantMap
         .one(&db)
         .await
         .map_err(|e| TenantMapError::NotFound(e.to_string()))?
-        .unwrap();
+        .ok_or_else(|| TenantMapError::NotFound(format!("no tenant for domain {}", key)))?;
     let db_url = make_db_uri(&instance);
     let res = map_set(&instance.domain, &db_url, state).await?;
     assert_eq!(res.domain, instance.domain);
@@ -249,6 +448,84 @@ fn make_db_uri(inst: &instance::Model) -> String {
     res
 }
 
+/// Escape text so that it can be interpolated into an HTML page.
+pub(crate) fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Whether `reader` asked to see posts with their content warnings expanded.
+pub(crate) async fn expands_cws(
+    conn: &DatabaseConnection,
+    reader: Option<&AppUser>,
+) -> Result<bool, DbErr> {
+    let Some(id) = reader.and_then(|reader| reader.id) else {
+        return Ok(false);
+    };
+
+    Ok(User::find_by_id(id)
+        .one(conn)
+        .await?
+        .is_some_and(|user| user.expand_cws))
+}
+
+/// `content`, already HTML, behind the content warning `cw` if there is one.
+/// The warning starts out expanded if `expand` is set.
+pub(crate) fn cw_html(cw: Option<&str>, content: &str, expand: bool) -> String {
+    match cw {
+        Some(cw) => format!(
+            "<details{}>\n            <summary>{}</summary>\n            {}\n        </details>",
+            if expand { " open" } else { "" },
+            escape_html(cw),
+            content
+        ),
+        None => content.to_string(),
+    }
+}
+
+/// The options of `poll` with their counts, as HTML.
+pub(crate) fn poll_html(poll: &Poll) -> String {
+    let options = poll
+        .options
+        .iter()
+        .map(|option| {
+            format!(
+                "<li>{} <data class=\"votes\" value=\"{votes}\">{votes}</data></li>",
+                escape_html(&option.name),
+                votes = option.votes_count,
+            )
+        })
+        .collect::<String>();
+    let (state, at) = match (poll.poll.closed_at, poll.poll.expires_at) {
+        (Some(closed_at), _) => ("Closed", Some(closed_at)),
+        (None, expires_at) => ("Closes", expires_at),
+    };
+    let ends = match at {
+        Some(at) => format!(
+            r#" {} <time datetime="{at}">{at}</time>"#,
+            state,
+            at = format_timestamp(at)
+        ),
+        None => String::new(),
+    };
+
+    format!(
+        r#"<ul class="poll">{}</ul><p>{} voters.{}</p>"#,
+        options, poll.poll.voters_count, ends
+    )
+}
+
 fn generate_random_key(length: usize) -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
@@ -270,6 +547,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn html_special_chars_are_escaped() {
+        assert_eq!(
+            escape_html(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+    }
+
     #[test]
     fn key_does_not_include_invalid_chars() {
         let invalid_chars = vec![
@@ -0,0 +1,267 @@
+use axum::{
+    extract::{Host, Path, Query, State},
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    activitypub::{
+        collection::{OrderedCollection, OrderedCollectionPage},
+        is_http_url,
+        note::Note,
+        status_url, tag_url, wants_activity_json, ACTIVITY_JSON,
+    },
+    entities::{mention::Model as Mention, tag},
+    federation::{mention, policy::DomainPolicies},
+    polls::{self, Poll},
+    routes::{
+        cw_html, escape_html, expands_cws, get_db_from_host, poll_html, tenant_base_url, AppState,
+    },
+    session_state::AuthContext,
+    tags::{self, TaggedEntry, TaggedPost},
+};
+
+use super::TagError;
+
+const PAGE_SIZE: u64 = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct QueryParameters {
+    #[serde(default)]
+    page: bool,
+    max_id: Option<i64>,
+    min_id: Option<i64>,
+}
+
+/// The public posts tagged with a hashtag, as an HTML page or as an
+/// ActivityPub collection of their ids.
+#[tracing::instrument(
+    name = "Get tag",
+    skip(state, headers),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn tag(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query_params): Query<QueryParameters>,
+    auth: AuthContext,
+    headers: HeaderMap,
+) -> Result<Response, TagError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+
+    let tag = tags::find(&conn, &name)
+        .await
+        .map_err(|e| TagError::UnexpectedError(e.into()))?
+        .ok_or_else(|| TagError::NotFound(format!("no such tag: {}", name)))?;
+    let policies = DomainPolicies::load(&conn)
+        .await
+        .map_err(|e| TagError::UnexpectedError(e.into()))?;
+    let id = tag_url(&base_url, &tag.name);
+    let as_activity_json = wants_activity_json(&headers);
+
+    if as_activity_json && !query_params.page {
+        let total = tags::count_public(&conn, tag.id, &policies)
+            .await
+            .map_err(|e| TagError::UnexpectedError(e.into()))?;
+        let collection = OrderedCollection::new(
+            id.clone(),
+            total,
+            format!("{}?page=true", id),
+            Some(format!("{}?page=true&min_id=0", id)),
+        );
+        return Ok(activity_json(collection));
+    }
+
+    let (entries, cursor) = tags::public_page(
+        &conn,
+        tag.id,
+        &policies,
+        query_params.max_id,
+        query_params.min_id,
+        PAGE_SIZE,
+    )
+    .await
+    .map_err(|e| TagError::UnexpectedError(e.into()))?;
+
+    if as_activity_json {
+        let page_id = match (query_params.max_id, query_params.min_id) {
+            (Some(max_id), _) => format!("{}?page=true&max_id={}", id, max_id),
+            (None, Some(min_id)) => format!("{}?page=true&min_id={}", id, min_id),
+            (None, None) => format!("{}?page=true", id),
+        };
+        let prev = entries
+            .first()
+            .map(|first| format!("{}?page=true&min_id={}", id, first.id));
+        let items = entries
+            .iter()
+            .map(|entry| match &entry.post {
+                TaggedPost::Local(post, account) => status_url(
+                    &base_url,
+                    account.username.as_deref().unwrap_or_default(),
+                    post.id,
+                ),
+                TaggedPost::Remote(post) => post.object_id.clone(),
+            })
+            .collect();
+        let mut page = OrderedCollectionPage::new(page_id, id, items);
+        page.next = cursor.map(|last| format!("{}?page=true&max_id={}", page.part_of, last));
+        page.prev = prev;
+        return Ok(activity_json(page));
+    }
+
+    let local_ids: Vec<i64> = entries
+        .iter()
+        .filter_map(|entry| match &entry.post {
+            TaggedPost::Local(post, _) => Some(post.id),
+            TaggedPost::Remote(_) => None,
+        })
+        .collect();
+    let remote_ids = entries
+        .iter()
+        .filter_map(|entry| match &entry.post {
+            TaggedPost::Local(..) => None,
+            TaggedPost::Remote(post) => Some(post.id),
+        })
+        .collect();
+    let local_polls = polls::for_posts(&conn, local_ids.clone())
+        .await
+        .map_err(|e| TagError::UnexpectedError(e.into()))?;
+    let remote_polls = polls::for_remote_posts(&conn, remote_ids)
+        .await
+        .map_err(|e| TagError::UnexpectedError(e.into()))?;
+    let mentions = mention::for_posts(&conn, local_ids)
+        .await
+        .map_err(|e| TagError::UnexpectedError(e.into()))?;
+    let expand_cw = expands_cws(&conn, auth.current_user.as_ref())
+        .await
+        .map_err(|e| TagError::UnexpectedError(e.into()))?;
+    let items = entries
+        .iter()
+        .map(|entry| {
+            let poll = match &entry.post {
+                TaggedPost::Local(post, _) => local_polls.get(&post.id),
+                TaggedPost::Remote(post) => remote_polls.get(&post.id),
+            };
+            entry_html(&base_url, entry, &mentions, poll, expand_cw)
+        })
+        .collect::<String>();
+    let older = match cursor {
+        Some(last) => format!(r#"<p><a href="{}?max_id={}">Older posts</a></p>"#, id, last),
+        None => String::new(),
+    };
+
+    Ok((
+        [(header::VARY, "Accept")],
+        tag_html(&tag, &id, &items, &older),
+    )
+        .into_response())
+}
+
+fn entry_html(
+    base_url: &str,
+    entry: &TaggedEntry,
+    mentions: &HashMap<i64, Vec<Mention>>,
+    poll: Option<&Poll>,
+    expand_cw: bool,
+) -> String {
+    let (author, url, cw, content) = match &entry.post {
+        TaggedPost::Local(post, account) => {
+            let handle = account.username.as_deref().unwrap_or_default();
+            let mentions = mentions
+                .get(&post.id)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let note = Note::from_content(base_url, handle, post, mentions);
+            let url = note.url().unwrap_or(note.id);
+            // Rendered from escaped plain text
+            (
+                note.attributed_to,
+                url,
+                note.summary,
+                note.content.unwrap_or_default(),
+            )
+        }
+        TaggedPost::Remote(post) => (
+            post.actor_id.clone(),
+            // `is_http_url` is checked again when the page is rendered
+            post.url
+                .clone()
+                .filter(|url| is_http_url(url))
+                .unwrap_or_else(|| post.object_id.clone()),
+            post.summary.clone().filter(|cw| !cw.trim().is_empty()),
+            format!(
+                "<p>{}</p>",
+                strip_tags(post.content.as_deref().unwrap_or_default())
+            ),
+        ),
+    };
+
+    format!(
+        "\n        <article>\n            <p><a href=\"{author}\">{author}</a> <a href=\"{url}\">#</a></p>\n            {content}{poll}\n        </article>",
+        author = escape_html(&author),
+        url = escape_html(&url),
+        content = cw_html(cw.as_deref(), &content, expand_cw),
+        poll = poll.map(poll_html).unwrap_or_default(),
+    )
+}
+
+fn tag_html(tag: &tag::Model, id: &str, items: &str, older: &str) -> Html<String> {
+    let name = escape_html(&tag.display_name);
+
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8">
+        <title>#{name}</title>
+        <link rel="alternate" type="application/activity+json" href="{id}">
+    </head>
+    <body>
+        <h1>#{name}</h1>{items}
+        {older}
+    </body>
+</html>"#,
+        id = escape_html(id),
+    ))
+}
+
+/// The text of the HTML of a remote post. Markup from other servers is never
+/// rendered as is; entities are kept, as they are already escaped.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                text.push(' ');
+            }
+            '>' if in_tag => in_tag = false,
+            '>' => text.push_str("&gt;"),
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn activity_json<T: serde::Serialize>(body: T) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, ACTIVITY_JSON),
+            (header::VARY, "Accept"),
+        ],
+        Json(body),
+    )
+        .into_response()
+}
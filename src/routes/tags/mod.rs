@@ -0,0 +1,43 @@
+use axum::{http::StatusCode, response::IntoResponse};
+
+use crate::error::{error_chain_fmt, TenantMapError};
+
+pub mod get;
+
+#[derive(thiserror::Error)]
+pub enum TagError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl From<TenantMapError> for TagError {
+    fn from(e: TenantMapError) -> Self {
+        match e {
+            TenantMapError::NotFound(s) => Self::NotFound(s),
+            TenantMapError::UnexpectedError(s) => Self::UnexpectedError(anyhow::anyhow!(s)),
+        }
+    }
+}
+
+impl IntoResponse for TagError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            Self::NotFound(s) => {
+                tracing::info!("tag not found: {s:?}");
+                (StatusCode::NOT_FOUND, s).into_response()
+            }
+            Self::UnexpectedError(e) => {
+                tracing::error!("an unexpected error occurred: {e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")).into_response()
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for TagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
@@ -5,17 +5,22 @@ use axum::{
     response::IntoResponse,
     Form,
 };
-use sea_orm::{ActiveModelTrait, DatabaseTransaction, DbErr, EntityTrait, Set, TransactionTrait};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DatabaseTransaction, DbErr, EntityTrait,
+    QueryFilter, Set, TransactionTrait,
+};
 use secrecy::Secret;
 use serde::Deserialize;
 use uuid::Uuid;
 
 use super::super::{generate_random_key, get_db_from_host, AppState};
 use crate::{
-    domain::{user_email::UserEmail, AppUser, UserName, UserRole},
+    db,
+    domain::{user_email::UserEmail, AccountHandle, AppUser, UserName, UserRole},
     email_client::EmailClient,
-    entities::{prelude::*, user, user_token},
+    entities::{account, prelude::*, user, user_token},
     error::{error_chain_fmt, TenantMapError},
+    keys,
     smtp_client::SmtpMailer,
 };
 
@@ -25,6 +30,8 @@ pub struct InputUser {
     email: String,
     password: String,
     role: String,
+    #[serde(default)]
+    username: Option<String>,
 }
 
 #[tracing::instrument(
@@ -47,8 +54,30 @@ pub async fn create(
         TenantMapError::UnexpectedError(s) => UserError::UnexpectedError(anyhow::anyhow!(s)),
     })?;
 
+    if !db::microblog::open_registrations(&conn)
+        .await
+        .map_err(|e| UserError::UnexpectedError(anyhow::anyhow!(e)))?
+    {
+        return Err(UserError::AuthorizationError(
+            "registrations are closed".to_string(),
+        ));
+    }
+
     let new_user = parse_user(&form)?;
+    let handle = parse_handle(&form)?;
+    let handle_taken = handle_taken(&conn, &handle)
+        .await
+        .context("Failed to look up the handle of a new user")?;
+    if handle_taken && form.username.is_some() {
+        return Err(UserError::ValidationError(format!(
+            "{} is already taken",
+            handle
+        )));
+    }
     let token = generate_random_key(25);
+    let keypair = keys::generate_keypair(&state.global_config.server.secret_key)
+        .await
+        .context("Failed to generate a signing key for the new account")?;
 
     let new_user2 = new_user.clone();
     let token2 = token.clone();
@@ -58,6 +87,14 @@ pub async fn create(
                 .await
                 .context("Failed to insert a new user into the database")?;
 
+            let account_id = insert_account(txn, user_id, &handle, handle_taken)
+                .await
+                .context("Failed to insert the account of a new user into the database")?;
+
+            keys::insert_account_key(txn, account_id, keys::MAIN_KEY_ID, &keypair)
+                .await
+                .context("Failed to store the signing key of a new account")?;
+
             store_token(user_id, &token2, txn)
                 .await
                 .context("Failed to store the new user confirmation token in the database")?;
@@ -129,6 +166,49 @@ async fn insert_user(conn: &DatabaseTransaction, new_user: &AppUser) -> Result<i
     Ok(res.last_insert_id)
 }
 
+/// Insert the account of a new user. A handle derived from an email address
+/// that is already taken is disambiguated with the account id, the same way
+/// the migration that introduced handles did.
+async fn insert_account(
+    conn: &DatabaseTransaction,
+    user_id: i64,
+    handle: &AccountHandle,
+    handle_taken: bool,
+) -> Result<i64, DbErr> {
+    let data = account::ActiveModel {
+        user_id: Set(user_id),
+        username: Set((!handle_taken).then(|| handle.to_string())),
+        ..Default::default()
+    };
+    let res = Account::insert(data).exec(conn).await?;
+    if handle_taken {
+        account::ActiveModel {
+            id: Set(res.last_insert_id),
+            username: Set(Some(handle.with_suffix(res.last_insert_id).to_string())),
+            ..Default::default()
+        }
+        .update(conn)
+        .await?;
+    }
+
+    Ok(res.last_insert_id)
+}
+
+async fn handle_taken(conn: &DatabaseConnection, handle: &AccountHandle) -> Result<bool, DbErr> {
+    Ok(Account::find()
+        .filter(account::Column::Username.eq(handle.as_ref()))
+        .one(conn)
+        .await?
+        .is_some())
+}
+
+fn parse_handle(form: &InputUser) -> Result<AccountHandle, String> {
+    match &form.username {
+        Some(username) => AccountHandle::parse(username.clone()),
+        None => AccountHandle::from_email(&form.email),
+    }
+}
+
 fn parse_user(form: &InputUser) -> Result<AppUser, String> {
     let name = UserName::parse(form.name.clone())?;
     let email = UserEmail::parse(form.email.clone())?;
@@ -0,0 +1,115 @@
+use anyhow::{anyhow, Context};
+use axum::{
+    extract::{Host, Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{create::UserError, profile::current_account};
+use crate::{
+    domain::AppUser,
+    federation::follow_request,
+    routes::{get_db_from_host, tenant_base_url, AppState},
+};
+
+#[derive(Debug, Serialize)]
+pub struct FollowRequest {
+    pub id: i64,
+    pub actor_id: String,
+    pub requested_at: chrono::NaiveDateTime,
+}
+
+/// The follow requests of the current account waiting for approval.
+#[tracing::instrument(
+    name = "List follow requests",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn list(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<FollowRequest>>, UserError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state)
+        .await
+        .map_err(|e| UserError::UnexpectedError(anyhow!(e)))?;
+    let (account, _) = current_account(&user, &conn).await?;
+
+    let requests = follow_request::pending(&conn, account.id)
+        .await
+        .context("Failed to retrieve follow requests")?
+        .into_iter()
+        .map(|f| FollowRequest {
+            id: f.id,
+            actor_id: f.actor_id,
+            requested_at: f.updated_at,
+        })
+        .collect();
+
+    Ok(Json(requests))
+}
+
+/// Make the follow request `id` a follower and send it an `Accept`.
+#[tracing::instrument(
+    name = "Accept follow request",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn accept(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, UserError> {
+    answer(user, host, state, id, true).await
+}
+
+/// Drop the follow request `id` and send it a `Reject`.
+#[tracing::instrument(
+    name = "Reject follow request",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn reject(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, UserError> {
+    answer(user, host, state, id, false).await
+}
+
+async fn answer(
+    user: AppUser,
+    host: String,
+    state: AppState,
+    id: i64,
+    accept: bool,
+) -> Result<StatusCode, UserError> {
+    let conn = get_db_from_host(&host, &state)
+        .await
+        .map_err(|e| UserError::UnexpectedError(anyhow!(e)))?;
+    let base_url = tenant_base_url(&host, &state);
+    let (account, _) = current_account(&user, &conn).await?;
+
+    let found = follow_request::answer(&conn, &base_url, &account, id, accept)
+        .await
+        .context("Failed to answer follow request")?;
+    if !found {
+        return Err(UserError::ValidationError(format!(
+            "there is no pending follow request {}",
+            id
+        )));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,79 @@
+use anyhow::{anyhow, Context};
+use axum::{
+    extract::{Host, State},
+    Extension, Json,
+};
+use sea_orm::{ActiveModelTrait, Set, TransactionTrait};
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{create::UserError, profile::current_account};
+use crate::{
+    activitypub::actor_url,
+    domain::AppUser,
+    entities::account,
+    federation::queue,
+    keys,
+    routes::{get_db_from_host, tenant_base_url, AppState},
+};
+
+#[derive(Debug, Serialize)]
+pub struct RotatedKey {
+    pub key_id: String,
+    pub previous_key_expires_at: chrono::NaiveDateTime,
+}
+
+/// Rotate the signing key of the current account and queue an `Update` of
+/// its actor, so the followers' servers pick up the new key.
+#[tracing::instrument(
+    name = "Rotate signing key",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn rotate(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+) -> Result<Json<RotatedKey>, UserError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state)
+        .await
+        .map_err(|e| UserError::UnexpectedError(anyhow!(e)))?;
+
+    let (account, user) = current_account(&user, &conn).await?;
+
+    let grace = chrono::Duration::days(keys::ROTATION_GRACE_DAYS);
+    let txn = conn.begin().await.context("failed to start transaction")?;
+    let key = keys::rotate_account_key(
+        &txn,
+        account.id,
+        &state.global_config.server.secret_key,
+        grace,
+    )
+    .await
+    .map_err(|e| UserError::UnexpectedError(anyhow!(e)))?;
+    // A new updated time gives the Update announcing the key an id of its own
+    let account = account::ActiveModel {
+        id: Set(account.id),
+        updated_at: Set(chrono::Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .update(&txn)
+    .await
+    .context("failed to update account")?;
+    queue::enqueue_actor_update(&txn, &tenant_base_url(&hst, &state), &account, &user)
+        .await
+        .context("failed to queue the update of the actor")?;
+    txn.commit().await.context("failed to rotate the key")?;
+
+    let actor = actor_url(
+        &tenant_base_url(&hst, &state),
+        &account.username.unwrap_or_default(),
+    );
+    Ok(Json(RotatedKey {
+        key_id: format!("{}#{}", actor, key.key_id),
+        previous_key_expires_at: key.created_at + grace,
+    }))
+}
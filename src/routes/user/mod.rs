@@ -1,4 +1,8 @@
 pub mod change_password;
 pub mod confirm;
 pub mod create;
+pub mod follow_requests;
+pub mod keys;
 pub mod logout;
+pub mod moving;
+pub mod profile;
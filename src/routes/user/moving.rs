@@ -0,0 +1,155 @@
+use anyhow::{anyhow, Context};
+use axum::{
+    extract::{Host, State},
+    Extension, Json,
+};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::create::UserError;
+use crate::{
+    domain::AppUser,
+    entities::{account, prelude::*},
+    federation::{
+        account_move::{self, MoveError},
+        fetch::Fetcher,
+        instance_signer,
+    },
+    routes::{get_db_from_host, tenant_base_url, AppState},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct AliasData {
+    /// An actor URL or a `user@domain` address
+    pub alias: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Aliases {
+    pub also_known_as: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MoveData {
+    /// An actor URL or a `user@domain` address
+    pub target: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Moved {
+    pub moved_to: String,
+}
+
+#[tracing::instrument(
+    name = "Add account alias",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn add_alias(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Json(body): Json<AliasData>,
+) -> Result<Json<Aliases>, UserError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state)
+        .await
+        .map_err(|e| UserError::UnexpectedError(anyhow!(e)))?;
+    let base_url = tenant_base_url(&hst, &state);
+    let account = current_account(&user, &conn).await?;
+
+    let signer = instance_signer(&conn, &base_url, &state.global_config.server.secret_key)
+        .await
+        .context("failed to load the instance key")?;
+    let fetcher = Fetcher::new(&state.federation).signed_by(signer.as_ref());
+    let also_known_as = account_move::add_alias(&fetcher, &conn, &base_url, &account, &body.alias)
+        .await
+        .map_err(into_user_error)?;
+
+    Ok(Json(Aliases { also_known_as }))
+}
+
+#[tracing::instrument(
+    name = "Remove account alias",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn remove_alias(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Json(body): Json<AliasData>,
+) -> Result<Json<Aliases>, UserError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state)
+        .await
+        .map_err(|e| UserError::UnexpectedError(anyhow!(e)))?;
+    let account = current_account(&user, &conn).await?;
+
+    let also_known_as = account_move::remove_alias(&conn, &account, body.alias.trim())
+        .await
+        .map_err(into_user_error)?;
+
+    Ok(Json(Aliases { also_known_as }))
+}
+
+/// Move the account of the current user to another account, which has to
+/// list it as an alias. Its followers are asked to follow the new account.
+#[tracing::instrument(
+    name = "Move account",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn move_account(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Json(body): Json<MoveData>,
+) -> Result<Json<Moved>, UserError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state)
+        .await
+        .map_err(|e| UserError::UnexpectedError(anyhow!(e)))?;
+    let base_url = tenant_base_url(&hst, &state);
+    let account = current_account(&user, &conn).await?;
+
+    let signer = instance_signer(&conn, &base_url, &state.global_config.server.secret_key)
+        .await
+        .context("failed to load the instance key")?;
+    let fetcher = Fetcher::new(&state.federation).signed_by(signer.as_ref());
+    let moved_to = account_move::move_to(&fetcher, &conn, &base_url, &account, &body.target)
+        .await
+        .map_err(into_user_error)?;
+
+    Ok(Json(Moved { moved_to }))
+}
+
+async fn current_account(
+    user: &AppUser,
+    conn: &DatabaseConnection,
+) -> Result<account::Model, UserError> {
+    Account::find()
+        .filter(account::Column::UserId.eq(user.id.unwrap_or_default()))
+        .one(conn)
+        .await
+        .context("Unable to retrieve account associated with current user")?
+        .ok_or_else(|| {
+            UserError::ValidationError("There is no account associated with current user".into())
+        })
+}
+
+/// Accounts that cannot be found or do not qualify are the user's mistake.
+fn into_user_error(e: MoveError) -> UserError {
+    match e {
+        MoveError::Invalid(s) => UserError::ValidationError(s),
+        MoveError::Fetch(e) => UserError::ValidationError(format!("unknown account: {}", e)),
+        e => UserError::UnexpectedError(e.into()),
+    }
+}
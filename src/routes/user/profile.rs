@@ -0,0 +1,229 @@
+use anyhow::{anyhow, Context};
+use axum::{
+    extract::{Host, State},
+    response::{Html, Redirect},
+    Extension, Form,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set,
+    TransactionTrait,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::create::UserError;
+use crate::{
+    activitypub::actor::ProfileField,
+    domain::AppUser,
+    entities::{account, prelude::*, user},
+    federation::queue,
+    routes::{escape_html, get_db_from_host, tenant_base_url, AppState},
+};
+
+const MAX_NAME_CHARS: usize = 100;
+const MAX_SUMMARY_CHARS: usize = 500;
+/// Profiles list at most this many extra fields
+pub const MAX_FIELDS: usize = 4;
+
+#[derive(Debug, Deserialize)]
+pub struct FormData {
+    name: String,
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    avatar_url: String,
+    #[serde(default)]
+    field_name_1: String,
+    #[serde(default)]
+    field_value_1: String,
+    #[serde(default)]
+    field_name_2: String,
+    #[serde(default)]
+    field_value_2: String,
+    #[serde(default)]
+    field_name_3: String,
+    #[serde(default)]
+    field_value_3: String,
+    #[serde(default)]
+    field_name_4: String,
+    #[serde(default)]
+    field_value_4: String,
+    /// Set if posts are shown with their content warnings expanded
+    #[serde(default)]
+    expand_cws: Option<String>,
+}
+
+impl FormData {
+    /// The extra fields that have a name.
+    fn fields(&self) -> Vec<ProfileField> {
+        [
+            (&self.field_name_1, &self.field_value_1),
+            (&self.field_name_2, &self.field_value_2),
+            (&self.field_name_3, &self.field_value_3),
+            (&self.field_name_4, &self.field_value_4),
+        ]
+        .into_iter()
+        .filter(|(name, _)| !name.trim().is_empty())
+        .map(|(name, value)| ProfileField {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+        })
+        .collect()
+    }
+}
+
+pub async fn form(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+) -> Result<Html<String>, UserError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state)
+        .await
+        .map_err(|e| UserError::UnexpectedError(anyhow!(e)))?;
+    let (account, user) = current_account(&user, &conn).await?;
+
+    Ok(profile_form(&account, &user))
+}
+
+/// Save the profile of the current user and let the followers of the account
+/// know about it.
+#[tracing::instrument(
+    name = "Update profile",
+    skip(state, form),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn update(
+    Extension(user): Extension<AppUser>,
+    Host(host): Host,
+    State(state): State<AppState>,
+    Form(form): Form<FormData>,
+) -> Result<Redirect, UserError> {
+    let hst = host.to_string();
+    let conn = get_db_from_host(&hst, &state)
+        .await
+        .map_err(|e| UserError::UnexpectedError(anyhow!(e)))?;
+    let (account, user) = current_account(&user, &conn).await?;
+
+    let name = form.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_CHARS {
+        return Err(UserError::ValidationError(
+            "empty display name or too long".to_string(),
+        ));
+    }
+    if form.summary.chars().count() > MAX_SUMMARY_CHARS {
+        return Err(UserError::ValidationError("bio too long".to_string()));
+    }
+    let avatar_url = match form.avatar_url.trim() {
+        "" => None,
+        url => match url::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Some(url.to_string()),
+            _ => {
+                return Err(UserError::ValidationError(format!(
+                    "invalid avatar URL: {}",
+                    url
+                )))
+            }
+        },
+    };
+    let summary = match form.summary.trim() {
+        "" => None,
+        summary => Some(summary.to_string()),
+    };
+
+    let txn = conn.begin().await.context("failed to start transaction")?;
+    let user = user::ActiveModel {
+        id: Set(user.id),
+        name: Set(name.to_string()),
+        expand_cws: Set(form.expand_cws.is_some()),
+        ..Default::default()
+    }
+    .update(&txn)
+    .await
+    .context("failed to update user")?;
+    let account = account::ActiveModel {
+        id: Set(account.id),
+        summary: Set(summary),
+        avatar_url: Set(avatar_url),
+        fields: Set(serde_json::to_value(form.fields()).context("failed to serialize fields")?),
+        ..Default::default()
+    }
+    .update(&txn)
+    .await
+    .context("failed to update account")?;
+    queue::enqueue_actor_update(&txn, &tenant_base_url(&hst, &state), &account, &user)
+        .await
+        .context("failed to queue profile update")?;
+    txn.commit().await.context("failed to update profile")?;
+
+    Ok(Redirect::to("/user/profile"))
+}
+
+pub(super) async fn current_account(
+    user: &AppUser,
+    conn: &DatabaseConnection,
+) -> Result<(account::Model, user::Model), UserError> {
+    Account::find()
+        .filter(account::Column::UserId.eq(user.id.unwrap_or_default()))
+        .find_also_related(User)
+        .one(conn)
+        .await
+        .context("Unable to retrieve account associated with current user")?
+        .and_then(|(account, user)| Some((account, user?)))
+        .ok_or_else(|| {
+            UserError::ValidationError("There is no account associated with current user".into())
+        })
+}
+
+fn profile_form(account: &account::Model, user: &user::Model) -> Html<String> {
+    let fields: Vec<ProfileField> =
+        serde_json::from_value(account.fields.clone()).unwrap_or_default();
+    let field_inputs = (1..=MAX_FIELDS)
+        .map(|i| {
+            let field = fields.get(i - 1);
+            format!(
+                r#"
+            <label>Field {i}
+                <input type="text" name="field_name_{i}" value="{}">
+                <input type="text" name="field_value_{i}" value="{}">
+            </label>"#,
+                escape_html(field.map(|f| f.name.as_str()).unwrap_or_default()),
+                escape_html(field.map(|f| f.value.as_str()).unwrap_or_default()),
+            )
+        })
+        .collect::<String>();
+
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8">
+        <title>Edit profile</title>
+    </head>
+    <body>
+        <form action="/user/profile" method="post">
+            <label>Display name
+                <input type="text" name="name" value="{name}">
+            </label>
+            <label>Bio
+                <textarea name="summary">{summary}</textarea>
+            </label>
+            <label>Avatar URL
+                <input type="url" name="avatar_url" value="{avatar_url}">
+            </label>{field_inputs}
+            <label>
+                <input type="checkbox" name="expand_cws" value="true"{expand_cws}>
+                Always expand content warnings
+            </label>
+            <button type="submit">Save</button>
+        </form>
+    </body>
+</html>"#,
+        name = escape_html(&user.name),
+        summary = escape_html(account.summary.as_deref().unwrap_or_default()),
+        avatar_url = escape_html(account.avatar_url.as_deref().unwrap_or_default()),
+        expand_cws = if user.expand_cws { " checked" } else { "" },
+    ))
+}
@@ -0,0 +1,2 @@
+pub mod nodeinfo;
+pub mod webfinger;
@@ -0,0 +1,209 @@
+use axum::{
+    extract::{Host, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use sea_orm::EntityTrait;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    db,
+    entities::prelude::*,
+    error::{error_chain_fmt, TenantMapError},
+    routes::{get_tenant_from_host, tenant_base_url, AppState},
+    APP_NAME,
+};
+
+pub const NODEINFO_2_1_SCHEMA: &str = "http://nodeinfo.diaspora.software/ns/schema/2.1";
+
+const NODEINFO_2_1_JSON: &str =
+    r#"application/json; profile="http://nodeinfo.diaspora.software/ns/schema/2.1#""#;
+
+/// The periods, in days, users are counted as active over
+const MONTH_DAYS: i64 = 30;
+const HALF_YEAR_DAYS: i64 = 180;
+
+#[derive(Debug, Serialize)]
+pub struct NodeInfoLinks {
+    pub links: Vec<NodeInfoLink>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NodeInfoLink {
+    pub rel: String,
+    pub href: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeInfo {
+    pub version: String,
+    pub software: Software,
+    pub protocols: Vec<String>,
+    pub services: Services,
+    pub open_registrations: bool,
+    pub usage: Usage,
+    pub metadata: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Software {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Services {
+    pub inbound: Vec<String>,
+    pub outbound: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    pub users: UserUsage,
+    pub local_posts: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserUsage {
+    pub total: u64,
+    pub active_month: u64,
+    pub active_halfyear: u64,
+}
+
+/// The NodeInfo discovery document, pointing crawlers at the schema versions
+/// we serve.
+#[tracing::instrument(
+    name = "NodeInfo discovery",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn nodeinfo_links(
+    Host(host): Host,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, NodeInfoError> {
+    let hst = host.to_string();
+    get_tenant_from_host(&hst, &state).await?;
+    let base_url = tenant_base_url(&hst, &state);
+
+    Ok(Json(NodeInfoLinks {
+        links: vec![NodeInfoLink {
+            rel: NODEINFO_2_1_SCHEMA.to_string(),
+            href: format!("{}/nodeinfo/2.1", base_url),
+        }],
+    }))
+}
+
+/// NodeInfo 2.1 of the tenant serving `host`. Users count as active if they
+/// published something in the period.
+#[tracing::instrument(
+    name = "NodeInfo",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn nodeinfo(
+    Host(host): Host,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, NodeInfoError> {
+    let hst = host.to_string();
+    let tenant = get_tenant_from_host(&hst, &state).await?;
+    let conn = tenant.db;
+    let now = chrono::Utc::now().naive_utc();
+
+    let total = db::user::count(&conn).await.map_err(unexpected)?;
+    let active_month =
+        db::user::count_active_since(&conn, now - chrono::Duration::days(MONTH_DAYS))
+            .await
+            .map_err(unexpected)?;
+    let active_halfyear =
+        db::user::count_active_since(&conn, now - chrono::Duration::days(HALF_YEAR_DAYS))
+            .await
+            .map_err(unexpected)?;
+    let local_posts = db::content::count_local(&conn).await.map_err(unexpected)?;
+    let open_registrations = db::microblog::open_registrations(&conn)
+        .await
+        .map_err(unexpected)?;
+    let microblog = Microblog::find()
+        .one(&conn)
+        .await
+        .map_err(|e| NodeInfoError::UnexpectedError(e.into()))?;
+    let metadata = match microblog {
+        Some(m) => serde_json::json!({ "nodeName": m.name, "nodeDescription": m.short_desc }),
+        None => serde_json::json!({ "nodeName": tenant.domain }),
+    };
+
+    let nodeinfo = NodeInfo {
+        version: "2.1".to_string(),
+        software: Software {
+            name: APP_NAME.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+        protocols: vec!["activitypub".to_string()],
+        services: Services {
+            inbound: vec![],
+            outbound: vec![],
+        },
+        open_registrations,
+        usage: Usage {
+            users: UserUsage {
+                total,
+                active_month,
+                active_halfyear,
+            },
+            local_posts,
+        },
+        metadata,
+    };
+
+    Ok(([(header::CONTENT_TYPE, NODEINFO_2_1_JSON)], Json(nodeinfo)))
+}
+
+fn unexpected(e: String) -> NodeInfoError {
+    NodeInfoError::UnexpectedError(anyhow::anyhow!(e))
+}
+
+#[derive(thiserror::Error)]
+pub enum NodeInfoError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl From<TenantMapError> for NodeInfoError {
+    fn from(e: TenantMapError) -> Self {
+        match e {
+            TenantMapError::NotFound(s) => Self::NotFound(s),
+            TenantMapError::UnexpectedError(s) => Self::UnexpectedError(anyhow::anyhow!(s)),
+        }
+    }
+}
+
+impl std::fmt::Debug for NodeInfoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for NodeInfoError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            Self::NotFound(s) => {
+                tracing::info!("tenant not found: {s:?}");
+                (StatusCode::NOT_FOUND, s).into_response()
+            }
+            Self::UnexpectedError(e) => {
+                tracing::error!("an unexpected error occurred: {e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")).into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use axum::{
+    extract::{Host, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    activitypub::{actor_url, ACTIVITY_JSON, JRD_JSON, PROFILE_PAGE_REL},
+    domain::AccountHandle,
+    error::{error_chain_fmt, TenantMapError},
+    orm,
+    routes::{get_tenant_from_host, tenant_base_url, AppState},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct QueryParameters {
+    resource: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Jrd {
+    pub subject: String,
+    pub aliases: Vec<String>,
+    pub links: Vec<JrdLink>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JrdLink {
+    pub rel: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub href: String,
+}
+
+#[tracing::instrument(
+    name = "WebFinger lookup",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+pub async fn webfinger(
+    Host(host): Host,
+    State(state): State<AppState>,
+    Query(query_params): Query<QueryParameters>,
+) -> Result<impl IntoResponse, WebFingerError> {
+    let hst = host.to_string();
+    let tenant = get_tenant_from_host(&hst, &state)
+        .await
+        .map_err(|e| match e {
+            TenantMapError::NotFound(s) => WebFingerError::NotFound(s),
+            TenantMapError::UnexpectedError(s) => {
+                WebFingerError::UnexpectedError(anyhow::anyhow!(s))
+            }
+        })?;
+    let base_url = tenant_base_url(&hst, &state);
+
+    let resource = query_params
+        .resource
+        .ok_or_else(|| WebFingerError::BadRequest("missing resource parameter".to_string()))?;
+    let handle = parse_resource(&resource, &tenant.domain, &base_url)?;
+    let (account, _user) = orm::get_account_by_handle(&handle, &tenant.db)
+        .await
+        .map_err(|e| WebFingerError::UnexpectedError(e.into()))?
+        .ok_or_else(|| WebFingerError::NotFound(format!("no such account: {}", resource)))?;
+    let handle = account.username.unwrap_or_default();
+
+    let actor = actor_url(&base_url, &handle);
+    let jrd = Jrd {
+        subject: format!("acct:{}@{}", handle, tenant.domain),
+        aliases: vec![actor.clone()],
+        links: vec![
+            JrdLink {
+                rel: "self".to_string(),
+                kind: ACTIVITY_JSON.to_string(),
+                href: actor.clone(),
+            },
+            JrdLink {
+                rel: PROFILE_PAGE_REL.to_string(),
+                kind: "text/html".to_string(),
+                href: actor,
+            },
+        ],
+    };
+
+    Ok(([(header::CONTENT_TYPE, JRD_JSON)], Json(jrd)))
+}
+
+/// Extract the account handle from a WebFinger `resource`. Both the
+/// `acct:name@domain` form and the actor URL form are accepted, but only if
+/// they refer to this tenant.
+fn parse_resource(resource: &str, domain: &str, base_url: &str) -> Result<String, WebFingerError> {
+    let actor_prefix = actor_url(base_url, "");
+    if let Some(name) = resource.strip_prefix(&actor_prefix) {
+        return parse_handle(name, resource);
+    }
+
+    let acct = resource.strip_prefix("acct:").unwrap_or(resource);
+    let (name, acct_domain) = acct
+        .split_once('@')
+        .ok_or_else(|| WebFingerError::BadRequest(format!("malformed resource: {}", resource)))?;
+    let acct_domain = acct_domain.split(':').next().unwrap_or_default();
+    if !acct_domain.eq_ignore_ascii_case(domain) {
+        return Err(WebFingerError::NotFound(format!(
+            "{} is not hosted on {}",
+            resource, domain
+        )));
+    }
+
+    parse_handle(name, resource)
+}
+
+fn parse_handle(name: &str, resource: &str) -> Result<String, WebFingerError> {
+    AccountHandle::parse(name.to_string())
+        .map(|h| h.to_string())
+        .map_err(|_| WebFingerError::NotFound(format!("no such account: {}", resource)))
+}
+
+#[derive(thiserror::Error)]
+pub enum WebFingerError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for WebFingerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for WebFingerError {
+    fn into_response(self) -> axum::response::Response {
+        let (status, err_msg) = match self {
+            Self::BadRequest(s) => {
+                tracing::info!("bad webfinger request: {s:?}");
+                (StatusCode::BAD_REQUEST, s)
+            }
+            Self::NotFound(s) => {
+                tracing::info!("webfinger resource not found: {s:?}");
+                (StatusCode::NOT_FOUND, s)
+            }
+            Self::UnexpectedError(e) => {
+                tracing::error!("an unexpected error occurred: {e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}"))
+            }
+        };
+        (
+            status,
+            [(header::CONTENT_TYPE, JRD_JSON)],
+            Json(serde_json::json!({ "error": err_msg })),
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_resource;
+
+    #[test]
+    fn acct_resource_on_this_domain_is_parsed() {
+        let cases = [
+            "acct:alice@example.com",
+            "alice@example.com",
+            "acct:Alice@EXAMPLE.com",
+            "acct:alice@example.com:8080",
+            "https://example.com/users/alice",
+        ];
+        for case in cases {
+            let handle = parse_resource(case, "example.com", "https://example.com").unwrap();
+            assert_eq!(handle, "alice", "{} resolves to alice", case);
+        }
+    }
+
+    #[test]
+    fn resource_on_other_domain_is_rejected() {
+        assert!(parse_resource(
+            "acct:alice@other.example",
+            "example.com",
+            "https://example.com"
+        )
+        .is_err());
+        assert!(parse_resource(
+            "https://other.example/users/alice",
+            "example.com",
+            "https://example.com"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn malformed_resource_is_rejected() {
+        assert!(parse_resource("acct:alice", "example.com", "https://example.com").is_err());
+    }
+}
@@ -12,6 +12,7 @@ use super::APP_NAME;
 
 const ENV_DBPASS: &str = "DB_PASSWORD";
 const ENV_REDIS_URI: &str = "REDIS_URI";
+const ENV_SECRET_KEY: &str = "SECRET_KEY";
 
 pub fn dotenv_override(global_config: &mut Settings) {
     // Get various secrets from .env
@@ -26,6 +27,11 @@ pub fn dotenv_override(global_config: &mut Settings) {
     if env::var(ENV_REDIS_URI).is_ok() && !env::var(ENV_REDIS_URI).unwrap().is_empty() {
         global_config.server.redis_uri = Secret::from(env::var(ENV_REDIS_URI).unwrap());
     }
+
+    // Key used to encrypt private keys at rest
+    if env::var(ENV_SECRET_KEY).is_ok() && !env::var(ENV_SECRET_KEY).unwrap().is_empty() {
+        global_config.server.secret_key = Secret::from(env::var(ENV_SECRET_KEY).unwrap());
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -35,6 +41,12 @@ pub struct Server {
     pub log_level: String,
     pub base_url: String,
     pub redis_uri: Secret<String>,
+    pub secret_key: Secret<String>,
+    /// Days the tombstones of deleted posts are kept before they are purged
+    pub tombstone_retention_days: u32,
+    /// Let federation traffic reach loopback, link-local and private
+    /// addresses. Only meant for tests and local development.
+    pub allow_private_addresses: bool,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -156,6 +168,9 @@ impl Settings {
             .set_default("env", env.as_str())?
             .set_default("server.port", "8080")?
             .set_default("server.log_level", "info")?
+            .set_default("server.secret_key", "")?
+            .set_default("server.tombstone_retention_days", 30)?
+            .set_default("server.allow_private_addresses", false)?
             .set_default("database.db_host", "")?
             .set_default("database.db_port", 5432)?
             .set_default("database.db_user", "")?
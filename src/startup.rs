@@ -63,7 +63,7 @@ pub async fn initialize_database(args: &Args, global_config: &Settings) -> Resul
         tracing::error!("No databases to initialize!")
     };
     for uri in uri_list {
-        migration::initialize_and_migrate_database(&uri).await?;
+        migration::initialize_and_migrate_database(&uri, &global_config.server.secret_key).await?;
     }
 
     Ok(())
@@ -81,8 +81,8 @@ pub async fn build(
         .unwrap();
 
     let mut addr = format!("0.0.0.0:{}", global_config.server.port);
-    if bind_address.is_some() {
-        addr = bind_address.unwrap();
+    if let Some(bind_address) = bind_address {
+        addr = bind_address;
     }
     let listener = TcpListener::bind(addr)
         .map_err(|e| {
@@ -0,0 +1,288 @@
+//! Hashtags of local and remote posts.
+//!
+//! A tag is stored once, under its case folded name, along with the spelling
+//! it was first seen with. It is linked to the local posts that use it and to
+//! the public remote posts received with it. Tag listings only show posts
+//! anyone may see, and leave out posts from silenced domains.
+use sea_orm::{
+    sea_query::{OnConflict, Query},
+    ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, FromQueryResult, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Select, Set,
+};
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    domain::{parsed_hashtag::fold, ParsedHashtag, Visibility},
+    entities::{account, content, content_tag, prelude::*, remote_post, tag},
+    federation::policy::DomainPolicies,
+};
+
+/// A post in a tag listing.
+#[derive(Debug, Clone)]
+pub enum TaggedPost {
+    Local(content::Model, account::Model),
+    Remote(remote_post::Model),
+}
+
+/// An entry of a tag listing. Listings are paged by `id`, the order in which
+/// posts were tagged.
+#[derive(Debug, Clone)]
+pub struct TaggedEntry {
+    pub id: i64,
+    pub post: TaggedPost,
+}
+
+/// The tag named `name`, in any spelling.
+pub async fn find<C: ConnectionTrait>(conn: &C, name: &str) -> Result<Option<tag::Model>, DbErr> {
+    Tag::find()
+        .filter(tag::Column::Name.eq(fold(name)))
+        .one(conn)
+        .await
+}
+
+/// Tag the local post `content_id` with the hashtags in its `text`, replacing
+/// the tags it had.
+pub async fn tag_post<C: ConnectionTrait>(
+    conn: &C,
+    content_id: i64,
+    text: &str,
+) -> Result<Vec<tag::Model>, DbErr> {
+    ContentTag::delete_many()
+        .filter(content_tag::Column::ContentId.eq(content_id))
+        .exec(conn)
+        .await?;
+    let names = ParsedHashtag::parse_all(text)
+        .into_iter()
+        .map(|t| t.name)
+        .collect::<Vec<_>>();
+    let tags = upsert(conn, &names).await?;
+    link(conn, &tags, |row| row.content_id = Set(Some(content_id))).await?;
+
+    Ok(tags)
+}
+
+/// Tag the remote post `remote_post_id` with `names`, the names of the
+/// `Hashtag` objects it was received with, replacing the tags it had.
+pub async fn tag_remote_post<C: ConnectionTrait>(
+    conn: &C,
+    remote_post_id: i64,
+    names: &[String],
+) -> Result<Vec<tag::Model>, DbErr> {
+    ContentTag::delete_many()
+        .filter(content_tag::Column::RemotePostId.eq(remote_post_id))
+        .exec(conn)
+        .await?;
+    let tags = upsert(conn, names).await?;
+    link(conn, &tags, |row| {
+        row.remote_post_id = Set(Some(remote_post_id))
+    })
+    .await?;
+
+    Ok(tags)
+}
+
+/// Number of posts listed under the tag `tag_id`.
+pub async fn count_public<C: ConnectionTrait>(
+    conn: &C,
+    tag_id: i64,
+    policies: &DomainPolicies,
+) -> Result<u64, DbErr> {
+    #[derive(FromQueryResult)]
+    struct Author {
+        actor_id: String,
+    }
+
+    let local = public_entries(tag_id)
+        .filter(content_tag::Column::ContentId.is_not_null())
+        .count(conn)
+        .await?;
+    let remote = RemotePost::find()
+        .select_only()
+        .column(remote_post::Column::ActorId)
+        .filter(remote_post::Column::DeletedAt.is_null())
+        .filter(
+            remote_post::Column::Id.in_subquery(
+                Query::select()
+                    .column(content_tag::Column::RemotePostId)
+                    .from(ContentTag)
+                    .and_where(content_tag::Column::TagId.eq(tag_id))
+                    .to_owned(),
+            ),
+        )
+        .into_model::<Author>()
+        .all(conn)
+        .await?
+        .iter()
+        .filter(|a| !policies.is_silenced(&a.actor_id))
+        .count();
+
+    Ok(local + remote as u64)
+}
+
+/// A page of the posts listed under the tag `tag_id`, most recently tagged
+/// first. Without bounds the newest entries are returned; `max_id` pages
+/// backwards in time and `min_id` forwards. Posts from silenced domains are
+/// left out, so a page may be shorter than `limit`; the second value is the
+/// id of the last entry looked at, to continue from.
+pub async fn public_page<C: ConnectionTrait>(
+    conn: &C,
+    tag_id: i64,
+    policies: &DomainPolicies,
+    max_id: Option<i64>,
+    min_id: Option<i64>,
+    limit: u64,
+) -> Result<(Vec<TaggedEntry>, Option<i64>), DbErr> {
+    let mut query = public_entries(tag_id);
+    if let Some(max_id) = max_id {
+        query = query.filter(content_tag::Column::Id.lt(max_id));
+    }
+    let rows = match min_id {
+        Some(min_id) => {
+            // Take the entries right after min_id, then restore newest-first order
+            let mut rows = query
+                .filter(content_tag::Column::Id.gt(min_id))
+                .order_by_asc(content_tag::Column::Id)
+                .limit(limit)
+                .all(conn)
+                .await?;
+            rows.reverse();
+            rows
+        }
+        None => {
+            query
+                .order_by_desc(content_tag::Column::Id)
+                .limit(limit)
+                .all(conn)
+                .await?
+        }
+    };
+    let cursor = if rows.len() as u64 == limit {
+        rows.last().map(|r| r.id)
+    } else {
+        None
+    };
+
+    let local: HashMap<i64, (content::Model, account::Model)> = Content::find()
+        .filter(content::Column::Id.is_in(rows.iter().filter_map(|r| r.content_id)))
+        .find_also_related(Account)
+        .all(conn)
+        .await?
+        .into_iter()
+        .filter_map(|(post, account)| Some((post.id, (post, account?))))
+        .collect();
+    let remote: HashMap<i64, remote_post::Model> = RemotePost::find()
+        .filter(remote_post::Column::Id.is_in(rows.iter().filter_map(|r| r.remote_post_id)))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|post| (post.id, post))
+        .collect();
+
+    let entries = rows
+        .iter()
+        .filter_map(|row| {
+            let post = match (row.content_id, row.remote_post_id) {
+                (Some(id), _) => {
+                    let (post, account) = local.get(&id)?.clone();
+                    TaggedPost::Local(post, account)
+                }
+                (None, Some(id)) => {
+                    let post = remote.get(&id)?;
+                    if policies.is_silenced(&post.actor_id) {
+                        return None;
+                    }
+                    TaggedPost::Remote(post.clone())
+                }
+                (None, None) => return None,
+            };
+            Some(TaggedEntry { id: row.id, post })
+        })
+        .collect();
+
+    Ok((entries, cursor))
+}
+
+/// Store the tags named `names`, keeping the spelling of tags that already
+/// exist, and return them in the order they were named.
+async fn upsert<C: ConnectionTrait>(conn: &C, names: &[String]) -> Result<Vec<tag::Model>, DbErr> {
+    let mut seen = HashSet::new();
+    let names = names
+        .iter()
+        .map(|name| (fold(name), name.trim_start_matches('#')))
+        .filter(|(folded, _)| !folded.is_empty() && seen.insert(folded.clone()))
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        return Ok(vec![]);
+    }
+
+    Tag::insert_many(names.iter().map(|(folded, display)| tag::ActiveModel {
+        name: Set(folded.clone()),
+        display_name: Set(display.to_string()),
+        ..Default::default()
+    }))
+    .on_conflict(
+        OnConflict::column(tag::Column::Name)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(conn)
+    .await?;
+    let mut tags: HashMap<String, tag::Model> = Tag::find()
+        .filter(tag::Column::Name.is_in(names.iter().map(|(folded, _)| folded.clone())))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|t| (t.name.clone(), t))
+        .collect();
+
+    Ok(names
+        .iter()
+        .filter_map(|(folded, _)| tags.remove(folded))
+        .collect())
+}
+
+async fn link<C: ConnectionTrait>(
+    conn: &C,
+    tags: &[tag::Model],
+    post: impl Fn(&mut content_tag::ActiveModel),
+) -> Result<(), DbErr> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+    let rows = tags.iter().map(|tag| {
+        let mut row = content_tag::ActiveModel {
+            tag_id: Set(tag.id),
+            ..Default::default()
+        };
+        post(&mut row);
+        row
+    });
+    ContentTag::insert_many(rows).exec(conn).await?;
+
+    Ok(())
+}
+
+/// The entries of the tag `tag_id` for posts anyone may see: published,
+/// public local posts and remote posts that were not deleted.
+fn public_entries(tag_id: i64) -> Select<ContentTag> {
+    let local = Query::select()
+        .column(content::Column::Id)
+        .from(Content)
+        .and_where(content::Column::Published.eq(true))
+        .and_where(content::Column::DeletedAt.is_null())
+        .and_where(content::Column::Visibility.eq(Visibility::Public.to_string()))
+        .to_owned();
+    let remote = Query::select()
+        .column(remote_post::Column::Id)
+        .from(RemotePost)
+        .and_where(remote_post::Column::DeletedAt.is_null())
+        .to_owned();
+
+    ContentTag::find()
+        .filter(content_tag::Column::TagId.eq(tag_id))
+        .filter(
+            Condition::any()
+                .add(content_tag::Column::ContentId.in_subquery(local))
+                .add(content_tag::Column::RemotePostId.in_subquery(remote)),
+        )
+}
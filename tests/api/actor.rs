@@ -0,0 +1,149 @@
+use crate::helpers::{connect_to_db, spawn_app};
+
+const ACTIVITY_JSON: &str = "application/activity+json";
+
+#[tokio::test]
+async fn actor_document_is_served_to_federation_clients() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    client
+        .execute(
+            "UPDATE account SET summary=$1 WHERE id=$2;",
+            &[&"Just testing", &user.account_id],
+        )
+        .await
+        .expect("query to set account summary failed");
+
+    // Act
+    let response = state.get_actor(&user.handle, ACTIVITY_JSON).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        ACTIVITY_JSON,
+        "federation clients receive application/activity+json"
+    );
+    let actor: serde_json::Value = response.json().await.unwrap();
+    let id = format!("{}/users/{}", state.app_address, user.handle);
+    assert_eq!(actor["id"], id);
+    assert_eq!(actor["type"], "Person");
+    assert_eq!(actor["preferredUsername"], user.handle.as_str());
+    assert_eq!(actor["name"], user.name.as_str());
+    assert_eq!(actor["summary"], "Just testing");
+    assert_eq!(actor["inbox"], format!("{}/inbox", id));
+    assert_eq!(actor["outbox"], format!("{}/outbox", id));
+    assert_eq!(actor["followers"], format!("{}/followers", id));
+    assert_eq!(actor["following"], format!("{}/following", id));
+    assert_eq!(
+        actor["endpoints"]["sharedInbox"],
+        format!("{}/inbox", state.app_address)
+    );
+}
+
+#[tokio::test]
+async fn followers_and_following_collections_are_counted() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    for (actor, accepted) in [("zoe", true), ("yan", false)] {
+        client
+            .execute(
+                "INSERT INTO follower (account_id, actor_id, inbox, follow_id, accepted) \
+                 VALUES ($1, $2, $3, $4, $5)",
+                &[
+                    &user.account_id,
+                    &format!("https://remote.example/users/{}", actor),
+                    &format!("https://remote.example/users/{}/inbox", actor),
+                    &format!("https://remote.example/follows/{}", actor),
+                    &accepted,
+                ],
+            )
+            .await
+            .expect("query to add a follower failed");
+    }
+    let id = format!("{}/users/{}", state.app_address, user.handle);
+
+    // Act
+    let followers = state.get_json(&format!("{}/followers", id)).await;
+    let following = state.get_json(&format!("{}/following", id)).await;
+    let head = state
+        .api_client
+        .head(format!("{}/followers", id))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(followers["type"], "OrderedCollection");
+    assert_eq!(followers["id"], format!("{}/followers", id));
+    assert_eq!(
+        followers["totalItems"], 1,
+        "pending follows are not counted"
+    );
+    assert!(followers.get("first").is_none(), "followers are not listed");
+    assert_eq!(following["id"], format!("{}/following", id));
+    assert_eq!(following["totalItems"], 0);
+    assert_eq!(head.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn ld_json_accept_header_gets_actor_document() {
+    // Arrange
+    let state = spawn_app().await;
+
+    // Act
+    let response = state
+        .get_actor(
+            "admin",
+            r#"application/ld+json; profile="https://www.w3.org/ns/activitystreams""#,
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let actor: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(actor["preferredUsername"], "admin");
+}
+
+#[tokio::test]
+async fn browsers_get_an_html_profile_page() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+
+    // Act
+    let response = state
+        .get_actor(&user.handle, "text/html,application/xhtml+xml")
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .headers()
+        .get("Content-Type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    let html = response.text().await.unwrap();
+    assert!(
+        html.contains(&format!("@{}", user.handle)),
+        "profile page shows the handle"
+    );
+}
+
+#[tokio::test]
+async fn unknown_actor_is_not_found_404() {
+    // Arrange
+    let state = spawn_app().await;
+
+    // Act
+    let response = state.get_actor("nobody", ACTIVITY_JSON).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,209 @@
+use librhodos::{
+    federation::{fetch::Fetcher, remote, FederationState},
+    get_database_connection,
+};
+use tokio_postgres::Client;
+
+use crate::{
+    helpers::{assert_is_redirect_to, connect_to_db, spawn_app, TestState},
+    mock_server::{borrow_key, MockServer},
+};
+
+/// Limit federation of the tenant to `domains`.
+async fn allow_only(client: &Client, domains: &[&str]) {
+    client
+        .execute(
+            "INSERT INTO microblog (name, federation_mode) VALUES ('test', 'allowlist')",
+            &[],
+        )
+        .await
+        .unwrap();
+    for domain in domains {
+        client
+            .execute("INSERT INTO domain_allow (domain) VALUES ($1)", &[domain])
+            .await
+            .unwrap();
+    }
+}
+
+async fn follow_as_zoe(state: &TestState, mock: &MockServer) -> reqwest::Response {
+    let key_id = mock.url("/users/zoe#main-key");
+    let (signer, pem) = borrow_key(state, &state.test_user_superadmin, &key_id).await;
+    mock.mount_actor("zoe", &pem);
+    let follow = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": mock.url(&format!("/follows/{}", uuid::Uuid::new_v4())),
+        "type": "Follow",
+        "actor": mock.url("/users/zoe"),
+        "object": state.actor_url(&state.test_user_user),
+    });
+    let mut request = state.inbox_request(
+        &format!("/users/{}/inbox", state.test_user_user.handle),
+        &follow,
+    );
+    signer.sign(&mut request).unwrap();
+
+    state.post_inbox(request).await
+}
+
+#[tokio::test]
+async fn admin_sets_the_allowlist_from_the_dashboard() {
+    // Arrange
+    let state = spawn_app().await;
+    state.login_as(&state.test_user_superadmin).await;
+
+    // Act
+    let response = state
+        .api_client
+        .post(format!("{}/admin/federation", state.app_address))
+        .form(&[
+            ("mode", "allowlist"),
+            (
+                "allowed_domains",
+                "partner.example\r\nhttps://Friends.Example/\r\n",
+            ),
+        ])
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let client = connect_to_db(&state.db_name).await;
+    let mode: String = client
+        .query_one("SELECT federation_mode FROM microblog", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(mode, "allowlist");
+    let domains: Vec<String> = client
+        .query("SELECT domain FROM domain_allow ORDER BY domain", &[])
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+    assert_eq!(domains, vec!["friends.example", "partner.example"]);
+    let html = state.get_admin_dashboard_html().await;
+    assert!(html.contains(r#"<option value="allowlist" selected>"#));
+    assert!(html.contains("friends.example\npartner.example"));
+}
+
+#[tokio::test]
+async fn activities_from_other_domains_are_refused() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    let client = connect_to_db(&state.db_name).await;
+    allow_only(&client, &["partner.example"]).await;
+
+    // Act
+    let refused = follow_as_zoe(&state, &mock).await;
+    client
+        .execute(
+            "INSERT INTO domain_allow (domain) VALUES ('127.0.0.1')",
+            &[],
+        )
+        .await
+        .unwrap();
+    let allowed = follow_as_zoe(&state, &mock).await;
+
+    // Assert
+    assert_eq!(refused.status().as_u16(), 403);
+    assert_eq!(allowed.status().as_u16(), 202);
+    assert_eq!(
+        mock.received("/users/zoe").len(),
+        1,
+        "the key is only fetched once the domain is allowed"
+    );
+}
+
+#[tokio::test]
+async fn deliveries_only_go_to_allowed_domains() {
+    // Arrange
+    let state = spawn_app().await;
+    let client = connect_to_db(&state.db_name).await;
+    allow_only(&client, &["partner.example"]).await;
+    for host in ["social.partner.example", "other.example"] {
+        let actor = format!("https://{}/users/alice", host);
+        client
+            .execute(
+                "INSERT INTO follower (account_id, actor_id, inbox, follow_id, accepted)
+                    VALUES ($1, $2, $3, $4, true)",
+                &[
+                    &state.test_user_user.account_id,
+                    &actor,
+                    &format!("{}/inbox", actor),
+                    &format!("{}#follow", actor),
+                ],
+            )
+            .await
+            .unwrap();
+    }
+    state.login_as(&state.test_user_user).await;
+
+    // Act
+    let body = serde_json::json!({ "content": { "text": "partners only" } });
+    let response = state.post_content(&body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let hosts: Vec<String> = client
+        .query("SELECT host FROM delivery", &[])
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+    assert_eq!(hosts, vec!["social.partner.example"]);
+}
+
+#[tokio::test]
+async fn signed_fetches_from_other_domains_are_refused() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    allow_only(&connect_to_db(&state.db_name).await, &["partner.example"]).await;
+    let (signer, _) = borrow_key(
+        &state,
+        &state.test_user_superadmin,
+        &mock.url("/users/zoe#main-key"),
+    )
+    .await;
+    let actor = state.actor_url(&state.test_user_user);
+    let mut signed = state
+        .api_client
+        .get(&actor)
+        .header("Accept", "application/activity+json")
+        .build()
+        .unwrap();
+    signer.sign(&mut signed).unwrap();
+
+    // Act
+    let refused = state.api_client.execute(signed).await.unwrap();
+    let anonymous = state
+        .get_actor(&state.test_user_user.handle, "application/activity+json")
+        .await;
+
+    // Assert
+    assert_eq!(refused.status().as_u16(), 403);
+    assert_eq!(anonymous.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn nothing_is_fetched_from_other_domains() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    mock.mount_actor("zoe", "PEM");
+    allow_only(&connect_to_db(&state.db_name).await, &["partner.example"]).await;
+    let db = get_database_connection(&state.global_config).await.unwrap();
+    let federation = FederationState::new(true).unwrap();
+
+    // Act
+    let result = remote::actor(&Fetcher::new(&federation), &db, &mock.url("/users/zoe")).await;
+
+    // Assert
+    assert!(result.is_err());
+    assert!(mock.received("/users/zoe").is_empty());
+}
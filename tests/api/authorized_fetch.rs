@@ -0,0 +1,233 @@
+use librhodos::{
+    federation::{fetch::Fetcher, FederationState, Signer},
+    get_database_connection,
+};
+
+use crate::{
+    helpers::{assert_is_redirect_to, connect_to_db, spawn_app, TestState},
+    mock_server::{borrow_key, MockServer},
+};
+
+/// Turn on authorized fetch mode for the tenant of `state`.
+async fn require_signed_fetches(state: &TestState) {
+    connect_to_db(&state.db_name)
+        .await
+        .execute(
+            "INSERT INTO microblog (name, authorized_fetch) VALUES ('test', true)",
+            &[],
+        )
+        .await
+        .unwrap();
+}
+
+/// A signed GET of `url` asking for ActivityPub JSON.
+fn signed_get(state: &TestState, url: &str, signer: &Signer) -> reqwest::Request {
+    let mut request = state
+        .api_client
+        .get(url)
+        .header("Accept", "application/activity+json")
+        .build()
+        .unwrap();
+    signer.sign(&mut request).unwrap();
+
+    request
+}
+
+#[tokio::test]
+async fn admin_turns_on_authorized_fetch_from_the_dashboard() {
+    // Arrange
+    let state = spawn_app().await;
+    state.login_as(&state.test_user_superadmin).await;
+
+    // Act
+    let response = state
+        .api_client
+        .post(format!("{}/admin/federation", state.app_address))
+        .form(&[("mode", "open"), ("authorized_fetch", "on")])
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let authorized_fetch: bool = connect_to_db(&state.db_name)
+        .await
+        .query_one("SELECT authorized_fetch FROM microblog", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert!(authorized_fetch);
+    let html = state.get_admin_dashboard_html().await;
+    assert!(html.contains(r#"name="authorized_fetch" checked"#));
+}
+
+#[tokio::test]
+async fn unsigned_fetches_are_refused_in_authorized_fetch_mode() {
+    // Arrange
+    let state = spawn_app().await;
+    require_signed_fetches(&state).await;
+    state.login_as(&state.test_user_user).await;
+    let body = serde_json::json!({ "content": { "text": "members only" } });
+    assert_eq!(state.post_content(&body).await.status().as_u16(), 200);
+    let post_id: i64 = connect_to_db(&state.db_name)
+        .await
+        .query_one("SELECT id FROM content", &[])
+        .await
+        .unwrap()
+        .get(0);
+    let handle = &state.test_user_user.handle;
+    let actor = state.actor_url(&state.test_user_user);
+
+    // Act
+    let actor_json = state.get_actor(handle, "application/activity+json").await;
+    let outbox = state.get_outbox(handle, "").await;
+    let status = state
+        .get_status(
+            &format!("{}/statuses/{}", actor, post_id),
+            "application/activity+json",
+        )
+        .await;
+    let profile_page = state.get_actor(handle, "text/html").await;
+    let scraped_page = state.get_actor(handle, "*/*").await;
+    let status_page = state
+        .api_client
+        .get(format!("{}/@{}/{}", state.app_address, handle, post_id))
+        .send()
+        .await
+        .unwrap();
+    let instance_actor = state
+        .get_json(&format!("{}/actor", state.app_address))
+        .await;
+
+    // Assert
+    assert_eq!(actor_json.status().as_u16(), 401);
+    assert_eq!(outbox.status().as_u16(), 401);
+    assert_eq!(status.status().as_u16(), 401);
+    assert_eq!(profile_page.status().as_u16(), 200, "pages stay public");
+    assert_eq!(
+        scraped_page.status().as_u16(),
+        401,
+        "only browsers may read pages unsigned"
+    );
+    assert_eq!(status_page.status().as_u16(), 401);
+    assert_eq!(instance_actor["type"], "Application");
+    assert_eq!(
+        instance_actor["publicKey"]["id"],
+        format!("{}/actor#main-key", state.app_address)
+    );
+}
+
+#[tokio::test]
+async fn fetches_signed_by_another_server_are_served() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    require_signed_fetches(&local).await;
+    require_signed_fetches(&remote).await;
+    let signer = remote.instance_signer().await;
+    let db = get_database_connection(&remote.global_config)
+        .await
+        .unwrap();
+    let federation = FederationState::new(true).unwrap();
+
+    // Act
+    let document = Fetcher::new(&federation)
+        .signed_by(Some(&signer))
+        .fetch_json(&db, &local.actor_url(&local.test_user_user))
+        .await;
+
+    // Assert
+    let document = document.expect("the signed fetch was answered");
+    assert_eq!(document["id"], local.actor_url(&local.test_user_user));
+    let cached: i64 = connect_to_db(&local.db_name)
+        .await
+        .query_one(
+            "SELECT COUNT(*) FROM remote_actor WHERE actor_id = $1",
+            &[&format!("{}/actor", remote.app_address)],
+        )
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(
+        cached, 1,
+        "the local server verified the remote instance key"
+    );
+}
+
+#[tokio::test]
+async fn signed_fetches_from_blocked_domains_are_refused() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    require_signed_fetches(&state).await;
+    let (signer, pem) = borrow_key(
+        &state,
+        &state.test_user_superadmin,
+        &mock.url("/users/zoe#main-key"),
+    )
+    .await;
+    mock.mount_actor("zoe", &pem);
+    let actor = state.actor_url(&state.test_user_user);
+
+    // Act
+    let allowed = state
+        .api_client
+        .execute(signed_get(&state, &actor, &signer))
+        .await
+        .unwrap();
+    connect_to_db(&state.db_name)
+        .await
+        .execute(
+            "INSERT INTO domain_block (domain, severity) VALUES ('127.0.0.1', 'suspend')",
+            &[],
+        )
+        .await
+        .unwrap();
+    let blocked = state
+        .api_client
+        .execute(signed_get(&state, &actor, &signer))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(allowed.status().as_u16(), 200);
+    assert_eq!(blocked.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn servers_in_authorized_fetch_mode_can_follow_each_other() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    require_signed_fetches(&local).await;
+    require_signed_fetches(&remote).await;
+    let follow = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}#follows/1", remote.actor_url(&remote.test_user_user)),
+        "type": "Follow",
+        "actor": remote.actor_url(&remote.test_user_user),
+        "object": local.actor_url(&local.test_user_user),
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &follow)
+        .await;
+
+    // Assert
+    assert_eq!(
+        response.status().as_u16(),
+        202,
+        "the key of the follower was fetched with a signed request"
+    );
+    let followers: i64 = connect_to_db(&local.db_name)
+        .await
+        .query_one(
+            "SELECT COUNT(*) FROM follower WHERE actor_id = $1",
+            &[&remote.actor_url(&remote.test_user_user)],
+        )
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(followers, 1);
+}
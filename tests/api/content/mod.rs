@@ -3,6 +3,8 @@ use rand::{distributions::Alphanumeric, thread_rng, Rng};
 pub mod get;
 pub mod post;
 pub mod post_form;
+pub mod publish;
+pub mod read;
 
 fn generate_random_data(len: usize) -> String {
     let mut rng = thread_rng();
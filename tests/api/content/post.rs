@@ -161,3 +161,99 @@ async fn request_missing_authorization_redirect_303() {
     // Assert
     assert_is_redirect_to(&response, "/login")
 }
+
+#[tokio::test]
+async fn new_post_is_published_with_requested_visibility() {
+    // Arrange
+    let state = spawn_app().await;
+    let client = connect_to_db(&state.db_name.clone()).await;
+    state.login_as(&state.test_user_user).await;
+
+    // Act
+    let body = serde_json::json!({
+        "content": {
+            "text": "Just for my followers.",
+            "visibility": "followers",
+        }
+    });
+    let response = state.post_content(&body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let row = client
+        .query_one(
+            "SELECT published, published_at IS NOT NULL, visibility FROM content;",
+            &[],
+        )
+        .await
+        .expect("query to retrieve just added content failed");
+    let published: bool = row.get(0);
+    let has_timestamp: bool = row.get(1);
+    let visibility: &str = row.get(2);
+    assert!(published && has_timestamp, "the post is published");
+    assert_eq!(visibility, "followers");
+}
+
+#[tokio::test]
+async fn unknown_visibility_is_bad_request_400() {
+    // Arrange
+    let state = spawn_app().await;
+    state.login_as(&state.test_user_user).await;
+
+    // Act
+    let body = serde_json::json!({
+        "content": {
+            "text": "Who can see this?",
+            "visibility": "friends",
+        }
+    });
+    let response = state.post_content(&body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn new_post_keeps_its_content_warning() {
+    // Arrange
+    let state = spawn_app().await;
+    let client = connect_to_db(&state.db_name.clone()).await;
+    state.login_as(&state.test_user_user).await;
+
+    // Act
+    let body = serde_json::json!({
+        "content": {
+            "text": "The butler did it.",
+            "cw": "  spoilers ",
+        }
+    });
+    let response = state.post_content(&body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let row = client
+        .query_one("SELECT cw FROM content;", &[])
+        .await
+        .expect("query to retrieve just added content failed");
+    let cw: Option<&str> = row.get(0);
+    assert_eq!(cw, Some("spoilers"));
+}
+
+#[tokio::test]
+async fn content_warning_longer_than_100_chars_is_bad_request_400() {
+    // Arrange
+    let state = spawn_app().await;
+    state.login_as(&state.test_user_user).await;
+
+    // Act
+    let body = serde_json::json!({
+        "content": {
+            "text": "The butler did it.",
+            "cw": generate_random_data(101),
+        }
+    });
+    let response = state.post_content(&body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -19,7 +19,7 @@ pub async fn content_form_invalid_bad_request_422() {
         // Assert
         let status = response.status().as_u16();
         assert!(
-            400 <= status && status <= 499,
+            (400..=499).contains(&status),
             "{} returns Bad Request",
             desc
         );
@@ -0,0 +1,238 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::helpers::{assert_is_redirect_to, connect_to_db, ids, scalar, spawn_app, TestState};
+
+/// Make a remote actor a follower of the test user of `state`, so published
+/// posts are queued for delivery.
+async fn add_follower(state: &TestState) {
+    connect_to_db(&state.db_name)
+        .await
+        .execute(
+            "INSERT INTO follower (account_id, actor_id, inbox, follow_id, accepted) \
+             VALUES ($1, 'https://remote.example/users/zoe', \
+             'https://remote.example/users/zoe/inbox', 'https://remote.example/follows/1', true)",
+            &[&state.test_user_user.account_id],
+        )
+        .await
+        .unwrap();
+}
+
+async fn publish(state: &TestState, id: i64, body: &serde_json::Value) -> reqwest::Response {
+    state
+        .api_client
+        .post(format!("{}/content/{}/publish", state.app_address, id))
+        .json(body)
+        .send()
+        .await
+        .expect("Failed to publish content")
+}
+
+#[tokio::test]
+async fn drafts_are_only_delivered_once_published() {
+    // Arrange
+    let state = spawn_app().await;
+    add_follower(&state).await;
+    state.login_as(&state.test_user_user).await;
+    let body = serde_json::json!({ "content": { "text": "Not yet", "draft": true } });
+    assert_eq!(state.post_content(&body).await.status().as_u16(), 200);
+    let id = scalar(&state, "SELECT id FROM content").await;
+    let edit = state
+        .api_client
+        .post(format!("{}/content/{}/edit", state.app_address, id))
+        .json(&serde_json::json!({ "content": { "text": "Now" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(edit.status().as_u16(), 200);
+    let drafts: serde_json::Value = state.get_content("/drafts").await.json().await.unwrap();
+    assert_eq!(ids(&drafts), vec![id]);
+    assert_eq!(scalar(&state, "SELECT count(*) FROM delivery").await, 0);
+
+    // Act
+    let response = publish(&state, id, &serde_json::json!({})).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let post: serde_json::Value = state
+        .get_content(&format!("/{}", id))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(post["published"], true);
+    assert_eq!(post["text"], "Now");
+    assert!(post["published_at"].is_string());
+    assert!(post["edited_at"].is_null());
+    assert_eq!(scalar(&state, "SELECT count(*) FROM delivery").await, 1);
+    let drafts: serde_json::Value = state.get_content("/drafts").await.json().await.unwrap();
+    assert_eq!(ids(&drafts), Vec::<i64>::new());
+    let again = publish(&state, id, &serde_json::json!({})).await;
+    assert_eq!(again.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn scheduled_posts_are_published_when_due() {
+    // Arrange
+    let state = spawn_app().await;
+    add_follower(&state).await;
+    state.login_as(&state.test_user_user).await;
+    let at = (Utc::now() + chrono::Duration::seconds(2)).to_rfc3339();
+    let body = serde_json::json!({ "content": { "text": "Later", "scheduled_at": at } });
+
+    // Act
+    let response = state.post_content(&body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let scheduled: serde_json::Value = state.get_content("/scheduled").await.json().await.unwrap();
+    assert_eq!(scheduled.as_array().unwrap().len(), 1);
+    assert_eq!(scheduled[0]["published"], false);
+    assert!(scheduled[0]["scheduled_at"].is_string());
+    let drafts: serde_json::Value = state.get_content("/drafts").await.json().await.unwrap();
+    assert_eq!(ids(&drafts), Vec::<i64>::new());
+    let mut delivered = 0;
+    for _ in 0..100 {
+        delivered = scalar(&state, "SELECT count(*) FROM delivery").await;
+        if delivered > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(delivered, 1);
+    let create: String = connect_to_db(&state.db_name)
+        .await
+        .query_one("SELECT activity::text FROM delivery", &[])
+        .await
+        .unwrap()
+        .get(0);
+    let create: serde_json::Value = serde_json::from_str(&create).unwrap();
+    let actor = state.actor_url(&state.test_user_user);
+    let id = scalar(&state, "SELECT id FROM content").await;
+    assert_eq!(
+        create["actor"], actor,
+        "the ids are those of the served actor"
+    );
+    assert_eq!(create["object"]["id"], format!("{}/statuses/{}", actor, id));
+    let published = scalar(
+        &state,
+        "SELECT count(*) FROM content \
+         WHERE published AND published_at IS NOT NULL AND scheduled_at IS NULL",
+    )
+    .await;
+    assert_eq!(published, 1);
+}
+
+#[tokio::test]
+async fn invalid_schedules_are_rejected() {
+    // Arrange
+    let state = spawn_app().await;
+    state.login_as(&state.test_user_user).await;
+    let future = (Utc::now() + chrono::Duration::hours(1)).to_rfc3339();
+    let past = (Utc::now() - chrono::Duration::hours(1)).to_rfc3339();
+    let cases = vec![
+        (
+            serde_json::json!({ "text": "a", "scheduled_at": past }),
+            "scheduled in the past",
+        ),
+        (
+            serde_json::json!({ "text": "a", "scheduled_at": "tomorrow" }),
+            "not a time",
+        ),
+        (
+            serde_json::json!({ "text": "a", "draft": true, "scheduled_at": future }),
+            "both a draft and scheduled",
+        ),
+    ];
+
+    for (content, desc) in cases {
+        // Act
+        let response = state
+            .post_content(&serde_json::json!({ "content": content }))
+            .await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 400, "{}", desc);
+    }
+    assert_eq!(scalar(&state, "SELECT count(*) FROM content").await, 0);
+}
+
+#[tokio::test]
+async fn malformed_publish_bodies_do_not_publish() {
+    // Arrange
+    let state = spawn_app().await;
+    state.login_as(&state.test_user_user).await;
+    let body = serde_json::json!({ "content": { "text": "Not yet", "draft": true } });
+    assert_eq!(state.post_content(&body).await.status().as_u16(), 200);
+    let id = scalar(&state, "SELECT id FROM content").await;
+    let url = format!("{}/content/{}/publish", state.app_address, id);
+    let cases = vec![
+        (r#"{"scheduled_at": 1700000000}"#, "a non-string time"),
+        (r#"{"scheduled_at": "#, "truncated JSON"),
+        ("scheduled_at=tomorrow", "a form body"),
+    ];
+
+    for (body, desc) in cases {
+        // Act
+        let response = state
+            .api_client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 400, "{}", desc);
+    }
+    assert_eq!(
+        scalar(&state, "SELECT count(*) FROM content WHERE published").await,
+        0
+    );
+
+    // Act
+    let response = state.api_client.post(&url).send().await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200, "an empty body publishes");
+    assert_eq!(
+        scalar(&state, "SELECT count(*) FROM content WHERE published").await,
+        1
+    );
+}
+
+#[tokio::test]
+async fn drafts_are_listed_and_published_from_the_form() {
+    // Arrange
+    let state = spawn_app().await;
+    state.login_as(&state.test_user_user).await;
+    let saved = state
+        .post_content_form(&serde_json::json!({ "content": "Draft <b>text</b>", "draft": "true" }))
+        .await;
+    assert_is_redirect_to(&saved, "/content/form");
+    let id = scalar(&state, "SELECT id FROM content").await;
+
+    // Act
+    let html = state.get_content_form_html().await;
+    let response = state
+        .api_client
+        .post(format!("{}/content/form/{}/publish", state.app_address, id))
+        .form(&serde_json::json!({}))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html.contains("<h2>Drafts</h2>"));
+    assert!(html.contains("Draft &lt;b&gt;text&lt;/b&gt;"));
+    assert!(html.contains(&format!(r#"action="/content/form/{}/publish""#, id)));
+    assert_is_redirect_to(&response, "/content/form");
+    let published = scalar(&state, "SELECT count(*) FROM content WHERE published").await;
+    assert_eq!(published, 1);
+    assert!(!state
+        .get_content_form_html()
+        .await
+        .contains("<h2>Drafts</h2>"));
+}
@@ -0,0 +1,131 @@
+use tokio_postgres::Client;
+
+use crate::helpers::{connect_to_db, ids, insert_post, spawn_app};
+
+async fn insert_draft(client: &Client, account_id: i64, body: &str) -> i64 {
+    client
+        .query_one(
+            "INSERT INTO content (publisher_id, body, published) VALUES ($1, $2, false) RETURNING id",
+            &[&account_id, &body],
+        )
+        .await
+        .expect("query to insert a draft failed")
+        .get(0)
+}
+
+#[tokio::test]
+async fn posts_are_fetched_by_id() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    let id = insert_post(&client, user.account_id, "Hello", "public").await;
+    state.login_as(&state.test_user_superadmin).await;
+
+    // Act
+    let response = state.get_content(&format!("/{}", id)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let post: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(post["id"], id);
+    assert_eq!(post["account"], user.handle);
+    assert_eq!(post["text"], "Hello");
+    assert_eq!(post["published"], true);
+    assert_eq!(
+        post["url"],
+        format!(
+            "{}/users/{}/statuses/{}",
+            state.app_address, user.handle, id
+        )
+    );
+    let missing = state.get_content(&format!("/{}", id + 1000)).await;
+    assert_eq!(missing.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn drafts_are_only_seen_by_their_author() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    insert_post(&client, user.account_id, "Published", "public").await;
+    let draft = insert_draft(&client, user.account_id, "Not yet").await;
+    let private = insert_post(&client, user.account_id, "Friends", "followers").await;
+
+    // Act
+    state.login_as(user).await;
+    let own_draft = state.get_content(&format!("/{}", draft)).await;
+    let own_drafts: serde_json::Value = state.get_content("/drafts").await.json().await.unwrap();
+    state.login_as(&state.test_user_superadmin).await;
+    let other_draft = state.get_content(&format!("/{}", draft)).await;
+    let other_private = state.get_content(&format!("/{}", private)).await;
+    let other_drafts: serde_json::Value = state.get_content("/drafts").await.json().await.unwrap();
+
+    // Assert
+    assert_eq!(own_draft.status().as_u16(), 200);
+    let own_draft: serde_json::Value = own_draft.json().await.unwrap();
+    assert_eq!(own_draft["published"], false);
+    assert!(own_draft["url"].is_null());
+    assert_eq!(ids(&own_drafts), vec![draft]);
+    assert_eq!(other_draft.status().as_u16(), 404);
+    assert_eq!(other_private.status().as_u16(), 404);
+    assert_eq!(ids(&other_drafts), Vec::<i64>::new());
+}
+
+#[tokio::test]
+async fn account_posts_are_paged_with_link_headers() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    let mut posts = vec![];
+    for text in ["one", "two", "three"] {
+        posts.push(insert_post(&client, user.account_id, text, "public").await);
+    }
+    insert_post(&client, user.account_id, "friends only", "followers").await;
+    insert_draft(&client, user.account_id, "draft").await;
+    state.login_as(&state.test_user_superadmin).await;
+    let path = format!("/accounts/{}", user.handle);
+
+    // Act
+    let response = state.get_content(&format!("{}?limit=2", path)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let url = format!("{}/content{}", state.app_address, path);
+    assert_eq!(
+        response.headers()["link"].to_str().unwrap(),
+        format!(
+            r#"<{url}?limit=2&max_id={}>; rel="next", <{url}?limit=2&min_id={}>; rel="prev""#,
+            posts[1], posts[2]
+        )
+    );
+    let first: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(ids(&first), vec![posts[2], posts[1]]);
+
+    let next = state
+        .get_content(&format!("{}?limit=2&max_id={}", path, posts[1]))
+        .await;
+    let next: serde_json::Value = next.json().await.unwrap();
+    assert_eq!(ids(&next), vec![posts[0]]);
+
+    let after = state
+        .get_content(&format!("{}?limit=1&min_id={}", path, posts[0]))
+        .await;
+    let after: serde_json::Value = after.json().await.unwrap();
+    assert_eq!(ids(&after), vec![posts[1]], "min_id pages forwards");
+
+    let since = state
+        .get_content(&format!("{}?limit=1&since_id={}", path, posts[0]))
+        .await;
+    let since: serde_json::Value = since.json().await.unwrap();
+    assert_eq!(ids(&since), vec![posts[2]], "since_id starts at the newest");
+
+    let empty = state
+        .get_content(&format!("{}?max_id={}", path, posts[0]))
+        .await;
+    assert!(empty.headers().get("link").is_none());
+    let missing = state.get_content("/accounts/nobody").await;
+    assert_eq!(missing.status().as_u16(), 404);
+}
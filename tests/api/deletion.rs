@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use librhodos::{db, get_database_connection};
+
+use crate::{
+    helpers::{add_mock_follower, connect_to_db, scalar, spawn_app, TestState},
+    mock_server::{MockServer, ReceivedRequest},
+};
+
+/// Wait for an activity of type `kind` delivered to `path` on `mock`.
+async fn delivery_of(mock: &MockServer, path: &str, kind: &str) -> serde_json::Value {
+    for _ in 0..100 {
+        let received: Vec<ReceivedRequest> = mock.received(path);
+        if let Some(activity) = received
+            .iter()
+            .map(|r| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap())
+            .find(|a| a["type"] == kind)
+        {
+            return activity;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    panic!("no {} was delivered to {}", kind, path);
+}
+
+async fn delete(state: &TestState, id: i64) -> reqwest::Response {
+    state
+        .api_client
+        .post(format!("{}/content/{}/delete", state.app_address, id))
+        .send()
+        .await
+        .expect("Failed to delete content")
+}
+
+#[tokio::test]
+async fn deleted_posts_become_tombstones() {
+    // Arrange
+    let app = spawn_app().await;
+    let mock = MockServer::start().await;
+    add_mock_follower(&app, &mock).await;
+    app.login_as(&app.test_user_user).await;
+    let body = serde_json::json!({
+        "content": {
+            "text": "Which #cats?",
+            "poll": { "options": ["black", "white"] },
+        }
+    });
+    assert_eq!(app.post_content(&body).await.status().as_u16(), 200);
+    let id: i64 = scalar(&app, "SELECT id FROM content").await;
+    let created = delivery_of(&mock, "/users/zoe/inbox", "Create").await;
+    let edit = app
+        .api_client
+        .post(format!("{}/content/{}/edit", app.app_address, id))
+        .json(&serde_json::json!({ "content": { "text": "Which #cats now?" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(edit.status().as_u16(), 200);
+
+    // Act
+    let response = delete(&app, id).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let row = connect_to_db(&app.db_name)
+        .await
+        .query_one(
+            "SELECT body IS NULL, deleted_at IS NOT NULL FROM content WHERE id = $1",
+            &[&id],
+        )
+        .await
+        .unwrap();
+    assert!(row.get::<_, bool>(0), "the text is removed");
+    assert!(row.get::<_, bool>(1), "the post is a tombstone");
+    assert_eq!(scalar(&app, "SELECT count(*) FROM content_tag").await, 0);
+    assert_eq!(scalar(&app, "SELECT count(*) FROM poll").await, 0);
+    assert_eq!(
+        scalar(&app, "SELECT count(*) FROM content_revision").await,
+        0
+    );
+    let deleted = delivery_of(&mock, "/users/zoe/inbox", "Delete").await;
+    assert_eq!(deleted["actor"], app.actor_url(&app.test_user_user));
+    assert_eq!(deleted["object"]["id"], created["object"]["id"]);
+    assert_eq!(deleted["object"]["type"], "Tombstone");
+    let status = app
+        .api_client
+        .get(created["object"]["id"].as_str().unwrap())
+        .header("Accept", "application/activity+json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(status.status().as_u16(), 410);
+    assert_eq!(
+        app.get_content(&format!("/{}", id)).await.status().as_u16(),
+        404
+    );
+    assert_eq!(delete(&app, id).await.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn posts_of_others_cannot_be_deleted() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.test_user_superadmin).await;
+    let body = serde_json::json!({ "content": { "text": "mine" } });
+    assert_eq!(app.post_content(&body).await.status().as_u16(), 200);
+    let id: i64 = scalar(&app, "SELECT id FROM content").await;
+    app.post_logout().await;
+    app.login_as(&app.test_user_user).await;
+
+    // Act
+    let response = delete(&app, id).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(
+        scalar(
+            &app,
+            "SELECT count(*) FROM content WHERE deleted_at IS NULL"
+        )
+        .await,
+        1
+    );
+}
+
+#[tokio::test]
+async fn tombstones_are_purged_after_the_retention_period() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = connect_to_db(&app.db_name).await;
+    for days in [40, 10] {
+        client
+            .execute(
+                "INSERT INTO content (publisher_id, published, deleted_at) \
+                 VALUES ($1, true, now() - make_interval(days => $2))",
+                &[&app.test_user_user.account_id, &days],
+            )
+            .await
+            .unwrap();
+    }
+    let conn = get_database_connection(&app.global_config).await.unwrap();
+    let retention =
+        chrono::Duration::days(app.global_config.server.tombstone_retention_days.into());
+
+    // Act
+    let purged = db::content::purge_deleted(&conn, chrono::Utc::now().naive_utc() - retention)
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(purged, 1);
+    assert_eq!(scalar(&app, "SELECT count(*) FROM content").await, 1);
+}
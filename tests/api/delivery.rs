@@ -0,0 +1,462 @@
+use std::time::Duration;
+
+use tokio_postgres::Client;
+use uuid::Uuid;
+
+use crate::helpers::{connect_to_db, publish, spawn_app, TestState};
+
+/// Make the test user of `remote` an accepted follower of the test user of
+/// `local`, as seen by both servers.
+async fn follow(remote: &TestState, local: &TestState) {
+    let follower = remote.actor_url(&remote.test_user_user);
+    let followee = local.actor_url(&local.test_user_user);
+    let follow_id = format!("{}/activities/{}", remote.app_address, Uuid::new_v4());
+    connect_to_db(&remote.db_name)
+        .await
+        .execute(
+            "INSERT INTO following (account_id, actor_id, follow_id, accepted)
+                VALUES ($1, $2, $3, true)",
+            &[&remote.test_user_user.account_id, &followee, &follow_id],
+        )
+        .await
+        .unwrap();
+    add_follower(
+        &connect_to_db(&local.db_name).await,
+        local.test_user_user.account_id,
+        &follower,
+        &format!("{}/inbox", follower),
+        Some(&format!("{}/inbox", remote.app_address)),
+    )
+    .await;
+}
+
+async fn add_follower(
+    client: &Client,
+    account_id: i64,
+    actor: &str,
+    inbox: &str,
+    shared_inbox: Option<&str>,
+) {
+    client
+        .execute(
+            "INSERT INTO follower (account_id, actor_id, inbox, shared_inbox, follow_id, accepted)
+                VALUES ($1, $2, $3, $4, $5, true)",
+            &[
+                &account_id,
+                &actor,
+                &inbox,
+                &shared_inbox,
+                &format!("{}#follow", actor),
+            ],
+        )
+        .await
+        .unwrap();
+}
+
+/// Poll `query` until it returns a row for which `done` holds.
+async fn wait_for<F>(client: &Client, query: &str, done: F) -> Option<tokio_postgres::Row>
+where
+    F: Fn(&tokio_postgres::Row) -> bool,
+{
+    for _ in 0..100 {
+        let row = client.query_opt(query, &[]).await.unwrap();
+        if let Some(row) = row.filter(|r| done(r)) {
+            return Some(row);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    None
+}
+
+fn host_of(state: &TestState) -> String {
+    state.app_address.trim_start_matches("http://").to_string()
+}
+
+#[tokio::test]
+async fn new_post_is_delivered_to_followers() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    follow(&remote, &local).await;
+
+    // Act
+    publish(&local, "hello followers", "public").await;
+
+    // Assert
+    let remote_db = connect_to_db(&remote.db_name).await;
+    let row = wait_for(
+        &remote_db,
+        "SELECT actor_id, content FROM remote_post",
+        |_| true,
+    )
+    .await
+    .expect("the follower's server received the post");
+    let actor: &str = row.get(0);
+    let content: &str = row.get(1);
+    assert_eq!(actor, local.actor_url(&local.test_user_user));
+    assert_eq!(content, "<p>hello followers</p>");
+
+    let local_db = connect_to_db(&local.db_name).await;
+    let row = wait_for(
+        &local_db,
+        "SELECT status, attempts, inbox FROM delivery",
+        |r| r.get::<_, &str>(0) == "delivered",
+    )
+    .await
+    .expect("the delivery is recorded");
+    let attempts: i32 = row.get(1);
+    let inbox: &str = row.get(2);
+    assert_eq!(attempts, 1);
+    assert_eq!(inbox, format!("{}/inbox", remote.app_address));
+}
+
+#[tokio::test]
+async fn followers_sharing_an_inbox_get_one_delivery() {
+    // Arrange
+    let state = spawn_app().await;
+    let client = connect_to_db(&state.db_name).await;
+    let account_id = state.test_user_user.account_id;
+    for name in ["alice", "bob"] {
+        add_follower(
+            &client,
+            account_id,
+            &format!("https://shared.example/users/{}", name),
+            &format!("https://shared.example/users/{}/inbox", name),
+            Some("https://shared.example/inbox"),
+        )
+        .await;
+    }
+    add_follower(
+        &client,
+        account_id,
+        "https://solo.example/users/carol",
+        "https://solo.example/users/carol/inbox",
+        None,
+    )
+    .await;
+
+    // Act
+    publish(&state, "hello everyone", "public").await;
+
+    // Assert
+    let rows = client
+        .query("SELECT inbox, host FROM delivery ORDER BY inbox", &[])
+        .await
+        .unwrap();
+    let deliveries: Vec<(String, String)> = rows.iter().map(|r| (r.get(0), r.get(1))).collect();
+    assert_eq!(
+        deliveries,
+        vec![
+            (
+                "https://shared.example/inbox".to_string(),
+                "shared.example".to_string()
+            ),
+            (
+                "https://solo.example/users/carol/inbox".to_string(),
+                "solo.example".to_string()
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn direct_posts_are_not_delivered_to_followers() {
+    // Arrange
+    let state = spawn_app().await;
+    let client = connect_to_db(&state.db_name).await;
+    add_follower(
+        &client,
+        state.test_user_user.account_id,
+        "https://solo.example/users/carol",
+        "https://solo.example/users/carol/inbox",
+        None,
+    )
+    .await;
+    state.login_as(&state.test_user_user).await;
+
+    // Act
+    let body = serde_json::json!({ "content": { "text": "psst", "visibility": "direct" } });
+    let response = state.post_content(&body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let row = client
+        .query_one("SELECT count(*) FROM delivery", &[])
+        .await
+        .unwrap();
+    assert_eq!(row.get::<_, i64>(0), 0);
+}
+
+#[tokio::test]
+async fn failing_host_backs_off() {
+    // Arrange
+    let state = spawn_app().await;
+    let client = connect_to_db(&state.db_name).await;
+    // Nothing is listening on a port we just gave back.
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let host = format!("127.0.0.1:{}", port);
+    add_follower(
+        &client,
+        state.test_user_user.account_id,
+        &format!("http://{}/users/dave", host),
+        &format!("http://{}/users/dave/inbox", host),
+        None,
+    )
+    .await;
+
+    // Act
+    publish(&state, "is anyone there?", "public").await;
+
+    // Assert
+    let row = wait_for(
+        &client,
+        "SELECT status, last_error, next_attempt_at > (now() AT TIME ZONE 'UTC') + interval '10 seconds'
+            FROM delivery WHERE attempts = 1",
+        |_| true,
+    )
+    .await
+    .expect("the delivery was attempted");
+    let status: &str = row.get(0);
+    let last_error: Option<&str> = row.get(1);
+    let postponed: bool = row.get(2);
+    assert_eq!(status, "pending", "the delivery will be retried");
+    assert!(last_error.is_some());
+    assert!(postponed, "the retry waits for the host's backoff");
+
+    let row = client
+        .query_one(
+            "SELECT failures, unreachable_since IS NULL FROM delivery_host WHERE host = $1",
+            &[&host],
+        )
+        .await
+        .expect("the host's failure is recorded");
+    assert_eq!(row.get::<_, i32>(0), 1);
+    assert!(row.get::<_, bool>(1), "one failure is not unreachable");
+}
+
+#[tokio::test]
+async fn host_is_marked_unreachable_after_repeated_failures() {
+    // Arrange
+    let state = spawn_app().await;
+    let client = connect_to_db(&state.db_name).await;
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let host = format!("127.0.0.1:{}", port);
+    add_follower(
+        &client,
+        state.test_user_user.account_id,
+        &format!("http://{}/users/dave", host),
+        &format!("http://{}/users/dave/inbox", host),
+        None,
+    )
+    .await;
+    // The host has failed many times before and is due for another try.
+    client
+        .execute(
+            "INSERT INTO delivery_host (host, failures, next_attempt_at)
+                VALUES ($1, 9, now() AT TIME ZONE 'UTC' - interval '1 minute')",
+            &[&host],
+        )
+        .await
+        .unwrap();
+
+    // Act
+    publish(&state, "is anyone there?", "public").await;
+
+    // Assert
+    let row = wait_for(
+        &client,
+        &format!(
+            "SELECT unreachable_since IS NOT NULL FROM delivery_host
+                WHERE host = '{}' AND failures = 10",
+            host
+        ),
+        |_| true,
+    )
+    .await
+    .expect("the failure is recorded");
+    assert!(row.get::<_, bool>(0), "the host is unreachable");
+}
+
+#[tokio::test]
+async fn queued_deliveries_resume_when_host_recovers() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    follow(&remote, &local).await;
+    let client = connect_to_db(&local.db_name).await;
+    let host = host_of(&remote);
+    client
+        .execute(
+            "INSERT INTO delivery_host (host, failures, next_attempt_at, unreachable_since)
+                VALUES ($1, 12, now() AT TIME ZONE 'UTC' + interval '1 hour',
+                    now() AT TIME ZONE 'UTC' - interval '1 day')",
+            &[&host],
+        )
+        .await
+        .unwrap();
+    publish(&local, "catching up", "public").await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let row = client
+        .query_one("SELECT status, attempts FROM delivery", &[])
+        .await
+        .unwrap();
+    assert_eq!(row.get::<_, &str>(0), "pending");
+    assert_eq!(
+        row.get::<_, i32>(1),
+        0,
+        "nothing is sent to an unreachable host before its next probe"
+    );
+
+    // Act
+    client
+        .execute(
+            "UPDATE delivery_host SET next_attempt_at = now() AT TIME ZONE 'UTC' WHERE host = $1",
+            &[&host],
+        )
+        .await
+        .unwrap();
+
+    // Assert
+    wait_for(&client, "SELECT status FROM delivery", |r| {
+        r.get::<_, &str>(0) == "delivered"
+    })
+    .await
+    .expect("the queued delivery went out");
+    let row = client
+        .query_one(
+            "SELECT failures, unreachable_since IS NULL, last_success_at IS NOT NULL
+                FROM delivery_host WHERE host = $1",
+            &[&host],
+        )
+        .await
+        .unwrap();
+    assert_eq!(row.get::<_, i32>(0), 0);
+    assert!(row.get::<_, bool>(1), "the host is reachable again");
+    assert!(row.get::<_, bool>(2));
+    let row = wait_for(
+        &connect_to_db(&remote.db_name).await,
+        "SELECT content FROM remote_post",
+        |_| true,
+    )
+    .await
+    .expect("the post arrived");
+    assert_eq!(row.get::<_, &str>(0), "<p>catching up</p>");
+}
+
+#[tokio::test]
+async fn hung_host_does_not_hold_up_other_hosts() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    let client = connect_to_db(&local.db_name).await;
+    // Connections are accepted, but no response ever comes.
+    let hung = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let hung_host = hung.local_addr().unwrap().to_string();
+    add_follower(
+        &client,
+        local.test_user_user.account_id,
+        &format!("http://{}/users/dave", hung_host),
+        &format!("http://{}/users/dave/inbox", hung_host),
+        None,
+    )
+    .await;
+    publish(&local, "is anyone there?", "public").await;
+    wait_for(
+        &client,
+        "SELECT 1 FROM delivery
+            WHERE next_attempt_at > (now() AT TIME ZONE 'UTC') + interval '1 minute'",
+        |_| true,
+    )
+    .await
+    .expect("the delivery to the hung host is under way");
+    follow(&remote, &local).await;
+
+    // Act
+    publish(&local, "hello", "public").await;
+
+    // Assert
+    wait_for(
+        &client,
+        &format!(
+            "SELECT 1 FROM delivery WHERE host = '{}' AND status = 'delivered'",
+            host_of(&remote)
+        ),
+        |_| true,
+    )
+    .await
+    .expect("the other host got its delivery");
+    let row = client
+        .query_one(
+            "SELECT count(*) FROM delivery WHERE host = $1 AND attempts = 0",
+            &[&hung_host],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        row.get::<_, i64>(0),
+        2,
+        "the hung host is still being waited for"
+    );
+}
+
+#[tokio::test]
+async fn pending_and_failed_deliveries_are_listed_for_admins() {
+    // Arrange
+    let state = spawn_app().await;
+    let client = connect_to_db(&state.db_name).await;
+    client
+        .execute(
+            "INSERT INTO delivery (account_id, actor_id, inbox, host, activity, status, attempts,
+                next_attempt_at, last_error)
+            VALUES
+                ($1, 'https://local.example/users/a', 'https://slow.example/inbox',
+                    'slow.example', '{\"type\": \"Create\"}', 'pending', 3,
+                    now() AT TIME ZONE 'UTC' + interval '1 hour', 'connection timed out'),
+                ($1, 'https://local.example/users/a', 'https://gone.example/inbox',
+                    'gone.example', '{\"type\": \"Update\"}', 'failed', 1,
+                    now() AT TIME ZONE 'UTC', 'the inbox answered 410 Gone')",
+            &[&state.test_user_user.account_id],
+        )
+        .await
+        .unwrap();
+    client
+        .execute(
+            "INSERT INTO delivery_host (host, failures, next_attempt_at)
+                VALUES ('slow.example', 3, now() AT TIME ZONE 'UTC' + interval '1 hour')",
+            &[],
+        )
+        .await
+        .unwrap();
+    state.login_as(&state.test_user_superadmin).await;
+
+    // Act
+    let page = state
+        .api_client
+        .get(format!("{}/admin/deliveries", state.app_address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    for expected in [
+        "https://slow.example/inbox",
+        "connection timed out",
+        "https://gone.example/inbox",
+        "the inbox answered 410 Gone",
+        "<td>slow.example</td>",
+    ] {
+        assert!(page.contains(expected), "{}", expected);
+    }
+}
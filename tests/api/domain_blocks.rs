@@ -0,0 +1,288 @@
+use std::time::Duration;
+
+use tokio_postgres::Client;
+
+use crate::{
+    helpers::{assert_is_redirect_to, connect_to_db, spawn_app, TestState},
+    mock_server::{borrow_key, MockServer},
+};
+
+async fn block(client: &Client, domain: &str, severity: &str, reject_media: bool) {
+    client
+        .execute(
+            "INSERT INTO domain_block (domain, severity, reject_media) VALUES ($1, $2, $3)",
+            &[&domain, &severity, &reject_media],
+        )
+        .await
+        .unwrap();
+}
+
+/// Sign `activity` as the mock actor zoe and deliver it to `path`.
+async fn deliver_as_zoe(
+    state: &TestState,
+    mock: &MockServer,
+    path: &str,
+    activity: &serde_json::Value,
+) -> reqwest::Response {
+    let key_id = mock.url("/users/zoe#main-key");
+    let (signer, pem) = borrow_key(state, &state.test_user_superadmin, &key_id).await;
+    mock.mount_actor("zoe", &pem);
+    let mut request = state.inbox_request(path, activity);
+    signer.sign(&mut request).unwrap();
+
+    state.post_inbox(request).await
+}
+
+fn follow(state: &TestState, mock: &MockServer) -> serde_json::Value {
+    serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": mock.url(&format!("/follows/{}", uuid::Uuid::new_v4())),
+        "type": "Follow",
+        "actor": mock.url("/users/zoe"),
+        "object": state.actor_url(&state.test_user_user),
+    })
+}
+
+#[tokio::test]
+async fn block_lists_are_imported_and_exported() {
+    // Arrange
+    let state = spawn_app().await;
+    state.login_as(&state.test_user_superadmin).await;
+    let csv = "#domain,#severity,#reject_media,#reject_reports,#public_comment,#obfuscate\n\
+               spam.example,suspend,false,false,Spam,false\n\
+               loud.example,silence,true,true,\"rude, often\",false\n";
+
+    // Act
+    let response = state
+        .post_domain_blocks_form("/import", &[("csv", csv)])
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/domain-blocks");
+    let html = state.get_domain_blocks("").await.text().await.unwrap();
+    assert!(html.contains("spam.example"));
+    assert!(html.contains("rude, often"));
+    let response = state.get_domain_blocks("/export").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .headers()
+        .get("Content-Type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("text/csv"));
+    let exported = response.text().await.unwrap();
+    assert_eq!(
+        exported,
+        "#domain,#severity,#reject_media,#reject_reports,#public_comment,#obfuscate\n\
+         loud.example,silence,true,true,\"rude, often\",false\n\
+         spam.example,suspend,false,false,Spam,false\n"
+    );
+}
+
+#[tokio::test]
+async fn invalid_block_lists_are_rejected_as_a_whole() {
+    // Arrange
+    let state = spawn_app().await;
+    state.login_as(&state.test_user_superadmin).await;
+
+    // Act
+    let response = state
+        .post_domain_blocks_form("/import", &[("csv", "ok.example\nnot a domain\n")])
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let count: i64 = connect_to_db(&state.db_name)
+        .await
+        .query_one("SELECT count(*) FROM domain_block", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn only_admins_manage_domain_blocks() {
+    // Arrange
+    let state = spawn_app().await;
+    state.login_as(&state.test_user_user).await;
+
+    // Act
+    let response = state
+        .post_domain_blocks_form("", &[("domain", "spam.example")])
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 303);
+    let count: i64 = connect_to_db(&state.db_name)
+        .await
+        .query_one("SELECT count(*) FROM domain_block", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn suspended_domains_are_refused_at_the_inbox() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    block(
+        &connect_to_db(&state.db_name).await,
+        "127.0.0.1",
+        "suspend",
+        false,
+    )
+    .await;
+
+    // Act
+    let response = deliver_as_zoe(
+        &state,
+        &mock,
+        &format!("/users/{}/inbox", state.test_user_user.handle),
+        &follow(&state, &mock),
+    )
+    .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(
+        mock.received("/users/zoe").is_empty(),
+        "the key of a suspended domain is not fetched"
+    );
+}
+
+#[tokio::test]
+async fn suspending_a_domain_severs_follows_and_stops_delivery() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    let response = deliver_as_zoe(
+        &state,
+        &mock,
+        &format!("/users/{}/inbox", state.test_user_user.handle),
+        &follow(&state, &mock),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let client = connect_to_db(&state.db_name).await;
+
+    // Act
+    state.login_as(&state.test_user_superadmin).await;
+    let response = state
+        .post_domain_blocks_form(
+            "",
+            &[("domain", "http://127.0.0.1/"), ("severity", "suspend")],
+        )
+        .await;
+    state.login_as(&state.test_user_user).await;
+    let body = serde_json::json!({ "content": { "text": "hello?" } });
+    assert_eq!(state.post_content(&body).await.status().as_u16(), 200);
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/domain-blocks");
+    let followers: i64 = client
+        .query_one("SELECT count(*) FROM follower", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(followers, 0, "the follow is severed");
+    let pending: i64 = client
+        .query_one(
+            "SELECT count(*) FROM delivery WHERE status = 'pending'",
+            &[],
+        )
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(pending, 0, "nothing is sent to the suspended domain");
+}
+
+#[tokio::test]
+async fn follows_from_silenced_domains_need_approval() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    block(
+        &connect_to_db(&state.db_name).await,
+        "127.0.0.1",
+        "silence",
+        false,
+    )
+    .await;
+
+    // Act
+    let response = deliver_as_zoe(
+        &state,
+        &mock,
+        &format!("/users/{}/inbox", state.test_user_user.handle),
+        &follow(&state, &mock),
+    )
+    .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let accepted: bool = connect_to_db(&state.db_name)
+        .await
+        .query_one("SELECT accepted FROM follower", &[])
+        .await
+        .expect("the follow request is kept")
+        .get(0);
+    assert!(!accepted);
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    assert!(
+        mock.received("/users/zoe/inbox").is_empty(),
+        "the follow is not accepted automatically"
+    );
+}
+
+#[tokio::test]
+async fn media_from_domains_with_rejected_media_is_dropped() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    block(
+        &connect_to_db(&state.db_name).await,
+        "127.0.0.1",
+        "noop",
+        true,
+    )
+    .await;
+    let note_id = mock.url("/notes/1");
+    let create = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": mock.url("/notes/1/activity"),
+        "type": "Create",
+        "actor": mock.url("/users/zoe"),
+        "object": {
+            "id": note_id,
+            "type": "Note",
+            "attributedTo": mock.url("/users/zoe"),
+            "content": "<p>look</p>",
+            "to": [state.actor_url(&state.test_user_user)],
+            "attachment": {
+                "type": "Document",
+                "mediaType": "image/png",
+                "url": mock.url("/media/1.png"),
+            },
+        },
+    });
+
+    // Act
+    let response = deliver_as_zoe(&state, &mock, "/inbox", &create).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let row = connect_to_db(&state.db_name)
+        .await
+        .query_one(
+            "SELECT content, attachment::text FROM remote_post WHERE object_id=$1",
+            &[&note_id],
+        )
+        .await
+        .expect("the post itself is kept");
+    assert_eq!(row.get::<_, &str>(0), "<p>look</p>");
+    assert_eq!(row.get::<_, &str>(1), "[]");
+}
@@ -19,7 +19,7 @@ async fn send_email() {
         Secret::from("password".to_string()),
     );
     let email_client = EmailClient::new(UserEmail::parse(SafeEmail().fake()).unwrap());
-    let _ = email_client
+    email_client
         .send_email(
             &UserEmail::parse(SafeEmail().fake()).unwrap(),
             &smtp_subject,
@@ -34,7 +34,7 @@ async fn send_email() {
 
     // Check MailHog for the sent email
     let response = client
-        .get(&format!(
+        .get(format!(
             "http://localhost:8025/api/v2/search?kind=containing&query={}",
             smtp_subject
         ))
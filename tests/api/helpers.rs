@@ -12,10 +12,14 @@ use uuid::Uuid;
 
 use librhodos::telemetry::{get_subscriber, init_subscriber};
 use librhodos::{
+    federation::{self, Signer},
+    get_database_connection, keys,
     migration::{self, DbUri},
     serve, settings,
 };
 
+use crate::mock_server::{MockServer, ReceivedRequest};
+
 pub struct TestUser {
     pub name: String,
     pub user_id: i64,
@@ -23,6 +27,7 @@ pub struct TestUser {
     pub password: Secret<String>,
     pub role: UserRole,
     pub account_id: i64,
+    pub handle: String,
 }
 
 impl TestUser {
@@ -34,6 +39,7 @@ impl TestUser {
             password: Secret::from(Uuid::new_v4().to_string()),
             role,
             account_id: 0,
+            handle: format!("user_{}", &Uuid::new_v4().simple().to_string()[..12]),
         }
     }
 
@@ -62,7 +68,7 @@ impl TestUser {
         self.user_id = uid.get(0);
 
         // Create account
-        self.account_id = add_test_account(client, self.user_id).await;
+        self.account_id = add_test_account(client, self.user_id, &self.handle).await;
     }
 }
 
@@ -85,7 +91,7 @@ pub struct TestState {
 impl TestState {
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.app_address))
+            .get(format!("{}/admin/dashboard", &self.app_address))
             .send()
             .await
             .expect("Failed to get admin dashboard")
@@ -95,9 +101,29 @@ impl TestState {
         self.get_admin_dashboard().await.text().await.unwrap()
     }
 
+    pub async fn get_domain_blocks(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/domain-blocks{}", &self.app_address, path))
+            .send()
+            .await
+            .expect("Failed to get domain blocks")
+    }
+
+    pub async fn post_domain_blocks_form<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/domain-blocks{}", &self.app_address, path))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to post domain blocks form")
+    }
+
     pub async fn get_content_form(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/content/form", &self.app_address))
+            .get(format!("{}/content/form", &self.app_address))
             .send()
             .await
             .expect("Failed to get home (/content/form)")
@@ -109,7 +135,26 @@ impl TestState {
 
     pub async fn post_content(&self, body: &serde_json::Value) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/content", self.app_address))
+            .post(format!("{}/content", self.app_address))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Get one of the JSON endpoints under `/content`.
+    pub async fn get_content(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/content{}", self.app_address, path))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Post `body` as JSON to one of the account endpoints under `/user`.
+    pub async fn post_user_json(&self, path: &str, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/user{}", self.app_address, path))
             .json(&body)
             .send()
             .await
@@ -121,16 +166,185 @@ impl TestState {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/content/form", self.app_address))
+            .post(format!("{}/content/form", self.app_address))
             .form(&body)
             .send()
             .await
             .expect("Failed to post content form")
     }
 
+    pub async fn get_webfinger(&self, resource: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/.well-known/webfinger", &self.app_address))
+            .query(&[("resource", resource)])
+            .send()
+            .await
+            .expect("Failed to execute webfinger request")
+    }
+
+    pub async fn get_actor(&self, handle: &str, accept: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/users/{}", &self.app_address, handle))
+            .header("Accept", accept)
+            .send()
+            .await
+            .expect("Failed to execute actor request")
+    }
+
+    pub async fn get_outbox(&self, handle: &str, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/users/{}/outbox{}",
+                &self.app_address, handle, query
+            ))
+            .header("Accept", "application/activity+json")
+            .send()
+            .await
+            .expect("Failed to execute outbox request")
+    }
+
+    pub async fn get_status(&self, url: &str, accept: &str) -> reqwest::Response {
+        self.api_client
+            .get(url)
+            .header("Accept", accept)
+            .send()
+            .await
+            .expect("Failed to execute status request")
+    }
+
+    pub async fn get_tag(&self, name: &str, accept: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/tags/{}", &self.app_address, name))
+            .header("Accept", accept)
+            .send()
+            .await
+            .expect("Failed to execute tag request")
+    }
+
+    pub async fn get_json(&self, url: &str) -> serde_json::Value {
+        self.api_client
+            .get(url)
+            .header("Accept", "application/activity+json")
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .json()
+            .await
+            .expect("response is JSON")
+    }
+
+    pub async fn post_rotate_key(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/user/keys/rotate", &self.app_address))
+            .send()
+            .await
+            .expect("Failed to execute key rotation request")
+    }
+
+    pub fn actor_url(&self, user: &TestUser) -> String {
+        format!("{}/users/{}", self.app_address, user.handle)
+    }
+
+    /// Sign `activity` as `user` of `self` and deliver it to `path` on `receiver`.
+    pub async fn deliver_to(
+        &self,
+        user: &TestUser,
+        receiver: &TestState,
+        path: &str,
+        activity: &serde_json::Value,
+    ) -> reqwest::Response {
+        let signer = self.signer_for(user).await;
+        let mut request = receiver.inbox_request(path, activity);
+        signer.sign(&mut request).unwrap();
+
+        receiver.post_inbox(request).await
+    }
+
+    /// A signer holding the active key of one of this server's test users.
+    pub async fn signer_for(&self, user: &TestUser) -> Signer {
+        let db = get_database_connection(&self.global_config).await.unwrap();
+        let actor = format!("{}/users/{}", self.app_address, user.handle);
+        federation::signer_for_account(
+            &db,
+            user.account_id,
+            &actor,
+            &self.global_config.server.secret_key,
+        )
+        .await
+        .expect("failed to load signing key")
+        .expect("test user has a signing key")
+    }
+
+    /// A signer holding the key of this server's instance actor.
+    pub async fn instance_signer(&self) -> Signer {
+        let db = get_database_connection(&self.global_config).await.unwrap();
+        federation::instance_signer(
+            &db,
+            &self.app_address,
+            &self.global_config.server.secret_key,
+        )
+        .await
+        .expect("failed to load the instance key")
+        .expect("the instance actor has a key")
+    }
+
+    /// POST a request to an inbox and, if the activity was accepted, wait
+    /// for the inbox workers to apply it.
+    pub async fn post_inbox(&self, request: reqwest::Request) -> reqwest::Response {
+        assert!(request.url().as_str().starts_with(&self.app_address));
+        let activity_id = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .and_then(|body| serde_json::from_slice::<serde_json::Value>(body).ok())
+            .and_then(|activity| activity["id"].as_str().map(str::to_string));
+        let response = self
+            .api_client
+            .execute(request)
+            .await
+            .expect("Failed to execute inbox request");
+        if let (202, Some(id)) = (response.status().as_u16(), activity_id) {
+            self.wait_for_activity(&id).await;
+        }
+
+        response
+    }
+
+    /// Wait until the inbox workers tried to apply the activity `id` and
+    /// return its state.
+    pub async fn wait_for_activity(&self, id: &str) -> String {
+        let client = connect_to_db(&self.db_name).await;
+        for _ in 0..200 {
+            let row = client
+                .query_opt(
+                    "SELECT status, attempts FROM inbox_queue WHERE activity_id=$1",
+                    &[&id],
+                )
+                .await
+                .expect("Failed to query inbox queue");
+            if let Some(row) = row {
+                let status: String = row.get(0);
+                if status != "pending" || row.get::<_, i32>(1) > 0 {
+                    return status;
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+
+        panic!("activity {} was not processed", id)
+    }
+
+    pub fn inbox_request(&self, path: &str, body: &serde_json::Value) -> reqwest::Request {
+        self.api_client
+            .post(format!("{}{}", self.app_address, path))
+            .header("Content-Type", "application/activity+json")
+            .body(body.to_string())
+            .build()
+            .unwrap()
+    }
+
     pub async fn get_home_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/home", &self.app_address))
+            .get(format!("{}/home", &self.app_address))
             .send()
             .await
             .expect("Failed to get home (/home)")
@@ -142,7 +356,7 @@ impl TestState {
 
     pub async fn get_password_reset(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/user/change-password", &self.app_address))
+            .get(format!("{}/user/change-password", &self.app_address))
             .send()
             .await
             .expect("Failed to get reset password")
@@ -157,7 +371,7 @@ impl TestState {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/user/change-password", &self.app_address))
+            .post(format!("{}/user/change-password", &self.app_address))
             .form(body)
             .send()
             .await
@@ -169,7 +383,7 @@ impl TestState {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", self.app_address))
+            .post(format!("{}/login", self.app_address))
             .form(body)
             .send()
             .await
@@ -178,7 +392,7 @@ impl TestState {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/user/logout", &self.app_address))
+            .post(format!("{}/user/logout", &self.app_address))
             .send()
             .await
             .expect("Failed to execute logout request.")
@@ -186,7 +400,7 @@ impl TestState {
 
     pub async fn post_user(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/user", self.app_address))
+            .post(format!("{}/user", self.app_address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -197,7 +411,7 @@ impl TestState {
     pub async fn get_confirmation_links(&self, recepient: &String) -> ConfirmationLinks {
         let client = reqwest::Client::new();
         let response = client
-            .get(&format!(
+            .get(format!(
                 "http://localhost:8025/api/v2/search?kind=to&query={}",
                 recepient
             ))
@@ -239,7 +453,7 @@ impl TestState {
 
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.app_address))
+            .get(format!("{}/login", &self.app_address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -290,11 +504,11 @@ pub async fn connect_to_db(db_name: &str) -> tokio_postgres::Client {
     client
 }
 
-pub async fn add_test_account(client: &Client, user_id: i64) -> i64 {
+pub async fn add_test_account(client: &Client, user_id: i64, handle: &str) -> i64 {
     let _res = client
         .execute(
-            r#"INSERT INTO "account" (user_id) VALUES($1);"#,
-            &[&user_id],
+            r#"INSERT INTO "account" (user_id, username) VALUES($1, $2);"#,
+            &[&user_id, &handle],
         )
         .await
         .expect("query to add an account failed");
@@ -306,6 +520,80 @@ pub async fn add_test_account(client: &Client, user_id: i64) -> i64 {
     res.get(0)
 }
 
+/// Insert a post published now by the account `account_id`, returning its id.
+pub async fn insert_post(client: &Client, account_id: i64, body: &str, visibility: &str) -> i64 {
+    client
+        .query_one(
+            "INSERT INTO content (publisher_id, body, published, published_at, visibility)
+                VALUES ($1, $2, true, now() AT TIME ZONE 'UTC', $3) RETURNING id",
+            &[&account_id, &body, &visibility],
+        )
+        .await
+        .expect("query to insert a post failed")
+        .get(0)
+}
+
+/// Post `text` as the test user of `state` with the given `visibility`.
+pub async fn publish(state: &TestState, text: &str, visibility: &str) {
+    state.login_as(&state.test_user_user).await;
+    let body = serde_json::json!({ "content": { "text": text, "visibility": visibility } });
+    let response = state.post_content(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+/// The single value returned by `query` on the database of `state`.
+pub async fn scalar(state: &TestState, query: &str) -> i64 {
+    connect_to_db(&state.db_name)
+        .await
+        .query_one(query, &[])
+        .await
+        .unwrap()
+        .get(0)
+}
+
+/// The ids of a JSON array of posts.
+pub fn ids(posts: &serde_json::Value) -> Vec<i64> {
+    posts
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["id"].as_i64().unwrap())
+        .collect()
+}
+
+/// Make an actor on `mock` a follower of the test user of `state`.
+pub async fn add_mock_follower(state: &TestState, mock: &MockServer) {
+    connect_to_db(&state.db_name)
+        .await
+        .execute(
+            "INSERT INTO follower (account_id, actor_id, inbox, follow_id, accepted) \
+             VALUES ($1, $2, $3, $4, true)",
+            &[
+                &state.test_user_user.account_id,
+                &mock.url("/users/zoe"),
+                &mock.url("/users/zoe/inbox"),
+                &mock.url("/follows/1"),
+            ],
+        )
+        .await
+        .unwrap();
+}
+
+/// Wait for the first activity delivered to `path` on `mock`.
+pub async fn first_delivery(mock: &MockServer, path: &str) -> serde_json::Value {
+    let mut received: Vec<ReceivedRequest> = vec![];
+    for _ in 0..100 {
+        received = mock.received(path);
+        if !received.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(!received.is_empty(), "nothing was delivered to {}", path);
+
+    serde_json::from_slice(&received[0].body).unwrap()
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(
         response.status().as_u16(),
@@ -326,8 +614,7 @@ pub async fn spawn_app() -> TestState {
 
     let mut global_config = settings::Settings::new(None, None)
         .map_err(|e| {
-            eprintln!("Failed to get settings: {}", e.to_string());
-            return;
+            eprintln!("Failed to get settings: {}", e);
         })
         .unwrap();
     global_config.database.db_host = "localhost".to_string();
@@ -335,6 +622,9 @@ pub async fn spawn_app() -> TestState {
     global_config.database.db_user = "postgres".to_string();
     global_config.database.db_password = Secret::from("password".to_string());
     global_config.database.db_name = Uuid::new_v4().to_string();
+    global_config.server.secret_key = Secret::from(Uuid::new_v4().to_string());
+    // The other servers of a test all listen on localhost
+    global_config.server.allow_private_addresses = true;
     let db_uri = DbUri {
         full: global_config.database.connection_string(),
         path: global_config.database.connection_string_no_db(),
@@ -342,18 +632,16 @@ pub async fn spawn_app() -> TestState {
         // randomize db name for test isolation
         db_name: global_config.database.db_name.clone(),
     };
-    let _ = migration::initialize_and_migrate_database(&db_uri)
+    let _ = migration::initialize_and_migrate_database(&db_uri, &global_config.server.secret_key)
         .await
         .map_err(|err_str| {
             eprintln!("{}", err_str);
-            return;
         });
 
-    let (router, listener) =
-        startup::build(&mut global_config, Some("0.0.0.0:0".to_string())).await;
+    let (router, listener) = startup::build(&global_config, Some("0.0.0.0:0".to_string())).await;
     let port = listener.local_addr().unwrap().port();
 
-    let _ = tokio::spawn(serve(router, listener));
+    tokio::spawn(serve(router, listener));
     let db_client = connect_to_db(&global_config.database.db_name.clone()).await;
 
     let reqwest_client = reqwest::Client::builder()
@@ -368,10 +656,17 @@ pub async fn spawn_app() -> TestState {
     let mut test_u = TestUser::generate_fake_user(UserRole::User);
     test_u.store(&db_client).await;
 
+    // Test users are inserted behind the application's back, so give them
+    // signing keys the same way a migration would.
+    let db = get_database_connection(&global_config).await.unwrap();
+    keys::backfill_account_keys(&db, &global_config.server.secret_key)
+        .await
+        .expect("failed to generate keys for test users");
+
     let res = TestState {
         app_address: format!("http://localhost:{}", port),
         db_name: global_config.database.db_name.clone(),
-        port: port,
+        port,
         test_user_superadmin: test_sa,
         test_user_user: test_u,
         user_admin: TestUser {
@@ -381,9 +676,10 @@ pub async fn spawn_app() -> TestState {
             password: Secret::from("rhodos".to_string()),
             role: UserRole::SuperAdmin,
             account_id: 1,
+            handle: "admin".to_string(),
         },
         api_client: reqwest_client,
-        global_config: global_config,
+        global_config,
     };
 
     res
@@ -0,0 +1,174 @@
+use librhodos::{federation::Signer, get_database_connection, keys};
+use std::time::{Duration, SystemTime};
+
+use crate::helpers::spawn_app;
+
+fn follow(actor: &str, object: &str) -> serde_json::Value {
+    serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}#follows/1", actor),
+        "type": "Follow",
+        "actor": actor,
+        "object": object,
+    })
+}
+
+#[tokio::test]
+async fn signed_delivery_between_servers_is_accepted_202() {
+    // Arrange
+    let sender = spawn_app().await;
+    let receiver = spawn_app().await;
+    let signer = sender.signer_for(&sender.test_user_user).await;
+    let body = follow(
+        &format!(
+            "{}/users/{}",
+            sender.app_address, sender.test_user_user.handle
+        ),
+        &format!("{}/users/admin", receiver.app_address),
+    );
+    let mut request = receiver.inbox_request("/inbox", &body);
+    signer.sign(&mut request).unwrap();
+
+    // Act
+    let response = receiver.post_inbox(request).await;
+
+    // Assert
+    assert_eq!(
+        response.status().as_u16(),
+        202,
+        "the receiver fetched the sender's key and accepted the signature"
+    );
+}
+
+#[tokio::test]
+async fn rfc9421_signed_delivery_is_accepted_202() {
+    // Arrange
+    let sender = spawn_app().await;
+    let receiver = spawn_app().await;
+    let signer = sender.signer_for(&sender.test_user_user).await;
+    let body = follow(
+        &format!(
+            "{}/users/{}",
+            sender.app_address, sender.test_user_user.handle
+        ),
+        &format!("{}/users/admin", receiver.app_address),
+    );
+    let mut request = receiver.inbox_request("/inbox", &body);
+    signer.sign_rfc9421(&mut request).unwrap();
+
+    // Act
+    let response = receiver.post_inbox(request).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+#[tokio::test]
+async fn unsigned_delivery_is_unauthorized_401() {
+    // Arrange
+    let receiver = spawn_app().await;
+    let body = follow("https://example.com/users/alice", "https://example.com/b");
+    let request = receiver.inbox_request("/inbox", &body);
+
+    // Act
+    let response = receiver.post_inbox(request).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn tampered_body_is_unauthorized_401() {
+    // Arrange
+    let sender = spawn_app().await;
+    let receiver = spawn_app().await;
+    let signer = sender.signer_for(&sender.test_user_user).await;
+    let actor = format!(
+        "{}/users/{}",
+        sender.app_address, sender.test_user_user.handle
+    );
+    let mut request = receiver.inbox_request("/inbox", &follow(&actor, "a"));
+    signer.sign(&mut request).unwrap();
+    *request.body_mut() = Some(follow(&actor, "b").to_string().into());
+
+    // Act
+    let response = receiver.post_inbox(request).await;
+
+    // Assert
+    assert_eq!(
+        response.status().as_u16(),
+        401,
+        "body no longer matches the signed digest"
+    );
+}
+
+#[tokio::test]
+async fn signature_by_another_key_is_unauthorized_401() {
+    // Arrange
+    let sender = spawn_app().await;
+    let receiver = spawn_app().await;
+    let db = get_database_connection(&sender.global_config)
+        .await
+        .unwrap();
+    let (_, admin_key) = keys::signing_key(
+        &db,
+        sender.user_admin.account_id,
+        &sender.global_config.server.secret_key,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    // Claim the test user's key id but sign with the admin's key
+    let user_key_id = sender.signer_for(&sender.test_user_user).await.key_id;
+    let signer = Signer::new(user_key_id.clone(), admin_key);
+    let mut request = receiver.inbox_request("/inbox", &follow(&user_key_id, "a"));
+    signer.sign(&mut request).unwrap();
+
+    // Act
+    let response = receiver.post_inbox(request).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn stale_date_is_unauthorized_401() {
+    // Arrange
+    let sender = spawn_app().await;
+    let receiver = spawn_app().await;
+    let signer = sender.signer_for(&sender.test_user_user).await;
+    let actor = format!(
+        "{}/users/{}",
+        sender.app_address, sender.test_user_user.handle
+    );
+    let mut request = receiver.inbox_request("/inbox", &follow(&actor, "a"));
+    signer
+        .sign_at(
+            &mut request,
+            SystemTime::now() - Duration::from_secs(24 * 60 * 60),
+        )
+        .unwrap();
+
+    // Act
+    let response = receiver.post_inbox(request).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn malformed_signature_is_bad_request_400() {
+    // Arrange
+    let receiver = spawn_app().await;
+    let mut request = receiver.inbox_request("/inbox", &follow("a", "b"));
+    request.headers_mut().insert(
+        "Signature",
+        reqwest::header::HeaderValue::from_static(r#"algorithm="rsa-sha256""#),
+    );
+
+    // Act
+    let response = receiver.post_inbox(request).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
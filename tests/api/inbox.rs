@@ -0,0 +1,511 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::helpers::{connect_to_db, spawn_app, TestState};
+
+fn activity_id(state: &TestState) -> String {
+    format!("{}/activities/{}", state.app_address, Uuid::new_v4())
+}
+
+fn note(sender: &TestState, author: &str, to: &str) -> serde_json::Value {
+    let id = format!("{}/notes/{}", sender.app_address, Uuid::new_v4());
+    serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}/activity", id),
+        "type": "Create",
+        "actor": author,
+        "object": {
+            "id": id,
+            "type": "Note",
+            "attributedTo": author,
+            "content": "<p>Hello from afar</p>",
+            "summary": "greeting",
+            "sensitive": true,
+            "published": "2023-01-02T03:04:05Z",
+            "to": [to],
+            "cc": [],
+        },
+    })
+}
+
+async fn wait_for_accepted_follow(state: &TestState, follow_id: &str) -> bool {
+    let client = connect_to_db(&state.db_name).await;
+    for _ in 0..50 {
+        let row = client
+            .query_one(
+                "SELECT accepted FROM following WHERE follow_id=$1",
+                &[&follow_id],
+            )
+            .await
+            .expect("query to fetch follow failed");
+        if row.get(0) {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    false
+}
+
+#[tokio::test]
+async fn follow_of_unlocked_account_is_accepted() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    let follower = remote.actor_url(&remote.test_user_user);
+    let followee = local.actor_url(&local.test_user_user);
+    let follow_id = activity_id(&remote);
+    // The remote server remembers the follow it sent
+    connect_to_db(&remote.db_name)
+        .await
+        .execute(
+            "INSERT INTO following (account_id, actor_id, follow_id) VALUES ($1, $2, $3)",
+            &[&remote.test_user_user.account_id, &followee, &follow_id],
+        )
+        .await
+        .unwrap();
+    let follow = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": follow_id,
+        "type": "Follow",
+        "actor": follower,
+        "object": followee,
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(
+            &remote.test_user_user,
+            &local,
+            &format!("/users/{}/inbox", local.test_user_user.handle),
+            &follow,
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let row = connect_to_db(&local.db_name)
+        .await
+        .query_one(
+            "SELECT inbox, accepted FROM follower WHERE account_id=$1 AND actor_id=$2",
+            &[&local.test_user_user.account_id, &follower],
+        )
+        .await
+        .expect("the follower is recorded");
+    let inbox: &str = row.get(0);
+    let accepted: bool = row.get(1);
+    assert_eq!(inbox, format!("{}/inbox", follower));
+    assert!(accepted, "unlocked accounts accept follows automatically");
+    assert!(
+        wait_for_accepted_follow(&remote, &follow_id).await,
+        "the follower's server received the Accept"
+    );
+}
+
+#[tokio::test]
+async fn follow_of_locked_account_is_pending() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    connect_to_db(&local.db_name)
+        .await
+        .execute(
+            "UPDATE account SET locked=true WHERE id=$1",
+            &[&local.test_user_user.account_id],
+        )
+        .await
+        .unwrap();
+    let follower = remote.actor_url(&remote.test_user_user);
+    let follow = serde_json::json!({
+        "id": activity_id(&remote),
+        "type": "Follow",
+        "actor": follower,
+        "object": local.actor_url(&local.test_user_user),
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &follow)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let row = connect_to_db(&local.db_name)
+        .await
+        .query_one(
+            "SELECT accepted FROM follower WHERE actor_id=$1",
+            &[&follower],
+        )
+        .await
+        .expect("the follow request is recorded");
+    let accepted: bool = row.get(0);
+    assert!(!accepted, "locked accounts approve followers manually");
+}
+
+/// Have the test user of `remote` ask to follow the locked test user of
+/// `local`, and return the id of the follow request.
+async fn request_follow(remote: &TestState, local: &TestState) -> (i64, String) {
+    connect_to_db(&local.db_name)
+        .await
+        .execute(
+            "UPDATE account SET locked=true WHERE id=$1",
+            &[&local.test_user_user.account_id],
+        )
+        .await
+        .unwrap();
+    let followee = local.actor_url(&local.test_user_user);
+    let follow_id = activity_id(remote);
+    connect_to_db(&remote.db_name)
+        .await
+        .execute(
+            "INSERT INTO following (account_id, actor_id, follow_id) VALUES ($1, $2, $3)",
+            &[&remote.test_user_user.account_id, &followee, &follow_id],
+        )
+        .await
+        .unwrap();
+    let follow = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": follow_id,
+        "type": "Follow",
+        "actor": remote.actor_url(&remote.test_user_user),
+        "object": followee,
+    });
+    remote
+        .deliver_to(&remote.test_user_user, local, "/inbox", &follow)
+        .await;
+
+    local.login_as(&local.test_user_user).await;
+    let requests: serde_json::Value = local
+        .api_client
+        .get(format!("{}/user/follow-requests", local.app_address))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        requests[0]["actor_id"],
+        remote.actor_url(&remote.test_user_user)
+    );
+
+    (requests[0]["id"].as_i64().unwrap(), follow_id)
+}
+
+#[tokio::test]
+async fn approved_follow_requests_are_accepted() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    let (id, follow_id) = request_follow(&remote, &local).await;
+
+    // Act
+    let response = local
+        .api_client
+        .post(format!(
+            "{}/user/follow-requests/{}/accept",
+            local.app_address, id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 204);
+    let accepted: bool = connect_to_db(&local.db_name)
+        .await
+        .query_one("SELECT accepted FROM follower WHERE id=$1", &[&id])
+        .await
+        .unwrap()
+        .get(0);
+    assert!(accepted);
+    assert!(
+        wait_for_accepted_follow(&remote, &follow_id).await,
+        "the follower's server received the Accept"
+    );
+}
+
+#[tokio::test]
+async fn rejected_follow_requests_are_dropped() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    let (id, follow_id) = request_follow(&remote, &local).await;
+
+    // Act
+    let response = local
+        .api_client
+        .post(format!(
+            "{}/user/follow-requests/{}/reject",
+            local.app_address, id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 204);
+    let local_db = connect_to_db(&local.db_name).await;
+    let remaining: i64 = local_db
+        .query_one("SELECT count(*) FROM follower", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(remaining, 0);
+    let reject: String = local_db
+        .query_one(
+            "SELECT activity::text FROM delivery WHERE activity->>'type' = 'Reject'",
+            &[],
+        )
+        .await
+        .expect("a Reject is queued")
+        .get(0);
+    let reject: serde_json::Value = serde_json::from_str(&reject).unwrap();
+    assert_eq!(reject["object"]["id"], follow_id);
+    let remote_db = connect_to_db(&remote.db_name).await;
+    let mut following = 1;
+    for _ in 0..50 {
+        following = remote_db
+            .query_one("SELECT count(*) FROM following", &[])
+            .await
+            .unwrap()
+            .get::<_, i64>(0);
+        if following == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(following, 0, "the follower's server received the Reject");
+}
+
+#[tokio::test]
+async fn undo_follow_removes_follower() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    let follower = remote.actor_url(&remote.test_user_user);
+    let follow = serde_json::json!({
+        "id": activity_id(&remote),
+        "type": "Follow",
+        "actor": follower,
+        "object": local.actor_url(&local.test_user_user),
+    });
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &follow)
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let undo = serde_json::json!({
+        "id": activity_id(&remote),
+        "type": "Undo",
+        "actor": follower,
+        "object": follow,
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &undo)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let rows = connect_to_db(&local.db_name)
+        .await
+        .query("SELECT id FROM follower WHERE actor_id=$1", &[&follower])
+        .await
+        .unwrap();
+    assert!(rows.is_empty(), "the follower was removed");
+}
+
+#[tokio::test]
+async fn note_addressed_to_local_account_is_stored() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    let author = remote.actor_url(&remote.test_user_user);
+    let create = note(&remote, &author, &local.actor_url(&local.test_user_user));
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &create)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let row = connect_to_db(&local.db_name)
+        .await
+        .query_one(
+            "SELECT actor_id, content, summary, sensitive FROM remote_post WHERE object_id=$1",
+            &[&create["object"]["id"].as_str().unwrap()],
+        )
+        .await
+        .expect("the note is stored");
+    let actor: &str = row.get(0);
+    let content: &str = row.get(1);
+    let summary: &str = row.get(2);
+    let sensitive: bool = row.get(3);
+    assert_eq!(actor, author);
+    assert_eq!(content, "<p>Hello from afar</p>");
+    assert_eq!(summary, "greeting");
+    assert!(sensitive);
+}
+
+#[tokio::test]
+async fn unsolicited_note_is_ignored() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    let author = remote.actor_url(&remote.test_user_user);
+    let create = note(
+        &remote,
+        &author,
+        "https://www.w3.org/ns/activitystreams#Public",
+    );
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &create)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let rows = connect_to_db(&local.db_name)
+        .await
+        .query("SELECT id FROM remote_post", &[])
+        .await
+        .unwrap();
+    assert!(
+        rows.is_empty(),
+        "notes from actors nobody follows are dropped"
+    );
+}
+
+#[tokio::test]
+async fn delete_tombstones_remote_note() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    let author = remote.actor_url(&remote.test_user_user);
+    let create = note(&remote, &author, &local.actor_url(&local.test_user_user));
+    let note_id = create["object"]["id"].as_str().unwrap();
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &create)
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    connect_to_db(&local.db_name)
+        .await
+        .execute(
+            "INSERT INTO content_revision (remote_post_id, body, written_at) \
+             SELECT id, 'earlier', now() FROM remote_post",
+            &[],
+        )
+        .await
+        .unwrap();
+    let delete = serde_json::json!({
+        "id": activity_id(&remote),
+        "type": "Delete",
+        "actor": author,
+        "object": { "id": note_id, "type": "Tombstone" },
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &delete)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let row = connect_to_db(&local.db_name)
+        .await
+        .query_one(
+            "SELECT content, deleted_at IS NOT NULL FROM remote_post WHERE object_id=$1",
+            &[&note_id],
+        )
+        .await
+        .expect("the tombstone is kept");
+    let content: Option<&str> = row.get(0);
+    let deleted: bool = row.get(1);
+    assert!(deleted, "the note is marked deleted");
+    assert_eq!(content, None, "the content is removed");
+    let revisions: i64 = connect_to_db(&local.db_name)
+        .await
+        .query_one("SELECT count(*) FROM content_revision", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(revisions, 0, "earlier versions are removed");
+}
+
+#[tokio::test]
+async fn activity_by_another_actor_is_unauthorized_401() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    let follow = serde_json::json!({
+        "id": format!("{}/activities/1", remote.app_address),
+        "type": "Follow",
+        "actor": remote.actor_url(&remote.user_admin),
+        "object": local.actor_url(&local.test_user_user),
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &follow)
+        .await;
+
+    // Assert
+    assert_eq!(
+        response.status().as_u16(),
+        401,
+        "the signer is not the actor of the activity"
+    );
+}
+
+#[tokio::test]
+async fn inbox_of_unknown_account_is_not_found_404() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    let follow = serde_json::json!({
+        "id": activity_id(&remote),
+        "type": "Follow",
+        "actor": remote.actor_url(&remote.test_user_user),
+        "object": format!("{}/users/nobody", local.app_address),
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(
+            &remote.test_user_user,
+            &local,
+            "/users/nobody/inbox",
+            &follow,
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn follows_of_unknown_accounts_are_dead_letters() {
+    // Arrange
+    let remote = spawn_app().await;
+    let local = spawn_app().await;
+    let follow = serde_json::json!({
+        "id": activity_id(&remote),
+        "type": "Follow",
+        "actor": remote.actor_url(&remote.test_user_user),
+        "object": format!("{}/users/nobody", local.app_address),
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &follow)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let activity_id = follow["id"].as_str().unwrap();
+    assert_eq!(local.wait_for_activity(activity_id).await, "dead");
+}
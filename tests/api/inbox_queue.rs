@@ -0,0 +1,185 @@
+use reqwest::StatusCode;
+use uuid::Uuid;
+
+use crate::{
+    helpers::{assert_is_redirect_to, connect_to_db, scalar, spawn_app},
+    mock_server::{borrow_key, MockServer},
+};
+
+#[tokio::test]
+async fn activities_are_applied_once() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    let follow = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}/activities/{}", remote.app_address, Uuid::new_v4()),
+        "type": "Follow",
+        "actor": remote.actor_url(&remote.test_user_user),
+        "object": local.actor_url(&local.test_user_user),
+    });
+    let account_inbox = format!("/users/{}/inbox", local.test_user_user.handle);
+
+    // Act
+    let mut responses = vec![];
+    for path in ["/inbox", &account_inbox, "/inbox"] {
+        responses.push(
+            remote
+                .deliver_to(&remote.test_user_user, &local, path, &follow)
+                .await,
+        );
+    }
+
+    // Assert
+    for response in responses {
+        assert_eq!(response.status().as_u16(), 202);
+    }
+    let activity_id = follow["id"].as_str().unwrap();
+    assert_eq!(local.wait_for_activity(activity_id).await, "processed");
+    assert_eq!(scalar(&local, "SELECT count(*) FROM inbox_queue").await, 1);
+    assert_eq!(scalar(&local, "SELECT count(*) FROM follower").await, 1);
+    assert_eq!(
+        scalar(
+            &local,
+            "SELECT count(*) FROM delivery WHERE activity->>'type' = 'Accept'"
+        )
+        .await,
+        1
+    );
+}
+
+#[tokio::test]
+async fn failed_activities_are_kept_and_can_be_replayed() {
+    // Arrange
+    let app = spawn_app().await;
+    let mock = MockServer::start().await;
+    let key_id = mock.url("/users/zoe#main-key");
+    let (signer, pem) = borrow_key(&app, &app.test_user_superadmin, &key_id).await;
+    let zoe = mock.mount_actor("zoe", &pem)["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let note_id = mock.url("/notes/1");
+    let create = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": mock.url("/notes/1/activity"),
+        "type": "Create",
+        "actor": zoe,
+        "object": note_id,
+    });
+    let mut request = app.inbox_request("/inbox", &create);
+    signer.sign(&mut request).unwrap();
+    // The note cannot be fetched yet
+    let response = app.post_inbox(request).await;
+    assert_eq!(response.status().as_u16(), 202);
+    let activity_id = create["id"].as_str().unwrap();
+    assert_eq!(app.wait_for_activity(activity_id).await, "dead");
+    app.login_as(&app.test_user_superadmin).await;
+    let page = app
+        .api_client
+        .get(format!("{}/admin/inbox-queue", app.app_address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains(activity_id));
+    mock.mount(
+        "/notes/1",
+        serde_json::json!({
+            "id": note_id,
+            "type": "Note",
+            "attributedTo": zoe,
+            "content": "<p>Hello</p>",
+            "to": [app.actor_url(&app.test_user_user)],
+        }),
+    );
+    let id: i64 = connect_to_db(&app.db_name)
+        .await
+        .query_one("SELECT id FROM inbox_queue", &[])
+        .await
+        .unwrap()
+        .get(0);
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/inbox-queue/{}/replay",
+            app.app_address, id
+        ))
+        .send()
+        .await
+        .expect("Failed to replay activity");
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/inbox-queue");
+    assert_eq!(app.wait_for_activity(activity_id).await, "processed");
+    assert_eq!(scalar(&app, "SELECT count(*) FROM remote_post").await, 1);
+}
+
+#[tokio::test]
+async fn activities_are_retried_while_their_object_is_unavailable() {
+    // Arrange
+    let app = spawn_app().await;
+    let mock = MockServer::start().await;
+    let key_id = mock.url("/users/zoe#main-key");
+    let (signer, pem) = borrow_key(&app, &app.test_user_superadmin, &key_id).await;
+    let zoe = mock.mount_actor("zoe", &pem)["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let note_id = mock.url("/notes/1");
+    mock.fail("/notes/1", StatusCode::SERVICE_UNAVAILABLE);
+    let create = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": mock.url("/notes/1/activity"),
+        "type": "Create",
+        "actor": zoe,
+        "object": note_id,
+    });
+    let mut request = app.inbox_request("/inbox", &create);
+    signer.sign(&mut request).unwrap();
+    let response = app.post_inbox(request).await;
+    assert_eq!(response.status().as_u16(), 202);
+    let activity_id = create["id"].as_str().unwrap();
+    assert_eq!(app.wait_for_activity(activity_id).await, "pending");
+    mock.mount(
+        "/notes/1",
+        serde_json::json!({
+            "id": note_id,
+            "type": "Note",
+            "attributedTo": zoe,
+            "content": "<p>Hello</p>",
+            "to": [app.actor_url(&app.test_user_user)],
+        }),
+    );
+
+    // Act
+    let client = connect_to_db(&app.db_name).await;
+    client
+        .execute(
+            "UPDATE inbox_queue SET next_attempt_at = now() AT TIME ZONE 'UTC'",
+            &[],
+        )
+        .await
+        .unwrap();
+    let mut status = String::new();
+    for _ in 0..200 {
+        status = client
+            .query_one("SELECT status FROM inbox_queue", &[])
+            .await
+            .unwrap()
+            .get(0);
+        if status != "pending" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    // Assert
+    assert_eq!(status, "processed");
+    assert_eq!(mock.received("/notes/1").len(), 2);
+    assert_eq!(scalar(&app, "SELECT count(*) FROM remote_post").await, 1);
+}
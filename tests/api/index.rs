@@ -8,7 +8,7 @@ async fn root_index_contains_login_link() {
     // Act
     let page = state
         .api_client
-        .get(&format!("{}/", &state.app_address))
+        .get(format!("{}/", &state.app_address))
         .send()
         .await
         .expect("Failed to get index (/)")
@@ -74,7 +74,7 @@ async fn seed_user_super_admin_login_ok_200() {
     // Act- 2 - follow redirect
     let html_page = state.get_admin_dashboard_html().await;
     assert!(
-        html_page.contains(&format!("Welcome Administrator")),
+        html_page.contains("Welcome Administrator"),
         "home page shows welcome to user"
     );
 }
@@ -1,16 +1,38 @@
+mod actor;
 mod admin_dashboard;
+mod allowlist;
+mod authorized_fetch;
 mod content;
+mod deletion;
+mod delivery;
+mod domain_blocks;
 mod email_client;
 mod health_check;
 mod helpers;
 mod home_dashboard;
+mod http_signatures;
+mod inbox;
+mod inbox_queue;
 mod index;
+mod keys;
 mod login;
 mod logout;
+mod mentions;
 mod migration;
+mod mock_server;
+mod moves;
+mod nodeinfo;
+mod outbox;
 mod password_reset;
+mod polls;
+mod relays;
+mod remote_cache;
 mod settings;
+mod status;
+mod tags;
+mod updates;
 mod user;
 mod user_confirm;
+mod webfinger;
 
 mod test_utils;
@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use crate::{
+    helpers::{connect_to_db, publish, spawn_app, TestState},
+    mock_server::MockServer,
+};
+
+/// The ActivityPub representation of the only post on `state`.
+async fn only_note(state: &TestState) -> serde_json::Value {
+    let id: i64 = connect_to_db(&state.db_name)
+        .await
+        .query_one("SELECT id FROM content", &[])
+        .await
+        .unwrap()
+        .get(0);
+    let url = format!("{}/statuses/{}", state.actor_url(&state.test_user_user), id);
+
+    state.get_json(&url).await
+}
+
+#[tokio::test]
+async fn local_mentions_are_linked_tagged_and_addressed() {
+    // Arrange
+    let state = spawn_app().await;
+    let admin = state.actor_url(&state.user_admin);
+
+    // Act
+    publish(&state, "hello @admin and @nobody", "public").await;
+
+    // Assert
+    let note = only_note(&state).await;
+    let content = note["content"].as_str().unwrap();
+    assert!(
+        content.contains(&format!(
+            r#"<a href="{}" class="u-url mention">@admin</a>"#,
+            admin
+        )),
+        "{}",
+        content
+    );
+    assert!(
+        content.contains(" @nobody</p>"),
+        "unknown handles stay text"
+    );
+    assert_eq!(note["tag"][0]["type"], "Mention");
+    assert_eq!(note["tag"][0]["href"], admin);
+    assert!(note["cc"]
+        .as_array()
+        .unwrap()
+        .contains(&admin.clone().into()));
+    let mentions: i64 = connect_to_db(&state.db_name)
+        .await
+        .query_one("SELECT COUNT(*) FROM mention", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(mentions, 1);
+}
+
+#[tokio::test]
+async fn remote_mentions_are_resolved_with_webfinger() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    let domain = mock.address.trim_start_matches("http://").to_string();
+    let zoe = mock.mount_actor("zoe", "PEM");
+    mock.mount(
+        "/.well-known/webfinger",
+        serde_json::json!({
+            "subject": format!("acct:zoe@{}", domain),
+            "links": [{
+                "rel": "self",
+                "type": "application/activity+json",
+                "href": zoe["id"],
+            }],
+        }),
+    );
+
+    // Act
+    publish(&state, &format!("hi @zoe@{}!", domain), "public").await;
+
+    // Assert
+    let webfinger = mock.received("/.well-known/webfinger");
+    assert_eq!(webfinger.len(), 1);
+    let client = connect_to_db(&state.db_name).await;
+    let row = client
+        .query_one("SELECT actor_id, domain, inbox FROM mention", &[])
+        .await
+        .unwrap();
+    assert_eq!(row.get::<_, &str>(0), zoe["id"].as_str().unwrap());
+    assert_eq!(row.get::<_, Option<&str>>(1), Some(domain.as_str()));
+    assert_eq!(row.get::<_, Option<&str>>(2), zoe["inbox"].as_str());
+    let inboxes: Vec<String> = client
+        .query("SELECT inbox FROM delivery", &[])
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+    assert_eq!(inboxes, vec![zoe["inbox"].as_str().unwrap()]);
+    let note = only_note(&state).await;
+    assert_eq!(note["tag"][0]["name"], format!("@zoe@{}", domain));
+}
+
+#[tokio::test]
+async fn mentions_on_suspended_domains_are_not_looked_up() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    let domain = mock.address.trim_start_matches("http://").to_string();
+    connect_to_db(&state.db_name)
+        .await
+        .execute(
+            "INSERT INTO domain_block (domain, severity) VALUES ('127.0.0.1', 'suspend')",
+            &[],
+        )
+        .await
+        .unwrap();
+
+    // Act
+    publish(&state, &format!("hi @zoe@{}!", domain), "public").await;
+
+    // Assert
+    assert!(mock.received("/.well-known/webfinger").is_empty());
+    let mentions: i64 = connect_to_db(&state.db_name)
+        .await
+        .query_one("SELECT COUNT(*) FROM mention", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(mentions, 0);
+}
+
+#[tokio::test]
+async fn direct_posts_reach_the_mentioned_accounts() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    let recipient = format!(
+        "@{}@localhost:{}",
+        remote.test_user_user.handle, remote.port
+    );
+
+    // Act
+    publish(&local, &format!("{} psst", recipient), "direct").await;
+
+    // Assert
+    let activity: String = connect_to_db(&local.db_name)
+        .await
+        .query_one("SELECT activity::text FROM delivery", &[])
+        .await
+        .unwrap()
+        .get(0);
+    let activity: serde_json::Value = serde_json::from_str(&activity).unwrap();
+    assert_eq!(
+        activity["object"]["to"],
+        serde_json::json!([remote.actor_url(&remote.test_user_user)])
+    );
+    let remote_db = connect_to_db(&remote.db_name).await;
+    let mut received = None;
+    for _ in 0..100 {
+        received = remote_db
+            .query_opt("SELECT content FROM remote_post", &[])
+            .await
+            .unwrap();
+        if received.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let content: String = received
+        .expect("the mentioned account received the post")
+        .get(0);
+    assert!(content.contains("psst"), "{}", content);
+}
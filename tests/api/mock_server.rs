@@ -0,0 +1,183 @@
+//! A stand-in for a remote ActivityPub server. It serves whatever documents a
+//! test mounts on it and records the requests it receives.
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use std::{
+    collections::HashMap,
+    net::TcpListener,
+    sync::{Arc, Mutex},
+};
+
+use librhodos::federation::Signer;
+
+use crate::helpers::TestState;
+
+#[derive(Clone)]
+enum Mounted {
+    Json(serde_json::Value),
+    Redirect(String),
+    Status(StatusCode),
+}
+
+#[derive(Clone, Debug)]
+pub struct ReceivedRequest {
+    pub method: Method,
+    pub path: String,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+#[derive(Clone, Default)]
+struct MockState {
+    mounted: Arc<Mutex<HashMap<String, Mounted>>>,
+    received: Arc<Mutex<Vec<ReceivedRequest>>>,
+}
+
+pub struct MockServer {
+    pub address: String,
+    state: MockState,
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        let state = MockState::default();
+        let app = Router::new().fallback(handle).with_state(state.clone());
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+
+        Self { address, state }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.address, path)
+    }
+
+    /// Serve `document` as ActivityPub JSON at `path`.
+    pub fn mount(&self, path: &str, document: serde_json::Value) {
+        self.state
+            .mounted
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), Mounted::Json(document));
+    }
+
+    /// Redirect requests for `path` to `location`.
+    pub fn redirect(&self, path: &str, location: &str) {
+        self.state
+            .mounted
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), Mounted::Redirect(location.to_string()));
+    }
+
+    /// Answer requests for `path` with `status` and no body.
+    pub fn fail(&self, path: &str, status: StatusCode) {
+        self.state
+            .mounted
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), Mounted::Status(status));
+    }
+
+    /// Requests received for `path`, oldest first.
+    pub fn received(&self, path: &str) -> Vec<ReceivedRequest> {
+        self.state
+            .received
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.path == path)
+            .cloned()
+            .collect()
+    }
+
+    /// Mount the actor `name` with the public key `public_key_pem` and return
+    /// its document.
+    pub fn mount_actor(&self, name: &str, public_key_pem: &str) -> serde_json::Value {
+        let path = format!("/users/{}", name);
+        let id = self.url(&path);
+        let actor = serde_json::json!({
+            "@context": [
+                "https://www.w3.org/ns/activitystreams",
+                "https://w3id.org/security/v1"
+            ],
+            "id": id,
+            "type": "Person",
+            "preferredUsername": name,
+            "name": name.to_uppercase(),
+            "inbox": format!("{}/inbox", id),
+            "outbox": format!("{}/outbox", id),
+            "followers": format!("{}/followers", id),
+            "endpoints": { "sharedInbox": self.url("/inbox") },
+            "publicKey": {
+                "id": format!("{}#main-key", id),
+                "owner": id,
+                "publicKeyPem": public_key_pem,
+            },
+        });
+        self.mount(&path, actor.clone());
+
+        actor
+    }
+}
+
+async fn handle(
+    State(state): State<MockState>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let path = uri.path().to_string();
+    state.received.lock().unwrap().push(ReceivedRequest {
+        method: method.clone(),
+        path: path.clone(),
+        headers,
+        body,
+    });
+    if method == Method::POST {
+        return StatusCode::ACCEPTED.into_response();
+    }
+
+    let mounted = state.mounted.lock().unwrap().get(&path).cloned();
+    match mounted {
+        Some(Mounted::Json(document)) => (
+            [("Content-Type", "application/activity+json")],
+            document.to_string(),
+        )
+            .into_response(),
+        Some(Mounted::Redirect(location)) => Redirect::temporary(&location).into_response(),
+        Some(Mounted::Status(status)) => status.into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Borrow the key pair of `user` of `state` for an actor on a mock server,
+/// returning a signer for `key_id` and the public key.
+pub async fn borrow_key(
+    state: &TestState,
+    user: &crate::helpers::TestUser,
+    key_id: &str,
+) -> (Signer, String) {
+    let mut signer = state.signer_for(user).await;
+    signer.key_id = key_id.to_string();
+    let row = crate::helpers::connect_to_db(&state.db_name)
+        .await
+        .query_one(
+            "SELECT public_key_pem FROM account_key WHERE account_id=$1 AND expires_at IS NULL",
+            &[&user.account_id],
+        )
+        .await
+        .expect("test user has a key");
+
+    (signer, row.get(0))
+}
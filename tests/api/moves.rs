@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use crate::{
+    helpers::{add_mock_follower, connect_to_db, first_delivery, spawn_app, TestState, TestUser},
+    mock_server::MockServer,
+};
+
+/// The `user@host:port` address of `user` on `state`.
+fn address(state: &TestState, user: &TestUser) -> String {
+    format!("{}@localhost:{}", user.handle, state.port)
+}
+
+async fn add_alias(state: &TestState, user: &TestUser, alias: &str) -> reqwest::Response {
+    state.login_as(user).await;
+    state
+        .post_user_json("/aliases", &serde_json::json!({ "alias": alias }))
+        .await
+}
+
+/// Poll `query` on `db_name` until it returns a row.
+async fn wait_for_row(db_name: &str, query: &str) -> Option<tokio_postgres::Row> {
+    let client = connect_to_db(db_name).await;
+    for _ in 0..100 {
+        if let Some(row) = client.query_opt(query, &[]).await.unwrap() {
+            return Some(row);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    None
+}
+
+#[tokio::test]
+async fn aliases_are_published_in_the_actor_document() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    let alias = remote.actor_url(&remote.test_user_user);
+
+    // Act
+    let response = add_alias(
+        &local,
+        &local.test_user_user,
+        &format!("@{}", address(&remote, &remote.test_user_user)),
+    )
+    .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["also_known_as"], serde_json::json!([alias]));
+    let actor = local
+        .get_json(&local.actor_url(&local.test_user_user))
+        .await;
+    assert_eq!(actor["alsoKnownAs"], serde_json::json!([alias]));
+    let response = local
+        .post_user_json("/aliases/remove", &serde_json::json!({ "alias": alias }))
+        .await;
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["also_known_as"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn moves_need_the_target_to_list_the_account_as_alias() {
+    // Arrange
+    let old = spawn_app().await;
+    let new = spawn_app().await;
+    old.login_as(&old.test_user_user).await;
+
+    // Act
+    let response = old
+        .post_user_json(
+            "/move",
+            &serde_json::json!({ "target": new.actor_url(&new.test_user_user) }),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let moved_to: Option<String> = connect_to_db(&old.db_name)
+        .await
+        .query_one(
+            "SELECT moved_to FROM account WHERE id=$1",
+            &[&old.test_user_user.account_id],
+        )
+        .await
+        .unwrap()
+        .get(0);
+    assert!(moved_to.is_none());
+}
+
+#[tokio::test]
+async fn moved_accounts_tell_their_followers() {
+    // Arrange
+    let old = spawn_app().await;
+    let new = spawn_app().await;
+    let mock = MockServer::start().await;
+    let old_actor = old.actor_url(&old.test_user_user);
+    let new_actor = new.actor_url(&new.test_user_user);
+    let response = add_alias(&new, &new.test_user_user, &old_actor).await;
+    assert_eq!(response.status().as_u16(), 200);
+    add_mock_follower(&old, &mock).await;
+    old.login_as(&old.test_user_user).await;
+
+    // Act
+    let response = old
+        .post_user_json(
+            "/move",
+            &serde_json::json!({ "target": address(&new, &new.test_user_user) }),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let actor = old.get_json(&old_actor).await;
+    assert_eq!(actor["movedTo"], new_actor);
+    let activity = first_delivery(&mock, "/users/zoe/inbox").await;
+    assert_eq!(activity["type"], "Move");
+    assert_eq!(activity["object"], old_actor);
+    assert_eq!(activity["target"], new_actor);
+    let body = serde_json::json!({ "content": { "text": "still here?" } });
+    let response = old.post_content(&body).await;
+    assert_eq!(
+        response.status().as_u16(),
+        400,
+        "moved accounts cannot post"
+    );
+}
+
+#[tokio::test]
+async fn follows_of_moved_accounts_are_redirected() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    let origin = remote.actor_url(&remote.test_user_user);
+    let target = remote.actor_url(&remote.test_user_superadmin);
+    let response = add_alias(&remote, &remote.test_user_superadmin, &origin).await;
+    assert_eq!(response.status().as_u16(), 200);
+    connect_to_db(&local.db_name)
+        .await
+        .execute(
+            "INSERT INTO following (account_id, actor_id, follow_id, accepted) \
+             VALUES ($1, $2, $3, true)",
+            &[
+                &local.test_user_user.account_id,
+                &origin,
+                &format!("{}/follows/1", local.app_address),
+            ],
+        )
+        .await
+        .unwrap();
+    let activity = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}#moves/1", origin),
+        "type": "Move",
+        "actor": origin,
+        "object": origin,
+        "target": target,
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &activity)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let actors: Vec<String> = connect_to_db(&local.db_name)
+        .await
+        .query("SELECT actor_id FROM following", &[])
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+    assert_eq!(actors, vec![target.clone()]);
+    let follower = wait_for_row(&remote.db_name, "SELECT actor_id FROM follower")
+        .await
+        .expect("the new account received the Follow");
+    assert_eq!(
+        follower.get::<_, &str>(0),
+        local.actor_url(&local.test_user_user)
+    );
+}
+
+#[tokio::test]
+async fn moves_the_target_does_not_confirm_are_refused() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    let origin = remote.actor_url(&remote.test_user_user);
+    let activity = serde_json::json!({
+        "id": format!("{}#moves/1", origin),
+        "type": "Move",
+        "actor": origin,
+        "object": origin,
+        "target": remote.actor_url(&remote.test_user_superadmin),
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &activity)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let activity_id = activity["id"].as_str().unwrap();
+    assert_eq!(local.wait_for_activity(activity_id).await, "dead");
+}
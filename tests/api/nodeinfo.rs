@@ -0,0 +1,136 @@
+use crate::helpers::{assert_is_redirect_to, connect_to_db, spawn_app};
+
+#[tokio::test]
+async fn nodeinfo_is_discoverable() {
+    // Arrange
+    let state = spawn_app().await;
+
+    // Act
+    let links = state
+        .get_json(&format!("{}/.well-known/nodeinfo", state.app_address))
+        .await;
+
+    // Assert
+    assert_eq!(
+        links["links"][0]["rel"],
+        "http://nodeinfo.diaspora.software/ns/schema/2.1"
+    );
+    assert_eq!(
+        links["links"][0]["href"],
+        format!("{}/nodeinfo/2.1", state.app_address)
+    );
+}
+
+#[tokio::test]
+async fn nodeinfo_describes_the_software() {
+    // Arrange
+    let state = spawn_app().await;
+
+    // Act
+    let response = state
+        .api_client
+        .get(format!("{}/nodeinfo/2.1", state.app_address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .contains("nodeinfo.diaspora.software/ns/schema/2.1"));
+    let nodeinfo: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(nodeinfo["version"], "2.1");
+    assert_eq!(nodeinfo["software"]["name"], "rhodos");
+    assert_eq!(nodeinfo["software"]["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(nodeinfo["protocols"], serde_json::json!(["activitypub"]));
+    assert_eq!(nodeinfo["openRegistrations"], true);
+}
+
+#[tokio::test]
+async fn nodeinfo_counts_users_and_posts() {
+    // Arrange
+    let state = spawn_app().await;
+    let client = connect_to_db(&state.db_name).await;
+    let posts = [
+        (state.test_user_user.account_id, "now()", true),
+        (state.test_user_user.account_id, "now()", false),
+        (
+            state.test_user_superadmin.account_id,
+            "now() - interval '60 days'",
+            true,
+        ),
+        (
+            state.user_admin.account_id,
+            "now() - interval '1 year'",
+            true,
+        ),
+    ];
+    for (account_id, published_at, published) in posts {
+        client
+            .execute(
+                &format!(
+                    "INSERT INTO content (publisher_id, body, published, published_at)
+                        VALUES ($1, 'hello', $2, {})",
+                    published_at
+                ),
+                &[&account_id, &published],
+            )
+            .await
+            .unwrap();
+    }
+    let users: i64 = client
+        .query_one(r#"SELECT count(*) FROM "user""#, &[])
+        .await
+        .unwrap()
+        .get(0);
+
+    // Act
+    let nodeinfo = state
+        .get_json(&format!("{}/nodeinfo/2.1", state.app_address))
+        .await;
+
+    // Assert
+    let usage = &nodeinfo["usage"];
+    assert_eq!(usage["users"]["total"], users);
+    assert_eq!(usage["users"]["activeMonth"], 1);
+    assert_eq!(usage["users"]["activeHalfyear"], 2);
+    assert_eq!(usage["localPosts"], 3, "unpublished posts are not counted");
+}
+
+#[tokio::test]
+async fn closed_registrations_are_advertised_and_enforced() {
+    // Arrange
+    let state = spawn_app().await;
+    state.login_as(&state.test_user_superadmin).await;
+
+    // Act
+    let response = state
+        .api_client
+        .post(format!("{}/admin/registrations", state.app_address))
+        .form(&Vec::<(&str, &str)>::new())
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let nodeinfo = state
+        .get_json(&format!("{}/nodeinfo/2.1", state.app_address))
+        .await;
+    assert_eq!(nodeinfo["openRegistrations"], false);
+    let body = "name=Sonja%20Hemphill&email=sonja%40lowdelhi.example&password=a&role=user";
+    let response = state.post_user(body.to_string()).await;
+    assert_eq!(response.status().as_u16(), 401, "sign ups are refused");
+    let users: i64 = connect_to_db(&state.db_name)
+        .await
+        .query_one(
+            r#"SELECT count(*) FROM "user" WHERE email = 'sonja@lowdelhi.example'"#,
+            &[],
+        )
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(users, 0);
+}
@@ -0,0 +1,169 @@
+use crate::helpers::{connect_to_db, insert_post, spawn_app};
+
+#[tokio::test]
+async fn outbox_is_an_ordered_collection() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    state.login_as(user).await;
+    for text in ["first post", "second post"] {
+        let body = serde_json::json!({ "content": { "text": text } });
+        let response = state.post_content(&body).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Act
+    let response = state.get_outbox(&user.handle, "").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "application/activity+json"
+    );
+    let outbox: serde_json::Value = response.json().await.unwrap();
+    let id = format!("{}/users/{}/outbox", state.app_address, user.handle);
+    assert_eq!(outbox["type"], "OrderedCollection");
+    assert_eq!(outbox["id"], id);
+    assert_eq!(outbox["totalItems"], 2);
+
+    let page = state.get_json(outbox["first"].as_str().unwrap()).await;
+    assert_eq!(page["type"], "OrderedCollectionPage");
+    assert_eq!(page["partOf"], id);
+    let items = page["orderedItems"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0]["type"], "Create");
+    assert_eq!(
+        items[0]["actor"],
+        format!("{}/users/{}", state.app_address, user.handle)
+    );
+    let note = &items[0]["object"];
+    assert_eq!(note["type"], "Note");
+    assert_eq!(note["content"], "<p>second post</p>", "newest post first");
+    assert!(note["published"].is_string(), "the note has a timestamp");
+    assert_eq!(
+        note["to"][0],
+        "https://www.w3.org/ns/activitystreams#Public"
+    );
+    assert_eq!(
+        note["cc"][0],
+        format!("{}/users/{}/followers", state.app_address, user.handle)
+    );
+    assert_eq!(items[1]["object"]["content"], "<p>first post</p>");
+}
+
+#[tokio::test]
+async fn outbox_pages_through_history() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    for i in 0..25 {
+        insert_post(&client, user.account_id, &format!("post {}", i), "public").await;
+    }
+
+    // Act
+    let outbox: serde_json::Value = state
+        .get_outbox(&user.handle, "")
+        .await
+        .json()
+        .await
+        .unwrap();
+    let first = state.get_json(outbox["first"].as_str().unwrap()).await;
+    let second = state.get_json(first["next"].as_str().unwrap()).await;
+    let back = state.get_json(second["prev"].as_str().unwrap()).await;
+
+    // Assert
+    assert_eq!(outbox["totalItems"], 25);
+    assert_eq!(first["orderedItems"].as_array().unwrap().len(), 20);
+    assert_eq!(
+        first["orderedItems"][0]["object"]["content"],
+        "<p>post 24</p>"
+    );
+    let second_items = second["orderedItems"].as_array().unwrap();
+    assert_eq!(second_items.len(), 5, "the second page holds the rest");
+    assert_eq!(second_items[0]["object"]["content"], "<p>post 4</p>");
+    assert!(second.get("next").is_none(), "the last page has no next");
+    let back_items = back["orderedItems"].as_array().unwrap();
+    assert_eq!(back_items.len(), 20, "prev leads back to newer posts");
+    assert_eq!(back_items[19]["object"]["content"], "<p>post 5</p>");
+}
+
+#[tokio::test]
+async fn outbox_only_lists_public_published_posts() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    insert_post(&client, user.account_id, "public", "public").await;
+    insert_post(&client, user.account_id, "unlisted", "unlisted").await;
+    insert_post(&client, user.account_id, "followers", "followers").await;
+    insert_post(&client, user.account_id, "direct", "direct").await;
+    client
+        .execute(
+            "INSERT INTO content (publisher_id, body) VALUES ($1, 'unpublished')",
+            &[&user.account_id],
+        )
+        .await
+        .unwrap();
+
+    // Act
+    let page = state
+        .get_outbox(&user.handle, "?page=true")
+        .await
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+
+    // Assert
+    let items = page["orderedItems"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    let unlisted = &items[0]["object"];
+    assert_eq!(unlisted["content"], "<p>unlisted</p>");
+    assert_eq!(
+        unlisted["to"][0],
+        format!("{}/users/{}/followers", state.app_address, user.handle)
+    );
+    assert_eq!(
+        unlisted["cc"][0],
+        "https://www.w3.org/ns/activitystreams#Public"
+    );
+}
+
+#[tokio::test]
+async fn content_warning_becomes_summary() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    let id = insert_post(&client, user.account_id, "spoilers ahead", "public").await;
+    client
+        .execute("UPDATE content SET cw='film plot' WHERE id=$1", &[&id])
+        .await
+        .unwrap();
+
+    // Act
+    let page = state
+        .get_outbox(&user.handle, "?page=true")
+        .await
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+
+    // Assert
+    let note = &page["orderedItems"][0]["object"];
+    assert_eq!(note["summary"], "film plot");
+    assert_eq!(note["sensitive"], true);
+}
+
+#[tokio::test]
+async fn outbox_of_unknown_account_is_not_found_404() {
+    // Arrange
+    let state = spawn_app().await;
+
+    // Act
+    let response = state.get_outbox("nobody", "").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -112,7 +112,7 @@ async fn password_reset_empty_password() {
     // Assert- 2 follow the redirect
     let html_page = state.get_password_reset_html().await;
     assert!(
-        html_page.contains(&format!("<p><i>You didn't specify a new password</i></p>")),
+        html_page.contains("<p><i>You didn't specify a new password</i></p>"),
         "reset page shows empty password message"
     );
 }
@@ -137,9 +137,7 @@ async fn password_reset_wrong_current_password() {
     // Assert- 2 follow the redirect
     let html_page = state.get_password_reset_html().await;
     assert!(
-        html_page.contains(&format!(
-            "<p><i>Your current password does not match</i></p>"
-        )),
+        html_page.contains("<p><i>Your current password does not match</i></p>"),
         "reset page shows wrong current password message"
     );
 }
@@ -164,9 +162,7 @@ async fn password_reset_confirm_password_mismatch() {
     // Assert- 2 follow the redirect
     let html_page = state.get_password_reset_html().await;
     assert!(
-        html_page.contains(&format!(
-            "<p><i>The new password and the confirmation do not match</i></p>"
-        )),
+        html_page.contains("<p><i>The new password and the confirmation do not match</i></p>"),
         "reset page shows password mismatch message"
     );
 }
@@ -193,9 +189,9 @@ async fn happy_path_password_reset_redirect_303() {
     assert_is_redirect_to(&response, "/login");
     let html_page = state.get_login_html().await;
     assert!(
-        html_page.contains(&format!(
+        html_page.contains(
             "<p><i>Your password has been successfully updated. Please log in again.</i></p>"
-        )),
+        ),
         "reset page shows success message"
     );
 
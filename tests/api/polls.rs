@@ -0,0 +1,348 @@
+use std::time::Duration;
+
+use tokio_postgres::Client;
+use uuid::Uuid;
+
+use crate::{
+    helpers::{add_mock_follower, connect_to_db, spawn_app, TestState},
+    mock_server::MockServer,
+};
+
+/// Post `text` with a poll offering `options` as the logged in user and
+/// return the ids of the post and of the poll.
+async fn post_poll(state: &TestState, text: &str, options: &[&str], multiple: bool) -> (i64, i64) {
+    let body = serde_json::json!({
+        "content": {
+            "text": text,
+            "poll": { "options": options, "multiple": multiple, "expires_in": 3600 },
+        }
+    });
+    let response = state.post_content(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let row = connect_to_db(&state.db_name)
+        .await
+        .query_one(
+            "SELECT content.id, poll.id FROM content JOIN poll ON poll.content_id = content.id \
+             ORDER BY content.id DESC LIMIT 1",
+            &[],
+        )
+        .await
+        .unwrap();
+
+    (row.get(0), row.get(1))
+}
+
+async fn vote(state: &TestState, poll_id: i64, choices: &[usize]) -> reqwest::Response {
+    state
+        .api_client
+        .post(format!(
+            "{}/content/polls/{}/votes",
+            state.app_address, poll_id
+        ))
+        .json(&serde_json::json!({ "choices": choices }))
+        .send()
+        .await
+        .expect("Failed to vote")
+}
+
+fn status_url(state: &TestState, handle: &str, id: i64) -> String {
+    format!("{}/users/{}/statuses/{}", state.app_address, handle, id)
+}
+
+/// The vote counts of the options of the poll `poll_id`, in order.
+async fn counts(client: &Client, poll_id: i64) -> Vec<i32> {
+    client
+        .query(
+            "SELECT votes_count FROM poll_option WHERE poll_id=$1 ORDER BY position",
+            &[&poll_id],
+        )
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.get(0))
+        .collect()
+}
+
+#[tokio::test]
+async fn polls_are_served_as_questions() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.test_user_user).await;
+
+    // Act
+    let (post_id, _) = post_poll(&app, "Tea or coffee?", &["tea", "coffee"], false).await;
+
+    // Assert
+    let question = app
+        .get_json(&status_url(&app, &app.test_user_user.handle, post_id))
+        .await;
+    assert_eq!(question["type"], "Question");
+    assert_eq!(question["oneOf"][0]["name"], "tea");
+    assert_eq!(question["oneOf"][1]["replies"]["totalItems"], 0);
+    assert!(question["anyOf"].is_null());
+    assert!(question["endTime"].is_string());
+    assert_eq!(question["votersCount"], 0);
+}
+
+#[tokio::test]
+async fn invalid_polls_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.test_user_user).await;
+    let cases = [
+        (
+            serde_json::json!({ "options": ["only"] }),
+            "a single option",
+        ),
+        (
+            serde_json::json!({ "options": ["1", "2", "3", "4", "5", "6", "7", "8", "9"] }),
+            "nine options",
+        ),
+        (serde_json::json!({ "options": ["a", "a"] }), "duplicates"),
+        (
+            serde_json::json!({ "options": ["a", "b"], "expires_in": 10 }),
+            "ten seconds",
+        ),
+    ];
+
+    for (poll, case) in cases {
+        // Act
+        let body = serde_json::json!({ "content": { "text": "vote!", "poll": poll } });
+        let response = app.post_content(&body).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 400, "{}", case);
+    }
+    let polls: i64 = connect_to_db(&app.db_name)
+        .await
+        .query_one("SELECT count(*) FROM poll", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(polls, 0);
+}
+
+#[tokio::test]
+async fn local_users_vote_once() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.test_user_superadmin).await;
+    let (_, poll_id) = post_poll(&app, "Pick any", &["a", "b", "c"], true).await;
+    let own = vote(&app, poll_id, &[0]).await;
+    app.post_logout().await;
+    app.login_as(&app.test_user_user).await;
+
+    // Act
+    let response = vote(&app, poll_id, &[0, 2]).await;
+
+    // Assert
+    assert_eq!(own.status().as_u16(), 400, "authors cannot vote");
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["voters_count"], 1);
+    assert_eq!(body["options"][2]["votes_count"], 1);
+    assert_eq!(body["own_votes"], serde_json::json!([0, 2]));
+    let again = vote(&app, poll_id, &[1]).await;
+    assert_eq!(again.status().as_u16(), 400, "no second vote");
+    let client = connect_to_db(&app.db_name).await;
+    assert_eq!(counts(&client, poll_id).await, vec![1, 0, 1]);
+}
+
+#[tokio::test]
+async fn single_choice_polls_take_one_option() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.test_user_superadmin).await;
+    let (_, poll_id) = post_poll(&app, "Pick one", &["a", "b"], false).await;
+    app.post_logout().await;
+    app.login_as(&app.test_user_user).await;
+
+    // Act
+    let response = vote(&app, poll_id, &[0, 1]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(vote(&app, poll_id, &[5]).await.status().as_u16(), 400);
+    let client = connect_to_db(&app.db_name).await;
+    assert_eq!(counts(&client, poll_id).await, vec![0, 0]);
+}
+
+#[tokio::test]
+async fn remote_votes_are_counted() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    local.login_as(&local.test_user_user).await;
+    let (post_id, poll_id) = post_poll(&local, "Tea or coffee?", &["tea", "coffee"], false).await;
+    let voter = remote.actor_url(&remote.test_user_user);
+    let vote_for = |name: &str| {
+        let id = format!("{}#votes/{}", voter, Uuid::new_v4());
+        serde_json::json!({
+            "@context": "https://www.w3.org/ns/activitystreams",
+            "id": format!("{}/activity", id),
+            "type": "Create",
+            "actor": voter,
+            "object": {
+                "id": id,
+                "type": "Note",
+                "name": name,
+                "attributedTo": voter,
+                "inReplyTo": status_url(&local, &local.test_user_user.handle, post_id),
+                "to": [local.actor_url(&local.test_user_user)],
+            },
+        })
+    };
+
+    // Act
+    let response = remote
+        .deliver_to(
+            &remote.test_user_user,
+            &local,
+            "/inbox",
+            &vote_for("coffee"),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let second = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &vote_for("tea"))
+        .await;
+    assert_eq!(second.status().as_u16(), 202);
+    let client = connect_to_db(&local.db_name).await;
+    assert_eq!(counts(&client, poll_id).await, vec![0, 1]);
+    let row = client
+        .query_one(
+            "SELECT voters_count, (SELECT count(*) FROM remote_post) FROM poll WHERE id=$1",
+            &[&poll_id],
+        )
+        .await
+        .unwrap();
+    assert_eq!(row.get::<_, i32>(0), 1);
+    assert_eq!(row.get::<_, i64>(1), 0, "votes are not posts");
+}
+
+#[tokio::test]
+async fn remote_polls_can_be_voted_on() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    remote.login_as(&remote.test_user_user).await;
+    let (remote_post_id, remote_poll_id) =
+        post_poll(&remote, "Tea or coffee?", &["tea", "coffee"], false).await;
+    let author = remote.actor_url(&remote.test_user_user);
+    connect_to_db(&local.db_name)
+        .await
+        .execute(
+            "INSERT INTO following (account_id, actor_id, follow_id, accepted) \
+             VALUES ($1, $2, $3, true)",
+            &[
+                &local.test_user_user.account_id,
+                &author,
+                &format!("{}/follows/1", local.app_address),
+            ],
+        )
+        .await
+        .unwrap();
+    let question = remote
+        .get_json(&status_url(
+            &remote,
+            &remote.test_user_user.handle,
+            remote_post_id,
+        ))
+        .await;
+    let create = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}/activity", question["id"].as_str().unwrap()),
+        "type": "Create",
+        "actor": author,
+        "object": question,
+    });
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &create)
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let poll_id: i64 = connect_to_db(&local.db_name)
+        .await
+        .query_one(
+            "SELECT poll.id FROM poll JOIN remote_post ON remote_post.id = poll.remote_post_id",
+            &[],
+        )
+        .await
+        .expect("the remote poll was stored")
+        .get(0);
+    local.login_as(&local.test_user_user).await;
+
+    // Act
+    let response = vote(&local, poll_id, &[1]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["options"][1]["title"], "coffee");
+    assert_eq!(body["options"][1]["votes_count"], 1);
+    let client = connect_to_db(&remote.db_name).await;
+    let mut remote_counts = vec![];
+    for _ in 0..100 {
+        remote_counts = counts(&client, remote_poll_id).await;
+        if remote_counts == vec![0, 1] {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(remote_counts, vec![0, 1], "the vote reached the author");
+}
+
+#[tokio::test]
+async fn expired_polls_are_closed_and_announced() {
+    // Arrange
+    let app = spawn_app().await;
+    let mock = MockServer::start().await;
+    let client = connect_to_db(&app.db_name).await;
+    add_mock_follower(&app, &mock).await;
+    app.login_as(&app.test_user_user).await;
+    let (content_id, poll_id) = post_poll(&app, "Quick one", &["yes", "no"], false).await;
+
+    // Act
+    client
+        .execute(
+            "UPDATE poll SET expires_at = now() AT TIME ZONE 'UTC' - interval '1 minute' \
+             WHERE id=$1",
+            &[&poll_id],
+        )
+        .await
+        .unwrap();
+
+    // Assert
+    let mut update = None;
+    for _ in 0..100 {
+        update = mock
+            .received("/users/zoe/inbox")
+            .iter()
+            .map(|r| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap())
+            .find(|a| a["type"] == "Update");
+        if update.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let update = update.expect("the closed poll was announced");
+    assert_eq!(update["object"]["type"], "Question");
+    let status = status_url(&app, &app.test_user_user.handle, content_id);
+    assert_eq!(update["actor"], app.actor_url(&app.test_user_user));
+    assert_eq!(
+        update["object"]["id"], status,
+        "the ids the poll was created with"
+    );
+    assert_eq!(update["id"], format!("{}#updates/closed", status));
+    assert!(update["object"]["closed"].is_string());
+    let closed_at: Option<chrono::NaiveDateTime> = client
+        .query_one("SELECT closed_at FROM poll WHERE id=$1", &[&poll_id])
+        .await
+        .unwrap()
+        .get(0);
+    assert!(closed_at.is_some());
+    app.post_logout().await;
+    app.login_as(&app.test_user_superadmin).await;
+    assert_eq!(vote(&app, poll_id, &[0]).await.status().as_u16(), 400);
+}
@@ -0,0 +1,263 @@
+use librhodos::federation::Signer;
+
+use crate::{
+    helpers::{assert_is_redirect_to, connect_to_db, first_delivery, spawn_app, TestState},
+    mock_server::{borrow_key, MockServer},
+};
+
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+async fn subscribe(state: &TestState, url: &str) -> reqwest::Response {
+    state
+        .api_client
+        .post(format!("{}/admin/relays", state.app_address))
+        .form(&[("url", url)])
+        .send()
+        .await
+        .expect("Failed to subscribe to relay")
+}
+
+/// Mount a LitePub relay on `mock`, subscribe the tenant of `state` to it
+/// and return a signer for the relay along with the `Follow` it received.
+async fn subscribe_to_mock_relay(
+    state: &TestState,
+    mock: &MockServer,
+) -> (Signer, serde_json::Value) {
+    let key_id = mock.url("/users/relay#main-key");
+    let (signer, pem) = borrow_key(state, &state.test_user_superadmin, &key_id).await;
+    mock.mount_actor("relay", &pem);
+    state.login_as(&state.test_user_superadmin).await;
+    let response = subscribe(state, &mock.url("/users/relay")).await;
+    assert_is_redirect_to(&response, "/admin/relays");
+    let follow = first_delivery(mock, "/users/relay/inbox").await;
+
+    (signer, follow)
+}
+
+/// Deliver `activity` from the relay on `mock` to the shared inbox of `state`.
+async fn deliver_from_relay(
+    state: &TestState,
+    signer: &Signer,
+    activity: &serde_json::Value,
+) -> reqwest::Response {
+    let mut request = state.inbox_request("/inbox", activity);
+    signer.sign(&mut request).unwrap();
+
+    state.post_inbox(request).await
+}
+
+fn accept(mock: &MockServer, follow: &serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": mock.url("/accepts/1"),
+        "type": "Accept",
+        "actor": mock.url("/users/relay"),
+        "object": follow,
+    })
+}
+
+/// Publish a public post on `state` and return the id of its note.
+async fn remote_note(state: &TestState) -> String {
+    state.login_as(&state.test_user_user).await;
+    let body = serde_json::json!({ "content": { "text": "Hello, relay" } });
+    assert_eq!(state.post_content(&body).await.status().as_u16(), 200);
+    let id: i64 = connect_to_db(&state.db_name)
+        .await
+        .query_one("SELECT id FROM content", &[])
+        .await
+        .unwrap()
+        .get(0);
+
+    format!(
+        "{}/users/{}/statuses/{}",
+        state.app_address, state.test_user_user.handle, id
+    )
+}
+
+fn announce(mock: &MockServer, object: &str) -> serde_json::Value {
+    serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": mock.url(&format!("/announces/{}", uuid::Uuid::new_v4())),
+        "type": "Announce",
+        "actor": mock.url("/users/relay"),
+        "object": object,
+        "to": [mock.url("/users/relay/followers")],
+    })
+}
+
+async fn stored_posts(state: &TestState, object_id: &str) -> i64 {
+    connect_to_db(&state.db_name)
+        .await
+        .query_one(
+            "SELECT count(*) FROM remote_post WHERE object_id=$1",
+            &[&object_id],
+        )
+        .await
+        .unwrap()
+        .get(0)
+}
+
+/// Subscribe the tenant of `state` to an accepted Mastodon-style relay whose
+/// inbox is on `mock` and return the id of the subscription.
+async fn add_accepted_relay(state: &TestState, mock: &MockServer) -> i64 {
+    connect_to_db(&state.db_name)
+        .await
+        .query_one(
+            "INSERT INTO relay (url, inbox, actor_id, follow_id, state) \
+             VALUES ($1, $1, $2, $3, 'accepted') RETURNING id",
+            &[
+                &mock.url("/inbox"),
+                &mock.url("/actor"),
+                &format!("{}/actor#relays/1", state.app_address),
+            ],
+        )
+        .await
+        .unwrap()
+        .get(0)
+}
+
+#[tokio::test]
+async fn mastodon_relays_are_followed_with_the_public_collection() {
+    // Arrange
+    let app = spawn_app().await;
+    let mock = MockServer::start().await;
+    app.login_as(&app.test_user_superadmin).await;
+
+    // Act
+    let response = subscribe(&app, &mock.url("/inbox")).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/relays");
+    let follow = first_delivery(&mock, "/inbox").await;
+    assert_eq!(follow["type"], "Follow");
+    assert_eq!(follow["actor"], format!("{}/actor", app.app_address));
+    assert_eq!(follow["object"], PUBLIC);
+    let signature = mock.received("/inbox")[0].headers["signature"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(signature.contains(&format!("keyId=\"{}/actor#", app.app_address)));
+    let state: String = connect_to_db(&app.db_name)
+        .await
+        .query_one("SELECT state FROM relay", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(state, "pending");
+    let page = app
+        .api_client
+        .get(format!("{}/admin/relays", app.app_address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains(&mock.url("/inbox")));
+}
+
+#[tokio::test]
+async fn litepub_relays_are_followed_like_actors() {
+    // Arrange
+    let app = spawn_app().await;
+    let mock = MockServer::start().await;
+
+    // Act
+    let (_, follow) = subscribe_to_mock_relay(&app, &mock).await;
+
+    // Assert
+    assert_eq!(follow["type"], "Follow");
+    assert_eq!(follow["object"], mock.url("/users/relay"));
+    let again = subscribe(&app, &mock.url("/users/relay")).await;
+    assert_eq!(again.status().as_u16(), 400, "subscribed twice");
+}
+
+#[tokio::test]
+async fn accepted_relays_bring_in_announced_posts() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    let mock = MockServer::start().await;
+    let (signer, follow) = subscribe_to_mock_relay(&local, &mock).await;
+    let note = remote_note(&remote).await;
+    let early = deliver_from_relay(&local, &signer, &announce(&mock, &note)).await;
+    assert_eq!(early.status().as_u16(), 202);
+    assert_eq!(stored_posts(&local, &note).await, 0, "the relay is pending");
+    let response = deliver_from_relay(&local, &signer, &accept(&mock, &follow)).await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // Act
+    let response = deliver_from_relay(&local, &signer, &announce(&mock, &note)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let state: String = connect_to_db(&local.db_name)
+        .await
+        .query_one("SELECT state FROM relay", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(state, "accepted");
+    assert_eq!(stored_posts(&local, &note).await, 1);
+}
+
+#[tokio::test]
+async fn public_posts_are_forwarded_to_relays() {
+    // Arrange
+    let app = spawn_app().await;
+    let mock = MockServer::start().await;
+    add_accepted_relay(&app, &mock).await;
+    app.login_as(&app.test_user_user).await;
+    let followers_only =
+        serde_json::json!({ "content": { "text": "friends only", "visibility": "followers" } });
+    assert_eq!(
+        app.post_content(&followers_only).await.status().as_u16(),
+        200
+    );
+
+    // Act
+    let body = serde_json::json!({ "content": { "text": "Hello, fediverse" } });
+    let response = app.post_content(&body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let create = first_delivery(&mock, "/inbox").await;
+    assert_eq!(create["type"], "Create");
+    assert_eq!(create["actor"], app.actor_url(&app.test_user_user));
+    assert!(create["object"]["content"]
+        .as_str()
+        .unwrap()
+        .contains("Hello, fediverse"));
+    assert_eq!(mock.received("/inbox").len(), 1);
+}
+
+#[tokio::test]
+async fn unsubscribing_undoes_the_follow() {
+    // Arrange
+    let app = spawn_app().await;
+    let mock = MockServer::start().await;
+    let id = add_accepted_relay(&app, &mock).await;
+    app.login_as(&app.test_user_superadmin).await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/relays/{}/delete", app.app_address, id))
+        .send()
+        .await
+        .expect("Failed to unsubscribe");
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/relays");
+    let undo = first_delivery(&mock, "/inbox").await;
+    assert_eq!(undo["type"], "Undo");
+    assert_eq!(undo["object"]["type"], "Follow");
+    assert_eq!(undo["object"]["object"], PUBLIC);
+    let relays: i64 = connect_to_db(&app.db_name)
+        .await
+        .query_one("SELECT count(*) FROM relay", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(relays, 0);
+}
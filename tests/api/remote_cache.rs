@@ -0,0 +1,365 @@
+use librhodos::{
+    federation::{
+        fetch::{FetchError, Fetcher},
+        remote, FederationState, Signer,
+    },
+    get_database_connection,
+};
+use std::time::Duration;
+
+use crate::{
+    helpers::{connect_to_db, spawn_app, TestState},
+    mock_server::{borrow_key, MockServer},
+};
+
+/// Sign `activity` with `signer` and deliver it to `path` on `receiver`.
+async fn deliver(
+    signer: &Signer,
+    receiver: &TestState,
+    path: &str,
+    activity: &serde_json::Value,
+) -> reqwest::Response {
+    let mut request = receiver.inbox_request(path, activity);
+    signer.sign(&mut request).unwrap();
+
+    receiver.post_inbox(request).await
+}
+
+fn follow(mock: &MockServer, actor: &str, object: &str) -> serde_json::Value {
+    serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": mock.url(&format!("/follows/{}", uuid::Uuid::new_v4())),
+        "type": "Follow",
+        "actor": actor,
+        "object": object,
+    })
+}
+
+#[tokio::test]
+async fn follow_caches_the_remote_actor() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    let key_id = mock.url("/users/zoe#main-key");
+    let (signer, pem) = borrow_key(&state, &state.test_user_superadmin, &key_id).await;
+    let actor = mock.mount_actor("zoe", &pem);
+    let actor_id = actor["id"].as_str().unwrap();
+    let followee = state.actor_url(&state.test_user_user);
+
+    // Act
+    let response = deliver(
+        &signer,
+        &state,
+        &format!("/users/{}/inbox", state.test_user_user.handle),
+        &follow(&mock, actor_id, &followee),
+    )
+    .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let row = connect_to_db(&state.db_name)
+        .await
+        .query_one(
+            "SELECT username, inbox, shared_inbox, public_key_id, public_key_pem
+                FROM remote_actor WHERE actor_id=$1",
+            &[&actor_id],
+        )
+        .await
+        .expect("the actor is cached");
+    assert_eq!(row.get::<_, &str>(0), "zoe");
+    assert_eq!(row.get::<_, &str>(1), mock.url("/users/zoe/inbox"));
+    assert_eq!(row.get::<_, &str>(2), mock.url("/inbox"));
+    assert_eq!(row.get::<_, &str>(3), key_id);
+    assert_eq!(row.get::<_, &str>(4), pem);
+    assert_eq!(
+        mock.received("/users/zoe").len(),
+        1,
+        "the follow reuses the actor fetched to verify the signature"
+    );
+
+    let mut accepted = false;
+    for _ in 0..50 {
+        if let Some(request) = mock.received("/users/zoe/inbox").first() {
+            let accept: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            assert_eq!(request.method, reqwest::Method::POST);
+            assert_eq!(accept["type"], "Accept");
+            assert!(request.headers.contains_key("signature"));
+            accepted = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(accepted, "the Accept went to the cached inbox");
+}
+
+#[tokio::test]
+async fn fetches_are_signed_and_follow_redirects() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    mock.mount_actor("zoe", "PEM");
+    mock.redirect("/@zoe", "/users/zoe");
+    let signer = state.signer_for(&state.test_user_user).await;
+    let db = get_database_connection(&state.global_config).await.unwrap();
+    let federation = FederationState::new(true).unwrap();
+
+    // Act
+    let document = Fetcher::new(&federation)
+        .signed_by(Some(&signer))
+        .fetch_json(&db, &mock.url("/@zoe"))
+        .await
+        .expect("the redirect is followed");
+
+    // Assert
+    assert_eq!(document["id"], mock.url("/users/zoe"));
+    let requests = [mock.received("/@zoe"), mock.received("/users/zoe")].concat();
+    assert_eq!(requests.len(), 2);
+    for request in requests {
+        let signature = request.headers["signature"].to_str().unwrap();
+        assert!(
+            signature.contains(&format!(r#"keyId="{}""#, signer.key_id)),
+            "every hop is signed: {}",
+            signature
+        );
+    }
+}
+
+#[tokio::test]
+async fn redirects_to_refused_domains_are_not_followed() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    mock.mount_actor("zoe", "PEM");
+    // The mock server is reachable as localhost too, which is suspended
+    let port = mock.address.rsplit(':').next().unwrap();
+    mock.redirect("/@zoe", &format!("http://localhost:{}/users/zoe", port));
+    connect_to_db(&state.db_name)
+        .await
+        .execute(
+            "INSERT INTO domain_block (domain, severity) VALUES ('localhost', 'suspend')",
+            &[],
+        )
+        .await
+        .unwrap();
+    let db = get_database_connection(&state.global_config).await.unwrap();
+    let federation = FederationState::new(true).unwrap();
+
+    // Act
+    let result = Fetcher::new(&federation)
+        .fetch_json(&db, &mock.url("/@zoe"))
+        .await;
+
+    // Assert
+    assert!(matches!(result, Err(FetchError::Refused(_))));
+    assert_eq!(mock.received("/@zoe").len(), 1);
+    assert!(mock.received("/users/zoe").is_empty());
+}
+
+#[tokio::test]
+async fn document_from_another_origin_is_rejected() {
+    // Arrange
+    let state = spawn_app().await;
+    let db = get_database_connection(&state.global_config).await.unwrap();
+    let mock = MockServer::start().await;
+    mock.mount(
+        "/notes/1",
+        serde_json::json!({
+            "id": "https://elsewhere.example/notes/1",
+            "type": "Note",
+            "content": "not mine",
+        }),
+    );
+    let federation = FederationState::new(true).unwrap();
+
+    // Act
+    let result = Fetcher::new(&federation)
+        .fetch_json(&db, &mock.url("/notes/1"))
+        .await;
+
+    // Assert
+    assert!(result.is_err(), "the note is not hosted where it claims");
+}
+
+#[tokio::test]
+async fn cached_actor_is_refreshed_after_its_ttl() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    mock.mount_actor("zoe", "PEM");
+    let id = mock.url("/users/zoe");
+    let db = get_database_connection(&state.global_config).await.unwrap();
+    let federation = FederationState::new(true).unwrap();
+    let fetcher = Fetcher::new(&federation);
+    remote::actor(&fetcher, &db, &id).await.unwrap();
+
+    // Act
+    let cached = remote::actor(&fetcher, &db, &id).await.unwrap();
+    let requests_while_fresh = mock.received("/users/zoe").len();
+    connect_to_db(&state.db_name)
+        .await
+        .execute(
+            "UPDATE remote_actor SET fetched_at = fetched_at - interval '2 days'",
+            &[],
+        )
+        .await
+        .unwrap();
+    mock.mount_actor("zoe", "NEW PEM");
+    let refreshed = remote::actor(&fetcher, &db, &id).await.unwrap();
+
+    // Assert
+    assert_eq!(requests_while_fresh, 1, "a fresh copy is not fetched again");
+    assert_eq!(cached.public_key_pem.as_deref(), Some("PEM"));
+    assert_eq!(mock.received("/users/zoe").len(), 2);
+    assert_eq!(refreshed.public_key_pem.as_deref(), Some("NEW PEM"));
+}
+
+#[tokio::test]
+async fn key_mismatch_refreshes_cached_actor() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    let key_id = mock.url("/users/zoe#main-key");
+    let (old_signer, old_pem) = borrow_key(&state, &state.test_user_superadmin, &key_id).await;
+    let (new_signer, new_pem) = borrow_key(&state, &state.test_user_user, &key_id).await;
+    let actor_id = mock.mount_actor("zoe", &old_pem)["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let followee = state.actor_url(&state.test_user_user);
+    let path = format!("/users/{}/inbox", state.test_user_user.handle);
+    let response = deliver(
+        &old_signer,
+        &state,
+        &path,
+        &follow(&mock, &actor_id, &followee),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // Act
+    // zoe replaces the key without changing its id
+    mock.mount_actor("zoe", &new_pem);
+    let response = deliver(
+        &new_signer,
+        &state,
+        &path,
+        &follow(&mock, &actor_id, &followee),
+    )
+    .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let row = connect_to_db(&state.db_name)
+        .await
+        .query_one(
+            "SELECT public_key_pem FROM remote_actor WHERE actor_id=$1",
+            &[&actor_id],
+        )
+        .await
+        .unwrap();
+    assert_eq!(row.get::<_, &str>(0), new_pem);
+}
+
+#[tokio::test]
+async fn create_of_object_reference_is_dereferenced() {
+    // Arrange
+    let state = spawn_app().await;
+    let mock = MockServer::start().await;
+    let key_id = mock.url("/users/zoe#main-key");
+    let (signer, pem) = borrow_key(&state, &state.test_user_superadmin, &key_id).await;
+    let actor_id = mock.mount_actor("zoe", &pem)["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let note_id = mock.url("/notes/1");
+    mock.mount(
+        "/notes/1",
+        serde_json::json!({
+            "id": note_id,
+            "type": "Note",
+            "attributedTo": actor_id,
+            "content": "<p>by reference</p>",
+            "to": [state.actor_url(&state.test_user_user)],
+        }),
+    );
+    let create = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": mock.url("/notes/1/activity"),
+        "type": "Create",
+        "actor": actor_id,
+        "object": note_id,
+    });
+
+    // Act
+    let response = deliver(&signer, &state, "/inbox", &create).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let client = connect_to_db(&state.db_name).await;
+    let row = client
+        .query_one(
+            "SELECT content FROM remote_post WHERE object_id=$1",
+            &[&note_id],
+        )
+        .await
+        .expect("the note is stored");
+    assert_eq!(row.get::<_, &str>(0), "<p>by reference</p>");
+    let row = client
+        .query_one(
+            "SELECT kind, attributed_to FROM remote_object WHERE object_id=$1",
+            &[&note_id],
+        )
+        .await
+        .expect("the note is cached");
+    assert_eq!(row.get::<_, &str>(0), "Note");
+    assert_eq!(row.get::<_, &str>(1), actor_id);
+}
+
+#[tokio::test]
+async fn private_addresses_are_not_fetched() {
+    // Arrange
+    let state = spawn_app().await;
+    let db = get_database_connection(&state.global_config).await.unwrap();
+    let mock = MockServer::start().await;
+    mock.mount_actor("zoe", "PEM");
+    let port = mock.address.rsplit(':').next().unwrap().to_string();
+    mock.redirect("/@zoe", &mock.url("/users/zoe"));
+    let federation = FederationState::new(false).unwrap();
+    let fetcher = Fetcher::new(&federation);
+
+    // Act
+    let by_address = fetcher.fetch_json(&db, &mock.url("/users/zoe")).await;
+    let by_name = fetcher
+        .fetch_json(&db, &format!("http://localhost:{}/@zoe", port))
+        .await;
+
+    // Assert
+    assert!(matches!(by_address, Err(FetchError::Unreachable(_))));
+    assert!(by_name.is_err(), "localhost resolves to loopback only");
+    assert!(mock.received("/users/zoe").is_empty());
+    assert!(mock.received("/@zoe").is_empty());
+}
+
+#[tokio::test]
+async fn oversized_documents_are_not_read() {
+    // Arrange
+    let state = spawn_app().await;
+    let db = get_database_connection(&state.global_config).await.unwrap();
+    let mock = MockServer::start().await;
+    let id = mock.url("/notes/1");
+    mock.mount(
+        "/notes/1",
+        serde_json::json!({
+            "id": id,
+            "type": "Note",
+            "content": "a".repeat(2 * 1024 * 1024),
+        }),
+    );
+    let federation = FederationState::new(true).unwrap();
+
+    // Act
+    let result = Fetcher::new(&federation).fetch_json(&db, &id).await;
+
+    // Assert
+    assert!(matches!(result, Err(FetchError::InvalidDocument(_))));
+}
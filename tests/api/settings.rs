@@ -110,7 +110,7 @@ async fn email_outgoing_00() {
     assert_eq!(user, "macgregor");
     assert_eq!(password.expose_secret(), "buttercup");
     assert_eq!(sender.as_ref(), "wh@benji.org");
-    assert_eq!(disable_ssl, false, "ssl is enabled by default");
+    assert!(!disable_ssl, "ssl is enabled by default");
 }
 
 #[tokio::test]
@@ -0,0 +1,197 @@
+use crate::helpers::{connect_to_db, insert_post, spawn_app, TestState};
+
+const ACTIVITY_JSON: &str = "application/activity+json";
+
+fn page_url(state: &TestState, handle: &str, id: i64) -> String {
+    format!("{}/@{}/{}", state.app_address, handle, id)
+}
+
+fn note_url(state: &TestState, handle: &str, id: i64) -> String {
+    format!("{}/users/{}/statuses/{}", state.app_address, handle, id)
+}
+
+#[tokio::test]
+async fn status_page_renders_html_for_browsers() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    let id = insert_post(&client, user.account_id, "hello <world>", "public").await;
+
+    // Act
+    let response = state
+        .get_status(&page_url(&state, &user.handle, id), "text/html")
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .headers()
+        .get("Content-Type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<p>hello &lt;world&gt;</p>"));
+    assert!(html.contains(&format!(r#"href="{}""#, note_url(&state, &user.handle, id))));
+}
+
+#[tokio::test]
+async fn status_is_a_note_for_activitypub_clients() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    let id = insert_post(&client, user.account_id, "hello", "unlisted").await;
+
+    for url in [
+        page_url(&state, &user.handle, id),
+        note_url(&state, &user.handle, id),
+    ] {
+        for accept in [
+            ACTIVITY_JSON,
+            r#"application/ld+json; profile="https://www.w3.org/ns/activitystreams""#,
+        ] {
+            // Act
+            let response = state.get_status(&url, accept).await;
+
+            // Assert
+            assert_eq!(response.status().as_u16(), 200);
+            assert_eq!(
+                response.headers().get("Content-Type").unwrap(),
+                ACTIVITY_JSON
+            );
+            let note: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(note["@context"], "https://www.w3.org/ns/activitystreams");
+            assert_eq!(note["type"], "Note");
+            assert_eq!(note["id"], note_url(&state, &user.handle, id));
+            assert_eq!(note["url"], page_url(&state, &user.handle, id));
+            assert_eq!(note["content"], "<p>hello</p>");
+        }
+    }
+}
+
+#[tokio::test]
+async fn hidden_statuses_are_not_found() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    let draft: i64 = client
+        .query_one(
+            "INSERT INTO content (publisher_id, body) VALUES ($1, 'draft') RETURNING id",
+            &[&user.account_id],
+        )
+        .await
+        .unwrap()
+        .get(0);
+    let followers = insert_post(&client, user.account_id, "followers", "followers").await;
+    let direct = insert_post(&client, user.account_id, "direct", "direct").await;
+    let public = insert_post(&client, user.account_id, "public", "public").await;
+    let other = &state.test_user_superadmin.handle;
+
+    for url in [
+        page_url(&state, &user.handle, draft),
+        page_url(&state, &user.handle, followers),
+        page_url(&state, &user.handle, direct),
+        page_url(&state, &user.handle, public + 1),
+        page_url(&state, other, public),
+    ] {
+        // Act
+        let response = state.get_status(&url, ACTIVITY_JSON).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 404, "{} is hidden", url);
+    }
+}
+
+#[tokio::test]
+async fn deleted_status_is_gone() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    let id = insert_post(&client, user.account_id, "regrets", "public").await;
+    client
+        .execute(
+            "UPDATE content SET deleted_at = '2023-01-02 03:04:05' WHERE id=$1",
+            &[&id],
+        )
+        .await
+        .unwrap();
+
+    // Act
+    let json = state
+        .get_status(&note_url(&state, &user.handle, id), ACTIVITY_JSON)
+        .await;
+    let html = state
+        .get_status(&page_url(&state, &user.handle, id), "text/html")
+        .await;
+
+    // Assert
+    assert_eq!(json.status().as_u16(), 410);
+    let tombstone: serde_json::Value = json.json().await.unwrap();
+    assert_eq!(tombstone["type"], "Tombstone");
+    assert_eq!(tombstone["formerType"], "Note");
+    assert_eq!(tombstone["id"], note_url(&state, &user.handle, id));
+    assert_eq!(tombstone["deleted"], "2023-01-02T03:04:05Z");
+    assert_eq!(html.status().as_u16(), 410);
+    assert!(!html.text().await.unwrap().contains("regrets"));
+    let outbox: serde_json::Value = state
+        .get_outbox(&user.handle, "")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(outbox["totalItems"], 0, "deleted posts leave the outbox");
+}
+
+#[tokio::test]
+async fn content_warnings_are_collapsed_unless_the_reader_expands_them() {
+    // Arrange
+    let state = spawn_app().await;
+    let user = &state.test_user_user;
+    let client = connect_to_db(&state.db_name).await;
+    let id = insert_post(&client, user.account_id, "the butler did it", "public").await;
+    client
+        .execute("UPDATE content SET cw = 'spoilers' WHERE id=$1", &[&id])
+        .await
+        .unwrap();
+    let url = page_url(&state, &user.handle, id);
+
+    // Act
+    let note: serde_json::Value = state
+        .get_status(&url, ACTIVITY_JSON)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let collapsed = state
+        .get_status(&url, "text/html")
+        .await
+        .text()
+        .await
+        .unwrap();
+    client
+        .execute(
+            r#"UPDATE "user" SET expand_cws = true WHERE id=$1"#,
+            &[&user.user_id],
+        )
+        .await
+        .unwrap();
+    state.login_as(user).await;
+    let expanded = state
+        .get_status(&url, "text/html")
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(note["summary"], "spoilers");
+    assert_eq!(note["sensitive"], true);
+    assert!(collapsed.contains("<details>"));
+    assert!(collapsed.contains("<summary>spoilers</summary>"));
+    assert!(expanded.contains("<details open>"));
+}
@@ -0,0 +1,157 @@
+use crate::helpers::{connect_to_db, publish, spawn_app, TestState};
+
+/// A public Note by the test user of `sender`, tagged with `#Rust`.
+fn tagged_create(sender: &TestState, receiver: &TestState) -> serde_json::Value {
+    let author = sender.actor_url(&sender.test_user_user);
+    let id = format!("{}/notes/1", sender.app_address);
+    serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}/activity", id),
+        "type": "Create",
+        "actor": author,
+        "object": {
+            "id": id,
+            "type": "Note",
+            "attributedTo": author,
+            "content": "<p>Remote <a href=\"https://x.example/tags/rust\">#<span>Rust</span></a></p>",
+            "to": [
+                "https://www.w3.org/ns/activitystreams#Public",
+                receiver.actor_url(&receiver.test_user_user),
+            ],
+            "tag": [{ "type": "Hashtag", "href": "https://x.example/tags/rust", "name": "#Rust" }],
+        },
+    })
+}
+
+#[tokio::test]
+async fn public_posts_are_listed_under_their_tags() {
+    // Arrange
+    let state = spawn_app().await;
+    publish(&state, "Learning #Rust today", "public").await;
+    publish(&state, "more #rust, quietly", "unlisted").await;
+
+    // Act
+    let response = state.get_tag("RUST", "text/html").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<title>#Rust</title>"), "{}", html);
+    assert!(html.contains("Learning"));
+    assert!(!html.contains("quietly"), "unlisted posts are not listed");
+    let tags: i64 = connect_to_db(&state.db_name)
+        .await
+        .query_one("SELECT COUNT(*) FROM tag WHERE name='rust'", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(tags, 1, "both spellings share one tag");
+}
+
+#[tokio::test]
+async fn tags_are_ordered_collections_of_post_ids() {
+    // Arrange
+    let state = spawn_app().await;
+    publish(&state, "un #Été chaud", "public").await;
+    let id: i64 = connect_to_db(&state.db_name)
+        .await
+        .query_one("SELECT id FROM content", &[])
+        .await
+        .unwrap()
+        .get(0);
+
+    // Act
+    let collection = state.get_tag("ÉTÉ", "application/activity+json").await;
+
+    // Assert
+    assert_eq!(collection.status().as_u16(), 200);
+    let collection: serde_json::Value = collection.json().await.unwrap();
+    assert_eq!(collection["type"], "OrderedCollection");
+    assert_eq!(
+        collection["id"],
+        format!("{}/tags/%C3%A9t%C3%A9", state.app_address)
+    );
+    assert_eq!(collection["totalItems"], 1);
+    let page = state.get_json(collection["first"].as_str().unwrap()).await;
+    assert_eq!(page["type"], "OrderedCollectionPage");
+    assert_eq!(
+        page["orderedItems"],
+        serde_json::json!([format!(
+            "{}/statuses/{}",
+            state.actor_url(&state.test_user_user),
+            id
+        )])
+    );
+    let missing = state.get_tag("nothing", "text/html").await;
+    assert_eq!(missing.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn hashtags_of_remote_notes_are_ingested() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    let create = tagged_create(&remote, &local);
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &create)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let collection = local
+        .get_json(&format!("{}/tags/rust", local.app_address))
+        .await;
+    assert_eq!(collection["totalItems"], 1);
+    let page = local.get_json(collection["first"].as_str().unwrap()).await;
+    assert_eq!(page["orderedItems"][0], create["object"]["id"]);
+    let html = local
+        .get_tag("rust", "text/html")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(
+        html.contains("Remote # Rust"),
+        "markup is stripped: {}",
+        html
+    );
+    assert!(!html.contains("x.example"));
+}
+
+#[tokio::test]
+async fn silenced_domains_are_left_out_of_tag_listings() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    let create = tagged_create(&remote, &local);
+    remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &create)
+        .await;
+    publish(&local, "local #rust", "public").await;
+
+    // Act
+    connect_to_db(&local.db_name)
+        .await
+        .execute(
+            "INSERT INTO domain_block (domain, severity) VALUES ('localhost', 'silence')",
+            &[],
+        )
+        .await
+        .unwrap();
+
+    // Assert
+    let collection = local
+        .get_json(&format!("{}/tags/rust", local.app_address))
+        .await;
+    assert_eq!(collection["totalItems"], 1);
+    let html = local
+        .get_tag("rust", "text/html")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("<p>local "), "{}", html);
+    assert!(!html.contains("Remote"), "{}", html);
+}
@@ -0,0 +1,385 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::{
+    helpers::{
+        add_mock_follower, assert_is_redirect_to, connect_to_db, first_delivery, spawn_app,
+        TestState,
+    },
+    mock_server::MockServer,
+};
+
+/// A `Create` of a note by the test user of `sender` addressed to `to`.
+fn create_note(sender: &TestState, to: &str) -> serde_json::Value {
+    let author = sender.actor_url(&sender.test_user_user);
+    let id = format!("{}/notes/{}", sender.app_address, Uuid::new_v4());
+    serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}/activity", id),
+        "type": "Create",
+        "actor": author,
+        "object": {
+            "id": id,
+            "type": "Note",
+            "attributedTo": author,
+            "content": "<p>first draft</p>",
+            "published": "2023-01-02T03:04:05Z",
+            "to": [to],
+            "cc": [],
+        },
+    })
+}
+
+#[tokio::test]
+async fn profile_changes_are_sent_to_followers() {
+    // Arrange
+    let app = spawn_app().await;
+    let mock = MockServer::start().await;
+    add_mock_follower(&app, &mock).await;
+    app.login_as(&app.test_user_user).await;
+    let form = [
+        ("name", "Renamed"),
+        ("summary", "I like <b>tea</b>"),
+        ("avatar_url", "https://example.com/avatar.png"),
+        ("field_name_1", "Website"),
+        ("field_value_1", "https://example.com"),
+    ];
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/user/profile", app.app_address))
+        .form(&form)
+        .send()
+        .await
+        .expect("Failed to post profile form");
+
+    // Assert
+    assert_is_redirect_to(&response, "/user/profile");
+    let actor_id = app.actor_url(&app.test_user_user);
+    let actor = app.get_json(&actor_id).await;
+    assert_eq!(actor["name"], "Renamed");
+    assert_eq!(actor["icon"]["url"], "https://example.com/avatar.png");
+    assert_eq!(actor["attachment"][0]["type"], "PropertyValue");
+    assert_eq!(actor["attachment"][0]["name"], "Website");
+    let activity = first_delivery(&mock, "/users/zoe/inbox").await;
+    assert_eq!(activity["type"], "Update");
+    assert_eq!(activity["actor"], actor_id);
+    assert_eq!(activity["object"]["id"], actor_id);
+    assert_eq!(activity["object"]["name"], "Renamed");
+    assert!(activity["object"]["publicKey"]["publicKeyPem"].is_string());
+}
+
+#[tokio::test]
+async fn invalid_profiles_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.test_user_user).await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/user/profile", app.app_address))
+        .form(&[("name", "Someone"), ("avatar_url", "javascript:alert(1)")])
+        .send()
+        .await
+        .expect("Failed to post profile form");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn edited_posts_are_sent_to_followers() {
+    // Arrange
+    let app = spawn_app().await;
+    let mock = MockServer::start().await;
+    add_mock_follower(&app, &mock).await;
+    app.login_as(&app.test_user_user).await;
+    let body = serde_json::json!({ "content": { "text": "first draft" } });
+    assert_eq!(app.post_content(&body).await.status().as_u16(), 200);
+    let id: i64 = connect_to_db(&app.db_name)
+        .await
+        .query_one("SELECT id FROM content", &[])
+        .await
+        .unwrap()
+        .get(0);
+    let created = first_delivery(&mock, "/users/zoe/inbox").await;
+    assert_eq!(created["type"], "Create");
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/content/{}/edit", app.app_address, id))
+        .json(&serde_json::json!({ "content": { "text": "second #draft" } }))
+        .send()
+        .await
+        .expect("Failed to edit content");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let mut update = None;
+    for _ in 0..100 {
+        update = mock
+            .received("/users/zoe/inbox")
+            .iter()
+            .map(|r| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap())
+            .find(|a| a["type"] == "Update");
+        if update.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let update = update.expect("the edit was delivered");
+    assert_eq!(update["object"]["id"], created["object"]["id"]);
+    assert!(update["object"]["content"]
+        .as_str()
+        .unwrap()
+        .contains("second"));
+    assert!(update["object"]["updated"].is_string());
+    let row = connect_to_db(&app.db_name)
+        .await
+        .query_one(
+            "SELECT tag.name FROM tag JOIN content_tag ON content_tag.tag_id = tag.id",
+            &[],
+        )
+        .await
+        .unwrap();
+    assert_eq!(row.get::<_, String>(0), "draft");
+}
+
+#[tokio::test]
+async fn edits_keep_the_earlier_versions() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.test_user_user).await;
+    let body = serde_json::json!({ "content": { "text": "teh first post" } });
+    assert_eq!(app.post_content(&body).await.status().as_u16(), 200);
+    let id: i64 = connect_to_db(&app.db_name)
+        .await
+        .query_one("SELECT id FROM content", &[])
+        .await
+        .unwrap()
+        .get(0);
+    let edit = |text: &str, cw: &str| {
+        app.api_client
+            .post(format!("{}/content/{}/edit", app.app_address, id))
+            .json(&serde_json::json!({ "content": { "text": text, "cw": cw } }))
+            .send()
+    };
+
+    // Act
+    assert_eq!(
+        edit("the first post", "typo")
+            .await
+            .unwrap()
+            .status()
+            .as_u16(),
+        200
+    );
+    assert_eq!(
+        edit("the first post", "typo")
+            .await
+            .unwrap()
+            .status()
+            .as_u16(),
+        200
+    );
+
+    // Assert
+    let history: serde_json::Value = app
+        .get_content(&format!("/{}/history", id))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let versions = history.as_array().unwrap();
+    assert_eq!(versions.len(), 2, "unchanged edits are no versions");
+    assert_eq!(versions[0]["text"], "teh first post");
+    assert!(versions[0]["cw"].is_null());
+    assert_eq!(versions[1]["text"], "the first post");
+    assert_eq!(versions[1]["cw"], "typo");
+    assert_eq!(
+        versions[1]["diff"],
+        serde_json::json!([
+            { "op": "delete", "text": "teh" },
+            { "op": "insert", "text": "the" },
+            { "op": "equal", "text": " first post" },
+        ])
+    );
+    let post: serde_json::Value = app
+        .get_content(&format!("/{}", id))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(post["cw"], "typo");
+    assert!(post["edited_at"].is_string());
+    let page = app
+        .api_client
+        .get(format!(
+            "{}/@{}/{}",
+            app.app_address, app.test_user_user.handle, id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains(", edited <time"));
+}
+
+#[tokio::test]
+async fn posts_of_others_cannot_be_edited() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.test_user_superadmin).await;
+    let body = serde_json::json!({ "content": { "text": "mine" } });
+    assert_eq!(app.post_content(&body).await.status().as_u16(), 200);
+    let id: i64 = connect_to_db(&app.db_name)
+        .await
+        .query_one("SELECT id FROM content", &[])
+        .await
+        .unwrap()
+        .get(0);
+    app.post_logout().await;
+    app.login_as(&app.test_user_user).await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/content/{}/edit", app.app_address, id))
+        .json(&serde_json::json!({ "content": { "text": "yours now" } }))
+        .send()
+        .await
+        .expect("Failed to edit content");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn remote_actor_updates_refresh_the_cache() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    let actor_id = remote.actor_url(&remote.test_user_user);
+    let mut document = remote.get_json(&actor_id).await;
+    document["name"] = serde_json::json!("Renamed remotely");
+    let activity = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}#updates/1", actor_id),
+        "type": "Update",
+        "actor": actor_id,
+        "object": document,
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &activity)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let name: Option<String> = connect_to_db(&local.db_name)
+        .await
+        .query_one(
+            "SELECT name FROM remote_actor WHERE actor_id=$1",
+            &[&actor_id],
+        )
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(name.as_deref(), Some("Renamed remotely"));
+}
+
+#[tokio::test]
+async fn remote_note_updates_replace_the_stored_note() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    let create = create_note(&remote, &local.actor_url(&local.test_user_user));
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &create)
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let mut note = create["object"].clone();
+    note["content"] = serde_json::json!("<p>second draft</p>");
+    note["updated"] = serde_json::json!("2023-01-02T04:00:00Z");
+    let update = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}#updates/1", note["id"].as_str().unwrap()),
+        "type": "Update",
+        "actor": create["actor"],
+        "object": note,
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &update)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let content: Option<String> = connect_to_db(&local.db_name)
+        .await
+        .query_one(
+            "SELECT content FROM remote_post WHERE object_id=$1",
+            &[&create["object"]["id"].as_str().unwrap()],
+        )
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(content.as_deref(), Some("<p>second draft</p>"));
+    let client = connect_to_db(&local.db_name).await;
+    let row = client
+        .query_one(
+            "SELECT content_revision.body, remote_post.edited_at::text FROM content_revision \
+             JOIN remote_post ON remote_post.id = content_revision.remote_post_id",
+            &[],
+        )
+        .await
+        .unwrap();
+    assert_eq!(row.get::<_, &str>(0), "<p>first draft</p>");
+    assert_eq!(row.get::<_, &str>(1), "2023-01-02 04:00:00");
+}
+
+#[tokio::test]
+async fn notes_cannot_be_updated_by_other_actors() {
+    // Arrange
+    let local = spawn_app().await;
+    let remote = spawn_app().await;
+    let create = create_note(&remote, &local.actor_url(&local.test_user_user));
+    remote
+        .deliver_to(&remote.test_user_user, &local, "/inbox", &create)
+        .await;
+    let intruder = remote.actor_url(&remote.test_user_superadmin);
+    let mut note = create["object"].clone();
+    note["content"] = serde_json::json!("<p>defaced</p>");
+    let update = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}#updates/1", intruder),
+        "type": "Update",
+        "actor": intruder,
+        "object": note,
+    });
+
+    // Act
+    let response = remote
+        .deliver_to(&remote.test_user_superadmin, &local, "/inbox", &update)
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let activity_id = update["id"].as_str().unwrap();
+    assert_eq!(local.wait_for_activity(activity_id).await, "dead");
+    let content: Option<String> = connect_to_db(&local.db_name)
+        .await
+        .query_one("SELECT content FROM remote_post", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(content.as_deref(), Some("<p>first draft</p>"));
+}
@@ -179,3 +179,44 @@ async fn insert_user_fails_if_fatal_db_err() {
     // Assert
     assert_eq!(response.status().as_u16(), 500)
 }
+
+#[tokio::test]
+async fn add_user_with_taken_handle() {
+    // Arrange
+    let state = spawn_app().await;
+    let client = connect_to_db(&state.db_name).await;
+    let first = "name=Alice&email=alice%40lowdelhi.example&password=a&role=user";
+    assert_eq!(
+        state.post_user(first.to_string()).await.status().as_u16(),
+        200
+    );
+
+    // Act
+    let derived = "name=Alice&email=alice%40other.example&password=a&role=user";
+    let derived = state.post_user(derived.to_string()).await;
+    let chosen = "name=Alice&email=alice%40third.example&password=a&role=user&username=alice";
+    let chosen = state.post_user(chosen.to_string()).await;
+
+    // Assert
+    assert_eq!(
+        derived.status().as_u16(),
+        200,
+        "a derived handle is disambiguated"
+    );
+    let row = client
+        .query_one(
+            r#"SELECT a.id, a.username FROM account a JOIN "user" u ON u.id = a.user_id
+                WHERE u.email = 'alice@other.example'"#,
+            &[],
+        )
+        .await
+        .expect("query to fetch the account failed");
+    let id: i64 = row.get(0);
+    let username: &str = row.get(1);
+    assert_eq!(username, format!("alice_{}", id));
+    assert_eq!(
+        chosen.status().as_u16(),
+        400,
+        "a chosen handle that is taken is rejected"
+    );
+}
@@ -0,0 +1,121 @@
+use crate::helpers::{connect_to_db, spawn_app};
+
+#[tokio::test]
+async fn webfinger_resolves_local_account() {
+    // Arrange
+    let state = spawn_app().await;
+    let handle = &state.test_user_user.handle;
+
+    // Act
+    let response = state
+        .get_webfinger(&format!("acct:{}@localhost", handle))
+        .await;
+
+    // Assert
+    assert_eq!(
+        response.status().as_u16(),
+        200,
+        "known handle returns 200 Ok"
+    );
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "application/jrd+json",
+        "response is a JRD document"
+    );
+    let jrd: serde_json::Value = response.json().await.unwrap();
+    let actor = format!("{}/users/{}", state.app_address, handle);
+    assert_eq!(jrd["subject"], format!("acct:{}@localhost", handle));
+    let links = jrd["links"].as_array().unwrap();
+    assert!(
+        links.iter().any(|l| l["rel"] == "self"
+            && l["type"] == "application/activity+json"
+            && l["href"] == actor),
+        "JRD contains a self link to the actor"
+    );
+    assert!(
+        links
+            .iter()
+            .any(|l| l["rel"] == "http://webfinger.net/rel/profile-page" && l["href"] == actor),
+        "JRD contains a link to the profile page"
+    );
+}
+
+#[tokio::test]
+async fn webfinger_resolves_seed_admin() {
+    // Arrange
+    let state = spawn_app().await;
+
+    // Act
+    let response = state.get_webfinger("acct:admin@localhost").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200, "seed admin has a handle");
+}
+
+#[tokio::test]
+async fn webfinger_unknown_handle_is_not_found_404() {
+    // Arrange
+    let state = spawn_app().await;
+    let cases = [
+        ("acct:nobody@localhost", "unknown handle"),
+        (
+            &*format!("acct:{}@example.com", state.test_user_user.handle),
+            "handle on another domain",
+        ),
+    ];
+
+    for (resource, desc) in cases {
+        // Act
+        let response = state.get_webfinger(resource).await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            404,
+            "{} returns 404 Not Found",
+            desc
+        );
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert!(body["error"].is_string(), "{} returns a JRD error", desc);
+    }
+}
+
+#[tokio::test]
+async fn webfinger_malformed_resource_is_bad_request_400() {
+    // Arrange
+    let state = spawn_app().await;
+
+    // Act
+    let response = state.get_webfinger("acct:nobody").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn new_user_gets_an_account_handle() {
+    // Arrange
+    let state = spawn_app().await;
+
+    // Act
+    let body = "name=Sonja%20Hemphill&email=sonja.h%40lowdelhi.example&password=a&role=user";
+    let _ = state.post_user(body.to_string()).await;
+
+    // Assert
+    let client = connect_to_db(&state.db_name).await;
+    let row = client
+        .query_one(
+            r#"SELECT a.username FROM account a JOIN "user" u ON u.id = a.user_id WHERE u.email=$1;"#,
+            &[&"sonja.h@lowdelhi.example"],
+        )
+        .await
+        .expect("query to fetch account failed");
+    let handle: &str = row.get(0);
+    assert_eq!(
+        handle, "sonja_h",
+        "handle is derived from the email address"
+    );
+
+    let response = state.get_webfinger("acct:sonja_h@localhost").await;
+    assert_eq!(response.status().as_u16(), 200);
+}